@@ -5,23 +5,12 @@ export type EGuardOptions = JsEGuardConfig;
 
 export function eGuardMiddleware(opts: EGuardOptions) {
   const guard = new JsEGuard(opts);
-  const headerName = opts.sessionExtraction.headerName?.toLowerCase();
 
   return async function eGuard(req: Request, res: Response, next: NextFunction) {
     
-    if (!guard.isSecure(req.path, req.method)) return next();
+    if (!guard.isSecure(req.path, req.method, req.hostname)) return next();
 
-    const cookieHeader = req.headers['cookie'] as string | undefined;
-    const headerVal =
-      headerName && typeof req.headers[headerName] === 'string'
-        ? (req.headers[headerName] as string)
-        : undefined;
-
-    const sid = guard.extractSessionId(
-      cookieHeader ?? null,
-      opts.sessionExtraction.headerName ?? null,
-      headerVal ?? null
-    );
+    const sid = guard.extractSessionIdFromHeaders(req.headers, req.originalUrl.split('?')[1] ?? null);
 
     if (!sid) {
       return res.status(401).json({ error: 'missing_session' });
@@ -30,6 +19,12 @@ export function eGuardMiddleware(opts: EGuardOptions) {
     try {
       const decision = (await guard.decide(sid)) as JsDecision;
       if (decision.allow) return next();
+      if (decision.redirectUrl) {
+        return res
+          .status(302)
+          .location(decision.redirectUrl)
+          .json({ error: 'challenge', kind: decision.challengeKind, redirect_url: decision.redirectUrl });
+      }
       return res
         .status(decision.status ?? 403)
         .json({ error: 'forbidden', detail: decision.message });
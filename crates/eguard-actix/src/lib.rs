@@ -0,0 +1,152 @@
+use std::{
+    future::{Ready, ready},
+    rc::Rc,
+};
+
+use actix_web::{
+    Error, HttpResponse,
+    body::{EitherBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::{StatusCode, header::{HeaderMap, HeaderName, HeaderValue, LOCATION}},
+};
+use eguard_core::{Decision, EGuard, RequestContext};
+use futures_util::future::LocalBoxFuture;
+use serde_json::json;
+
+/// Protects the configured secure routes of an actix-web app:
+///
+/// ```ignore
+/// App::new().wrap(EGuardMiddleware::new(guard.clone())).service(checkout)
+/// ```
+#[derive(Clone)]
+pub struct EGuardMiddleware {
+    guard: EGuard,
+}
+
+impl EGuardMiddleware {
+    pub fn new(guard: EGuard) -> Self {
+        Self { guard }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for EGuardMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = EGuardService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(EGuardService { guard: self.guard.clone(), service: Rc::new(service) }))
+    }
+}
+
+pub struct EGuardService<S> {
+    guard: EGuard,
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for EGuardService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let guard = self.guard.clone();
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let ctx = context(&req);
+            let guard = guard.for_request(&ctx);
+            if !guard.is_secure_host(ctx.host(), &ctx.path, &ctx.method) {
+                return Ok(service.call(req).await?.map_into_left_body());
+            }
+
+            let Some(sid) = session_id(guard, req.headers(), req.query_string()) else {
+                return Ok(reject(req, StatusCode::UNAUTHORIZED, json!({ "error": "missing_session" })));
+            };
+            let ctx = ctx.with_device_id(device_id(guard, req.headers(), req.query_string()));
+
+            let (decision, trust) = match guard.decide_request_with_trust(&ctx, &sid).await {
+                Ok(decided) => decided,
+                Err(_) => return Ok(reject(req, StatusCode::BAD_GATEWAY, json!({ "error": "trust_service_unavailable" }))),
+            };
+            let request_id = guard.request_id(Some(&ctx));
+            let headers = guard.response_headers(&decision, trust.as_ref(), &request_id);
+            let mut resp = match decision {
+                Decision::Allow { .. } => {
+                    let mut req = req;
+                    if guard.config().response_headers.as_ref().is_some_and(|h| h.forward) {
+                        insert_headers(req.headers_mut(), &headers);
+                    }
+                    service.call(req).await?.map_into_left_body()
+                }
+                Decision::Deny { status, ref message } => {
+                    let code = StatusCode::from_u16(status).unwrap_or(StatusCode::FORBIDDEN);
+                    match guard.deny_body(&decision, trust.as_ref(), &request_id) {
+                        Some(body) => {
+                            let (req, _) = req.into_parts();
+                            let resp = HttpResponse::build(code).content_type(body.content_type).body(body.body);
+                            ServiceResponse::new(req, resp).map_into_right_body()
+                        }
+                        None => reject(req, code, json!({ "error": "forbidden", "detail": message })),
+                    }
+                }
+                Decision::Challenge { kind, redirect_url } => {
+                    let (req, _) = req.into_parts();
+                    let resp = HttpResponse::Found()
+                        .insert_header((LOCATION, redirect_url.as_str()))
+                        .json(json!({ "error": "challenge", "kind": kind, "redirect_url": redirect_url }));
+                    ServiceResponse::new(req, resp).map_into_right_body()
+                }
+            };
+            insert_headers(resp.headers_mut(), &headers);
+            Ok(resp)
+        })
+    }
+}
+
+fn context(req: &ServiceRequest) -> RequestContext {
+    let headers = req.headers().iter().filter_map(|(k, v)| Some((k.as_str(), v.to_str().ok()?)));
+    let mut ctx = RequestContext::new(req.method().as_str(), req.path())
+        .with_ip(req.peer_addr().map(|a| a.ip()))
+        .with_headers(headers);
+    if ctx.host().is_none() {
+        ctx.headers.insert("host".into(), req.connection_info().host().to_string());
+    }
+    ctx
+}
+
+fn session_id(guard: &EGuard, headers: &HeaderMap, query: &str) -> Option<String> {
+    let headers = headers.iter().filter_map(|(k, v)| Some((k.as_str(), v.to_str().ok()?)));
+    guard.extract_session_id_from_headers(headers, Some(query))
+}
+
+fn device_id(guard: &EGuard, headers: &HeaderMap, query: &str) -> Option<String> {
+    let headers = headers.iter().filter_map(|(k, v)| Some((k.as_str(), v.to_str().ok()?)));
+    guard.extract_device_id_from_headers(headers, Some(query))
+}
+
+fn insert_headers(map: &mut HeaderMap, headers: &[(&'static str, String)]) {
+    for (name, value) in headers {
+        if let Ok(value) = HeaderValue::from_str(value) {
+            map.insert(HeaderName::from_static(name), value);
+        }
+    }
+}
+
+fn reject<B>(req: ServiceRequest, status: StatusCode, body: serde_json::Value) -> ServiceResponse<EitherBody<B>> {
+    let (req, _) = req.into_parts();
+    let resp = HttpResponse::build(status).json(body);
+    ServiceResponse::new(req, resp).map_into_right_body()
+}
@@ -0,0 +1,165 @@
+//! The `envoy.service.auth.v3.Authorization` service, backed by an `EGuard`.
+
+use std::{
+    convert::Infallible,
+    task::{Context, Poll},
+};
+
+use eguard_core::{Decision, EGuard, RequestContext};
+use serde_json::json;
+use tonic::{
+    Request, Response, Status,
+    body::Body,
+    codegen::{BoxFuture, Service, http},
+    server::{Grpc, NamedService, UnaryService},
+};
+use tonic_prost::ProstCodec;
+
+use crate::proto::{
+    CHECK, CheckRequest, CheckResponse, DeniedHttpResponse, HeaderValueOption, HttpResponse, HttpStatus,
+    OkHttpResponse, RpcStatus,
+};
+
+// gRPC status codes for `CheckResponse.status`.
+const OK: i32 = 0;
+const PERMISSION_DENIED: i32 = 7;
+const UNAVAILABLE: i32 = 14;
+const UNAUTHENTICATED: i32 = 16;
+
+/// Answers Envoy's `Check` calls the way the tower middleware answers requests: requests
+/// off secure routes pass, the rest need a session and an allowing decision.
+#[derive(Clone)]
+pub struct AuthorizationServer {
+    guard: EGuard,
+}
+
+impl AuthorizationServer {
+    pub fn new(guard: EGuard) -> Self {
+        Self { guard }
+    }
+}
+
+impl NamedService for AuthorizationServer {
+    const NAME: &'static str = "envoy.service.auth.v3.Authorization";
+}
+
+impl Service<http::Request<Body>> for AuthorizationServer {
+    type Response = http::Response<Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Infallible>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        let guard = self.guard.clone();
+        Box::pin(async move {
+            if req.uri().path() != CHECK {
+                return Ok(Status::unimplemented(format!("Unknown method {}", req.uri().path())).into_http());
+            }
+            let mut grpc = Grpc::new(ProstCodec::<CheckResponse, CheckRequest>::default());
+            Ok(grpc.unary(Check(guard), req).await)
+        })
+    }
+}
+
+struct Check(EGuard);
+
+impl UnaryService<CheckRequest> for Check {
+    type Response = CheckResponse;
+    type Future = BoxFuture<Response<CheckResponse>, Status>;
+
+    fn call(&mut self, req: Request<CheckRequest>) -> Self::Future {
+        let guard = self.0.clone();
+        Box::pin(async move { Ok(Response::new(check(&guard, req.into_inner()).await)) })
+    }
+}
+
+async fn check(guard: &EGuard, req: CheckRequest) -> CheckResponse {
+    let attributes = req.attributes.unwrap_or_default();
+    let http = attributes.request.and_then(|r| r.http).unwrap_or_default();
+    let ip = attributes.source
+        .and_then(|p| p.address)
+        .and_then(|a| a.socket_address)
+        .and_then(|s| s.address.parse().ok());
+    let (path, query) = match http.path.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (http.path.as_str(), None),
+    };
+    // Pseudo-headers such as `:authority` are in the map too; the host has its own field.
+    let mut headers: Vec<(&str, &str)> = http.headers.iter()
+        .filter(|(name, _)| !name.starts_with(':'))
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    if !http.host.is_empty() && !http.headers.contains_key("host") {
+        headers.push(("host", &http.host));
+    }
+    let ctx = RequestContext::new(&http.method, path)
+        .with_ip(ip)
+        .with_headers(headers.iter().copied());
+
+    let guard = guard.for_request(&ctx);
+    if !guard.is_secure_host(ctx.host(), &ctx.path, &ctx.method) {
+        return allowed(Vec::new(), Vec::new());
+    }
+    let Some(sid) = guard.extract_session_id_from_headers(headers.iter().copied(), query) else {
+        return rejected(UNAUTHENTICATED, 401, json!({ "error": "missing_session" }), Vec::new());
+    };
+    let ctx = ctx.with_device_id(guard.extract_device_id_from_headers(headers.iter().copied(), query));
+    let (decision, trust) = match guard.decide_request_with_trust(&ctx, &sid).await {
+        Ok(decided) => decided,
+        Err(e) => {
+            tracing::warn!(error = %e, "trust check failed");
+            return rejected(UNAVAILABLE, 502, json!({ "error": "trust_service_unavailable" }), Vec::new());
+        }
+    };
+
+    let request_id = guard.request_id(Some(&ctx));
+    let headers: Vec<_> = guard.response_headers(&decision, trust.as_ref(), &request_id)
+        .into_iter()
+        .map(|(name, value)| HeaderValueOption::new(name, value))
+        .collect();
+    match decision {
+        Decision::Allow { .. } => {
+            let upstream = match guard.config().response_headers.as_ref().is_some_and(|h| h.forward) {
+                true => headers.clone(),
+                false => Vec::new(),
+            };
+            allowed(upstream, headers)
+        }
+        Decision::Deny { status, ref message } => match guard.deny_body(&decision, trust.as_ref(), &request_id) {
+            Some(body) => denied(PERMISSION_DENIED, status, body.content_type, body.body, headers),
+            None => rejected(PERMISSION_DENIED, status, json!({ "error": "forbidden", "detail": message }), headers),
+        },
+        Decision::Challenge { kind, redirect_url } => {
+            let body = json!({ "error": "challenge", "kind": kind, "redirect_url": redirect_url });
+            let mut headers = headers;
+            headers.push(HeaderValueOption::new("location", redirect_url));
+            rejected(PERMISSION_DENIED, 302, body, headers)
+        }
+    }
+}
+
+fn allowed(upstream: Vec<HeaderValueOption>, response: Vec<HeaderValueOption>) -> CheckResponse {
+    CheckResponse {
+        status: Some(RpcStatus { code: OK, message: String::new() }),
+        http_response: Some(HttpResponse::Ok(OkHttpResponse { headers: upstream, response_headers_to_add: response })),
+    }
+}
+
+fn rejected(code: i32, status: u16, body: serde_json::Value, headers: Vec<HeaderValueOption>) -> CheckResponse {
+    denied(code, status, "application/json", body.to_string(), headers)
+}
+
+fn denied(code: i32, status: u16, content_type: &str, body: String, mut headers: Vec<HeaderValueOption>) -> CheckResponse {
+    headers.push(HeaderValueOption::new("content-type", content_type.to_string()));
+    CheckResponse {
+        status: Some(RpcStatus { code, message: String::new() }),
+        http_response: Some(HttpResponse::Denied(DeniedHttpResponse {
+            status: Some(HttpStatus { code: i32::from(status) }),
+            headers,
+            body,
+        })),
+    }
+}
@@ -0,0 +1,54 @@
+//! `eguard-authz`: an Envoy external authorization (`ext_authz`) gRPC server backed by
+//! `EGuard`, so trust checks can be enforced at the proxy without touching application code.
+//!
+//! ```text
+//! eguard-authz -c eguard.yaml --listen 0.0.0.0:9191
+//! ```
+//!
+//! Point an `envoy.filters.http.ext_authz` filter's `grpc_service` at it, with
+//! `transport_api_version: V3`. Denials carry the same status codes, JSON bodies and
+//! `X-EGuard-*` headers as the tower middleware.
+
+mod authz;
+mod proto;
+
+use std::{net::SocketAddr, path::PathBuf};
+
+use clap::Parser;
+use eguard_core::{EGuard, EGuardConfig};
+use tonic::transport::Server;
+
+use crate::authz::AuthorizationServer;
+
+#[derive(Parser)]
+#[command(name = "eguard-authz", version, about = "Envoy ext_authz server for eGuard")]
+struct Cli {
+    /// `.yaml`, `.toml` or `.json` config, as loaded by `EGuardConfig::from_file`.
+    #[arg(short, long, env = "EGUARD_CONFIG")]
+    config: PathBuf,
+    #[arg(long, env = "EGUARD_AUTHZ_LISTEN", default_value = "0.0.0.0:9191")]
+    listen: SocketAddr,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let guard = EGuard::new(EGuardConfig::from_file(&cli.config)?)?;
+    guard.load_secrets().await?;
+    // Kept alive for as long as the server runs.
+    let _tasks = (
+        guard.spawn_refresher(),
+        guard.spawn_secret_refresher(),
+        guard.spawn_denylist_sync(),
+        guard.spawn_health_checker(),
+        guard.spawn_push_listener()?,
+    );
+
+    Server::builder()
+        .add_service(AuthorizationServer::new(guard))
+        .serve_with_shutdown(cli.listen, async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,8 @@
+//! Axum integration for eGuard. The middleware is the generic tower layer from
+//! `eguard-core`, which axum's `Body` satisfies directly:
+//!
+//! ```ignore
+//! let app = Router::new().route("/checkout", post(checkout)).layer(EGuardLayer::new(guard));
+//! ```
+
+pub use eguard_core::tower::{EGuardLayer, EGuardService};
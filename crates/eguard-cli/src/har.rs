@@ -0,0 +1,55 @@
+//! Requests from a HAR capture, e.g. "Save all as HAR" in the browser devtools.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::Input;
+
+#[derive(Deserialize)]
+struct Har {
+    log: Log,
+}
+
+#[derive(Deserialize)]
+struct Log {
+    entries: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    request: Request,
+}
+
+#[derive(Deserialize)]
+struct Request {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: Vec<Header>,
+}
+
+#[derive(Deserialize)]
+struct Header {
+    name: String,
+    value: String,
+}
+
+pub(crate) fn load(path: &Path) -> anyhow::Result<Vec<Input>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path.display(), e))?;
+    let har: Har = serde_json::from_str(&text)
+        .map_err(|e| anyhow::anyhow!("Invalid HAR file {}: {}", path.display(), e))?;
+
+    har.log.entries.into_iter()
+        .map(|entry| {
+            let req = entry.request;
+            // HTTP/2 captures list pseudo-headers such as `:authority`; the host comes from the URL.
+            let headers = req.headers.into_iter()
+                .filter(|h| !h.name.starts_with(':'))
+                .map(|h| (h.name, h.value))
+                .collect();
+            Input::new(&req.method, &req.url, headers, None)
+        })
+        .collect()
+}
@@ -0,0 +1,217 @@
+//! `eguard`: run a config against requests from the command line, to answer
+//! "why was this request blocked?" without writing code.
+//!
+//! ```text
+//! eguard -c eguard.yaml is-secure -X POST --url https://shop.example.com/checkout
+//! eguard -c eguard.yaml extract -H 'Cookie: sid=abc' --url /checkout
+//! eguard -c eguard.yaml decide --har capture.har
+//! eguard -c eguard.yaml decide --har capture.har --scores scores.yaml
+//! ```
+
+mod har;
+
+use std::{net::IpAddr, path::PathBuf, sync::Arc};
+
+use clap::{Args, Parser, Subcommand};
+use eguard_core::{Decision, EGuard, EGuardConfig, RequestContext, TrustResponse, provider::MemoryProvider};
+use url::Url;
+
+#[derive(Parser)]
+#[command(name = "eguard", version, about = "Check eGuard decisions for requests")]
+struct Cli {
+    /// `.yaml`, `.toml` or `.json` config, as loaded by `EGuardConfig::from_file`.
+    #[arg(short, long, env = "EGUARD_CONFIG")]
+    config: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Whether the requests match a secure route.
+    IsSecure(RequestArgs),
+    /// The session id extracted from each request.
+    Extract(RequestArgs),
+    /// The decision for each request, as the middleware would make it. Calls the Trust API.
+    Decide {
+        #[command(flatten)]
+        request: RequestArgs,
+        /// Decide for this session instead of the one extracted from the request.
+        #[arg(long)]
+        session_id: Option<String>,
+        /// Take scores from this file (session id to score) instead of the Trust API.
+        #[arg(long)]
+        scores: Option<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+struct RequestArgs {
+    /// Path with an optional query, or an absolute URL, which also sets the host.
+    #[arg(long, default_value = "/", conflicts_with = "har")]
+    url: String,
+    #[arg(short = 'X', long, default_value = "GET", conflicts_with = "har")]
+    method: String,
+    /// `Name: value`; may be repeated.
+    #[arg(short = 'H', long = "header", conflicts_with = "har")]
+    headers: Vec<String>,
+    /// Client IP, for `ip_rules`, `geoip` and per-IP rate limits.
+    #[arg(long, conflicts_with = "har")]
+    ip: Option<IpAddr>,
+    /// Use every request in this HAR capture instead.
+    #[arg(long)]
+    har: Option<PathBuf>,
+}
+
+impl RequestArgs {
+    fn inputs(&self) -> anyhow::Result<Vec<Input>> {
+        if let Some(path) = &self.har {
+            return har::load(path);
+        }
+        let headers = self.headers.iter()
+            .map(|h| {
+                let (name, value) = h.split_once(':')
+                    .ok_or_else(|| anyhow::anyhow!("Header `{}` is not `Name: value`", h))?;
+                Ok((name.trim().to_string(), value.trim().to_string()))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(vec![Input::new(&self.method, &self.url, headers, self.ip)?])
+    }
+}
+
+/// One request to run the config against.
+pub(crate) struct Input {
+    method: String,
+    path: String,
+    query: Option<String>,
+    headers: Vec<(String, String)>,
+    ip: Option<IpAddr>,
+}
+
+impl Input {
+    pub(crate) fn new(method: &str, url: &str, mut headers: Vec<(String, String)>, ip: Option<IpAddr>) -> anyhow::Result<Self> {
+        let parsed = match Url::parse(url) {
+            Ok(parsed) => {
+                if let Some(host) = parsed.host_str()
+                    && !headers.iter().any(|(n, _)| n.eq_ignore_ascii_case("host"))
+                {
+                    let host = match parsed.port() {
+                        Some(port) => format!("{host}:{port}"),
+                        None => host.to_string(),
+                    };
+                    headers.push(("host".into(), host));
+                }
+                parsed
+            }
+            Err(url::ParseError::RelativeUrlWithoutBase) => Url::parse("http://localhost")?.join(url)?,
+            Err(e) => return Err(anyhow::anyhow!("Invalid URL `{}`: {}", url, e)),
+        };
+        Ok(Self {
+            method: method.to_ascii_uppercase(),
+            path: parsed.path().to_string(),
+            query: parsed.query().map(str::to_string),
+            headers,
+            ip,
+        })
+    }
+
+    fn context(&self) -> RequestContext {
+        RequestContext::new(&self.method, &self.path)
+            .with_ip(self.ip)
+            .with_headers(self.headers.iter().map(|(n, v)| (n.as_str(), v.as_str())))
+    }
+
+    fn session_id(&self, guard: &EGuard) -> Option<String> {
+        guard.extract_session_id_from_headers(
+            self.headers.iter().map(|(n, v)| (n.as_str(), v.as_str())),
+            self.query.as_deref(),
+        )
+    }
+
+    fn device_id(&self, guard: &EGuard) -> Option<String> {
+        guard.extract_device_id_from_headers(
+            self.headers.iter().map(|(n, v)| (n.as_str(), v.as_str())),
+            self.query.as_deref(),
+        )
+    }
+
+    fn label(&self) -> String {
+        match &self.query {
+            Some(query) => format!("{} {}?{}", self.method, self.path, query),
+            None => format!("{} {}", self.method, self.path),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let cfg = EGuardConfig::from_file(&cli.config)?;
+    let mut guard = EGuard::new(cfg)?;
+
+    match cli.command {
+        Command::IsSecure(args) => {
+            for input in args.inputs()? {
+                let ctx = input.context();
+                let secure = guard.for_request(&ctx).is_secure_host(ctx.host(), &ctx.path, &ctx.method);
+                println!("{}: {}", input.label(), if secure { "secure" } else { "not secure" });
+            }
+        }
+        Command::Extract(args) => {
+            for input in args.inputs()? {
+                let ctx = input.context();
+                let guard = guard.for_request(&ctx);
+                match (input.session_id(guard), input.device_id(guard)) {
+                    (Some(sid), Some(device)) => println!("{}: {} (device {})", input.label(), sid, device),
+                    (Some(sid), None) => println!("{}: {}", input.label(), sid),
+                    (None, _) => println!("{}: no session id", input.label()),
+                }
+            }
+        }
+        Command::Decide { request, session_id, scores } => {
+            match scores {
+                Some(path) => guard = guard.with_provider(Arc::new(MemoryProvider::from_file(path)?)),
+                None => guard.load_secrets().await?,
+            }
+            for input in request.inputs()? {
+                println!("{}: {}", input.label(), decide(&guard, &input, session_id.as_deref()).await);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// What the middleware would do with `input`, with the score behind it.
+async fn decide(guard: &EGuard, input: &Input, session_id: Option<&str>) -> String {
+    let ctx = input.context();
+    let guard = guard.for_request(&ctx);
+    if !guard.is_secure_host(ctx.host(), &ctx.path, &ctx.method) {
+        return "not secure, passed through".into();
+    }
+    let Some(sid) = session_id.map(str::to_string).or_else(|| input.session_id(guard)) else {
+        return "no session id, rejected with 401".into();
+    };
+    let ctx = ctx.with_device_id(input.device_id(guard));
+
+    match guard.decide_request_with_trust(&ctx, &sid).await {
+        Ok((decision, trust)) => {
+            let outcome = match decision {
+                Decision::Allow { .. } => "allow".to_string(),
+                Decision::Deny { status, message } => format!("deny {status} ({message})"),
+                Decision::Challenge { kind, redirect_url } => format!("challenge {kind:?} at {redirect_url}"),
+            };
+            match trust {
+                Some(trust) => format!("{} [session {}, {}]", outcome, sid, score(&trust)),
+                None => format!("{} [session {}, decided without a score]", outcome, sid),
+            }
+        }
+        Err(e) => format!("error [session {}]: {:#}", sid, e),
+    }
+}
+
+fn score(trust: &TrustResponse) -> String {
+    match &trust.reason {
+        Some(reason) => format!("score {}, reason {}", trust.trust_score, reason),
+        None => format!("score {}", trust.trust_score),
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Derive the Trust API timeout from recent latencies instead of always waiting
+/// `timeout_ms`, which stays the upper bound.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdaptiveTimeoutConfig {
+    /// The latency percentile the timeout follows.
+    #[serde(default = "default_percentile")]
+    pub percentile: f64,
+    /// The timeout as a multiple of that percentile.
+    #[serde(default = "default_multiplier")]
+    pub multiplier: f64,
+    /// Lower bound of the timeout.
+    #[serde(default = "default_min_ms")]
+    pub min_ms: u64,
+    /// How many recent calls the percentile is taken over.
+    #[serde(default = "default_window")]
+    pub window: usize,
+    /// Calls to observe before adapting; until then the timeout is `timeout_ms`.
+    #[serde(default = "default_min_samples")]
+    pub min_samples: usize,
+}
+
+fn default_percentile() -> f64 { 99.0 }
+fn default_multiplier() -> f64 { 3.0 }
+fn default_min_ms() -> u64 { 50 }
+fn default_window() -> usize { 500 }
+fn default_min_samples() -> usize { 50 }
+
+impl Default for AdaptiveTimeoutConfig {
+    fn default() -> Self {
+        Self {
+            percentile: default_percentile(),
+            multiplier: default_multiplier(),
+            min_ms: default_min_ms(),
+            window: default_window(),
+            min_samples: default_min_samples(),
+        }
+    }
+}
+
+/// The current timeout, from a sliding window of latencies. Calls that time out count
+/// as taking the whole timeout, so a degraded API raises it rather than lowering it.
+/// It rises at once but falls a quarter of the way per call, so one fast burst after
+/// a slow spell doesn't bring it straight back down.
+pub(crate) struct AdaptiveTimeout {
+    cfg: AdaptiveTimeoutConfig,
+    max: Duration,
+    state: Mutex<State>,
+}
+
+struct State {
+    samples: LatencyWindow,
+    current: Duration,
+}
+
+impl AdaptiveTimeout {
+    pub(crate) fn new(cfg: &AdaptiveTimeoutConfig, max: Duration) -> Self {
+        let state = State { samples: LatencyWindow::new(cfg.window), current: max };
+        Self { cfg: cfg.clone(), max, state: Mutex::new(state) }
+    }
+
+    pub(crate) fn current(&self) -> Duration {
+        self.state.lock().unwrap().current
+    }
+
+    /// Records how long a call that answered (or timed out) took.
+    pub(crate) fn record(&self, latency: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.samples.push(latency);
+        let Some(percentile) = state.samples.percentile(self.cfg.percentile, self.cfg.min_samples) else {
+            return;
+        };
+        let min = Duration::from_millis(self.cfg.min_ms).min(self.max);
+        let target = percentile.mul_f64(self.cfg.multiplier.max(0.0)).clamp(min, self.max);
+        state.current = match target >= state.current {
+            true => target,
+            false => state.current - (state.current - target) / 4,
+        };
+    }
+}
+
+/// The most recent latencies, up to a fixed number.
+pub(crate) struct LatencyWindow {
+    samples: VecDeque<Duration>,
+    size: usize,
+}
+
+impl LatencyWindow {
+    pub(crate) fn new(size: usize) -> Self {
+        Self { samples: VecDeque::with_capacity(size), size: size.max(1) }
+    }
+
+    pub(crate) fn push(&mut self, latency: Duration) {
+        if self.samples.len() >= self.size {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(latency);
+    }
+
+    /// The `percentile` (0-100) of the window, once it holds at least `min_samples`.
+    pub(crate) fn percentile(&self, percentile: f64, min_samples: usize) -> Option<Duration> {
+        if self.samples.is_empty() || self.samples.len() < min_samples {
+            return None;
+        }
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        let rank = (percentile.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f64).round() as usize;
+        let (_, value, _) = sorted.select_nth_unstable(rank);
+        Some(*value)
+    }
+}
@@ -0,0 +1,120 @@
+//! Audit trail of trust decisions.
+
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use serde::Serialize;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use crate::Decision;
+
+/// One decision as written to the audit log. Session ids are only ever stored hashed.
+#[derive(Clone, Debug, Serialize)]
+pub struct DecisionRecord {
+    /// Unix time in milliseconds.
+    pub timestamp_ms: u64,
+    /// Request path the decision was made for, when the caller provided one.
+    pub route: Option<String>,
+    pub session_hash: String,
+    /// `None` when no score was used (fallbacks and local IP rules).
+    pub trust_score: Option<f32>,
+    pub outcome: &'static str,
+    /// Deny status code; absent for allows and challenges.
+    pub status: Option<u16>,
+    /// Whether the circuit breaker or failure mode decided instead of a score.
+    pub fallback: bool,
+    /// False when `enforcement_percentage` let the request through regardless of `outcome`.
+    pub enforced: bool,
+    pub latency_ms: f64,
+}
+
+impl DecisionRecord {
+    pub(crate) fn new(
+        route: Option<&str>,
+        session_hash: String,
+        trust_score: Option<f32>,
+        decision: &Decision,
+        fallback: bool,
+        enforced: bool,
+        latency_ms: f64,
+    ) -> Self {
+        let timestamp_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+        Self {
+            timestamp_ms,
+            route: route.map(str::to_string),
+            session_hash,
+            trust_score,
+            outcome: decision.outcome(),
+            status: match decision {
+                Decision::Allow { .. } | Decision::Challenge { .. } => None,
+                Decision::Deny { status, .. } => Some(*status),
+            },
+            fallback,
+            enforced,
+            latency_ms,
+        }
+    }
+}
+
+/// Receives every decision an `EGuard` makes. Called inline on the request path,
+/// so implementations should not block for long.
+pub trait DecisionLogger: Send + Sync {
+    fn log(&self, record: &DecisionRecord);
+}
+
+/// Writes each record as one line of JSON. Write errors are ignored so a full
+/// disk never fails a request.
+pub struct JsonLinesLogger {
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonLinesLogger {
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        Self { out: Mutex::new(Box::new(out)) }
+    }
+
+    pub fn stdout() -> Self {
+        Self::new(std::io::stdout())
+    }
+
+    /// Appends to `path`, creating the file if needed.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new().create(true).append(true).open(path)
+            .map_err(|e| anyhow::anyhow!("Cannot open audit log {}: {}", path.display(), e))?;
+        Ok(Self::new(file))
+    }
+}
+
+impl DecisionLogger for JsonLinesLogger {
+    fn log(&self, record: &DecisionRecord) {
+        let Ok(mut line) = serde_json::to_vec(record) else { return };
+        line.push(b'\n');
+        let mut out = self.out.lock().unwrap();
+        let _ = out.write_all(&line);
+        let _ = out.flush();
+    }
+}
+
+/// Passes every record to each logger in turn.
+pub struct FanOutLogger {
+    loggers: Vec<Arc<dyn DecisionLogger>>,
+}
+
+impl FanOutLogger {
+    pub fn new(loggers: Vec<Arc<dyn DecisionLogger>>) -> Self {
+        Self { loggers }
+    }
+}
+
+impl DecisionLogger for FanOutLogger {
+    fn log(&self, record: &DecisionRecord) {
+        for logger in &self.loggers {
+            logger.log(record);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    sync::Mutex,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use web_time::Instant;
+
+use crate::Decision;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive transient failures before the circuit opens.
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a single probe request is let through.
+    #[serde(default = "default_open_ms")]
+    pub open_ms: u64,
+    /// Decision returned while the circuit is open.
+    #[serde(default = "default_fallback")]
+    pub fallback: Decision,
+}
+
+fn default_failure_threshold() -> u32 { 5 }
+fn default_open_ms() -> u64 { 10_000 }
+fn default_fallback() -> Decision {
+    Decision::Deny { status: 503, message: "Trust service unavailable".into() }
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: default_failure_threshold(),
+            open_ms: default_open_ms(),
+            fallback: default_fallback(),
+        }
+    }
+}
+
+/// Returned by `fetch_trust` when the circuit is open and the call was not attempted.
+#[derive(Debug)]
+pub struct CircuitOpenError;
+
+impl std::fmt::Display for CircuitOpenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Trust API circuit is open")
+    }
+}
+
+impl std::error::Error for CircuitOpenError {}
+
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    /// A probe is in flight; if it never reports back (e.g. the caller was
+    /// cancelled) another probe is admitted after `until`.
+    HalfOpen { until: Instant },
+}
+
+pub struct CircuitBreaker {
+    threshold: u32,
+    open_for: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(cfg: &CircuitBreakerConfig) -> Self {
+        Self {
+            threshold: cfg.failure_threshold.max(1),
+            open_for: Duration::from_millis(cfg.open_ms),
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Whether a call may go out now. Once the open period elapses exactly one
+    /// caller is admitted as the probe; everyone else is rejected until it reports back.
+    pub fn allow_request(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match *state {
+            State::Closed { .. } => true,
+            State::Open { until } | State::HalfOpen { until } if now >= until => {
+                *state = State::HalfOpen { until: now + self.open_for };
+                true
+            }
+            State::Open { .. } | State::HalfOpen { .. } => false,
+        }
+    }
+
+    pub fn record_success(&self) {
+        *self.state.lock().unwrap() = State::Closed { failures: 0 };
+    }
+
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        let failures = match *state {
+            State::Closed { failures } => failures + 1,
+            State::Open { .. } | State::HalfOpen { .. } => self.threshold,
+        };
+        *state = if failures >= self.threshold {
+            State::Open { until: Instant::now() + self.open_for }
+        } else {
+            State::Closed { failures }
+        };
+    }
+
+    pub fn is_open(&self) -> bool {
+        !matches!(*self.state.lock().unwrap(), State::Closed { .. })
+    }
+}
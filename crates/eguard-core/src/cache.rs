@@ -0,0 +1,177 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Mutex,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use web_time::Instant;
+
+use crate::TrustResponse;
+
+#[cfg(feature = "redis")]
+mod redis;
+#[cfg(feature = "redis")]
+pub use self::redis::RedisCache;
+
+/// Storage for fetched trust scores. Implementations own their TTL policy.
+///
+/// Backend failures are reported to the caller, which treats them as a miss
+/// so a broken cache never blocks a decision.
+#[async_trait]
+pub trait TrustCache: Send + Sync {
+    async fn get(&self, session_id: &str) -> anyhow::Result<Option<TrustResponse>>;
+    async fn insert(&self, session_id: &str, trust: &TrustResponse) -> anyhow::Result<()>;
+
+    /// An expired entry that is still within the cache's maximum staleness, used when
+    /// the Trust API is failing. The default keeps nothing past expiry.
+    async fn get_stale(&self, session_id: &str) -> anyhow::Result<Option<TrustResponse>> {
+        let _ = session_id;
+        Ok(None)
+    }
+}
+
+struct Entry {
+    trust: TrustResponse,
+    expires_at: Instant,
+}
+
+/// Entries by session id, with the ids in order of expiry so the first to expire can
+/// be evicted without a scan.
+#[derive(Default)]
+struct Entries {
+    map: HashMap<String, Entry>,
+    by_expiry: BTreeSet<(Instant, String)>,
+}
+
+impl Entries {
+    fn remove(&mut self, session_id: &str) {
+        if let Some(e) = self.map.remove(session_id) {
+            self.by_expiry.remove(&(e.expires_at, session_id.to_string()));
+        }
+    }
+}
+
+/// Bounded in-process TTL cache of trust responses keyed by session id.
+pub struct MemoryCache {
+    ttl: Duration,
+    max_stale: Duration,
+    max_entries: usize,
+    entries: Mutex<Entries>,
+}
+
+impl MemoryCache {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self { ttl, max_stale: Duration::ZERO, max_entries, entries: Mutex::default() }
+    }
+
+    /// Keep entries for `max_stale` after they expire so `get_stale` can return them.
+    pub fn with_max_stale(mut self, max_stale: Duration) -> Self {
+        self.max_stale = max_stale;
+        self
+    }
+
+    pub fn get(&self, session_id: &str) -> Option<TrustResponse> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        match entries.map.get(session_id) {
+            Some(e) if e.expires_at > now => Some(e.trust.clone()),
+            Some(e) if e.expires_at + self.max_stale <= now => {
+                entries.remove(session_id);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    pub fn get_stale(&self, session_id: &str) -> Option<TrustResponse> {
+        let now = Instant::now();
+        self.entries.lock().unwrap().map.get(session_id)
+            .filter(|e| e.expires_at + self.max_stale > now)
+            .map(|e| e.trust.clone())
+    }
+
+    pub fn insert(&self, session_id: &str, trust: TrustResponse) {
+        if self.max_entries == 0 { return; }
+        let now = Instant::now();
+        let mut guard = self.entries.lock().unwrap();
+        let entries = &mut *guard;
+
+        entries.remove(session_id);
+        // Drop entries past their staleness, then, if still full, whichever would
+        // have expired first.
+        while let Some((expires_at, _)) = entries.by_expiry.first()
+            && (*expires_at + self.max_stale <= now || entries.map.len() >= self.max_entries)
+        {
+            let (_, oldest) = entries.by_expiry.pop_first().unwrap();
+            entries.map.remove(&oldest);
+        }
+
+        let expires_at = now + self.ttl;
+        entries.by_expiry.insert((expires_at, session_id.to_string()));
+        entries.map.insert(session_id.to_string(), Entry { trust, expires_at });
+    }
+
+    pub fn remove(&self, session_id: &str) {
+        self.entries.lock().unwrap().remove(session_id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl TrustCache for MemoryCache {
+    async fn get(&self, session_id: &str) -> anyhow::Result<Option<TrustResponse>> {
+        Ok(MemoryCache::get(self, session_id))
+    }
+
+    async fn insert(&self, session_id: &str, trust: &TrustResponse) -> anyhow::Result<()> {
+        MemoryCache::insert(self, session_id, trust.clone());
+        Ok(())
+    }
+
+    async fn get_stale(&self, session_id: &str) -> anyhow::Result<Option<TrustResponse>> {
+        Ok(MemoryCache::get_stale(self, session_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trust(session_id: &str) -> TrustResponse {
+        TrustResponse { session_id: session_id.to_string(), trust_score: 0.5, ..Default::default() }
+    }
+
+    #[test]
+    fn full_cache_evicts_the_first_to_expire() {
+        let cache = MemoryCache::new(Duration::from_secs(60), 2);
+        cache.insert("a", trust("a"));
+        cache.insert("b", trust("b"));
+        // Refreshing `a` moves it behind `b`.
+        std::thread::sleep(Duration::from_millis(1));
+        cache.insert("a", trust("a"));
+        cache.insert("c", trust("c"));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("a").is_some() && cache.get("c").is_some());
+        assert!(cache.get("b").is_none());
+
+        cache.remove("a");
+        cache.insert("d", trust("d"));
+        assert!(cache.get("c").is_some() && cache.get("d").is_some());
+    }
+
+    #[test]
+    fn dead_entries_are_dropped_on_insert() {
+        let cache = MemoryCache::new(Duration::ZERO, 10);
+        cache.insert("a", trust("a"));
+        cache.insert("b", trust("b"));
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use ::redis::{AsyncCommands, Client, aio::MultiplexedConnection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use super::TrustCache;
+use crate::TrustResponse;
+
+const KEY_PREFIX: &str = "eguard:trust:";
+
+/// Trust cache shared across processes through Redis. Entries expire server-side
+/// once they are also too old to be served stale.
+pub struct RedisCache {
+    client: Client,
+    ttl: Duration,
+    max_stale: Duration,
+    conn: OnceCell<MultiplexedConnection>,
+}
+
+/// A cached response with its logical expiry. Entries written without one count as fresh.
+#[derive(Serialize, Deserialize)]
+struct Stored {
+    #[serde(flatten)]
+    trust: TrustResponse,
+    #[serde(default)]
+    expires_at_ms: Option<u64>,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+impl RedisCache {
+    pub fn new(url: &str, ttl: Duration) -> anyhow::Result<Self> {
+        let client = Client::open(url)
+            .map_err(|e| anyhow::anyhow!("Invalid redis url {}: {}", url, e))?;
+        Ok(Self { client, ttl, max_stale: Duration::ZERO, conn: OnceCell::new() })
+    }
+
+    /// Keep entries for `max_stale` after they expire so `get_stale` can return them.
+    pub fn with_max_stale(mut self, max_stale: Duration) -> Self {
+        self.max_stale = max_stale;
+        self
+    }
+
+    async fn get_stored(&self, session_id: &str) -> anyhow::Result<Option<Stored>> {
+        let raw: Option<String> = self.conn().await?
+            .get(format!("{KEY_PREFIX}{session_id}"))
+            .await?;
+        Ok(raw.map(|s| serde_json::from_str(&s)).transpose()?)
+    }
+
+    async fn conn(&self) -> anyhow::Result<MultiplexedConnection> {
+        let conn = self.conn
+            .get_or_try_init(|| self.client.get_multiplexed_async_connection())
+            .await?;
+        Ok(conn.clone())
+    }
+}
+
+#[async_trait]
+impl TrustCache for RedisCache {
+    async fn get(&self, session_id: &str) -> anyhow::Result<Option<TrustResponse>> {
+        let stored = self.get_stored(session_id).await?;
+        Ok(stored.filter(|s| s.expires_at_ms.is_none_or(|at| at > now_ms())).map(|s| s.trust))
+    }
+
+    async fn insert(&self, session_id: &str, trust: &TrustResponse) -> anyhow::Result<()> {
+        let ttl_ms = self.ttl.as_millis() as u64;
+        let stored = Stored { trust: trust.clone(), expires_at_ms: Some(now_ms() + ttl_ms) };
+        let raw = serde_json::to_string(&stored)?;
+        let _: () = self.conn().await?
+            .pset_ex(format!("{KEY_PREFIX}{session_id}"), raw, ttl_ms + self.max_stale.as_millis() as u64)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_stale(&self, session_id: &str) -> anyhow::Result<Option<TrustResponse>> {
+        Ok(self.get_stored(session_id).await?.map(|s| s.trust))
+    }
+}
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::rt;
+
+/// Caps the Trust API calls in flight at once, protecting both sides during traffic spikes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConcurrencyLimitConfig {
+    /// At least 1.
+    pub max_in_flight: usize,
+    /// What happens to calls over the limit.
+    #[serde(default)]
+    pub shed: ShedPolicy,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum ShedPolicy {
+    /// Fail the call at once, so the stale score, local scorer or `failure_mode` applies.
+    #[default]
+    Fallback,
+    /// Wait up to `timeout_ms` for a call to finish, then fail as `Fallback` does.
+    Queue { timeout_ms: u64 },
+}
+
+/// Returned when a Trust API call was shed by `concurrency_limit`.
+#[derive(Debug)]
+pub struct Overloaded;
+
+impl std::fmt::Display for Overloaded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Too many Trust API calls in flight")
+    }
+}
+
+impl std::error::Error for Overloaded {}
+
+pub(crate) struct ConcurrencyLimit {
+    permits: Semaphore,
+    shed: ShedPolicy,
+}
+
+impl ConcurrencyLimit {
+    pub(crate) fn new(cfg: &ConcurrencyLimitConfig) -> Self {
+        Self { permits: Semaphore::new(cfg.max_in_flight), shed: cfg.shed.clone() }
+    }
+
+    /// A slot for one call, held until the permit is dropped.
+    pub(crate) async fn acquire(&self) -> Result<SemaphorePermit<'_>, Overloaded> {
+        match &self.shed {
+            ShedPolicy::Fallback => self.permits.try_acquire().map_err(|_| Overloaded),
+            ShedPolicy::Queue { timeout_ms } => {
+                rt::timeout(Duration::from_millis(*timeout_ms), self.permits.acquire())
+                    .await
+                    .and_then(Result::ok)
+                    .ok_or(Overloaded)
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::{path::Path, sync::LazyLock};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{path::PathBuf, time::Duration};
+
+use regex::{Captures, Regex};
+use serde_json::Value;
+
+use crate::{EGuardConfig, EGuardError};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{EGuard, rt};
+
+static ENV_REF: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap());
+
+impl EGuardConfig {
+    /// Loads a config from a `.yaml`/`.yml`, `.toml` or `.json` file. `${VAR}` in any
+    /// string value is replaced with the environment variable `VAR`, so secrets such
+    /// as `api_key` can stay out of the file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, EGuardError> {
+        Self::load(path.as_ref()).map_err(EGuardError::ConfigInvalid)
+    }
+
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read config {}: {}", path.display(), e))?;
+
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+        let mut raw: Value = match ext.as_str() {
+            "yaml" | "yml" => serde_yaml::from_str(&text)?,
+            "toml" => toml::from_str(&text)?,
+            "json" => serde_json::from_str(&text)?,
+            _ => return Err(anyhow::anyhow!("Unsupported config format: {}", path.display())),
+        };
+
+        substitute_env(&mut raw)?;
+        serde_json::from_value(raw).map_err(|e| anyhow::anyhow!("Invalid config {}: {}", path.display(), e))
+    }
+}
+
+/// Stops the config file watcher when dropped.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct ConfigWatcher(tokio::task::JoinHandle<()>);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl EGuard {
+    /// Polls `path` every `interval` on the current tokio runtime and `reload`s the
+    /// guard whenever the file's modification time changes. Files that fail to load
+    /// are logged and skipped, leaving the current config in place.
+    pub fn watch_config(&self, path: impl Into<PathBuf>, interval: Duration) -> ConfigWatcher {
+        let path = path.into();
+        let guard = self.clone();
+        let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+        let task = tokio::spawn(async move {
+            let mut last = modified(&path);
+            loop {
+                rt::sleep(interval).await;
+                let current = modified(&path);
+                if current == last {
+                    continue;
+                }
+                last = current;
+                match EGuardConfig::from_file(&path).and_then(|cfg| guard.reload(cfg)) {
+                    Ok(()) => tracing::info!(path = %path.display(), "eguard config reloaded"),
+                    Err(e) => tracing::warn!(path = %path.display(), error = %e, "eguard config reload failed"),
+                }
+            }
+        });
+        ConfigWatcher(task)
+    }
+}
+
+fn substitute_env(value: &mut Value) -> anyhow::Result<()> {
+    match value {
+        Value::String(s) if s.contains("${") => {
+            let mut missing = None;
+            let replaced = ENV_REF.replace_all(s, |c: &Captures| {
+                std::env::var(&c[1]).unwrap_or_else(|_| {
+                    missing.get_or_insert_with(|| c[1].to_string());
+                    String::new()
+                })
+            });
+            if let Some(var) = missing {
+                return Err(anyhow::anyhow!("Environment variable {} is not set", var));
+            }
+            *s = replaced.into_owned();
+        }
+        Value::Array(items) => items.iter_mut().try_for_each(substitute_env)?,
+        Value::Object(map) => map.values_mut().try_for_each(substitute_env)?,
+        _ => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use std::{borrow::Cow, collections::BTreeMap, net::IpAddr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{SessionExtraction, SessionSource, geoip::GeoInfo, routes::percent_decode};
+
+/// Headers that carry credentials and are never forwarded to the Trust API.
+const SENSITIVE_HEADERS: [&str; 4] = ["authorization", "cookie", "proxy-authorization", "set-cookie"];
+
+/// What the caller knows about the request being decided. Sent to the Trust API
+/// with the session id so it can score on more than the session alone.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RequestContext {
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    /// Lowercased names; repeated headers are joined with `, `.
+    pub headers: BTreeMap<String, String>,
+    pub path: String,
+    pub method: String,
+    /// Filled in from `geoip` when configured, unless the caller already set it.
+    #[serde(default)]
+    pub geo: Option<GeoInfo>,
+    /// Device or fingerprint id, from `session_extraction.device_sources`.
+    #[serde(default)]
+    pub device_id: Option<String>,
+    /// JA3 fingerprint of the client's TLS handshake; see `TlsFingerprintConfig`.
+    #[serde(default)]
+    pub ja3: Option<String>,
+    /// JA4 fingerprint of the client's TLS handshake.
+    #[serde(default)]
+    pub ja4: Option<String>,
+}
+
+/// Headers in which a TLS-terminating load balancer passes on the client's JA3/JA4
+/// fingerprints. They fill `RequestContext::ja3`/`ja4` unless the caller set them, are
+/// forwarded to the Trust API and can be matched by rules (`if ja4 in [...] then deny`).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TlsFingerprintConfig {
+    /// e.g. `x-ja3-fingerprint`
+    #[serde(default)]
+    pub ja3_header: Option<String>,
+    /// e.g. `x-ja4-fingerprint`
+    #[serde(default)]
+    pub ja4_header: Option<String>,
+}
+
+impl TlsFingerprintConfig {
+    pub(crate) fn apply(&self, ctx: &mut Cow<'_, RequestContext>) {
+        if ctx.ja3.is_none()
+            && let Some(value) = header(ctx, self.ja3_header.as_deref())
+        {
+            ctx.to_mut().ja3 = Some(value);
+        }
+        if ctx.ja4.is_none()
+            && let Some(value) = header(ctx, self.ja4_header.as_deref())
+        {
+            ctx.to_mut().ja4 = Some(value);
+        }
+    }
+}
+
+fn header(ctx: &RequestContext, name: Option<&str>) -> Option<String> {
+    ctx.headers.get(&name?.to_ascii_lowercase()).filter(|v| !v.is_empty()).cloned()
+}
+
+impl RequestContext {
+    pub fn new(method: impl Into<String>, path: impl Into<String>) -> Self {
+        Self { method: method.into(), path: path.into(), ..Self::default() }
+    }
+
+    pub fn with_ip(mut self, ip: Option<IpAddr>) -> Self {
+        self.ip = ip;
+        self
+    }
+
+    pub fn with_device_id(mut self, device_id: Option<String>) -> Self {
+        self.device_id = device_id;
+        self
+    }
+
+    /// Adds request headers, dropping credentials. Also fills `user_agent` from `User-Agent`.
+    pub fn with_headers<'a>(mut self, headers: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        for (name, value) in headers {
+            let name = name.to_ascii_lowercase();
+            if SENSITIVE_HEADERS.contains(&name.as_str()) {
+                continue;
+            }
+            if name == "user-agent" && self.user_agent.is_none() {
+                self.user_agent = Some(value.to_string());
+            }
+            self.headers.entry(name)
+                .and_modify(|v| {
+                    v.push_str(", ");
+                    v.push_str(value);
+                })
+                .or_insert_with(|| value.to_string());
+        }
+        self
+    }
+
+    pub fn host(&self) -> Option<&str> {
+        self.headers.get("host").map(String::as_str)
+    }
+
+    /// This context as sent to the Trust API: without the headers and query parameter
+    /// that `extraction` reads session and device ids from, so raw ids don't reach it
+    /// beside hashed ones. Cookies are never forwarded anyway.
+    pub(crate) fn forwarded(&self, extraction: &SessionExtraction) -> Cow<'_, Self> {
+        let mut ctx = Cow::Borrowed(self);
+        for source in extraction.effective_sources().iter().chain(&extraction.device_sources) {
+            match source {
+                SessionSource::Header(name) | SessionSource::Bearer(name) => {
+                    let name = name.to_ascii_lowercase();
+                    if ctx.headers.contains_key(&name) {
+                        ctx.to_mut().headers.remove(&name);
+                    }
+                }
+                SessionSource::Query(name) => {
+                    if let Some(path) = without_query_param(&ctx.path, name) {
+                        ctx.to_mut().path = path;
+                    }
+                }
+                SessionSource::Cookie(_) => {}
+            }
+        }
+        ctx
+    }
+}
+
+/// `path` without the query parameter `name`; `None` if it doesn't have it.
+fn without_query_param(path: &str, name: &str) -> Option<String> {
+    let (base, query) = path.split_once('?')?;
+    let is_name = |pair: &str| percent_decode(pair.split_once('=').map_or(pair, |(k, _)| k)) == name;
+    if !query.split('&').any(is_name) {
+        return None;
+    }
+    let kept: Vec<&str> = query.split('&').filter(|pair| !is_name(pair)).collect();
+    Some(match kept.is_empty() {
+        true => base.to_string(),
+        false => format!("{base}?{}", kept.join("&")),
+    })
+}
@@ -0,0 +1,120 @@
+//! Operator-defined bodies for denied requests, so clients get a stable JSON error or a
+//! branded page instead of the internal deny message.
+//!
+//! Templates may use `{{status}}`, `{{message}}`, `{{score}}`, `{{reason}}` and
+//! `{{request_id}}`; unknown variables are left as they are, and a missing score or
+//! reason is empty.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::ReasonCode;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DenyBodyConfig {
+    /// A JSON document whose string values may use the variables. A string that is
+    /// exactly `{{status}}` or `{{score}}` becomes a number.
+    Json { template: Value },
+    /// An HTML page, read when the config is loaded. Variables are HTML-escaped.
+    Html { path: String },
+}
+
+/// A rendered deny body.
+#[derive(Clone, Debug)]
+pub struct DenyBody {
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+/// Values of the template variables.
+pub(crate) struct DenyVars<'a> {
+    pub(crate) status: u16,
+    pub(crate) message: &'a str,
+    pub(crate) score: Option<f32>,
+    pub(crate) reason: Option<&'a ReasonCode>,
+    pub(crate) request_id: &'a str,
+}
+
+pub(crate) enum DenyTemplate {
+    Json(Value),
+    Html(String),
+}
+
+impl DenyTemplate {
+    pub(crate) fn new(cfg: &DenyBodyConfig) -> anyhow::Result<Self> {
+        match cfg {
+            DenyBodyConfig::Json { template } => Ok(Self::Json(template.clone())),
+            DenyBodyConfig::Html { path } => {
+                let page = std::fs::read_to_string(path)
+                    .map_err(|e| anyhow::anyhow!("Cannot read deny_body page {}: {}", path, e))?;
+                Ok(Self::Html(page))
+            }
+        }
+    }
+
+    pub(crate) fn render(&self, vars: &DenyVars) -> DenyBody {
+        match self {
+            Self::Json(template) => DenyBody { content_type: "application/json", body: render_json(template, vars).to_string() },
+            Self::Html(page) => DenyBody { content_type: "text/html; charset=utf-8", body: substitute(page, vars, escape_html) },
+        }
+    }
+}
+
+fn render_json(value: &Value, vars: &DenyVars) -> Value {
+    match value {
+        Value::String(s) if s == "{{status}}" => vars.status.into(),
+        // Through the f32's shortest decimal form, so 0.1 doesn't become 0.10000000149011612.
+        Value::String(s) if s == "{{score}}" => vars.score.and_then(|s| s.to_string().parse::<f64>().ok()).map_or(Value::Null, Into::into),
+        Value::String(s) => Value::String(substitute(s, vars, str::to_string)),
+        Value::Array(items) => items.iter().map(|v| render_json(v, vars)).collect(),
+        Value::Object(fields) => fields.iter().map(|(k, v)| (k.clone(), render_json(v, vars))).collect(),
+        other => other.clone(),
+    }
+}
+
+/// Replaces the variables in one pass, so values are never expanded themselves.
+fn substitute(text: &str, vars: &DenyVars, escape: fn(&str) -> String) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else { break };
+        out.push_str(&rest[..start]);
+        let value = match &after[..end] {
+            "status" => Some(vars.status.to_string()),
+            "message" => Some(escape(vars.message)),
+            "score" => Some(vars.score.map(|s| s.to_string()).unwrap_or_default()),
+            "reason" => Some(escape(vars.reason.map_or("", ReasonCode::as_str))),
+            "request_id" => Some(escape(vars.request_id)),
+            _ => None,
+        };
+        match value {
+            Some(value) => {
+                out.push_str(&value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                out.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
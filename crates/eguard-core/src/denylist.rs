@@ -0,0 +1,80 @@
+//! A local copy of the Trust API's banned sessions, kept in sync in the background so
+//! known-bad sessions are denied without a Trust API call.
+//!
+//! The API serves it as a delta feed: `GET {api_base_url}/eguard/denylist?since=<cursor>`
+//! returns the sessions banned and unbanned since `cursor`, and a new cursor. The first
+//! sync, and any sync the API answers with `full: true`, replaces the whole list.
+
+use std::{
+    collections::HashSet,
+    sync::{Mutex, RwLock},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ReasonCode, TrustResponse};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DenylistConfig {
+    /// How often the background task pulls changes.
+    pub interval_ms: u64,
+}
+
+impl Default for DenylistConfig {
+    fn default() -> Self {
+        Self { interval_ms: 30_000 }
+    }
+}
+
+/// One page of the denylist feed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DenylistDelta {
+    /// Pass as `since` on the next sync.
+    pub cursor: String,
+    /// The sessions in `added` are the whole list; drop everything else.
+    #[serde(default)]
+    pub full: bool,
+    #[serde(default)]
+    pub added: Vec<String>,
+    #[serde(default)]
+    pub removed: Vec<String>,
+}
+
+#[derive(Default)]
+pub(crate) struct Denylist {
+    sessions: RwLock<HashSet<String>>,
+    cursor: Mutex<Option<String>>,
+}
+
+impl Denylist {
+    pub(crate) fn cursor(&self) -> Option<String> {
+        self.cursor.lock().unwrap().clone()
+    }
+
+    pub(crate) fn apply(&self, delta: DenylistDelta) {
+        let mut sessions = self.sessions.write().unwrap();
+        if delta.full {
+            sessions.clear();
+        }
+        for sid in &delta.removed {
+            sessions.remove(sid);
+        }
+        sessions.extend(delta.added);
+        *self.cursor.lock().unwrap() = Some(delta.cursor);
+    }
+
+    /// A zero score for a listed session.
+    pub(crate) fn get(&self, session_id: &str) -> Option<TrustResponse> {
+        self.sessions.read().unwrap().contains(session_id).then(|| TrustResponse {
+            session_id: session_id.to_string(),
+            trust_score: 0.0,
+            reason: Some(ReasonCode::SessionBanned),
+            ..Default::default()
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.sessions.read().unwrap().len()
+    }
+}
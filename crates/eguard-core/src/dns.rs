@@ -0,0 +1,144 @@
+//! DNS caching for Trust API calls, so a slow resolver doesn't eat into the request
+//! timeout. Addresses are looked up once, ahead of the first call when the guard is
+//! created on a tokio runtime, and reused for `ttl_ms`; after that they are looked up
+//! again in the background while the old ones stay in use.
+
+use std::{collections::BTreeMap, net::IpAddr};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DnsConfig {
+    #[serde(default = "default_ttl_ms")]
+    pub ttl_ms: u64,
+    /// Fixed addresses for hosts, used instead of DNS: `{"trust.example.com": ["10.0.0.5"]}`.
+    /// The port still comes from the URL.
+    #[serde(default)]
+    pub addresses: BTreeMap<String, Vec<IpAddr>>,
+}
+
+fn default_ttl_ms() -> u64 { 60_000 }
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        Self { ttl_ms: default_ttl_ms(), addresses: BTreeMap::new() }
+    }
+}
+
+impl DnsConfig {
+    /// Sets `builder` up to resolve through a cache, and starts looking up the host of
+    /// `base_url`.
+    pub(crate) fn apply(&self, builder: reqwest::ClientBuilder, base_url: &str) -> anyhow::Result<reqwest::ClientBuilder> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut builder = builder;
+            for (host, ips) in &self.addresses {
+                let addrs: Vec<_> = ips.iter().map(|ip| std::net::SocketAddr::new(*ip, 0)).collect();
+                builder = builder.resolve_to_addrs(host, &addrs);
+            }
+            let cache = DnsCache::new(self);
+            if let Some(host) = reqwest::Url::parse(base_url).ok().as_ref().and_then(|url| url.domain())
+                && !self.addresses.contains_key(host)
+            {
+                cache.prefetch(host);
+            }
+            Ok(builder.dns_resolver(std::sync::Arc::new(cache)))
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = (builder, base_url);
+            Err(anyhow::anyhow!("dns is not supported on wasm32"))
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use resolver::DnsCache;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod resolver {
+    use std::{
+        collections::HashMap,
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+    use super::DnsConfig;
+
+    struct Entry {
+        addrs: Vec<SocketAddr>,
+        resolved: Instant,
+        refreshing: bool,
+    }
+
+    #[derive(Clone)]
+    pub(crate) struct DnsCache {
+        ttl: Duration,
+        entries: Arc<Mutex<HashMap<String, Entry>>>,
+    }
+
+    impl DnsCache {
+        pub fn new(cfg: &DnsConfig) -> Self {
+            Self { ttl: Duration::from_millis(cfg.ttl_ms), entries: Arc::default() }
+        }
+
+        /// Looks `host` up in the background, if there is a runtime to do it on.
+        pub fn prefetch(&self, host: &str) {
+            let Ok(rt) = tokio::runtime::Handle::try_current() else { return };
+            let (cache, host) = (self.clone(), host.to_string());
+            rt.spawn(async move {
+                if let Err(e) = cache.refresh(&host).await {
+                    tracing::warn!(error = %e, host = %host, "DNS lookup failed");
+                }
+            });
+        }
+
+        async fn get(&self, host: &str) -> std::io::Result<Vec<SocketAddr>> {
+            {
+                let mut entries = self.entries.lock().unwrap();
+                if let Some(entry) = entries.get_mut(host) {
+                    if entry.resolved.elapsed() >= self.ttl && !entry.refreshing {
+                        entry.refreshing = true;
+                        self.prefetch(host);
+                    }
+                    return Ok(entry.addrs.clone());
+                }
+            }
+            self.refresh(host).await
+        }
+
+        /// Looks `host` up and caches the result. On failure, cached addresses are kept
+        /// and tried again after another `ttl`.
+        async fn refresh(&self, host: &str) -> std::io::Result<Vec<SocketAddr>> {
+            let result = tokio::net::lookup_host((host, 0)).await;
+            let mut entries = self.entries.lock().unwrap();
+            match result {
+                Ok(addrs) => {
+                    let addrs: Vec<SocketAddr> = addrs.collect();
+                    entries.insert(host.to_string(), Entry { addrs: addrs.clone(), resolved: Instant::now(), refreshing: false });
+                    Ok(addrs)
+                }
+                Err(e) => {
+                    if let Some(entry) = entries.get_mut(host) {
+                        entry.resolved = Instant::now();
+                        entry.refreshing = false;
+                    }
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    impl Resolve for DnsCache {
+        fn resolve(&self, name: Name) -> Resolving {
+            let cache = self.clone();
+            Box::pin(async move {
+                let addrs = cache.get(name.as_str()).await?;
+                Ok(Box::new(addrs.into_iter()) as Addrs)
+            })
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use web_time::Instant;
+
+use crate::{ReasonCode, RequestContext, TrustProvider, TrustResponse, denylist::DenylistDelta, events::{Event, Outcome}, redact::Redacted};
+
+/// Spread Trust API calls over several deployments, such as one per region, skipping
+/// the ones that are failing so an outage of one doesn't fail lookups.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EndpointsConfig {
+    /// Deployments in addition to `api_base_url`, which comes first. They use the same
+    /// transport, key and settings.
+    pub api_base_urls: Vec<String>,
+    #[serde(default)]
+    pub selection: EndpointSelection,
+    /// Consecutive failures after which an endpoint is marked unhealthy.
+    #[serde(default = "default_unhealthy_after")]
+    pub unhealthy_after: u32,
+    /// How long an unhealthy endpoint is skipped before a lookup tries it again.
+    #[serde(default = "default_retry_after_ms")]
+    pub retry_after_ms: u64,
+    /// Probe every endpoint in the background; see `EGuard::spawn_health_checker`.
+    #[serde(default)]
+    pub health_check: Option<HealthCheckConfig>,
+}
+
+fn default_unhealthy_after() -> u32 { 3 }
+fn default_retry_after_ms() -> u64 { 10_000 }
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointSelection {
+    /// The first healthy endpoint in the order listed.
+    #[default]
+    Priority,
+    /// The healthy endpoint with the lowest recent latency.
+    Latency,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HealthCheckConfig {
+    #[serde(default = "default_interval_ms")]
+    pub interval_ms: u64,
+    /// Looked up as the probe; any answer, including an unknown session, is healthy.
+    #[serde(default = "default_session_id")]
+    pub session_id: String,
+}
+
+fn default_interval_ms() -> u64 { 5_000 }
+fn default_session_id() -> String { "eguard-health-check".to_string() }
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self { interval_ms: default_interval_ms(), session_id: default_session_id() }
+    }
+}
+
+/// Weight of the newest call in an endpoint's average latency.
+const LATENCY_WEIGHT: f64 = 0.2;
+
+struct Endpoint {
+    url: String,
+    provider: Arc<dyn TrustProvider>,
+    health: Mutex<Health>,
+}
+
+#[derive(Default)]
+struct Health {
+    failures: u32,
+    down_since: Option<Instant>,
+    latency: Option<Duration>,
+}
+
+/// Calls endpoints in the configured order, healthy ones first, and moves on to the
+/// next one when a call fails. An unhealthy endpoint is tried again after
+/// `retry_after_ms`, or as soon as a health check succeeds; when every endpoint is
+/// unhealthy they are all still tried.
+pub struct EndpointPool {
+    endpoints: Vec<Endpoint>,
+    selection: EndpointSelection,
+    unhealthy_after: u32,
+    retry_after: Duration,
+}
+
+impl EndpointPool {
+    pub fn new(cfg: &EndpointsConfig) -> Self {
+        Self {
+            endpoints: Vec::new(),
+            selection: cfg.selection,
+            unhealthy_after: cfg.unhealthy_after.max(1),
+            retry_after: Duration::from_millis(cfg.retry_after_ms),
+        }
+    }
+
+    /// Appends the endpoint at `url`, served by `provider`.
+    pub fn with_endpoint(mut self, url: &str, provider: Arc<dyn TrustProvider>) -> Self {
+        self.endpoints.push(Endpoint { url: url.to_string(), provider, health: Mutex::default() });
+        self
+    }
+
+    /// The URLs of the endpoints currently marked unhealthy.
+    pub fn unhealthy(&self) -> Vec<String> {
+        self.endpoints.iter()
+            .filter(|e| e.health.lock().unwrap().down_since.is_some())
+            .map(|e| e.url.clone())
+            .collect()
+    }
+
+    /// Looks up `session_id` on every endpoint and records the outcome.
+    pub async fn check(&self, session_id: &str) {
+        for endpoint in &self.endpoints {
+            let started = Instant::now();
+            let result = endpoint.provider.fetch(session_id).await;
+            self.record(endpoint, result.as_ref().map(|_| started.elapsed()).map_err(|e| e.to_string()));
+        }
+    }
+
+    /// Indexes of the endpoints to try, in order.
+    fn order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let mut ranked: Vec<(bool, Duration, usize)> = self.endpoints.iter().enumerate()
+            .map(|(i, e)| {
+                let health = e.health.lock().unwrap();
+                let up = health.down_since.is_none_or(|since| now.duration_since(since) >= self.retry_after);
+                let latency = match self.selection {
+                    EndpointSelection::Priority => Duration::ZERO,
+                    EndpointSelection::Latency => health.latency.unwrap_or_default(),
+                };
+                (!up, latency, i)
+            })
+            .collect();
+        ranked.sort();
+        ranked.into_iter().map(|(_, _, i)| i).collect()
+    }
+
+    fn record(&self, endpoint: &Endpoint, outcome: Result<Duration, String>) {
+        let mut health = endpoint.health.lock().unwrap();
+        match outcome {
+            Ok(latency) => {
+                if health.down_since.take().is_some() {
+                    tracing::info!(endpoint = %endpoint.url, "Trust API endpoint is healthy again");
+                }
+                health.failures = 0;
+                health.latency = Some(match health.latency {
+                    Some(avg) => avg.mul_f64(1.0 - LATENCY_WEIGHT) + latency.mul_f64(LATENCY_WEIGHT),
+                    None => latency,
+                });
+            }
+            Err(error) => {
+                health.failures = health.failures.saturating_add(1);
+                if health.failures >= self.unhealthy_after {
+                    if health.down_since.is_none() {
+                        tracing::warn!(endpoint = %endpoint.url, error = %Redacted(&error), "Trust API endpoint marked unhealthy");
+                    }
+                    // Restarts the wait, so a failed retry skips it for another `retry_after`.
+                    health.down_since = Some(Instant::now());
+                }
+            }
+        }
+    }
+
+    async fn first<T, F, Fut>(&self, call: F) -> anyhow::Result<T>
+    where
+        F: Fn(Arc<dyn TrustProvider>) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let order = self.order();
+        let mut last = None;
+        for (n, i) in order.iter().enumerate() {
+            let endpoint = &self.endpoints[*i];
+            let started = Instant::now();
+            match call(endpoint.provider.clone()).await {
+                Ok(out) => {
+                    self.record(endpoint, Ok(started.elapsed()));
+                    return Ok(out);
+                }
+                Err(e) => {
+                    self.record(endpoint, Err(e.to_string()));
+                    if n + 1 < order.len() {
+                        tracing::warn!(error = %Redacted(&e), endpoint = %endpoint.url, "Trust API endpoint failed, trying the next one");
+                    }
+                    last = Some(e);
+                }
+            }
+        }
+        Err(last.unwrap_or_else(|| anyhow::anyhow!("EndpointPool has no endpoints")))
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl TrustProvider for EndpointPool {
+    async fn fetch(&self, session_id: &str) -> anyhow::Result<TrustResponse> {
+        self.first(|p| async move { p.fetch(session_id).await }).await
+    }
+
+    async fn fetch_with_context(&self, session_id: &str, ctx: &RequestContext) -> anyhow::Result<TrustResponse> {
+        self.first(|p| async move { p.fetch_with_context(session_id, ctx).await }).await
+    }
+
+    async fn fetch_batch(&self, session_ids: &[&str]) -> anyhow::Result<Vec<TrustResponse>> {
+        self.first(|p| async move { p.fetch_batch(session_ids).await }).await
+    }
+
+    async fn report_events(&self, events: &[Event]) -> anyhow::Result<()> {
+        self.first(|p| async move { p.report_events(events).await }).await
+    }
+
+    async fn report_outcome(&self, session_id: &str, outcome: Outcome) -> anyhow::Result<()> {
+        self.first(|p| async move { p.report_outcome(session_id, outcome).await }).await
+    }
+
+    async fn revoke_session(&self, session_id: &str, reason: &ReasonCode) -> anyhow::Result<()> {
+        self.first(|p| async move { p.revoke_session(session_id, reason).await }).await
+    }
+
+    async fn fetch_denylist(&self, cursor: Option<&str>) -> anyhow::Result<DenylistDelta> {
+        self.first(|p| async move { p.fetch_denylist(cursor).await }).await
+    }
+}
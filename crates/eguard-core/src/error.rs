@@ -0,0 +1,93 @@
+//! The error type of the public `EGuard` API. Providers and internals use `anyhow`;
+//! errors are sorted into kinds at the API boundary. Messages are redacted, as they
+//! may quote request URLs and response bodies.
+
+use crate::{ApiStatusError, DeadlineExceeded, breaker::CircuitOpenError, concurrency::Overloaded, flight::cause, provider::ProviderTimeout, redact::Redacted, throttle::Throttled};
+
+#[derive(Debug, thiserror::Error)]
+pub enum EGuardError {
+    /// The config, or something built from it, was rejected.
+    #[error("{}", Redacted(.0))]
+    ConfigInvalid(anyhow::Error),
+    /// The Trust API could not be reached.
+    #[error("{}", Redacted(.0))]
+    Network(anyhow::Error),
+    /// No answer in time: the transport or adaptive timeout, a fallback provider's
+    /// timeout or the `decide_with_deadline` budget.
+    #[error("{}", Redacted(.0))]
+    Timeout(anyhow::Error),
+    #[error(transparent)]
+    ApiStatus(#[from] ApiStatusError),
+    /// The Trust API's answer could not be parsed.
+    #[error("{}", Redacted(.0))]
+    Deserialize(anyhow::Error),
+    #[error(transparent)]
+    RateLimited(#[from] Throttled),
+    #[error(transparent)]
+    CircuitOpen(#[from] CircuitOpenError),
+    /// Shed by `concurrency_limit`.
+    #[error(transparent)]
+    Overloaded(#[from] Overloaded),
+    /// Anything else, such as a bad webhook signature or a failing secret store.
+    #[error("{}", Redacted(.0))]
+    Other(anyhow::Error),
+}
+
+impl EGuardError {
+    /// The variant's name, e.g. `RateLimited`, for bindings that only pass strings on.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::ConfigInvalid(_) => "ConfigInvalid",
+            Self::Network(_) => "Network",
+            Self::Timeout(_) => "Timeout",
+            Self::ApiStatus(_) => "ApiStatus",
+            Self::Deserialize(_) => "Deserialize",
+            Self::RateLimited(_) => "RateLimited",
+            Self::CircuitOpen(_) => "CircuitOpen",
+            Self::Overloaded(_) => "Overloaded",
+            Self::Other(_) => "Other",
+        }
+    }
+}
+
+/// Sorts an internal error by what caused it.
+impl From<anyhow::Error> for EGuardError {
+    fn from(err: anyhow::Error) -> Self {
+        let err = match err.downcast::<EGuardError>() { Ok(e) => return e, Err(err) => err };
+        let err = match err.downcast::<ApiStatusError>() { Ok(e) => return e.into(), Err(err) => err };
+        let err = match err.downcast::<Throttled>() { Ok(e) => return e.into(), Err(err) => err };
+        let err = match err.downcast::<CircuitOpenError>() { Ok(e) => return e.into(), Err(err) => err };
+        let err = match err.downcast::<Overloaded>() { Ok(e) => return e.into(), Err(err) => err };
+        if err.is::<ProviderTimeout>() || err.is::<DeadlineExceeded>() {
+            return Self::Timeout(err);
+        }
+        if cause::<serde_json::Error>(&err).is_some() {
+            return Self::Deserialize(err);
+        }
+        if let Some(e) = cause::<reqwest::Error>(&err) {
+            if e.is_timeout() {
+                return Self::Timeout(err);
+            }
+            if e.is_decode() {
+                return Self::Deserialize(err);
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if e.is_connect() {
+                return Self::Network(err);
+            }
+            if e.is_request() {
+                return Self::Network(err);
+            }
+        }
+        #[cfg(feature = "grpc")]
+        if let Some(s) = cause::<tonic::Status>(&err) {
+            use tonic::Code;
+            match s.code() {
+                Code::DeadlineExceeded => return Self::Timeout(err),
+                Code::Unavailable => return Self::Network(err),
+                _ => {}
+            }
+        }
+        Self::Other(err)
+    }
+}
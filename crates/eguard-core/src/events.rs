@@ -0,0 +1,114 @@
+//! Behavioural events (logins, checkouts, password resets) reported to the Trust API to
+//! feed its scoring model. `EGuard::report_event` only queues the event; a background
+//! task sends queued events in batches to `POST {api_base_url}/eguard/events`, so
+//! reporting never holds up the request. Not available on wasm32.
+//!
+//! Confirmed outcomes (a chargeback, or a manual review clearing a session) are sent
+//! one at a time with `EGuard::report_outcome`, as labels for the scoring model.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use web_time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use native::EventReporter;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Login,
+    LoginFailed,
+    Signup,
+    Checkout,
+    PasswordReset,
+}
+
+/// What a session turned out to be.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// Confirmed fraud, e.g. a chargeback.
+    Fraud,
+    /// Confirmed legitimate, e.g. cleared by a manual review.
+    Legit,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Event {
+    pub session_id: String,
+    pub kind: EventKind,
+    /// Free-form details such as the order value or the account id.
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub metadata: Map<String, Value>,
+    pub timestamp_ms: u64,
+}
+
+impl Event {
+    /// An event that happened now.
+    pub fn new(session_id: &str, kind: EventKind, metadata: Map<String, Value>) -> Self {
+        let timestamp_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+        Self { session_id: session_id.to_string(), kind, metadata, timestamp_ms }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::sync::{
+        Arc, OnceLock,
+        atomic::{AtomicU64, Ordering::Relaxed},
+    };
+
+    use tokio::sync::mpsc;
+
+    use super::Event;
+    use crate::{TrustProvider, redact::Redacted};
+
+    /// Events waiting for the background task; new events are dropped once it is full.
+    const QUEUE_CAPACITY: usize = 10_000;
+    /// Most events sent in one request.
+    const MAX_BATCH: usize = 500;
+
+    /// The background task is started on the first event, from inside the caller's
+    /// tokio runtime; events reported outside a runtime are dropped.
+    #[derive(Default)]
+    pub(crate) struct EventReporter {
+        tx: OnceLock<mpsc::Sender<Event>>,
+        dropped: AtomicU64,
+    }
+
+    impl EventReporter {
+        pub(crate) fn report(&self, provider: &Arc<dyn TrustProvider>, event: Event) {
+            let sent = self.sender(provider).is_some_and(|tx| tx.try_send(event).is_ok());
+            if !sent {
+                self.dropped.fetch_add(1, Relaxed);
+            }
+        }
+
+        /// Events discarded because the queue was full or no runtime was available.
+        pub(crate) fn dropped(&self) -> u64 {
+            self.dropped.load(Relaxed)
+        }
+
+        fn sender(&self, provider: &Arc<dyn TrustProvider>) -> Option<&mpsc::Sender<Event>> {
+            if let Some(tx) = self.tx.get() {
+                return Some(tx);
+            }
+            let handle = tokio::runtime::Handle::try_current().ok()?;
+            Some(self.tx.get_or_init(|| {
+                let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+                handle.spawn(run(rx, provider.clone()));
+                tx
+            }))
+        }
+    }
+
+    async fn run(mut rx: mpsc::Receiver<Event>, provider: Arc<dyn TrustProvider>) {
+        let mut batch = Vec::with_capacity(MAX_BATCH);
+        while rx.recv_many(&mut batch, MAX_BATCH).await > 0 {
+            if let Err(e) = provider.report_events(&batch).await {
+                tracing::warn!(error = %Redacted(&e), events = batch.len(), "event report failed");
+            }
+            batch.clear();
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::OnceCell;
+
+use crate::{ApiStatusError, DeadlineExceeded, Throttled, breaker::CircuitOpenError, concurrency::Overloaded, provider::ProviderTimeout};
+
+type Shared<T> = Arc<OnceCell<Result<T, Arc<anyhow::Error>>>>;
+
+/// Coalesces concurrent lookups for the same key into one call (single-flight).
+/// If the caller running the call is cancelled, the next waiter takes over.
+pub(crate) struct SingleFlight<T> {
+    calls: Mutex<HashMap<String, Shared<T>>>,
+}
+
+impl<T: Clone> SingleFlight<T> {
+    pub(crate) fn new() -> Self {
+        Self { calls: Mutex::new(HashMap::new()) }
+    }
+
+    /// Runs `call` unless one is already in flight for `key`, in which case its result
+    /// is shared. Errors are shared too; look into them with [`cause`].
+    pub(crate) async fn run<F, Fut>(&self, key: &str, call: F) -> anyhow::Result<T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let cell = self.calls.lock().unwrap().entry(key.to_string()).or_default().clone();
+
+        let shared = cell.get_or_init(|| async { call().await.map_err(Arc::new) }).await;
+
+        {
+            let mut calls = self.calls.lock().unwrap();
+            if calls.get(key).is_some_and(|c| Arc::ptr_eq(c, &cell)) {
+                calls.remove(key);
+            }
+        }
+        shared.clone().map_err(copy_error)
+    }
+}
+
+/// A copy of `err` with the same type when it is one of ours, so callers can still
+/// match on it; anything else (reqwest, tonic or serde errors) is shared as is.
+fn copy_error(err: Arc<anyhow::Error>) -> anyhow::Error {
+    if err.is::<CircuitOpenError>() {
+        CircuitOpenError.into()
+    } else if let Some(e) = err.downcast_ref::<ApiStatusError>() {
+        ApiStatusError { status: e.status, body: e.body.clone() }.into()
+    } else if let Some(e) = err.downcast_ref::<Throttled>() {
+        Throttled(e.0).into()
+    } else if err.is::<Overloaded>() {
+        Overloaded.into()
+    } else if let Some(e) = err.downcast_ref::<ProviderTimeout>() {
+        ProviderTimeout(e.0).into()
+    } else if let Some(e) = err.downcast_ref::<DeadlineExceeded>() {
+        DeadlineExceeded(e.0).into()
+    } else {
+        SharedError(err).into()
+    }
+}
+
+/// An error another caller's lookup returned, shared with the waiters on it.
+#[derive(Debug)]
+struct SharedError(Arc<anyhow::Error>);
+
+impl std::fmt::Display for SharedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for SharedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// `err` as an `E`, also when it was shared by a coalesced lookup.
+pub(crate) fn cause<E>(err: &anyhow::Error) -> Option<&E>
+where
+    E: std::fmt::Display + std::fmt::Debug + Send + Sync + 'static,
+{
+    match err.downcast_ref::<SharedError>() {
+        Some(shared) => shared.0.downcast_ref::<E>(),
+        None => err.downcast_ref::<E>(),
+    }
+}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+/// Local MaxMind databases used to enrich request contexts (requires the `geoip` feature).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GeoIpConfig {
+    /// GeoLite2/GeoIP2 Country or City database.
+    pub country_db_path: String,
+    /// GeoLite2/GeoIP2 ASN database.
+    #[serde(default)]
+    pub asn_db_path: Option<String>,
+}
+
+/// Where a client IP is located, as far as the configured databases know.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GeoInfo {
+    /// ISO 3166-1 alpha-2 code, e.g. `DE`.
+    pub country: Option<String>,
+    pub asn: Option<u32>,
+    pub as_org: Option<String>,
+}
+
+#[cfg(feature = "geoip")]
+pub(crate) use reader::GeoIp;
+
+#[cfg(not(feature = "geoip"))]
+pub(crate) struct GeoIp;
+
+#[cfg(not(feature = "geoip"))]
+impl GeoIp {
+    pub(crate) fn open(_: &GeoIpConfig) -> anyhow::Result<Self> {
+        Err(anyhow::anyhow!("geoip is set but eguard-core was built without the `geoip` feature"))
+    }
+
+    pub(crate) fn lookup(&self, _: std::net::IpAddr) -> GeoInfo {
+        GeoInfo::default()
+    }
+}
+
+#[cfg(feature = "geoip")]
+mod reader {
+    use std::net::IpAddr;
+
+    use maxminddb::{Reader, geoip2};
+
+    use super::{GeoInfo, GeoIpConfig};
+
+    pub(crate) struct GeoIp {
+        country: Reader<Vec<u8>>,
+        asn: Option<Reader<Vec<u8>>>,
+    }
+
+    impl GeoIp {
+        pub(crate) fn open(cfg: &GeoIpConfig) -> anyhow::Result<Self> {
+            let open = |path: &str| {
+                Reader::open_readfile(path).map_err(|e| anyhow::anyhow!("Cannot open GeoIP database {}: {}", path, e))
+            };
+            Ok(Self { country: open(&cfg.country_db_path)?, asn: cfg.asn_db_path.as_deref().map(open).transpose()? })
+        }
+
+        /// Addresses missing from the databases (private ranges, ...) give an empty `GeoInfo`.
+        pub(crate) fn lookup(&self, ip: IpAddr) -> GeoInfo {
+            let mut info = GeoInfo::default();
+            if let Ok(Some(c)) = self.country.lookup(ip).and_then(|r| r.decode::<geoip2::Country>()) {
+                info.country = c.country.iso_code.map(str::to_string);
+            }
+            if let Some(asn) = &self.asn
+                && let Ok(Some(a)) = asn.lookup(ip).and_then(|r| r.decode::<geoip2::Asn>())
+            {
+                info.asn = a.autonomous_system_number;
+                info.as_org = a.autonomous_system_organization.map(str::to_string);
+            }
+            info
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! `X-EGuard-*` headers describing a decision, added to the response or to the request
+//! passed on to the application, so a user's report can be matched to the decision
+//! behind it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Decision, RequestContext, TrustResponse};
+
+pub const SCORE: &str = "x-eguard-score";
+/// `allow`, `deny` or `challenge`.
+pub const DECISION: &str = "x-eguard-decision";
+pub const REQUEST_ID: &str = "x-eguard-request-id";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResponseHeadersConfig {
+    /// Omitted when the decision was made without a score.
+    pub score: bool,
+    pub decision: bool,
+    pub request_id: bool,
+    /// Reuse the id in this request header, e.g. one set by the load balancer; a random
+    /// id is generated when it is unset or missing.
+    pub request_id_header: Option<String>,
+    /// Also add the headers to allowed requests before they reach the application.
+    pub forward: bool,
+}
+
+impl Default for ResponseHeadersConfig {
+    fn default() -> Self {
+        Self {
+            score: true,
+            decision: true,
+            request_id: true,
+            request_id_header: Some("x-request-id".into()),
+            forward: false,
+        }
+    }
+}
+
+impl ResponseHeadersConfig {
+    pub(crate) fn headers(&self, decision: &Decision, trust: Option<&TrustResponse>, request_id: &str) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+        let score = match decision {
+            Decision::Allow { trust_score: Some(score), .. } => Some(*score),
+            _ => trust.map(|t| t.trust_score),
+        };
+        if self.score
+            && let Some(score) = score
+        {
+            headers.push((SCORE, score.to_string()));
+        }
+        if self.decision {
+            headers.push((DECISION, decision.outcome().to_string()));
+        }
+        if self.request_id {
+            headers.push((REQUEST_ID, request_id.to_string()));
+        }
+        headers
+    }
+}
+
+/// The id from the request's `request_id_header` (`x-request-id` without config), or a
+/// random one.
+pub(crate) fn request_id(cfg: Option<&ResponseHeadersConfig>, ctx: Option<&RequestContext>) -> String {
+    let header = match cfg {
+        Some(cfg) => cfg.request_id_header.as_deref(),
+        None => Some("x-request-id"),
+    };
+    let incoming = header
+        .and_then(|name| ctx?.headers.get(&name.to_ascii_lowercase()))
+        .filter(|id| !id.is_empty());
+    match incoming {
+        Some(id) => id.clone(),
+        None => hex::encode(rand::random::<[u8; 16]>()),
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    pin::Pin,
+    sync::Mutex,
+    task::Poll,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{adaptive::LatencyWindow, rt};
+
+/// Send a second lookup when the first is slow and use whichever answers first, cutting
+/// the tail latency of an occasionally slow Trust API instance.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HedgeConfig {
+    /// How long to wait for the first lookup before sending the second.
+    #[serde(default = "default_delay_ms")]
+    pub delay_ms: u64,
+    /// Wait for this latency percentile of recent lookups (e.g. 95) instead, once
+    /// enough have been seen; `delay_ms` applies until then.
+    #[serde(default)]
+    pub percentile: Option<f64>,
+}
+
+fn default_delay_ms() -> u64 { 100 }
+
+impl Default for HedgeConfig {
+    fn default() -> Self {
+        Self { delay_ms: default_delay_ms(), percentile: None }
+    }
+}
+
+/// Lookups seen before `percentile` is used.
+const MIN_SAMPLES: usize = 20;
+const WINDOW: usize = 500;
+
+pub(crate) struct Hedge {
+    cfg: HedgeConfig,
+    latencies: Mutex<LatencyWindow>,
+}
+
+impl Hedge {
+    pub(crate) fn new(cfg: &HedgeConfig) -> Self {
+        Self { cfg: cfg.clone(), latencies: Mutex::new(LatencyWindow::new(WINDOW)) }
+    }
+
+    fn delay(&self) -> Duration {
+        let fixed = Duration::from_millis(self.cfg.delay_ms);
+        match self.cfg.percentile {
+            Some(p) => self.latencies.lock().unwrap().percentile(p, MIN_SAMPLES).unwrap_or(fixed),
+            None => fixed,
+        }
+    }
+
+    /// Runs `call`, and again if it hasn't answered after the hedge delay, returning the
+    /// first success. A call that fails before the delay is not hedged; once both are
+    /// in flight the other one's answer is awaited. `on_hedge` runs when the second
+    /// call is sent.
+    pub(crate) async fn run<T, F, Fut>(&self, call: F, on_hedge: impl Fn()) -> anyhow::Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let started = web_time::Instant::now();
+        let mut calls: Vec<Pin<Box<Fut>>> = vec![Box::pin(call())];
+        let mut timer = Some(Box::pin(rt::sleep(self.delay())));
+        let mut last_err = None;
+        let result = std::future::poll_fn(|cx| {
+            if let Some(t) = &mut timer
+                && t.as_mut().poll(cx).is_ready()
+            {
+                timer = None;
+                on_hedge();
+                calls.push(Box::pin(call()));
+            }
+            let mut i = 0;
+            while i < calls.len() {
+                match calls[i].as_mut().poll(cx) {
+                    Poll::Ready(Ok(out)) => return Poll::Ready(Ok(out)),
+                    Poll::Ready(Err(e)) => {
+                        calls.remove(i);
+                        last_err = Some(e);
+                    }
+                    Poll::Pending => i += 1,
+                }
+            }
+            match (calls.is_empty(), last_err.take()) {
+                (true, Some(e)) => Poll::Ready(Err(e)),
+                (_, err) => {
+                    last_err = err;
+                    Poll::Pending
+                }
+            }
+        })
+        .await;
+        if result.is_ok() {
+            self.latencies.lock().unwrap().push(started.elapsed());
+        }
+        result
+    }
+}
@@ -0,0 +1,121 @@
+//! A local scorer used as the last resort when the Trust API can't be reached and no
+//! stale score is cached, so a fail-open setup still turns away the obvious bots.
+//!
+//! Scores start at `base_score` and lose a penalty for each signal: a bot-like or
+//! missing user agent, a client IP in a datacenter ASN (from `geoip`), and a session
+//! making more than `max_requests_per_minute`. The reason is `local_heuristic`, and
+//! the matched signals are logged at debug level.
+
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use regex::RegexSet;
+use serde::{Deserialize, Serialize};
+use web_time::Instant;
+
+use crate::{ReasonCode, RequestContext, TrustResponse};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LocalScorerConfig {
+    /// Score of a request with no signals.
+    pub base_score: f32,
+    /// Case-insensitive regexes for user agents of scripts, crawlers and headless browsers.
+    pub bot_user_agents: Vec<String>,
+    /// Applied to a bot-like user agent, or none at all.
+    pub user_agent_penalty: f32,
+    /// Hosting and cloud provider ASNs; needs `geoip` with an ASN database.
+    pub datacenter_asns: Vec<u32>,
+    pub datacenter_penalty: f32,
+    pub max_requests_per_minute: u32,
+    pub rate_penalty: f32,
+    /// Sessions whose request rate is tracked at once.
+    pub max_sessions: usize,
+}
+
+impl Default for LocalScorerConfig {
+    fn default() -> Self {
+        Self {
+            base_score: 0.6,
+            bot_user_agents: [
+                "bot", "crawl", "spider", "headless", "phantomjs", "selenium", "puppeteer", "playwright",
+                "^curl/", "^wget/", "python-requests", "python-urllib", "aiohttp", "go-http-client",
+                "^java/", "okhttp", "libwww-perl", "scrapy",
+            ]
+            .map(String::from)
+            .to_vec(),
+            user_agent_penalty: 0.4,
+            // AWS, Google Cloud, Azure, DigitalOcean, OVH, Hetzner, Linode, Vultr, Alibaba Cloud.
+            datacenter_asns: vec![16509, 14618, 15169, 396982, 8075, 14061, 16276, 24940, 63949, 20473, 45102],
+            datacenter_penalty: 0.3,
+            max_requests_per_minute: 120,
+            rate_penalty: 0.3,
+            max_sessions: 100_000,
+        }
+    }
+}
+
+struct Window {
+    started: Instant,
+    requests: u32,
+}
+
+pub(crate) struct LocalScorer {
+    cfg: LocalScorerConfig,
+    bot_user_agents: RegexSet,
+    windows: Mutex<HashMap<String, Window>>,
+}
+
+impl LocalScorer {
+    pub(crate) fn new(cfg: &LocalScorerConfig) -> anyhow::Result<Self> {
+        let patterns = cfg.bot_user_agents.iter().map(|p| format!("(?i){p}"));
+        let bot_user_agents = RegexSet::new(patterns)
+            .map_err(|e| anyhow::anyhow!("Invalid local_scorer.bot_user_agents: {}", e))?;
+        Ok(Self { cfg: cfg.clone(), bot_user_agents, windows: Mutex::new(HashMap::new()) })
+    }
+
+    pub(crate) fn score(&self, session_id: &str, ctx: Option<&RequestContext>) -> TrustResponse {
+        let mut signals = Vec::new();
+        let mut score = self.cfg.base_score;
+
+        if let Some(ctx) = ctx {
+            let bot_like = ctx.user_agent.as_deref().is_none_or(|ua| ua.trim().is_empty() || self.bot_user_agents.is_match(ua));
+            if bot_like {
+                score -= self.cfg.user_agent_penalty;
+                signals.push("user_agent");
+            }
+            let asn = ctx.geo.as_ref().and_then(|g| g.asn);
+            if asn.is_some_and(|asn| self.cfg.datacenter_asns.contains(&asn)) {
+                score -= self.cfg.datacenter_penalty;
+                signals.push("datacenter_asn");
+            }
+        }
+        if self.requests_this_minute(session_id) > self.cfg.max_requests_per_minute {
+            score -= self.cfg.rate_penalty;
+            signals.push("request_rate");
+        }
+
+        let score = score.clamp(0.0, 1.0);
+        tracing::debug!(score, signals = %signals.join(", "), "session scored locally");
+        TrustResponse { session_id: session_id.to_string(), trust_score: score, reason: Some(ReasonCode::LocalHeuristic), ..Default::default() }
+    }
+
+    /// Counts this request and returns the session's requests in the current window.
+    fn requests_this_minute(&self, session_id: &str) -> u32 {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        if windows.len() >= self.cfg.max_sessions && !windows.contains_key(session_id) {
+            windows.retain(|_, w| now.duration_since(w.started) < WINDOW);
+            if windows.len() >= self.cfg.max_sessions {
+                return 0;
+            }
+        }
+        let window = windows.entry(session_id.to_string()).or_insert(Window { started: now, requests: 0 });
+        if now.duration_since(window.started) >= WINDOW {
+            *window = Window { started: now, requests: 0 };
+        }
+        window.requests += 1;
+        window.requests
+    }
+}
@@ -0,0 +1,178 @@
+//! The whole-request pipeline for bindings that are handed a raw HTTP request, such as
+//! the sidecar, the edge (wasm), Node and proxy-wasm ones: route matching, session and
+//! device extraction, the trust check, and the headers and deny body to answer with.
+//! Bindings only translate their request and response types.
+
+use std::{collections::HashMap, net::IpAddr};
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{ChallengeKind, Decision, EGuard, EGuardError, RequestContext, TrustResponse, deny_body::DenyBody};
+
+/// One header's value(s) in a JSON or JS headers object.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum HeaderValues {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl HeaderValues {
+    pub fn as_slice(&self) -> &[String] {
+        match self {
+            HeaderValues::One(v) => std::slice::from_ref(v),
+            HeaderValues::Many(vs) => vs.as_slice(),
+        }
+    }
+}
+
+/// A headers object as name/value pairs, one per value of a repeated header.
+pub fn header_pairs(headers: &HashMap<String, HeaderValues>) -> impl Iterator<Item = (&str, &str)> {
+    headers.iter().flat_map(|(name, values)| values.as_slice().iter().map(move |v| (name.as_str(), v.as_str())))
+}
+
+/// Where `EGuard::route_http` sends a request.
+pub enum HttpRoute<'g> {
+    /// Not on a secure route; let it through unchecked.
+    Unmatched,
+    /// On a secure route but without a session id.
+    MissingSession,
+    /// To be decided with `decide_matched` by `guard`, the guard of the request's tenant.
+    Matched { guard: &'g EGuard, ctx: Box<RequestContext>, session_id: String },
+}
+
+/// What to answer a request with, from `EGuard::decide_http`. Serializes flat, in
+/// snake_case: `{ matched, allow, status?, message?, score?, session_id?,
+/// challenge_kind?, redirect_url?, headers?, body?, content_type? }`.
+#[derive(Debug)]
+pub struct RequestDecision {
+    /// Whether the request is on a secure route; other requests are allowed unchecked.
+    pub matched: bool,
+    /// A deny with 401 when the session id is missing, and with 502 when the Trust API
+    /// failed and no fallback is configured.
+    pub decision: Decision,
+    pub trust: Option<TrustResponse>,
+    pub session_id: Option<String>,
+    /// `X-EGuard-*` headers for the response; see `EGuard::response_headers`.
+    pub headers: Vec<(&'static str, String)>,
+    /// The rendered `deny_body`, to send instead of the deny message.
+    pub body: Option<DenyBody>,
+    /// Why the trust check failed, for the 502.
+    pub error: Option<EGuardError>,
+}
+
+impl RequestDecision {
+    fn unmatched() -> Self {
+        Self { matched: false, decision: Decision::allow(), trust: None, session_id: None, headers: Vec::new(), body: None, error: None }
+    }
+
+    fn rejected(status: u16, message: &str) -> Self {
+        Self { matched: true, decision: Decision::Deny { status, message: message.to_string() }, ..Self::unmatched() }
+    }
+}
+
+#[derive(Default, Serialize)]
+struct FlatDecision<'a> {
+    matched: bool,
+    allow: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    challenge_kind: Option<ChallengeKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    redirect_url: Option<&'a str>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    headers: HashMap<&'static str, &'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_type: Option<&'static str>,
+}
+
+impl Serialize for RequestDecision {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut flat = FlatDecision {
+            matched: self.matched,
+            score: self.trust.as_ref().map(|t| t.trust_score),
+            session_id: self.session_id.as_deref(),
+            headers: self.headers.iter().map(|(name, value)| (*name, value.as_str())).collect(),
+            body: self.body.as_ref().map(|b| b.body.as_str()),
+            content_type: self.body.as_ref().map(|b| b.content_type),
+            ..FlatDecision::default()
+        };
+        match &self.decision {
+            Decision::Allow { .. } => flat.allow = true,
+            Decision::Deny { status, message } => {
+                flat.status = Some(*status);
+                flat.message = Some(message);
+            }
+            Decision::Challenge { kind, redirect_url } => {
+                flat.challenge_kind = Some(*kind);
+                flat.redirect_url = Some(redirect_url);
+            }
+        }
+        flat.serialize(serializer)
+    }
+}
+
+impl EGuard {
+    /// Routes a request given as its method, target (the path, which may include the
+    /// query string), headers and client IP: picks its tenant, checks the route and
+    /// extracts the session and device ids.
+    pub fn route_http<'h>(&self, method: &str, target: &str, headers: &[(&'h str, &'h str)], ip: Option<IpAddr>) -> HttpRoute<'_> {
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (target, None),
+        };
+        let ctx = RequestContext::new(method, path)
+            .with_ip(ip)
+            .with_headers(headers.iter().copied());
+
+        let guard = self.for_request(&ctx);
+        if !guard.is_secure_host(ctx.host(), &ctx.path, &ctx.method) {
+            return HttpRoute::Unmatched;
+        }
+        let Some(session_id) = guard.extract_session_id_from_headers(headers.iter().copied(), query) else {
+            return HttpRoute::MissingSession;
+        };
+        let ctx = ctx.with_device_id(guard.extract_device_id_from_headers(headers.iter().copied(), query));
+        HttpRoute::Matched { guard, ctx: Box::new(ctx), session_id }
+    }
+
+    /// Decides a request `route_http` matched, with the response headers and deny body.
+    pub async fn decide_matched(&self, ctx: &RequestContext, session_id: String) -> RequestDecision {
+        let (decision, trust) = match self.decide_request_with_trust(ctx, &session_id).await {
+            Ok(decided) => decided,
+            Err(e) => {
+                let rejected = RequestDecision::rejected(502, "Trust service unavailable");
+                return RequestDecision { error: Some(e), ..rejected };
+            }
+        };
+        let request_id = self.request_id(Some(ctx));
+        RequestDecision {
+            matched: true,
+            headers: self.response_headers(&decision, trust.as_ref(), &request_id),
+            body: self.deny_body(&decision, trust.as_ref(), &request_id),
+            decision,
+            trust,
+            session_id: Some(session_id),
+            error: None,
+        }
+    }
+
+    /// `route_http` and `decide_matched` in one: requests off the secure routes are
+    /// allowed unchecked and those without a session are denied with 401.
+    pub async fn decide_http<'h>(&self, method: &str, target: &str, headers: &[(&'h str, &'h str)], ip: Option<IpAddr>) -> RequestDecision {
+        match self.route_http(method, target, headers, ip) {
+            HttpRoute::Unmatched => RequestDecision::unmatched(),
+            HttpRoute::MissingSession => RequestDecision::rejected(401, "Missing session"),
+            HttpRoute::Matched { guard, ctx, session_id } => guard.decide_matched(&ctx, session_id).await,
+        }
+    }
+}
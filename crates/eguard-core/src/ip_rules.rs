@@ -0,0 +1,51 @@
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+
+use crate::Decision;
+
+/// Client IP ranges decided locally, before the Trust API is consulted.
+/// Entries are CIDR ranges (`10.0.0.0/8`, `2001:db8::/32`) or single addresses.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IpRulesConfig {
+    /// Always allowed; takes precedence over `deny`.
+    pub allow: Vec<String>,
+    /// Always denied with 403.
+    pub deny: Vec<String>,
+}
+
+pub(crate) struct IpRules {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+}
+
+impl IpRules {
+    pub(crate) fn new(cfg: &IpRulesConfig) -> anyhow::Result<Self> {
+        Ok(Self { allow: parse(&cfg.allow)?, deny: parse(&cfg.deny)? })
+    }
+
+    /// The local decision for `ip`, if a rule covers it.
+    pub(crate) fn check(&self, ip: IpAddr) -> Option<Decision> {
+        let ip = ip.to_canonical();
+        if self.allow.iter().any(|net| net.contains(&ip)) {
+            Some(Decision::allow())
+        } else if self.deny.iter().any(|net| net.contains(&ip)) {
+            Some(Decision::Deny { status: 403, message: "IP address blocked".into() })
+        } else {
+            None
+        }
+    }
+}
+
+fn parse(entries: &[String]) -> anyhow::Result<Vec<IpNet>> {
+    entries.iter()
+        .map(|e| {
+            let e = e.trim();
+            e.parse::<IpNet>()
+                .or_else(|_| e.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| anyhow::anyhow!("Invalid IP rule {}", e))
+        })
+        .collect()
+}
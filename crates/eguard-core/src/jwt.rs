@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+
+/// Treat the extracted token as a JWT and use one of its claims as the session id
+/// (requires the `jwt` feature).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JwtConfig {
+    /// Dot-separated path to the claim, e.g. `sid` or `ctx.session_id`.
+    #[serde(default = "default_claim")]
+    pub claim: String,
+    /// Verify signatures against this JWKS. Without it tokens are decoded unverified,
+    /// which is only safe when something in front of the guard has already checked them.
+    #[serde(default)]
+    pub jwks_url: Option<String>,
+    /// How long fetched keys are used before they are fetched again.
+    #[serde(default = "default_jwks_refresh_ms")]
+    pub jwks_refresh_ms: u64,
+    #[serde(default)]
+    pub issuer: Option<String>,
+    #[serde(default)]
+    pub audience: Option<String>,
+}
+
+fn default_claim() -> String { "sid".into() }
+fn default_jwks_refresh_ms() -> u64 { 600_000 }
+
+/// The claim at `path` as a string; numbers are formatted, anything else is ignored.
+#[cfg_attr(not(feature = "jwt"), allow(dead_code))]
+fn claim_at(claims: &serde_json::Value, path: &str) -> Option<String> {
+    let value = path.split('.').try_fold(claims, |v, key| v.get(key))?;
+    match value {
+        serde_json::Value::String(s) if !s.is_empty() => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "jwt")]
+pub(crate) use decoder::JwtDecoder;
+
+#[cfg(not(feature = "jwt"))]
+pub(crate) struct JwtDecoder;
+
+#[cfg(not(feature = "jwt"))]
+impl JwtDecoder {
+    pub(crate) fn new(_: &JwtConfig, _: Option<&crate::ProxyConfig>) -> anyhow::Result<Self> {
+        Err(anyhow::anyhow!("session_extraction.jwt is set but eguard-core was built without the `jwt` feature"))
+    }
+
+    pub(crate) fn session_id(self: &std::sync::Arc<Self>, _: &str) -> Option<String> {
+        None
+    }
+
+    pub(crate) async fn refresh_keys(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "jwt")]
+mod decoder {
+    use std::{
+        sync::{
+            Arc, RwLock,
+            atomic::{AtomicBool, Ordering},
+        },
+        time::Duration,
+    };
+
+    use jsonwebtoken::{DecodingKey, Validation, dangerous, decode, decode_header, jwk::JwkSet};
+    use web_time::Instant;
+
+    use super::{JwtConfig, claim_at};
+    use crate::{redact::Redacted, transport::{ProxyConfig, client_builder}};
+
+    pub(crate) struct JwtDecoder {
+        cfg: JwtConfig,
+        client: reqwest::Client,
+        keys: RwLock<Option<(JwkSet, Instant)>>,
+        refreshing: AtomicBool,
+    }
+
+    impl JwtDecoder {
+        pub(crate) fn new(cfg: &JwtConfig, proxy: Option<&ProxyConfig>) -> anyhow::Result<Self> {
+            if cfg.claim.is_empty() {
+                return Err(anyhow::anyhow!("session_extraction.jwt.claim must not be empty"));
+            }
+            let client = client_builder(proxy)?.build()?;
+            Ok(Self { cfg: cfg.clone(), client, keys: RwLock::new(None), refreshing: AtomicBool::new(false) })
+        }
+
+        /// The session id claim of `token`, or `None` if it is malformed, fails
+        /// verification, or lacks the claim. Until the JWKS has been fetched (see
+        /// `EGuard::refresh_jwks`) verified tokens are rejected.
+        pub(crate) fn session_id(self: &Arc<Self>, token: &str) -> Option<String> {
+            let claims: serde_json::Value = match &self.cfg.jwks_url {
+                None => dangerous::insecure_decode_claims(token).ok()?,
+                Some(_) => self.verify(token)?,
+            };
+            claim_at(&claims, &self.cfg.claim)
+        }
+
+        fn verify(self: &Arc<Self>, token: &str) -> Option<serde_json::Value> {
+            let header = decode_header(token).ok()?;
+            let keys = self.keys.read().unwrap();
+            let stale = keys.as_ref().is_none_or(|(_, at)| at.elapsed() >= Duration::from_millis(self.cfg.jwks_refresh_ms));
+            let jwk = keys.as_ref().and_then(|(set, _)| match &header.kid {
+                Some(kid) => set.find(kid),
+                None if set.keys.len() == 1 => set.keys.first(),
+                None => None,
+            });
+            if stale || (jwk.is_none() && header.kid.is_some()) {
+                self.spawn_refresh();
+            }
+            let key = DecodingKey::from_jwk(jwk?).ok()?;
+
+            let mut validation = Validation::new(header.alg);
+            validation.validate_aud = self.cfg.audience.is_some();
+            if let Some(aud) = &self.cfg.audience {
+                validation.set_audience(&[aud]);
+            }
+            if let Some(iss) = &self.cfg.issuer {
+                validation.set_issuer(&[iss]);
+            }
+            decode(token, &key, &validation).ok().map(|data| data.claims)
+        }
+
+        /// Fetches the JWKS in the background, at most once at a time.
+        fn spawn_refresh(self: &Arc<Self>) {
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Ok(handle) = tokio::runtime::Handle::try_current()
+                && !self.refreshing.swap(true, Ordering::AcqRel)
+            {
+                let this = self.clone();
+                handle.spawn(async move {
+                    if let Err(e) = this.refresh_keys().await {
+                        tracing::warn!(error = %Redacted(&e), "JWKS refresh failed");
+                    }
+                    this.refreshing.store(false, Ordering::Release);
+                });
+            }
+        }
+
+        pub(crate) async fn refresh_keys(&self) -> anyhow::Result<()> {
+            let Some(url) = &self.cfg.jwks_url else { return Ok(()) };
+            let set: JwkSet = self.client.get(url).send().await?.error_for_status()?.json().await?;
+            *self.keys.write().unwrap() = Some((set, Instant::now()));
+            Ok(())
+        }
+    }
+}
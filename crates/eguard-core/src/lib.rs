@@ -1,87 +1,1253 @@
-use std::{sync::Arc, time::Duration};
-use regex::Regex;
-use reqwest::{Client, StatusCode};
+use std::{borrow::Cow, collections::BTreeMap, sync::{Arc, RwLock}, time::Duration};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{Instrument, field::Empty};
+use zeroize::Zeroizing;
+
+pub mod adaptive;
+pub mod audit;
+pub mod breaker;
+pub mod cache;
+pub mod concurrency;
+mod config_file;
+pub mod context;
+pub mod deny_body;
+pub mod denylist;
+pub mod dns;
+pub mod endpoints;
+pub mod error;
+pub mod events;
+mod flight;
+pub mod geoip;
+pub mod headers;
+pub mod hedge;
+pub mod heuristic;
+pub mod http;
+pub mod ip_rules;
+pub mod jwt;
+pub mod metrics;
+pub mod privacy;
+pub mod provider;
+pub mod push;
+pub mod rate_limit;
+pub mod reason;
+pub mod redact;
+pub mod refresh;
+pub mod response;
+pub mod retry;
+mod routes;
+mod rules;
+mod rt;
+pub mod secrets;
+pub mod session;
+pub mod signing;
+pub mod sink;
+pub mod telemetry;
+pub mod tenant;
+pub mod throttle;
+#[cfg(test)]
+mod testutil;
+#[cfg(feature = "tower")]
+pub mod tower;
+pub mod transport;
+pub mod webhook;
+pub mod wire;
+
+use adaptive::AdaptiveTimeout;
+pub use adaptive::AdaptiveTimeoutConfig;
+use audit::{DecisionLogger, DecisionRecord, FanOutLogger, JsonLinesLogger};
+use breaker::{CircuitBreaker, CircuitOpenError};
+pub use breaker::CircuitBreakerConfig;
+use cache::{MemoryCache, TrustCache};
+use concurrency::ConcurrencyLimit;
+pub use concurrency::{ConcurrencyLimitConfig, ShedPolicy};
+use flight::SingleFlight;
+pub use context::{RequestContext, TlsFingerprintConfig};
+use deny_body::{DenyBody, DenyTemplate, DenyVars};
+pub use deny_body::DenyBodyConfig;
+use denylist::Denylist;
+pub use denylist::DenylistConfig;
+pub use dns::DnsConfig;
+use endpoints::EndpointPool;
+pub use error::EGuardError;
+pub use endpoints::{EndpointSelection, EndpointsConfig, HealthCheckConfig};
+#[cfg(not(target_arch = "wasm32"))]
+use events::EventReporter;
+pub use events::{Event, EventKind, Outcome};
+use geoip::GeoIp;
+use heuristic::LocalScorer;
+pub use geoip::GeoIpConfig;
+pub use headers::ResponseHeadersConfig;
+use hedge::Hedge;
+pub use hedge::HedgeConfig;
+pub use heuristic::LocalScorerConfig;
+pub use http::{HeaderValues, HttpRoute, RequestDecision};
+use ip_rules::IpRules;
+pub use ip_rules::IpRulesConfig;
+use jwt::JwtDecoder;
+pub use jwt::JwtConfig;
+#[cfg(not(target_arch = "wasm32"))]
+pub use config_file::ConfigWatcher;
+use metrics::{Metrics, MetricsSnapshot};
+use refresh::HotSessions;
+pub use refresh::RefreshConfig;
+pub use retry::RetryPolicy;
+use rate_limit::{RateLimitAction, RateLimitKey, RateLimiter};
+pub use rate_limit::RateLimitConfig;
+pub use reason::ReasonCode;
+pub use response::{ProtocolVersion, ResponseParsing};
+use routes::RouteMatcher;
+use rules::Rules;
+pub use session::{CookieDuplicates, SessionExtraction, SessionSource};
+use session::SessionValidator;
+pub use secrets::{SecretProvider, SecretString, SecretsConfig};
+pub use signing::SigningConfig;
+pub use sink::DecisionSinkConfig;
+pub use tenant::TenantConfig;
+use throttle::Throttle;
+pub use throttle::{ThrottleConfig, Throttled};
+pub use provider::{FallbackConfig, TrustProvider};
+pub use push::PushConfig;
+pub use transport::{PoolConfig, ProxyConfig, RequestMiddleware, TransportKind};
+use secrets::SecretProviders;
+use signing::Signer;
+use provider::FallbackProvider;
+use privacy::SessionHasher;
+use redact::Redacted;
+pub use privacy::SessionHashingConfig;
+use transport::{ApiKeys, HttpHooks, HttpTransport};
+use webhook::WebhookEvent;
+pub use wire::WireFormat;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SecureRoute {
+    /// Names the route in `Decision::Allow`; defaults to `path_pattern`.
+    #[serde(default)]
+    pub id: Option<String>,
     pub path_pattern: String,
     pub methods: Option<Vec<String>>,
+    /// How `path_pattern` is interpreted.
+    #[serde(default)]
+    pub syntax: RouteSyntax,
+    /// Only apply this route to these hosts (`admin.example.com`, or `*.example.com`
+    /// for any subdomain); unset applies it to every host.
+    #[serde(default)]
+    pub hosts: Option<Vec<String>>,
+    /// Deny requests to this route from outside these countries (ISO codes such as `DE`),
+    /// including clients whose country is unknown. Requires `geoip`.
+    #[serde(default)]
+    pub allowed_countries: Option<Vec<String>>,
+    /// Overrides the global `min_trust_score` or `risk_bands` for requests matching this route.
+    #[serde(default)]
+    pub min_trust_score: Option<f32>,
+    /// Overrides the global `risk_bands` or `min_trust_score` for requests matching this route.
+    #[serde(default)]
+    pub risk_bands: Option<Vec<RiskBand>>,
+    /// Thresholds on the scores of a multi-score response, all of which must pass.
+    /// Replaces the `min_trust_score` check and risk bands when set; a score missing
+    /// from the response passes its threshold.
+    #[serde(default)]
+    pub scores: Vec<ScoreThreshold>,
+    /// Status code for denials on this route (default 403).
+    #[serde(default)]
+    pub deny_status: Option<u16>,
+    /// Message for denials on this route (default `Low trust score: <score>`).
+    #[serde(default)]
+    pub deny_message: Option<String>,
+}
+
+impl SecureRoute {
+    pub fn route_id(&self) -> &str {
+        self.id.as_deref().unwrap_or(&self.path_pattern)
+    }
+}
+
+/// One of the scores in a `TrustResponse`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreKind {
+    Trust,
+    Bot,
+    Fraud,
+    Abuse,
 }
 
+impl ScoreKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ScoreKind::Trust => "trust",
+            ScoreKind::Bot => "bot",
+            ScoreKind::Fraud => "fraud",
+            ScoreKind::Abuse => "abuse",
+        }
+    }
+}
+
+/// Bounds for one score of a route, e.g. `{ score: bot, max: 0.3 }`.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct SessionExtraction {
-    pub cookie_name: Option<String>,
-    pub header_name: Option<String>,
-    pub header_bearer: bool,
+pub struct ScoreThreshold {
+    pub score: ScoreKind,
+    /// Deny below this value; for trust-like scores, where higher is better.
+    #[serde(default)]
+    pub min: Option<f32>,
+    /// Deny above this value; for risk-like scores, where higher is worse.
+    #[serde(default)]
+    pub max: Option<f32>,
+}
+
+impl ScoreThreshold {
+    /// The deny message when `trust` is outside these bounds.
+    fn violation(&self, trust: &TrustResponse) -> Option<String> {
+        let value = trust.score(self.score)?;
+        if self.min.is_some_and(|min| value < min) {
+            Some(format!("Low {} score: {}", self.score.as_str(), value))
+        } else if self.max.is_some_and(|max| value > max) {
+            Some(format!("High {} score: {}", self.score.as_str(), value))
+        } else {
+            None
+        }
+    }
+}
+
+/// Pattern language of a `SecureRoute`. `Template` and `Glob` always match the whole path.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum RouteSyntax {
+    /// A regular expression, unanchored unless it uses `^`/`$`.
+    #[default]
+    Regex,
+    /// `/orders/:id`: `:name` matches exactly one path segment.
+    Template,
+    /// `/static/**`: `*` matches within a segment, `**` across segments, `?` one character.
+    Glob,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct EGuardConfig {
+    /// `unix:///var/run/eguard.sock` reaches a Trust API listening on a local socket
+    /// (HTTP transport only).
     pub api_base_url: String,
-    pub api_key: String,
+    /// May be left empty when `secrets.api_key` is set. Like every API key in the
+    /// config, it is left out when the config is serialized.
+    #[serde(default, skip_serializing)]
+    pub api_key: SecretString,
+    /// Used when the Trust API rejects `api_key` with 401, after which it stays in use.
+    /// Set it to the new key while rotating, or see `EGuard::rotate_key`.
+    #[serde(default, skip_serializing)]
+    pub secondary_api_key: Option<SecretString>,
     pub secure_routes: Vec<SecureRoute>,
+    /// Path regexes that are never secure, even when a `secure_routes` pattern matches.
+    #[serde(default)]
+    pub exclude_routes: Vec<String>,
+    /// Treat `/admin/` like `/admin` when matching routes.
+    #[serde(default)]
+    pub ignore_trailing_slash: bool,
+    /// Client IP ranges that are allowed or denied without calling the Trust API.
+    #[serde(default)]
+    pub ip_rules: Option<IpRulesConfig>,
+    /// Look up country and ASN of client IPs (requires the `geoip` feature).
+    #[serde(default)]
+    pub geoip: Option<GeoIpConfig>,
+    /// Take JA3/JA4 TLS fingerprints from load balancer headers.
+    #[serde(default)]
+    pub tls_fingerprint: Option<TlsFingerprintConfig>,
+    /// Local rules such as `if path ~ "^/export" and score < 0.9 then deny`; see the `rules` module.
+    #[serde(default)]
+    pub rules: Vec<String>,
+    /// Per-session or per-IP request rate enforced locally.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
     pub session_extraction: SessionExtraction,
-    pub min_trust_score: f32,
+    /// Scores below this are denied. Required unless `risk_bands` is set, which it
+    /// is ignored in favour of.
+    #[serde(default)]
+    pub min_trust_score: Option<f32>,
+    /// Score ranges mapped to actions, e.g. allow from 0.7, challenge from 0.4 and
+    /// deny below that, listed from the highest `min_score` down. Replaces
+    /// `min_trust_score` and `challenge`.
+    #[serde(default)]
+    pub risk_bands: Vec<RiskBand>,
+    /// Share of sessions (0-100) whose score-based denies and challenges (thresholds,
+    /// risk bands and post-API rules) are enforced; the rest are allowed and only
+    /// logged. Sessions are bucketed by a hash of their id. Decisions made without a
+    /// score, such as IP rules, countries, invalid session ids and fallbacks, always
+    /// apply, as do local bans.
+    #[serde(default = "default_enforcement_percentage")]
+    pub enforcement_percentage: f32,
+    /// Challenge instead of allowing scores just above `min_trust_score`; not used with `risk_bands`.
+    #[serde(default)]
+    pub challenge: Option<ChallengeConfig>,
     #[serde(default = "default_timeout_ms")]
     pub timeout_ms: u64,
+    /// Time out session lookups after a multiple of recent latency, capped at `timeout_ms`.
+    #[serde(default)]
+    pub adaptive_timeout: Option<AdaptiveTimeoutConfig>,
+    /// Send a second session lookup when the first is slow and use the first answer.
+    #[serde(default)]
+    pub hedge: Option<HedgeConfig>,
+    /// How long a fetched trust score is reused for the same session; 0 disables caching.
+    #[serde(default)]
+    pub cache_ttl_ms: u64,
+    #[serde(default = "default_cache_max_entries")]
+    pub cache_max_entries: usize,
+    /// How long an unknown session is remembered in-process so repeated lookups of
+    /// garbage ids skip the Trust API; 0 disables it. Independent of `cache_ttl_ms`.
+    #[serde(default)]
+    pub negative_cache_ttl_ms: u64,
+    /// When the Trust API fails, use a cached score up to this long past its expiry
+    /// instead of the failure mode; 0 (default) disables it.
+    #[serde(default)]
+    pub serve_stale_ms: u64,
+    /// Share cached scores across processes through Redis (requires the `redis` feature).
+    #[serde(default)]
+    pub cache_redis_url: Option<String>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Back-off after the Trust API answers 429, following its `Retry-After`.
+    #[serde(default)]
+    pub throttle: ThrottleConfig,
+    #[serde(default)]
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Cap on Trust API calls in flight; calls over it are shed or queued.
+    #[serde(default)]
+    pub concurrency_limit: Option<ConcurrencyLimitConfig>,
+    /// Decision to return when the Trust API can't be reached; unset propagates the error.
+    #[serde(default)]
+    pub failure_mode: Option<FailureMode>,
+    #[serde(default)]
+    pub transport: TransportKind,
+    /// How strictly Trust API responses are read (HTTP transport only).
+    #[serde(default)]
+    pub response_parsing: ResponseParsing,
+    /// Newest Trust API payload version to offer; the API answers in the version it picks.
+    /// Set `v1` to pin the old shape (HTTP transport only).
+    #[serde(default)]
+    pub protocol_version: ProtocolVersion,
+    /// Encoding of Trust API bodies (HTTP transport only).
+    #[serde(default)]
+    pub wire_format: WireFormat,
+    /// More deployments of the Trust API beside `api_base_url`, chosen between by
+    /// health and priority or latency.
+    #[serde(default)]
+    pub endpoints: Option<EndpointsConfig>,
+    /// Other deployments of the Trust API, such as regional replicas, tried in order
+    /// when the one at `api_base_url` (or every one of `endpoints`) fails. They use the same transport and settings.
+    #[serde(default)]
+    pub fallbacks: Vec<FallbackConfig>,
+    /// Score sessions locally from the user agent, ASN and request rate when the Trust
+    /// API fails and no stale score is available, before `failure_mode` applies.
+    #[serde(default)]
+    pub local_scorer: Option<LocalScorerConfig>,
+    /// Send outgoing HTTP requests through this proxy instead of the one from the environment.
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+    /// Connection pool and keep-alive settings for Trust API calls (HTTP transport only).
+    #[serde(default)]
+    pub pool: Option<PoolConfig>,
+    /// Cache Trust API DNS lookups, or skip them for fixed addresses (HTTP transport only).
+    #[serde(default)]
+    pub dns: Option<DnsConfig>,
+    /// Maximum session ids sent per `/eguard/trust/batch` call.
+    #[serde(default = "default_batch_max_size")]
+    pub batch_max_size: usize,
+    /// Shared secret for verifying pushed score updates; webhooks are rejected when unset.
+    /// Never serialized.
+    #[serde(default, skip_serializing)]
+    pub webhook_secret: Option<SecretString>,
+    /// HMAC-sign every Trust API request (HTTP transport only).
+    #[serde(default)]
+    pub request_signing: Option<SigningConfig>,
+    /// Privacy mode: replace session ids with a salted HMAC of them before they are
+    /// used; see the `privacy` module.
+    #[serde(default)]
+    pub session_hashing: Option<SessionHashingConfig>,
+    /// Load `api_key` and the signing key from a secret store; see `EGuard::load_secrets`.
+    #[serde(default)]
+    pub secrets: Option<SecretsConfig>,
+    /// Re-fetch hot sessions before their cache entry expires; see `EGuard::spawn_refresher`.
+    #[serde(default)]
+    pub refresh: Option<RefreshConfig>,
+    /// Keep a local copy of banned sessions; see `EGuard::spawn_denylist_sync`.
+    #[serde(default)]
+    pub denylist: Option<DenylistConfig>,
+    /// Apply score changes and bans streamed by the Trust API; see `EGuard::spawn_push_listener`.
+    #[serde(default)]
+    pub push: Option<PushConfig>,
+    /// Append every decision as a JSON line to this file (`-` for stdout).
+    #[serde(default)]
+    pub audit_log_path: Option<String>,
+    /// Stream every decision to these sinks in the background (not on wasm32).
+    #[serde(default)]
+    pub decision_sinks: Vec<DecisionSinkConfig>,
+    /// Describe decisions in `X-EGuard-*` headers; see `EGuard::response_headers`.
+    #[serde(default)]
+    pub response_headers: Option<ResponseHeadersConfig>,
+    /// Body for denied requests in place of the deny message; see `EGuard::deny_body`.
+    #[serde(default)]
+    pub deny_body: Option<DenyBodyConfig>,
+    /// Named tenants, each the config above with its own overrides. Requests that
+    /// match no tenant use the top-level config; see `EGuard::for_request`.
+    #[serde(default)]
+    pub tenants: BTreeMap<String, TenantConfig>,
+    /// Request header naming the tenant, checked before the tenants' `hosts`.
+    #[serde(default)]
+    pub tenant_header: Option<String>,
 }
 
 fn default_timeout_ms() -> u64 { 1500 }
+fn default_cache_max_entries() -> usize { 10_000 }
+fn default_batch_max_size() -> usize { 100 }
+fn default_enforcement_percentage() -> f32 { 100.0 }
 
-#[derive(Clone)]
-struct CompiledRoute {
-    re: Regex,
-    methods: Option<Vec<String>>,
+/// Whether `session_id` is in the enforced `percentage` of sessions. Depends only on
+/// the id, so a session stays in or out across requests, processes and restarts.
+fn is_enforced(session_id: &str, percentage: f32) -> bool {
+    if percentage >= 100.0 {
+        return true;
+    }
+    let digest = Sha256::digest(session_id.as_bytes());
+    let bucket = u64::from_be_bytes(digest[..8].try_into().unwrap()) % 10_000;
+    (bucket as f32) < percentage * 100.0
+}
+
+/// What a decision was made from.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Basis {
+    /// Local checks, before any score: invalid ids, IP rules, countries and rate limits.
+    Local,
+    /// A score from the Trust API or the cache.
+    Score,
+    /// A session revoked locally or on the synced denylist.
+    Ban,
+    /// `failure_mode` or another fallback, because the Trust API failed.
+    Fallback,
+}
+
+impl Basis {
+    fn score(banned: bool) -> Self {
+        if banned { Basis::Ban } else { Basis::Score }
+    }
+}
+
+/// Calls `f` with the combined `Cookie` headers and a case-insensitive header lookup.
+fn from_headers<'h, T>(
+    headers: impl IntoIterator<Item = (&'h str, &'h str)>,
+    f: impl FnOnce(Option<&str>, &dyn Fn(&str) -> Option<&'h str>) -> T,
+) -> T {
+    let headers: Vec<_> = headers.into_iter().collect();
+    let cookies = headers.iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("cookie"))
+        .map(|(_, value)| *value)
+        .collect::<Vec<_>>()
+        .join("; ");
+    let cookies = (!cookies.is_empty()).then_some(cookies.as_str());
+    let header = |name: &str| headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| *v);
+    f(cookies, &header)
+}
+
+/// The configured transport, or `endpoints` if set, followed by `fallbacks` if there are any.
+fn build_provider(
+    cfg: &EGuardConfig,
+    hooks: &HttpHooks,
+    endpoints: Option<Arc<EndpointPool>>,
+    keys: Arc<ApiKeys>,
+    signer: Option<Arc<Signer>>,
+) -> anyhow::Result<Arc<dyn TrustProvider>> {
+    let timeout = Duration::from_millis(cfg.timeout_ms);
+    let primary = match endpoints {
+        Some(pool) => pool,
+        None => build_transport(cfg, hooks, &cfg.api_base_url, keys.clone(), timeout, signer.clone())?,
+    };
+    if cfg.fallbacks.is_empty() {
+        return Ok(primary);
+    }
+    let mut provider = FallbackProvider::new().with_provider(primary, None);
+    for fallback in &cfg.fallbacks {
+        let keys = match &fallback.api_key {
+            Some(key) => Arc::new(ApiKeys::new(key.expose(), None)),
+            None => keys.clone(),
+        };
+        let timeout = fallback.timeout_ms.map_or(timeout, Duration::from_millis);
+        let transport = build_transport(cfg, hooks, &fallback.api_base_url, keys, timeout, signer.clone())?;
+        provider = provider.with_provider(transport, None);
+    }
+    Ok(Arc::new(provider))
+}
+
+fn build_transport(
+    cfg: &EGuardConfig,
+    hooks: &HttpHooks,
+    base_url: &str,
+    keys: Arc<ApiKeys>,
+    timeout: Duration,
+    signer: Option<Arc<Signer>>,
+) -> anyhow::Result<Arc<dyn TrustProvider>> {
+    match cfg.transport {
+        TransportKind::Http if hooks.client.is_some() && base_url.starts_with(transport::UNIX_SCHEME) => {
+            Err(anyhow::anyhow!("A custom HTTP client can't be used with a unix:// api_base_url"))
+        }
+        TransportKind::Http => {
+            let transport = match &hooks.client {
+                Some(client) => HttpTransport::with_client(base_url, &keys.primary(), timeout, client.clone()),
+                None => {
+                    let (client, base_url) = transport::api_client(base_url, cfg.proxy.as_ref(), cfg.pool.as_ref(), cfg.dns.as_ref())?;
+                    HttpTransport::with_client(&base_url, &keys.primary(), timeout, client)
+                }
+            };
+            let transport = hooks.middleware.iter()
+                .fold(transport, |t, m| t.with_middleware(m.clone()))
+                .with_keys(keys)
+                .with_parsing(cfg.response_parsing)
+                .with_protocol(cfg.protocol_version)
+                .with_format(cfg.wire_format)?;
+            match signer {
+                Some(signer) => Ok(Arc::new(transport.with_signer(signer))),
+                None => Ok(Arc::new(transport)),
+            }
+        }
+        TransportKind::Grpc if cfg.request_signing.is_some() => {
+            Err(anyhow::anyhow!("request_signing is only supported by the Http transport"))
+        }
+        TransportKind::Grpc if cfg.proxy.is_some() => {
+            Err(anyhow::anyhow!("proxy is only supported by the Http transport"))
+        }
+        TransportKind::Grpc if cfg.pool.is_some() => {
+            Err(anyhow::anyhow!("pool is only supported by the Http transport"))
+        }
+        TransportKind::Grpc if cfg.dns.is_some() => {
+            Err(anyhow::anyhow!("dns is only supported by the Http transport"))
+        }
+        TransportKind::Grpc if cfg.wire_format != WireFormat::Json => {
+            Err(anyhow::anyhow!("wire_format is only supported by the Http transport"))
+        }
+        TransportKind::Grpc if base_url.starts_with(transport::UNIX_SCHEME) => {
+            Err(anyhow::anyhow!("unix:// api_base_url is only supported by the Http transport"))
+        }
+        TransportKind::Grpc if !hooks.is_empty() => {
+            Err(anyhow::anyhow!("A custom HTTP client or middleware is only supported by the Http transport"))
+        }
+        #[cfg(feature = "grpc")]
+        TransportKind::Grpc => {
+            Ok(Arc::new(transport::GrpcTransport::new(base_url, &keys.primary(), timeout)?.with_keys(keys)))
+        }
+        #[cfg(not(feature = "grpc"))]
+        TransportKind::Grpc => Err(anyhow::anyhow!("transport is Grpc but eguard-core was built without the `grpc` feature")),
+    }
+}
+
+fn build_endpoints(cfg: &EGuardConfig, hooks: &HttpHooks, keys: Arc<ApiKeys>, signer: Option<Arc<Signer>>) -> anyhow::Result<Option<Arc<EndpointPool>>> {
+    let Some(endpoints) = &cfg.endpoints else {
+        return Ok(None);
+    };
+    let timeout = Duration::from_millis(cfg.timeout_ms);
+    let mut pool = EndpointPool::new(endpoints);
+    for url in std::iter::once(&cfg.api_base_url).chain(&endpoints.api_base_urls) {
+        pool = pool.with_endpoint(url, build_transport(cfg, hooks, url, keys.clone(), timeout, signer.clone())?);
+    }
+    Ok(Some(Arc::new(pool)))
+}
+
+fn build_cache(cfg: &EGuardConfig) -> anyhow::Result<Option<Arc<dyn TrustCache>>> {
+    if cfg.cache_ttl_ms == 0 {
+        return Ok(None);
+    }
+    let ttl = Duration::from_millis(cfg.cache_ttl_ms);
+    let max_stale = Duration::from_millis(cfg.serve_stale_ms);
+    match &cfg.cache_redis_url {
+        #[cfg(feature = "redis")]
+        Some(url) => Ok(Some(Arc::new(cache::RedisCache::new(url, ttl)?.with_max_stale(max_stale)))),
+        #[cfg(not(feature = "redis"))]
+        Some(_) => Err(anyhow::anyhow!("cache_redis_url is set but eguard-core was built without the `redis` feature")),
+        None => Ok(Some(Arc::new(MemoryCache::new(ttl, cfg.cache_max_entries).with_max_stale(max_stale)))),
+    }
+}
+
+fn build_limiter(cfg: &EGuardConfig) -> anyhow::Result<Option<Arc<RateLimiter>>> {
+    cfg.rate_limit.as_ref().map(|r| Ok(Arc::new(RateLimiter::new(r)?))).transpose()
+}
+
+fn build_geoip(cfg: &EGuardConfig) -> anyhow::Result<Option<Arc<GeoIp>>> {
+    cfg.geoip.as_ref().map(|g| Ok(Arc::new(GeoIp::open(g)?))).transpose()
+}
+
+fn build_logger(cfg: &EGuardConfig) -> anyhow::Result<Option<Arc<dyn DecisionLogger>>> {
+    let mut loggers: Vec<Arc<dyn DecisionLogger>> = Vec::new();
+    match cfg.audit_log_path.as_deref() {
+        None => {}
+        Some("-") => loggers.push(Arc::new(JsonLinesLogger::stdout())),
+        Some(path) => loggers.push(Arc::new(JsonLinesLogger::open(path)?)),
+    }
+    if !cfg.decision_sinks.is_empty() {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let sinks = cfg.decision_sinks.iter()
+                .map(|s| sink::build(s, cfg.proxy.as_ref()))
+                .collect::<anyhow::Result<_>>()?;
+            loggers.push(Arc::new(sink::SinkDispatcher::new(sinks)));
+        }
+        #[cfg(target_arch = "wasm32")]
+        return Err(anyhow::anyhow!("decision_sinks are not supported on wasm32"));
+    }
+    Ok(match loggers.len() {
+        0 => None,
+        1 => loggers.pop(),
+        _ => Some(Arc::new(FanOutLogger::new(loggers))),
+    })
+}
+
+/// The part of an `EGuard` that `reload` swaps: the config and the routes compiled from it.
+struct Policy {
+    cfg: Arc<EGuardConfig>,
+    routes: RouteMatcher,
+    ip_rules: Option<IpRules>,
+    rules: Rules,
+    jwt: Option<Arc<JwtDecoder>>,
+    session_validator: Option<SessionValidator>,
+    deny_body: Option<DenyTemplate>,
+}
+
+impl Policy {
+    fn compile(cfg: EGuardConfig) -> anyhow::Result<Self> {
+        if cfg.geoip.is_none()
+            && let Some(r) = cfg.secure_routes.iter().find(|r| r.allowed_countries.is_some())
+        {
+            return Err(anyhow::anyhow!("Route {} sets allowed_countries but geoip is not configured", r.path_pattern));
+        }
+        if cfg.min_trust_score.is_none() && cfg.risk_bands.is_empty() {
+            return Err(anyhow::anyhow!("Either min_trust_score or risk_bands must be set"));
+        }
+        if !(0.0..=100.0).contains(&cfg.enforcement_percentage) {
+            return Err(anyhow::anyhow!("enforcement_percentage must be between 0 and 100"));
+        }
+        if cfg.concurrency_limit.as_ref().is_some_and(|c| c.max_in_flight == 0) {
+            return Err(anyhow::anyhow!("concurrency_limit.max_in_flight must be at least 1"));
+        }
+        check_risk_bands(&cfg.risk_bands)?;
+        for r in &cfg.secure_routes {
+            if let Some(bands) = &r.risk_bands {
+                check_risk_bands(bands).map_err(|e| anyhow::anyhow!("Route {}: {}", r.path_pattern, e))?;
+            }
+        }
+        let routes = RouteMatcher::new(&cfg)?;
+        let ip_rules = cfg.ip_rules.as_ref().map(IpRules::new).transpose()?;
+        let rules = Rules::new(&cfg.rules, cfg.ignore_trailing_slash)?;
+        let jwt = cfg.session_extraction.jwt.as_ref().map(|j| JwtDecoder::new(j, cfg.proxy.as_ref()).map(Arc::new)).transpose()?;
+        let session_validator = SessionValidator::new(&cfg.session_extraction)?;
+        let deny_body = cfg.deny_body.as_ref().map(DenyTemplate::new).transpose()?;
+        Ok(Self { cfg: Arc::new(cfg), routes, ip_rules, rules, jwt, session_validator, deny_body })
+    }
+
+    /// Session id from the configured sources, decoded from a JWT when configured.
+    fn session_id<'h>(&self, cookies: Option<&str>, header: impl Fn(&str) -> Option<&'h str>, query: Option<&str>) -> Option<String> {
+        let token = self.cfg.session_extraction.token(cookies, header, query)?;
+        match &self.jwt {
+            Some(jwt) => jwt.session_id(&token),
+            None => Some(token),
+        }
+    }
+
+    /// The deny for a session id that fails `session_extraction.max_length` or `pattern`.
+    fn invalid_session(&self, session_id: &str) -> Option<Decision> {
+        if self.session_validator.as_ref()?.is_valid(session_id) {
+            return None;
+        }
+        Some(Decision::Deny { status: 400, message: "Invalid session id".into() })
+    }
+
+    /// The decision a score gets on `route`: its score thresholds, then its risk bands
+    /// or threshold, falling back to the global ones.
+    fn score_decision(&self, route: Option<&SecureRoute>, trust: &TrustResponse) -> Decision {
+        let deny = |message: String| Decision::Deny {
+            status: route.and_then(|r| r.deny_status).unwrap_or(403),
+            message: route.and_then(|r| r.deny_message.clone()).unwrap_or(message),
+        };
+        let low = || format!("Low trust score: {}", trust.trust_score);
+
+        let failed = match route {
+            Some(r) if !r.scores.is_empty() => r.scores.iter().find_map(|t| t.violation(trust)),
+            _ => {
+                let bands = match route {
+                    Some(SecureRoute { risk_bands: Some(bands), .. }) => bands.as_slice(),
+                    Some(SecureRoute { min_trust_score: Some(_), .. }) => &[],
+                    _ => self.cfg.risk_bands.as_slice(),
+                };
+                if !bands.is_empty() {
+                    return match risk_band(bands, trust.trust_score) {
+                        Some(BandAction::Allow) => Decision::allow(),
+                        Some(BandAction::Challenge { kind, redirect_url }) => {
+                            Decision::Challenge { kind: *kind, redirect_url: redirect_url.clone() }
+                        }
+                        Some(BandAction::Deny) | None => deny(low()),
+                    };
+                }
+                let min_trust_score = route.and_then(|r| r.min_trust_score).or(self.cfg.min_trust_score).unwrap_or_default();
+                (trust.trust_score < min_trust_score).then(low)
+            }
+        };
+
+        match (failed, &self.cfg.challenge) {
+            (Some(message), _) => deny(message),
+            (None, Some(c)) if trust.trust_score < c.below => {
+                Decision::Challenge { kind: c.kind, redirect_url: c.redirect_url.clone() }
+            }
+            _ => Decision::allow(),
+        }
+    }
+
+    /// The first secure route matching the request's host, path and method.
+    fn route(&self, ctx: &RequestContext) -> Option<&SecureRoute> {
+        self.routes.route_index(ctx.host(), &ctx.path, &ctx.method).map(|i| &self.cfg.secure_routes[i])
+    }
 }
 
 #[derive(Clone)]
 pub struct EGuard {
-    cfg: Arc<EGuardConfig>,
-    client: Client,
-    routes: Vec<CompiledRoute>,
+    policy: Arc<RwLock<Arc<Policy>>>,
+    provider: Arc<dyn TrustProvider>,
+    /// The configured `endpoints`, also held here for health checks.
+    endpoints: Option<Arc<EndpointPool>>,
+    cache: Option<Arc<dyn TrustCache>>,
+    breaker: Option<Arc<CircuitBreaker>>,
+    adaptive: Option<Arc<AdaptiveTimeout>>,
+    hedge: Option<Arc<Hedge>>,
+    in_flight: Option<Arc<ConcurrencyLimit>>,
+    throttle: Arc<Throttle>,
+    hot: Option<Arc<HotSessions>>,
+    metrics: Arc<Metrics>,
+    logger: Option<Arc<dyn DecisionLogger>>,
+    geoip: Option<Arc<GeoIp>>,
+    limiter: Option<Arc<RateLimiter>>,
+    /// Sessions the Trust API recently reported as unknown.
+    negative_cache: Option<Arc<MemoryCache>>,
+    /// Sessions banned through `revoke_session` or a webhook, denied without asking
+    /// the Trust API.
+    revoked: Arc<MemoryCache>,
+    denylist: Option<Arc<Denylist>>,
+    /// Trust API lookups in progress, shared by concurrent `decide` calls for the same session.
+    flights: Arc<SingleFlight<TrustResponse>>,
+    /// One guard per configured tenant.
+    tenants: Arc<BTreeMap<String, EGuard>>,
+    keys: Arc<ApiKeys>,
+    signer: Option<Arc<Signer>>,
+    /// Set by `with_http_client` and `with_request_middleware`; kept so each rebuilds
+    /// the transports with both.
+    hooks: HttpHooks,
+    hasher: Option<Arc<SessionHasher>>,
+    secrets: SecretProviders,
+    local_scorer: Option<Arc<LocalScorer>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    events: Arc<EventReporter>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// How long a revoked session is denied locally, without waiting for the Trust API
+/// to score it as banned.
+const REVOKED_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Most sessions denied locally at once. Separate from `cache_max_entries`, so busy
+/// caches (or a disabled one) don't push bans out; past it the oldest ban is dropped.
+const REVOKED_MAX_ENTRIES: usize = 1_000_000;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct TrustResponse {
     pub session_id: String,
     pub trust_score: f32,
-    pub reason: Option<String>,
+    pub reason: Option<ReasonCode>,
+    /// Likelihood the session is automated, from Trust API v2; higher is worse.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bot_score: Option<f32>,
+    /// Likelihood of payment or account fraud, from Trust API v2; higher is worse.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fraud_score: Option<f32>,
+    /// Likelihood of spam, scraping or other abuse, from Trust API v2; higher is worse.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abuse_score: Option<f32>,
+}
+
+impl TrustResponse {
+    /// The given score, if the Trust API returned it.
+    pub fn score(&self, kind: ScoreKind) -> Option<f32> {
+        match kind {
+            ScoreKind::Trust => Some(self.trust_score),
+            ScoreKind::Bot => self.bot_score,
+            ScoreKind::Fraud => self.fraud_score,
+            ScoreKind::Abuse => self.abuse_score,
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Non-success status returned by the Trust API.
+#[derive(Debug)]
+pub struct ApiStatusError {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl std::fmt::Display for ApiStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Trust API error {}: {}", self.status, redact::redact(&self.body))
+    }
+}
+
+impl std::error::Error for ApiStatusError {}
+
+/// The latency budget of `decide_with_deadline` ran out before the Trust API answered.
+#[derive(Debug)]
+pub struct DeadlineExceeded(pub Duration);
+
+impl std::fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No trust score within the {}ms budget", self.0.as_millis())
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+fn is_transient(err: &anyhow::Error) -> bool {
+    if err.is::<provider::ProviderTimeout>() {
+        return true;
+    }
+    if let Some(e) = err.downcast_ref::<ApiStatusError>() {
+        return e.status.is_server_error();
+    }
+    #[cfg(feature = "grpc")]
+    if let Some(s) = flight::cause::<tonic::Status>(err) {
+        use tonic::Code;
+        return matches!(s.code(), Code::Unavailable | Code::DeadlineExceeded | Code::Internal | Code::Unknown);
+    }
+    if let Some(e) = flight::cause::<reqwest::Error>(err) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if e.is_connect() {
+            return true;
+        }
+        return e.is_timeout() || e.is_request();
+    }
+    false
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "DecisionRepr")]
 pub enum Decision {
-    Allow,
+    /// `trust_score` and `reason` are set when the decision was based on a score, and
+    /// `route_id` when the request matched a secure route.
+    Allow { trust_score: Option<f32>, reason: Option<ReasonCode>, route_id: Option<String> },
+    Deny { status: u16, message: String },
+    /// Send the user through a CAPTCHA or step-up flow at `redirect_url` before retrying.
+    Challenge { kind: ChallengeKind, redirect_url: String },
+}
+
+/// `Decision` as written in config, where an allow may be just `Allow`.
+#[derive(Deserialize)]
+enum DecisionRepr {
+    Allow {
+        #[serde(default)]
+        trust_score: Option<f32>,
+        #[serde(default)]
+        reason: Option<ReasonCode>,
+        #[serde(default)]
+        route_id: Option<String>,
+    },
     Deny { status: u16, message: String },
+    Challenge { kind: ChallengeKind, redirect_url: String },
+    #[serde(untagged)]
+    Bare(BareAllow),
+}
+
+#[derive(Deserialize)]
+enum BareAllow {
+    Allow,
+}
+
+impl From<DecisionRepr> for Decision {
+    fn from(d: DecisionRepr) -> Self {
+        match d {
+            DecisionRepr::Allow { trust_score, reason, route_id } => Decision::Allow { trust_score, reason, route_id },
+            DecisionRepr::Deny { status, message } => Decision::Deny { status, message },
+            DecisionRepr::Challenge { kind, redirect_url } => Decision::Challenge { kind, redirect_url },
+            DecisionRepr::Bare(BareAllow::Allow) => Decision::allow(),
+        }
+    }
+}
+
+impl Decision {
+    /// An allow without a score or route.
+    pub fn allow() -> Self {
+        Decision::Allow { trust_score: None, reason: None, route_id: None }
+    }
+
+    /// `"allow"`, `"deny"` or `"challenge"`, for logs and metrics labels.
+    pub fn outcome(&self) -> &'static str {
+        match self {
+            Decision::Allow { .. } => "allow",
+            Decision::Deny { .. } => "deny",
+            Decision::Challenge { .. } => "challenge",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChallengeKind {
+    Captcha,
+    Turnstile,
+    Hcaptcha,
+    Recaptcha,
+    /// Re-authentication or a second factor.
+    StepUp,
+}
+
+impl ChallengeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChallengeKind::Captcha => "captcha",
+            ChallengeKind::Turnstile => "turnstile",
+            ChallengeKind::Hcaptcha => "hcaptcha",
+            ChallengeKind::Recaptcha => "recaptcha",
+            ChallengeKind::StepUp => "step_up",
+        }
+    }
+}
+
+/// Scores from the threshold (global or per route) up to `below` get a challenge
+/// rather than an allow; lower scores are still denied.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChallengeConfig {
+    pub below: f32,
+    pub kind: ChallengeKind,
+    pub redirect_url: String,
+}
+
+/// Scores from `min_score` up to the previous band's `min_score` get `action`.
+/// Scores below every band are denied.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RiskBand {
+    pub min_score: f32,
+    #[serde(flatten)]
+    pub action: BandAction,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum BandAction {
+    Allow,
+    Challenge { kind: ChallengeKind, redirect_url: String },
+    /// With the route's `deny_status` and `deny_message`, if set.
+    Deny,
+}
+
+/// Rejects bands that are out of order or share a `min_score`, which are almost
+/// always a typo in the thresholds.
+fn check_risk_bands(bands: &[RiskBand]) -> anyhow::Result<()> {
+    if let Some(b) = bands.iter().find(|b| !(0.0..=1.0).contains(&b.min_score)) {
+        return Err(anyhow::anyhow!("risk_bands min_score {} is not between 0 and 1", b.min_score));
+    }
+    if let Some(w) = bands.windows(2).find(|w| w[1].min_score >= w[0].min_score) {
+        return Err(anyhow::anyhow!(
+            "risk_bands must be listed from the highest min_score down, but {} follows {}",
+            w[1].min_score, w[0].min_score,
+        ));
+    }
+    Ok(())
+}
+
+/// The action of the band `score` falls in.
+fn risk_band(bands: &[RiskBand], score: f32) -> Option<&BandAction> {
+    bands.iter()
+        .filter(|b| score >= b.min_score)
+        .max_by(|a, b| a.min_score.total_cmp(&b.min_score))
+        .map(|b| &b.action)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum FailureMode {
+    FailOpen,
+    FailClosed,
+    Custom { status: u16, message: String },
+}
+
+impl FailureMode {
+    pub fn decision(&self) -> Decision {
+        match self {
+            FailureMode::FailOpen => Decision::allow(),
+            FailureMode::FailClosed => Decision::Deny {
+                status: 503,
+                message: "Trust service unavailable".into(),
+            },
+            FailureMode::Custom { status, message } => Decision::Deny {
+                status: *status,
+                message: message.clone(),
+            },
+        }
+    }
 }
 
 impl EGuard {
-    pub fn new(cfg: EGuardConfig) -> anyhow::Result<Self> {
-        let client = Client::builder()
-            .timeout(Duration::from_millis(cfg.timeout_ms))
-            .build()?;
-
-        let routes = cfg.secure_routes.iter()
-            .map(|r| {
-                let re = Regex::new(&r.path_pattern)
-                    .map_err(|e| anyhow::anyhow!("Invalid route regex {}: {}", r.path_pattern, e))?;
-                let methods = r.methods.as_ref().map(|v| v.iter().map(|m| m.to_uppercase()).collect());
-                Ok(CompiledRoute { re, methods })
+    pub fn new(cfg: EGuardConfig) -> Result<Self, EGuardError> {
+        Self::build(cfg).map_err(EGuardError::ConfigInvalid)
+    }
+
+    fn build(cfg: EGuardConfig) -> anyhow::Result<Self> {
+        let keys = Arc::new(ApiKeys::new(cfg.api_key.expose(), cfg.secondary_api_key.as_ref().map(SecretString::expose)));
+        let secrets = SecretProviders::new(&cfg)?;
+        let signer = match &cfg.request_signing {
+            Some(signing) if signing.key.is_empty() && secrets.signing_key.is_none() => {
+                return Err(anyhow::anyhow!("request_signing.key must not be empty"));
+            }
+            Some(signing) => Some(Arc::new(Signer::new(signing)?)),
+            None => None,
+        };
+        let hooks = HttpHooks::default();
+        let hasher = cfg.session_hashing.as_ref().map(|h| SessionHasher::new(h).map(Arc::new)).transpose()?;
+        let endpoints = build_endpoints(&cfg, &hooks, keys.clone(), signer.clone())?;
+        let provider = build_provider(&cfg, &hooks, endpoints.clone(), keys.clone(), signer.clone())?;
+        let cache = build_cache(&cfg)?;
+        let negative_cache = (cfg.negative_cache_ttl_ms > 0).then(|| {
+            Arc::new(MemoryCache::new(Duration::from_millis(cfg.negative_cache_ttl_ms), cfg.cache_max_entries))
+        });
+        let revoked = Arc::new(MemoryCache::new(REVOKED_TTL, REVOKED_MAX_ENTRIES));
+        let denylist = cfg.denylist.is_some().then(Arc::default);
+        let breaker = cfg.circuit_breaker.as_ref().map(|b| Arc::new(CircuitBreaker::new(b)));
+        let adaptive = cfg.adaptive_timeout.as_ref()
+            .map(|a| Arc::new(AdaptiveTimeout::new(a, Duration::from_millis(cfg.timeout_ms))));
+        let hedge = cfg.hedge.as_ref().map(|h| Arc::new(Hedge::new(h)));
+        let in_flight = cfg.concurrency_limit.as_ref().map(|c| Arc::new(ConcurrencyLimit::new(c)));
+        let throttle = Arc::new(Throttle::new(&cfg.throttle));
+        let hot = match (&cfg.refresh, cfg.cache_ttl_ms) {
+            (Some(r), ttl) if ttl > 0 => {
+                Some(Arc::new(HotSessions::new(r, Duration::from_millis(ttl), cfg.cache_max_entries)))
+            }
+            _ => None,
+        };
+
+        let logger = build_logger(&cfg)?;
+        let geoip = build_geoip(&cfg)?;
+        let limiter = build_limiter(&cfg)?;
+        let local_scorer = cfg.local_scorer.as_ref().map(|c| LocalScorer::new(c).map(Arc::new)).transpose()?;
+        let tenants = cfg.tenants.iter()
+            .map(|(name, t)| {
+                let guard = EGuard::new(t.apply(&cfg)).map_err(|e| anyhow::anyhow!("Tenant `{}`: {}", name, e))?;
+                Ok((name.clone(), guard))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let policy = Arc::new(RwLock::new(Arc::new(Policy::compile(cfg)?)));
+
+        Ok(Self {
+            policy,
+            provider,
+            endpoints,
+            cache,
+            breaker,
+            adaptive,
+            hedge,
+            in_flight,
+            throttle,
+            hot,
+            metrics: Arc::default(),
+            logger,
+            geoip,
+            limiter,
+            negative_cache,
+            revoked,
+            denylist,
+            flights: Arc::new(SingleFlight::new()),
+            tenants: Arc::new(tenants),
+            keys,
+            signer,
+            hooks,
+            hasher,
+            secrets,
+            local_scorer,
+            #[cfg(not(target_arch = "wasm32"))]
+            events: Arc::default(),
+        })
+    }
+
+    /// Get scores from `provider` instead of the configured transport. Tenants keep their own.
+    pub fn with_provider(mut self, provider: Arc<dyn TrustProvider>) -> Self {
+        self.provider = provider;
+        self.endpoints = None;
+        self
+    }
+
+    /// Call the Trust API with `client` instead of one built from the config, for this
+    /// guard and its tenants, e.g. for custom TLS or connection settings. `proxy`,
+    /// `pool` and `dns` are not applied to it, and it can't be used with a `unix://`
+    /// URL. Replaces a provider set with `with_provider`.
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Result<Self, EGuardError> {
+        self.hooks.client = Some(client.clone());
+        self.tenants = Arc::new(self.tenants.iter()
+            .map(|(name, guard)| Ok((name.clone(), guard.clone().with_http_client(client.clone())?)))
+            .collect::<Result<_, EGuardError>>()?);
+        self.rebuild_provider().map_err(EGuardError::ConfigInvalid)?;
+        Ok(self)
+    }
+
+    /// Pass every Trust API request through `middleware`, after any added before, for
+    /// this guard and its tenants. Replaces a provider set with `with_provider`.
+    pub fn with_request_middleware(mut self, middleware: Arc<dyn RequestMiddleware>) -> Result<Self, EGuardError> {
+        self.hooks.middleware.push(middleware.clone());
+        self.tenants = Arc::new(self.tenants.iter()
+            .map(|(name, guard)| Ok((name.clone(), guard.clone().with_request_middleware(middleware.clone())?)))
+            .collect::<Result<_, EGuardError>>()?);
+        self.rebuild_provider().map_err(EGuardError::ConfigInvalid)?;
+        Ok(self)
+    }
+
+    fn rebuild_provider(&mut self) -> anyhow::Result<()> {
+        let cfg = self.config();
+        self.endpoints = build_endpoints(&cfg, &self.hooks, self.keys.clone(), self.signer.clone())?;
+        self.provider = build_provider(&cfg, &self.hooks, self.endpoints.clone(), self.keys.clone(), self.signer.clone())?;
+        Ok(())
+    }
+
+    /// Replace the configured cache backend with a custom one. Tenants keep their own.
+    pub fn with_cache(mut self, cache: Arc<dyn TrustCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Send decisions to a custom audit sink instead of `audit_log_path` and `decision_sinks`,
+    /// for this guard and its tenants.
+    pub fn with_decision_logger(mut self, logger: Arc<dyn DecisionLogger>) -> Self {
+        if !self.tenants.is_empty() {
+            let tenants = self.tenants.iter()
+                .map(|(name, guard)| (name.clone(), guard.clone().with_decision_logger(logger.clone())))
+                .collect();
+            self.tenants = Arc::new(tenants);
+        }
+        self.logger = Some(logger);
+        self
+    }
+
+    /// Send decisions to `logger` as well as to the audit log and sinks already set up,
+    /// for this guard and its tenants.
+    pub fn with_extra_decision_logger(mut self, logger: Arc<dyn DecisionLogger>) -> Self {
+        if !self.tenants.is_empty() {
+            let tenants = self.tenants.iter()
+                .map(|(name, guard)| (name.clone(), guard.clone().with_extra_decision_logger(logger.clone())))
+                .collect();
+            self.tenants = Arc::new(tenants);
+        }
+        self.logger = Some(match self.logger.take() {
+            Some(current) => Arc::new(FanOutLogger::new(vec![current, logger])),
+            None => logger,
+        });
+        self
+    }
+
+    /// Load `api_key` from `provider` instead of `secrets.api_key`.
+    pub fn with_api_key_provider(mut self, provider: Arc<dyn SecretProvider>) -> Self {
+        self.secrets.api_key = Some(provider);
+        self
+    }
+
+    /// Load the `request_signing` key from `provider` instead of `secrets.signing_key`.
+    pub fn with_signing_key_provider(mut self, provider: Arc<dyn SecretProvider>) -> Result<Self, EGuardError> {
+        if self.signer.is_none() {
+            return Err(EGuardError::ConfigInvalid(anyhow::anyhow!("A signing key provider requires request_signing")));
+        }
+        self.secrets.signing_key = Some(provider);
+        Ok(self)
+    }
+
+    /// The config currently in effect; a later `reload` does not change the returned value.
+    pub fn config(&self) -> Arc<EGuardConfig> {
+        self.policy().cfg.clone()
+    }
+
+    fn policy(&self) -> Arc<Policy> {
+        self.policy.read().unwrap().clone()
+    }
+
+    /// Atomically applies a new config to this guard and all its clones. Routes,
+    /// thresholds, session extraction, retry and failure handling take effect for
+    /// the next request, as do changed API keys; the rest of the transport, the cache,
+    /// circuit breaker, refresh, rate limit and audit settings are fixed when the guard
+    /// is created. Tenants are reloaded with it, but cannot be added or removed. An
+    /// invalid config is rejected and the current one kept.
+    pub fn reload(&self, cfg: EGuardConfig) -> Result<(), EGuardError> {
+        if !cfg.tenants.keys().eq(self.tenants.keys()) {
+            return Err(EGuardError::ConfigInvalid(anyhow::anyhow!("Tenants cannot be added or removed by reload")));
+        }
+        let tenants = self.tenants.iter()
+            .map(|(name, guard)| {
+                let policy = Policy::compile(cfg.tenants[name].apply(&cfg))
+                    .map_err(|e| anyhow::anyhow!("Tenant `{}`: {}", name, e))?;
+                Ok((guard, Arc::new(policy)))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(EGuardError::ConfigInvalid)?;
+        let policy = Arc::new(Policy::compile(cfg).map_err(EGuardError::ConfigInvalid)?);
+        for (guard, policy) in tenants {
+            guard.reload_keys(&policy.cfg);
+            *guard.policy.write().unwrap() = policy;
+        }
+        self.reload_keys(&policy.cfg);
+        *self.policy.write().unwrap() = policy;
+        Ok(())
+    }
+
+    /// Keeps keys set by `rotate_key` or a failover unless the config changed them.
+    fn reload_keys(&self, cfg: &EGuardConfig) {
+        let current = self.policy();
+        if cfg.api_key != current.cfg.api_key || cfg.secondary_api_key != current.cfg.secondary_api_key {
+            self.keys.set(cfg.api_key.expose(), cfg.secondary_api_key.as_ref().map(SecretString::expose));
+        }
+    }
+
+    /// Switches to a new API key without a restart. The previous key is kept as the
+    /// secondary until the next rotation, so requests in flight, and a key that hasn't
+    /// propagated yet, keep working.
+    pub fn rotate_key(&self, key: &str) {
+        self.keys.rotate(key);
+    }
+
+    /// The guard of tenant `name`.
+    pub fn tenant(&self, name: &str) -> Option<&EGuard> {
+        self.tenants.get(name)
+    }
+
+    /// The guard to use for a request: the tenant named by the `tenant_header` header,
+    /// else the first tenant serving `host`, else this guard.
+    pub fn select_tenant<'h>(&self, host: Option<&str>, header: impl Fn(&str) -> Option<&'h str>) -> &EGuard {
+        if self.tenants.is_empty() {
+            return self;
+        }
+        let policy = self.policy();
+        let named = policy.cfg.tenant_header.as_deref()
+            .and_then(header)
+            .and_then(|name| self.tenants.get(name.trim()));
+        named
+            .or_else(|| {
+                let host = host?;
+                let (name, _) = policy.cfg.tenants.iter().find(|(_, t)| t.serves(host))?;
+                self.tenants.get(name)
             })
-            .collect::<anyhow::Result<Vec<_>>>()?;
+            .unwrap_or(self)
+    }
+
+    /// `select_tenant` using the host and headers in `ctx`.
+    pub fn for_request(&self, ctx: &RequestContext) -> &EGuard {
+        self.select_tenant(ctx.host(), |name| ctx.headers.get(&name.to_ascii_lowercase()).map(String::as_str))
+    }
 
-        Ok(Self { cfg: Arc::new(cfg), client, routes })
+    /// `decide` with the guard of tenant `tenant`.
+    pub async fn decide_for(&self, tenant: &str, session_id: &str) -> Result<Decision, EGuardError> {
+        let guard = self.tenant(tenant).ok_or_else(|| EGuardError::Other(anyhow::anyhow!("Unknown tenant `{}`", tenant)))?;
+        guard.decide(session_id).await
+    }
+
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
     }
 
     pub fn is_secure(&self, path: &str, method: &str) -> bool {
-        let m = method.to_uppercase();
-        self.routes.iter().any(|r| {
-            if !r.re.is_match(path) { return false; }
-            match &r.methods {
-                None => true,
-                Some(ms) => ms.iter().any(|mm| mm == &m),
-            }
-        })
+        self.is_secure_host(None, path, method)
+    }
+
+    /// `is_secure` that also applies the routes' `hosts` restrictions. With no host,
+    /// host-restricted routes match as if the host were listed.
+    pub fn is_secure_host(&self, host: Option<&str>, path: &str, method: &str) -> bool {
+        self.policy().routes.is_secure(host, path, method)
     }
 
     pub fn extract_session_id(
@@ -89,65 +1255,887 @@ impl EGuard {
         cookies: Option<&str>,
         header_name_val: Option<(&str, &str)>,
     ) -> Option<String> {
+        self.extract_session_id_with_query(cookies, header_name_val, None)
+    }
+
+    /// `extract_session_id` that falls back to `query_param` in `query`, the raw
+    /// query string without the leading `?`.
+    pub fn extract_session_id_with_query(
+        &self,
+        cookies: Option<&str>,
+        header_name_val: Option<(&str, &str)>,
+        query: Option<&str>,
+    ) -> Option<String> {
+        let policy = self.policy();
+        let header = |name: &str| header_name_val.filter(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v);
+        policy.session_id(cookies, header, query)
+    }
+
+    /// Session id from all request headers (names in any case) and the raw query
+    /// string. Repeated `Cookie` headers are combined; for other repeated headers
+    /// the first one counts.
+    pub fn extract_session_id_from_headers<'h>(
+        &self,
+        headers: impl IntoIterator<Item = (&'h str, &'h str)>,
+        query: Option<&str>,
+    ) -> Option<String> {
+        let policy = self.policy();
+        from_headers(headers, |cookies, header| policy.session_id(cookies, header, query))
+    }
+
+    /// Device id from `session_extraction.device_sources`, looked up like
+    /// `extract_session_id_from_headers`. Put it in `RequestContext::device_id`.
+    pub fn extract_device_id_from_headers<'h>(
+        &self,
+        headers: impl IntoIterator<Item = (&'h str, &'h str)>,
+        query: Option<&str>,
+    ) -> Option<String> {
+        let policy = self.policy();
+        from_headers(headers, |cookies, header| policy.cfg.session_extraction.device_id(cookies, header, query))
+    }
+
+    /// Fetches the `session_extraction.jwt` JWKS now. Keys are otherwise fetched in the
+    /// background on first use, so call this at startup to accept tokens from the first request.
+    pub async fn refresh_jwks(&self) -> Result<(), EGuardError> {
+        match self.policy().jwt.clone() {
+            Some(jwt) => Ok(jwt.refresh_keys().await?),
+            None => Ok(()),
+        }
+    }
+
+    /// Loads the secrets configured by `secrets` (or the `with_*_provider` builders), for
+    /// this guard and its tenants. A new API key is rotated in with `rotate_key`. Call this
+    /// at startup so the first request uses them; `spawn_secret_refresher` loads them again
+    /// periodically.
+    pub async fn load_secrets(&self) -> Result<(), EGuardError> {
+        for guard in std::iter::once(self).chain(self.tenants.values()) {
+            if let Some(provider) = &guard.secrets.api_key {
+                let key = Zeroizing::new(provider.load().await.map_err(|e| anyhow::anyhow!("Loading api_key: {:#}", e))?);
+                if key.is_empty() {
+                    return Err(EGuardError::Other(anyhow::anyhow!("Loading api_key: secret is empty")));
+                }
+                if key != guard.keys.primary() {
+                    guard.rotate_key(&key);
+                }
+            }
+            if let (Some(provider), Some(signer)) = (&guard.secrets.signing_key, &guard.signer) {
+                let key = provider.load().await.map_err(|e| anyhow::anyhow!("Loading signing key: {:#}", e))?;
+                if key.is_empty() {
+                    return Err(EGuardError::Other(anyhow::anyhow!("Loading signing key: secret is empty")));
+                }
+                signer.set_key(&key)?;
+            }
+        }
+        Ok(())
+    }
 
-        if let Some(cookie_name) = &self.cfg.session_extraction.cookie_name {
-            if let Some(raw) = cookies {
-                for pair in raw.split(';') {
-                    let mut it = pair.trim().splitn(2, '=');
-                    if let (Some(k), Some(v)) = (it.next(), it.next()) {
-                        if k == cookie_name {
-                            return Some(v.to_string());
+    pub async fn fetch_trust(&self, session_id: &str) -> Result<TrustResponse, EGuardError> {
+        Ok(self.fetch_trust_inner(&self.sid(session_id), None).await?.0)
+    }
+
+    /// `session_id` as the guard uses it: hashed in privacy mode.
+    fn sid<'a>(&self, session_id: &'a str) -> Cow<'a, str> {
+        match &self.hasher {
+            Some(hasher) => Cow::Owned(hasher.hash(session_id)),
+            None => Cow::Borrowed(session_id),
+        }
+    }
+
+    fn sids<'a>(&self, session_ids: &[&'a str]) -> Vec<Cow<'a, str>> {
+        session_ids.iter().map(|sid| self.sid(sid)).collect()
+    }
+
+    /// The score of `session_id`, and whether it is a local ban rather than a lookup.
+    /// Cached scores are reused whatever the context; only cache misses send it.
+    async fn fetch_trust_inner(&self, session_id: &str, ctx: Option<&RequestContext>) -> anyhow::Result<(TrustResponse, bool)> {
+        let span = tracing::info_span!(
+            "eguard.fetch_trust",
+            session_hash = %telemetry::session_hash(session_id),
+            cache_hit = Empty,
+            score = Empty,
+        );
+        async {
+            if let Some(banned) = self.banned(session_id) {
+                tracing::Span::current().record("score", banned.trust_score);
+                return Ok((banned, true));
+            }
+            if let Some(hit) = self.cache_get(session_id).await {
+                tracing::Span::current().record("score", hit.trust_score);
+                return Ok((hit, false));
+            }
+
+            // Concurrent misses for the session share one call, and whichever
+            // request got there first decides which context is sent.
+            let cfg = self.config();
+            let forwarded = ctx.map(|c| c.forwarded(&cfg.session_extraction));
+            let fetched = self.flights
+                .run(session_id, || async {
+                    let trust = match forwarded.as_deref() {
+                        Some(ctx) => self.call_api(|| self.lookup(|| self.provider.fetch_with_context(session_id, ctx))).await?,
+                        None => self.call_api(|| self.lookup(|| self.provider.fetch(session_id))).await?,
+                    };
+                    self.cache_insert(session_id, &trust).await;
+                    Ok(trust)
+                })
+                .await;
+            let trust = match fetched {
+                Ok(trust) => trust,
+                Err(e) => match self.cache_get_stale(session_id).await {
+                    Some(stale) => {
+                        tracing::warn!(error = %Redacted(&e), "trust lookup failed, using stale cached score");
+                        self.metrics.stale_served();
+                        stale
+                    }
+                    None => match &self.local_scorer {
+                        Some(scorer) => {
+                            tracing::warn!(error = %Redacted(&e), "trust lookup failed, using the local scorer");
+                            self.metrics.local_scored();
+                            scorer.score(session_id, ctx)
                         }
+                        None => return Err(e),
+                    },
+                },
+            };
+            tracing::Span::current().record("score", trust.trust_score);
+            Ok((trust, false))
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Scores many sessions at once. Cached sessions are served locally and the rest
+    /// are fetched in chunks of `batch_max_size`. Results are in input order.
+    pub async fn fetch_trust_batch(&self, session_ids: &[&str]) -> Result<Vec<TrustResponse>, EGuardError> {
+        let sids = self.sids(session_ids);
+        let fetched = self.fetch_trust_batch_inner(&sids.iter().map(|s| &**s).collect::<Vec<_>>()).await?;
+        Ok(fetched.into_iter().map(|(trust, _)| trust).collect())
+    }
+
+    /// `fetch_trust_inner` for many sessions, in input order.
+    async fn fetch_trust_batch_inner(&self, session_ids: &[&str]) -> anyhow::Result<Vec<(TrustResponse, bool)>> {
+        let mut results: Vec<Option<(TrustResponse, bool)>> = Vec::with_capacity(session_ids.len());
+        let mut missing = Vec::new();
+        for (i, sid) in session_ids.iter().enumerate() {
+            let hit = match self.banned(sid) {
+                Some(banned) => Some((banned, true)),
+                None => self.cache_get(sid).await.map(|hit| (hit, false)),
+            };
+            if hit.is_none() {
+                missing.push(i);
+            }
+            results.push(hit);
+        }
+
+        for chunk in missing.chunks(self.config().batch_max_size.max(1)) {
+            let ids: Vec<&str> = chunk.iter().map(|&i| session_ids[i]).collect();
+            let fetched = self.call_api(|| async {
+                let fetched = self.provider.fetch_batch(&ids).await?;
+                if fetched.len() != ids.len() {
+                    return Err(anyhow::anyhow!("Batch lookup returned {} scores for {} sessions", fetched.len(), ids.len()));
+                }
+                Ok(fetched)
+            }).await?;
+            for (&i, trust) in chunk.iter().zip(fetched) {
+                self.cache_insert(session_ids[i], &trust).await;
+                results[i] = Some((trust, false));
+            }
+        }
+
+        Ok(results.into_iter().zip(session_ids)
+            .map(|(trust, sid)| trust.unwrap_or_else(|| (provider::unknown_session(sid), false)))
+            .collect())
+    }
+
+    /// Verifies a webhook delivery from the Trust API and applies it to the cache,
+    /// so bans and score changes take effect before the cached entry expires.
+    pub async fn handle_webhook(&self, body: &[u8], timestamp: &str, signature: &str) -> Result<WebhookEvent, EGuardError> {
+        let cfg = self.config();
+        let secret = cfg.webhook_secret.as_ref().map(SecretString::expose)
+            .ok_or_else(|| EGuardError::ConfigInvalid(anyhow::anyhow!("webhook_secret is not configured")))?;
+        let event = webhook::verify(secret, body, timestamp, signature)?;
+        self.apply_event(&event).await?;
+        Ok(event)
+    }
+
+    /// Applies a score update or ban from a webhook or the push stream.
+    async fn apply_event(&self, event: &WebhookEvent) -> anyhow::Result<()> {
+        if let WebhookEvent::SessionBanned { session_id, .. } = event {
+            self.revoke_locally(session_id, event.trust());
+        }
+        if let Some(negative) = &self.negative_cache {
+            negative.remove(event.session_id());
+        }
+        if let Some(cache) = &self.cache {
+            cache.insert(event.session_id(), &event.trust()).await?;
+        }
+        Ok(())
+    }
+
+    /// Queues a behavioural event for the Trust API, e.g. a login or checkout by
+    /// `session_id`. Returns at once; events are sent in batches in the background and
+    /// dropped if the queue is full or there is no tokio runtime. Not available on wasm32.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn report_event(&self, session_id: &str, kind: EventKind, metadata: serde_json::Map<String, serde_json::Value>) {
+        self.events.report(&self.provider, Event::new(&self.sid(session_id), kind, metadata));
+    }
+
+    /// Reports a confirmed outcome for `session_id`, such as a chargeback, so the
+    /// scoring backend can learn from it. Unlike events, outcomes are sent at once.
+    pub async fn report_outcome(&self, session_id: &str, outcome: Outcome) -> Result<(), EGuardError> {
+        Ok(self.provider.report_outcome(&self.sid(session_id), outcome).await?)
+    }
+
+    /// Bans `session_id`: it is denied by this guard from the next request on, its
+    /// cache entry is replaced with a zero score, and the ban is sent to the Trust API.
+    /// The local ban holds even if the API call fails.
+    pub async fn revoke_session(&self, session_id: &str, reason: ReasonCode) -> Result<(), EGuardError> {
+        let session_id = &*self.sid(session_id);
+        let trust = TrustResponse { session_id: session_id.to_string(), trust_score: 0.0, reason: Some(reason.clone()), ..Default::default() };
+        self.revoke_locally(session_id, trust.clone());
+        if let Some(negative) = &self.negative_cache {
+            negative.remove(session_id);
+        }
+        if let Some(cache) = &self.cache {
+            let _ = cache.insert(session_id, &trust).await;
+        }
+        Ok(self.provider.revoke_session(session_id, &reason).await?)
+    }
+
+    /// Events `report_event` had to drop.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn events_dropped(&self) -> u64 {
+        self.events.dropped()
+    }
+
+    /// The cached score of `session_id`, or the `unknown_session` response if the
+    /// negative cache has it. Records a hit or miss when any cache is configured.
+    async fn cache_get(&self, session_id: &str) -> Option<TrustResponse> {
+        if self.cache.is_none() && self.negative_cache.is_none() {
+            return None;
+        }
+        let hit = match &self.cache {
+            Some(cache) => cache.get(session_id).await.ok().flatten(),
+            None => None,
+        };
+        if hit.is_some()
+            && let Some(hot) = &self.hot
+        {
+            hot.touch(session_id);
+        }
+        let hit = hit.or_else(|| self.negative_cache.as_ref()?.get(session_id));
+        self.metrics.cache(hit.is_some());
+        tracing::Span::current().record("cache_hit", hit.is_some());
+        hit
+    }
+
+    async fn cache_get_stale(&self, session_id: &str) -> Option<TrustResponse> {
+        if self.config().serve_stale_ms == 0 {
+            return None;
+        }
+        self.cache.as_ref()?.get_stale(session_id).await.ok().flatten()
+    }
+
+    async fn cache_insert(&self, session_id: &str, trust: &TrustResponse) {
+        if trust.reason == Some(ReasonCode::UnknownSession) {
+            if let Some(negative) = &self.negative_cache {
+                negative.insert(session_id, trust.clone());
+            }
+            return;
+        }
+        if let Some(cache) = &self.cache {
+            let _ = cache.insert(session_id, trust).await;
+            if let Some(hot) = &self.hot {
+                hot.fetched(session_id);
+            }
+        }
+    }
+
+    /// Starts the refresh-ahead task configured by `refresh` on the current tokio
+    /// runtime. Returns `None` if refresh (or caching) is disabled. The task stops
+    /// when the returned handle is dropped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn_refresher(&self) -> Option<refresh::RefreshHandle> {
+        let hot = self.hot.clone()?;
+        let interval = Duration::from_millis(self.config().refresh.as_ref()?.interval_ms.max(1));
+        let guard = self.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                rt::sleep(interval).await;
+                for sid in hot.due() {
+                    if let Ok(trust) = guard.call_api(|| guard.lookup(|| guard.provider.fetch(&sid))).await {
+                        guard.cache_insert(&sid, &trust).await;
                     }
                 }
             }
+        });
+        Some(refresh::RefreshHandle(task))
+    }
+
+    /// Starts a task on the current tokio runtime that loads secrets right away and then
+    /// every `secrets.refresh_ms`; failures are logged and the current secrets kept.
+    /// Returns `None` if no secrets are configured. The task stops when the handle is dropped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn_secret_refresher(&self) -> Option<refresh::RefreshHandle> {
+        if std::iter::once(self).chain(self.tenants.values()).all(|g| g.secrets.is_empty()) {
+            return None;
         }
-        
-        if let Some(hn) = &self.cfg.session_extraction.header_name {
-            if let Some((name, val)) = header_name_val {
-                if hn.eq_ignore_ascii_case(name) {
-                    if self.cfg.session_extraction.header_bearer {
-                        let v = val.trim();
-                        if let Some(rest) = v.strip_prefix("Bearer ") {
-                            return Some(rest.to_string());
-                        }
+        let interval = self.config().secrets.as_ref().map_or_else(secrets::default_refresh_ms, |s| s.refresh_ms);
+        let guard = self.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                if let Err(e) = guard.load_secrets().await {
+                    tracing::warn!(error = %Redacted(&e), "secret refresh failed");
+                }
+                if interval == 0 {
+                    break;
+                }
+                rt::sleep(Duration::from_millis(interval)).await;
+            }
+        });
+        Some(refresh::RefreshHandle(task))
+    }
+
+    /// Starts a task on the current tokio runtime that pulls `denylist` changes for this
+    /// guard and its tenants right away and then every `denylist.interval_ms`; failures
+    /// are logged and the current list kept. Returns `None` if no denylist is configured.
+    /// The task stops when the handle is dropped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn_denylist_sync(&self) -> Option<refresh::RefreshHandle> {
+        let interval = Duration::from_millis(self.config().denylist.as_ref()?.interval_ms.max(1));
+        let guard = self.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                for g in std::iter::once(&guard).chain(guard.tenants.values()) {
+                    if let Err(e) = g.sync_denylist().await {
+                        tracing::warn!(error = %Redacted(&e), "denylist sync failed");
                     }
-                    return Some(val.to_string());
                 }
+                rt::sleep(interval).await;
             }
+        });
+        Some(refresh::RefreshHandle(task))
+    }
+
+    /// Starts a task on the current tokio runtime that listens to the Trust API's push
+    /// stream and applies each update like a webhook, reconnecting after
+    /// `push.reconnect_ms` whenever the stream drops. Returns `None` if `push` is not
+    /// configured. The task stops when the handle is dropped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn_push_listener(&self) -> Result<Option<refresh::RefreshHandle>, EGuardError> {
+        let cfg = self.config();
+        let Some(push) = cfg.push.clone() else {
+            return Ok(None);
+        };
+        let (client, base_url) = transport::api_client(&cfg.api_base_url, cfg.proxy.as_ref(), cfg.pool.as_ref(), cfg.dns.as_ref())
+            .map_err(EGuardError::ConfigInvalid)?;
+        let guard = self.clone();
+        let task = tokio::spawn(async move {
+            let mut parser = push::SseParser::default();
+            loop {
+                match push::listen(&guard, &client, &base_url, &push, &mut parser).await {
+                    Ok(()) => tracing::debug!("push stream closed"),
+                    Err(e) => tracing::warn!(error = %Redacted(&e), "push stream failed"),
+                }
+                rt::sleep(Duration::from_millis(push.reconnect_ms)).await;
+            }
+        });
+        Ok(Some(refresh::RefreshHandle(task)))
+    }
+
+    /// Starts a task on the current tokio runtime that probes each of `endpoints` every
+    /// `endpoints.health_check.interval_ms`, for this guard and its tenants, so failing
+    /// ones are skipped and recovered ones used again without waiting for lookups.
+    /// Returns `None` if no health check is configured. The task stops when the handle is dropped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn_health_checker(&self) -> Option<refresh::RefreshHandle> {
+        let check = self.config().endpoints.as_ref()?.health_check.clone()?;
+        let guard = self.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                for g in std::iter::once(&guard).chain(guard.tenants.values()) {
+                    if let Some(pool) = &g.endpoints {
+                        pool.check(&check.session_id).await;
+                    }
+                }
+                rt::sleep(Duration::from_millis(check.interval_ms.max(1))).await;
+            }
+        });
+        Some(refresh::RefreshHandle(task))
+    }
+
+    /// URLs of the `endpoints` currently marked unhealthy.
+    pub fn unhealthy_endpoints(&self) -> Vec<String> {
+        self.endpoints.as_ref().map(|pool| pool.unhealthy()).unwrap_or_default()
+    }
+
+    /// Pulls the changes to the banned sessions since the last sync. Does nothing if
+    /// `denylist` is not configured.
+    pub async fn sync_denylist(&self) -> Result<(), EGuardError> {
+        let Some(denylist) = &self.denylist else {
+            return Ok(());
+        };
+        let cursor = denylist.cursor();
+        let delta = self.call_api(|| self.provider.fetch_denylist(cursor.as_deref())).await?;
+        denylist.apply(delta);
+        tracing::debug!(sessions = denylist.len(), "denylist synced");
+        Ok(())
+    }
+
+    /// Denies `session_id` here for `REVOKED_TTL`, whatever the Trust API says.
+    fn revoke_locally(&self, session_id: &str, trust: TrustResponse) {
+        if self.revoked.len() >= REVOKED_MAX_ENTRIES {
+            tracing::warn!(session_hash = %telemetry::session_hash(session_id), limit = REVOKED_MAX_ENTRIES, "too many local bans; dropping the oldest");
         }
-        None
+        self.revoked.insert(session_id, trust);
     }
 
-    pub async fn fetch_trust(&self, session_id: &str) -> anyhow::Result<TrustResponse> {
-        let url = format!("{}/eguard/trust", self.cfg.api_base_url);
-        let resp = self.client
-            .get(url)
-            .query(&[("sid", session_id)])
-            .bearer_auth(&self.cfg.api_key)
-            .send()
-            .await?;
+    /// A zero score for a session revoked locally or on the synced denylist.
+    fn banned(&self, session_id: &str) -> Option<TrustResponse> {
+        self.revoked.get(session_id).or_else(|| self.denylist.as_ref()?.get(session_id))
+    }
 
-        if resp.status().is_success() {
-            Ok(resp.json::<TrustResponse>().await?)
-        } else if resp.status() == StatusCode::NOT_FOUND {
-            Ok(TrustResponse { session_id: session_id.into(), trust_score: 0.0, reason: Some("unknown_session".into()) })
-        } else {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
-            Err(anyhow::anyhow!("Trust API error {}: {}", status, body))
+    /// Runs one logical Trust API call through the 429 back-off, concurrency limit,
+    /// circuit breaker and retry policy.
+    async fn call_api<T, F, Fut>(&self, call: F) -> anyhow::Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        self.throttle.check().inspect_err(|_| self.metrics.throttled())?;
+        let _permit = match &self.in_flight {
+            Some(limit) => Some(limit.acquire().await.inspect_err(|_| self.metrics.shed())?),
+            None => None,
+        };
+        if let Some(breaker) = &self.breaker
+            && !breaker.allow_request()
+        {
+            return Err(CircuitOpenError.into());
         }
+
+        let policy = self.config().retry.clone();
+        let mut retry = 0;
+        let result = loop {
+            let started = web_time::Instant::now();
+            let attempt = call().await;
+            self.metrics.api_call(started.elapsed(), attempt.is_ok());
+            match attempt {
+                Err(e) if retry + 1 < policy.max_attempts && is_transient(&e) => {
+                    retry += 1;
+                    rt::sleep(policy.delay(retry)).await;
+                }
+                other => break other,
+            }
+        };
+
+        if let Some(breaker) = &self.breaker {
+            match &result {
+                Err(e) if is_transient(e) => breaker.record_failure(),
+                _ => breaker.record_success(),
+            }
+        }
+        if let Err(e) = &result
+            && let Some(throttled) = e.downcast_ref::<Throttled>()
+        {
+            self.metrics.throttled();
+            self.throttle.record(throttled);
+        }
+        result
     }
 
-    pub async fn decide(&self, session_id: &str) -> anyhow::Result<Decision> {
-        let trust = self.fetch_trust(session_id).await?;
-        if trust.trust_score >= self.cfg.min_trust_score {
-            Ok(Decision::Allow)
-        } else {
-            Ok(Decision::Deny {
-                status: 403,
-                message: format!("Low trust score: {}", trust.trust_score),
+    /// One session lookup, hedged and under the adaptive timeout when those are
+    /// configured. The adaptive timeout learns from how long it took.
+    async fn lookup<T, F, Fut>(&self, call: F) -> anyhow::Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let lookup = async {
+            match &self.hedge {
+                Some(hedge) => hedge.run(&call, || self.metrics.hedged()).await,
+                None => call().await,
+            }
+        };
+        let Some(adaptive) = &self.adaptive else {
+            return lookup.await;
+        };
+        let timeout = adaptive.current();
+        let started = web_time::Instant::now();
+        let result = rt::timeout(timeout, lookup).await.unwrap_or_else(|| Err(provider::ProviderTimeout(timeout).into()));
+        if result.is_ok() || result.as_ref().is_err_and(|e| e.is::<provider::ProviderTimeout>()) {
+            adaptive.record(started.elapsed());
+        }
+        result
+    }
+
+    pub async fn decide(&self, session_id: &str) -> Result<Decision, EGuardError> {
+        Ok(self.decide_with_trust(session_id).await?.0)
+    }
+
+    /// Like `decide`, but also returns the trust response the decision was based on.
+    /// The response is `None` when a fallback decision was used instead.
+    pub async fn decide_with_trust(&self, session_id: &str) -> Result<(Decision, Option<TrustResponse>), EGuardError> {
+        Ok(self.decide_inner(None, session_id, None).await?)
+    }
+
+    /// `decide` within a latency budget: if the Trust API hasn't answered when `budget`
+    /// elapses, the lookup is abandoned and the fallback decision (`failure_mode`) is
+    /// returned. Without one the error is `EGuardError::Timeout`.
+    pub async fn decide_with_deadline(&self, session_id: &str, budget: Duration) -> Result<Decision, EGuardError> {
+        Ok(self.decide_inner(None, session_id, Some(budget)).await?.0)
+    }
+
+    /// `decide` for a specific request: the context is forwarded to the Trust API, and
+    /// the threshold and deny overrides of the route matching its host and path apply.
+    pub async fn decide_request(&self, ctx: &RequestContext, session_id: &str) -> Result<Decision, EGuardError> {
+        Ok(self.decide_inner(Some(ctx), session_id, None).await?.0)
+    }
+
+    /// `decide_with_trust` for a specific request; see `decide_request`.
+    pub async fn decide_request_with_trust(&self, ctx: &RequestContext, session_id: &str) -> Result<(Decision, Option<TrustResponse>), EGuardError> {
+        Ok(self.decide_inner(Some(ctx), session_id, None).await?)
+    }
+
+    async fn decide_inner(
+        &self,
+        ctx: Option<&RequestContext>,
+        session_id: &str,
+        budget: Option<Duration>,
+    ) -> anyhow::Result<(Decision, Option<TrustResponse>)> {
+        let invalid = self.invalid_session(session_id);
+        let session_id = &*self.sid(session_id);
+        let route = ctx.map(|c| c.path.as_str());
+        let span = tracing::info_span!(
+            "eguard.decide",
+            session_hash = %telemetry::session_hash(session_id),
+            http.route = route,
+            score = Empty,
+            outcome = Empty,
+            fallback = Empty,
+            enforced = Empty,
+        );
+        async {
+            let started = web_time::Instant::now();
+            let ctx = self.enrich(ctx);
+            let ctx = ctx.as_deref();
+            let local = invalid
+                .or_else(|| self.local_decision(ctx))
+                .or_else(|| self.rate_limit(ctx, session_id));
+            let (decision, trust, basis) = match local {
+                Some(decision) => {
+                    self.metrics.decision(&decision, false);
+                    (decision, None, Basis::Local)
+                }
+                None => match self.fetch_within(budget, started, session_id, ctx).await {
+                    Ok((trust, banned)) => (self.evaluate(ctx, &trust), Some(trust), Basis::score(banned)),
+                    Err(e) => {
+                        tracing::warn!(error = %Redacted(&e), "trust lookup failed");
+                        let fallback = self.fallback(e)?;
+                        self.metrics.decision(&fallback, true);
+                        (fallback, None, Basis::Fallback)
+                    }
+                },
+            };
+
+            let span = tracing::Span::current();
+            if let Some(t) = &trust {
+                span.record("score", t.trust_score);
+            }
+            span.record("outcome", decision.outcome());
+            span.record("fallback", basis == Basis::Fallback);
+            let mut decision = self.finish(route, session_id, trust.as_ref(), decision, basis, started);
+            if let Decision::Allow { route_id, .. } = &mut decision {
+                let policy = self.policy();
+                *route_id = ctx.and_then(|c| policy.route(c)).map(|r| r.route_id().to_string());
+            }
+            Ok((decision, trust))
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// `fetch_trust_inner`, given up on once `budget` has passed since `started`.
+    async fn fetch_within(
+        &self,
+        budget: Option<Duration>,
+        started: web_time::Instant,
+        session_id: &str,
+        ctx: Option<&RequestContext>,
+    ) -> anyhow::Result<(TrustResponse, bool)> {
+        let Some(budget) = budget else {
+            return self.fetch_trust_inner(session_id, ctx).await;
+        };
+        let remaining = budget.saturating_sub(started.elapsed());
+        rt::timeout(remaining, self.fetch_trust_inner(session_id, ctx))
+            .await
+            .unwrap_or_else(|| Err(DeadlineExceeded(budget).into()))
+    }
+
+    /// An id for the request in `ctx`: the one in its `response_headers.request_id_header`
+    /// (default `x-request-id`), or a random one. Pass it to `response_headers` and
+    /// `deny_body` so both show the same id.
+    pub fn request_id(&self, ctx: Option<&RequestContext>) -> String {
+        headers::request_id(self.config().response_headers.as_ref(), ctx)
+    }
+
+    /// The `X-EGuard-*` headers for a decision of this guard, as configured by
+    /// `response_headers`; empty when that is unset.
+    pub fn response_headers(&self, decision: &Decision, trust: Option<&TrustResponse>, request_id: &str) -> Vec<(&'static str, String)> {
+        match &self.config().response_headers {
+            Some(cfg) => cfg.headers(decision, trust, request_id),
+            None => Vec::new(),
+        }
+    }
+
+    /// The `deny_body` template rendered for a deny; `None` for other decisions or when
+    /// no template is configured.
+    pub fn deny_body(&self, decision: &Decision, trust: Option<&TrustResponse>, request_id: &str) -> Option<DenyBody> {
+        let Decision::Deny { status, message } = decision else {
+            return None;
+        };
+        let policy = self.policy();
+        let template = policy.deny_body.as_ref()?;
+        Some(template.render(&DenyVars {
+            status: *status,
+            message,
+            score: trust.map(|t| t.trust_score),
+            reason: trust.and_then(|t| t.reason.as_ref()),
+            request_id,
+        }))
+    }
+
+    /// Decisions for many sessions, in input order. If the Trust API fails every
+    /// session gets the fallback decision (or the error is returned).
+    pub async fn decide_batch(&self, session_ids: &[&str]) -> Result<Vec<Decision>, EGuardError> {
+        Ok(self.decide_batch_with_trust(session_ids).await?.into_iter().map(|(d, _)| d).collect())
+    }
+
+    /// Like `decide_batch`, but also returns the trust response each decision was based on;
+    /// see `decide_with_trust`.
+    pub async fn decide_batch_with_trust(&self, session_ids: &[&str]) -> Result<Vec<(Decision, Option<TrustResponse>)>, EGuardError> {
+        let started = web_time::Instant::now();
+        let invalid: Vec<_> = session_ids.iter().map(|sid| self.invalid_session(sid)).collect();
+        let valid: Vec<_> = session_ids.iter().zip(&invalid).filter(|(_, d)| d.is_none()).map(|(sid, _)| *sid).collect();
+        let sids = self.sids(&valid);
+        let hashed = &sids.iter().map(|s| &**s).collect::<Vec<_>>();
+        let decided = match hashed.is_empty() {
+            true => Vec::new(),
+            false => match self.fetch_trust_batch_inner(hashed).await {
+                Ok(trusts) => trusts.into_iter().zip(hashed)
+                    .map(|((t, banned), sid)| (self.finish(None, sid, Some(&t), self.evaluate(None, &t), Basis::score(banned), started), Some(t)))
+                    .collect(),
+                Err(e) => {
+                    let fallback = self.fallback(e)?;
+                    hashed.iter()
+                        .map(|sid| {
+                            self.metrics.decision(&fallback, true);
+                            (self.finish(None, sid, None, fallback.clone(), Basis::Fallback, started), None)
+                        })
+                        .collect()
+                }
+            },
+        };
+        let mut decided = decided.into_iter();
+        Ok(invalid.into_iter().zip(session_ids)
+            .map(|(invalid, sid)| match invalid {
+                Some(decision) => {
+                    self.metrics.decision(&decision, false);
+                    (self.finish(None, &self.sid(sid), None, decision, Basis::Local, started), None)
+                }
+                None => decided.next().unwrap_or_else(|| {
+                    let sid = self.sid(sid);
+                    let trust = provider::unknown_session(&sid);
+                    (self.finish(None, &sid, Some(&trust), self.evaluate(None, &trust), Basis::Score, started), Some(trust))
+                }),
             })
+            .collect())
+    }
+
+    /// Adds GeoIP data to a context that has an IP but no `geo` yet, and TLS
+    /// fingerprints from `tls_fingerprint` headers.
+    fn enrich<'a>(&self, ctx: Option<&'a RequestContext>) -> Option<Cow<'a, RequestContext>> {
+        let mut ctx = Cow::Borrowed(ctx?);
+        if let (Some(geoip), Some(ip)) = (&self.geoip, ctx.ip)
+            && ctx.geo.is_none()
+        {
+            ctx.to_mut().geo = Some(geoip.lookup(ip));
+        }
+        if let Some(tls) = &self.config().tls_fingerprint {
+            tls.apply(&mut ctx);
+        }
+        Some(ctx)
+    }
+
+    /// `Policy::invalid_session`, logged.
+    fn invalid_session(&self, session_id: &str) -> Option<Decision> {
+        let decision = self.policy().invalid_session(session_id)?;
+        tracing::warn!(session_hash = %telemetry::session_hash(session_id), "invalid session id");
+        Some(decision)
+    }
+
+    /// Decisions made without the Trust API: IP rules, the route's country allowlist,
+    /// then rules that don't need a score.
+    fn local_decision(&self, ctx: Option<&RequestContext>) -> Option<Decision> {
+        let policy = self.policy();
+        if let Some(ctx) = ctx {
+            if let (Some(rules), Some(ip)) = (&policy.ip_rules, ctx.ip)
+                && let Some(decision) = rules.check(ip)
+            {
+                return Some(decision);
+            }
+
+            if let Some(allowed) = policy.route(ctx).and_then(|r| r.allowed_countries.as_ref()) {
+                let country = ctx.geo.as_ref().and_then(|g| g.country.as_deref());
+                if !country.is_some_and(|c| allowed.iter().any(|a| a.eq_ignore_ascii_case(c))) {
+                    return Some(Decision::Deny { status: 403, message: "Country not allowed".into() });
+                }
+            }
         }
+        policy.rules.before_api(ctx)
+    }
+
+    /// Takes a token from the caller's bucket; `Some` when the request is over the
+    /// limit and should be denied.
+    fn rate_limit(&self, ctx: Option<&RequestContext>, session_id: &str) -> Option<Decision> {
+        let limiter = self.limiter.as_ref()?;
+        let allowed = match limiter.key {
+            RateLimitKey::Session => limiter.acquire(session_id),
+            RateLimitKey::Ip => limiter.acquire(&ctx?.ip?.to_canonical().to_string()),
+        };
+        if allowed {
+            return None;
+        }
+        self.metrics.rate_limited();
+        tracing::warn!(session_hash = %telemetry::session_hash(session_id), "rate limit exceeded");
+        match limiter.action {
+            RateLimitAction::Deny => Some(Decision::Deny { status: 429, message: "Too many requests".into() }),
+            RateLimitAction::Flag => None,
+        }
+    }
+
+    /// Applies score rules, then the threshold and deny response of the route matching
+    /// the request (or the global defaults) to a score.
+    fn evaluate(&self, ctx: Option<&RequestContext>, trust: &TrustResponse) -> Decision {
+        let policy = self.policy();
+        let route = ctx.and_then(|c| policy.route(c));
+        let decision = match policy.rules.after_api(ctx, trust.trust_score) {
+            Some(decision) => decision,
+            None => policy.score_decision(route, trust),
+        };
+        self.metrics.decision(&decision, false);
+        decision
     }
-}
\ No newline at end of file
+
+    /// Audits a decision and applies `enforcement_percentage` to decisions based on
+    /// `trust`: sessions outside the enforced share are allowed, with the decision they
+    /// would have got logged. Decisions made without a score, and those for banned
+    /// sessions, are always enforced.
+    fn finish(
+        &self,
+        route: Option<&str>,
+        session_id: &str,
+        trust: Option<&TrustResponse>,
+        decision: Decision,
+        basis: Basis,
+        started: web_time::Instant,
+    ) -> Decision {
+        let enforced = basis != Basis::Score || is_enforced(session_id, self.config().enforcement_percentage);
+        tracing::Span::current().record("enforced", enforced);
+        if let Some(logger) = &self.logger {
+            let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+            let score = trust.map(|t| t.trust_score);
+            let session_hash = telemetry::session_hash(session_id);
+            logger.log(&DecisionRecord::new(route, session_hash, score, &decision, basis == Basis::Fallback, enforced, latency_ms));
+        }
+        let allow = || Decision::Allow {
+            trust_score: trust.map(|t| t.trust_score),
+            reason: trust.and_then(|t| t.reason.clone()),
+            route_id: None,
+        };
+        match decision {
+            Decision::Allow { .. } => allow(),
+            decision if enforced => decision,
+            decision => {
+                self.metrics.monitored();
+                tracing::info!(outcome = decision.outcome(), "decision not enforced for this session");
+                allow()
+            }
+        }
+    }
+
+    /// Decision to use when the Trust API could not be consulted, if one is configured.
+    fn fallback(&self, err: anyhow::Error) -> anyhow::Result<Decision> {
+        let cfg = self.config();
+        let fallback = match (&cfg.circuit_breaker, &cfg.throttle.fallback) {
+            (Some(b), _) if err.is::<CircuitOpenError>() => Some(b.fallback.clone()),
+            (_, Some(f)) if err.is::<Throttled>() => Some(f.decision()),
+            _ => cfg.failure_mode.as_ref().map(FailureMode::decision),
+        };
+        fallback.ok_or(err)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::test_config;
+
+    const BANDS: &str = "risk_bands: [{min_score: 0.7, action: allow}, {min_score: 0.4, action: challenge, kind: captcha, redirect_url: /c}, {min_score: 0.1, action: deny}]";
+
+    fn scored(policy: &Policy, score: f32) -> &'static str {
+        let trust = TrustResponse { trust_score: score, ..Default::default() };
+        policy.score_decision(None, &trust).outcome()
+    }
+
+    #[test]
+    fn enforcement_extremes_are_absolute() {
+        for i in 0..1000 {
+            let sid = format!("session-{i}");
+            assert!(is_enforced(&sid, 100.0));
+            assert!(!is_enforced(&sid, 0.0));
+        }
+    }
+
+    #[test]
+    fn enforcement_bucket_is_stable() {
+        let sids: Vec<String> = (0..1000).map(|i| format!("session-{i}")).collect();
+        let enforced: Vec<bool> = sids.iter().map(|sid| is_enforced(sid, 30.0)).collect();
+        assert_eq!(enforced, sids.iter().map(|sid| is_enforced(sid, 30.0)).collect::<Vec<_>>());
+        let share = enforced.iter().filter(|e| **e).count();
+        assert!((200..400).contains(&share), "{share} of 1000 enforced");
+        // Raising the percentage only adds sessions.
+        assert!(sids.iter().zip(&enforced).all(|(sid, e)| !e || is_enforced(sid, 60.0)));
+    }
+
+    #[test]
+    fn risk_band_boundaries() {
+        let policy = Policy::compile(test_config(BANDS)).unwrap();
+        assert_eq!(scored(&policy, 1.0), "allow");
+        assert_eq!(scored(&policy, 0.7), "allow");
+        assert_eq!(scored(&policy, 0.69), "challenge");
+        assert_eq!(scored(&policy, 0.4), "challenge");
+        assert_eq!(scored(&policy, 0.39), "deny");
+        assert_eq!(scored(&policy, 0.1), "deny");
+        // Below every band.
+        assert_eq!(scored(&policy, 0.0), "deny");
+    }
+
+    #[test]
+    fn invalid_risk_bands_are_rejected() {
+        assert!(Policy::compile(test_config(&format!("secure_routes: [{{path_pattern: ^/a, {BANDS}}}]"))).is_ok());
+        for bands in [
+            "[{min_score: 0.4, action: deny}, {min_score: 0.7, action: allow}]",
+            "[{min_score: 0.7, action: allow}, {min_score: 0.7, action: deny}]",
+            "[{min_score: 1.5, action: allow}]",
+            "[{min_score: .nan, action: allow}]",
+        ] {
+            assert!(Policy::compile(test_config(&format!("risk_bands: {bands}"))).is_err(), "{bands}");
+            let route = format!("secure_routes: [{{path_pattern: ^/a, risk_bands: {bands}}}]");
+            assert!(Policy::compile(test_config(&route)).is_err(), "{route}");
+        }
+    }
+
+    #[test]
+    fn concurrency_limit_must_allow_a_call() {
+        assert!(Policy::compile(test_config("concurrency_limit: {max_in_flight: 1}")).is_ok());
+        assert!(Policy::compile(test_config("concurrency_limit: {max_in_flight: 0}")).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering::Relaxed},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::Decision;
+
+/// Upper bounds (seconds) of the Trust API latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+#[derive(Default)]
+pub(crate) struct Metrics {
+    decisions_allow: AtomicU64,
+    decisions_deny: AtomicU64,
+    decisions_challenge: AtomicU64,
+    decisions_fallback: AtomicU64,
+    decisions_monitored: AtomicU64,
+    rate_limited: AtomicU64,
+    api_requests: AtomicU64,
+    api_errors: AtomicU64,
+    api_shed: AtomicU64,
+    api_throttled: AtomicU64,
+    api_hedged: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    stale_served: AtomicU64,
+    local_scored: AtomicU64,
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    latency_sum_us: AtomicU64,
+}
+
+impl Metrics {
+    pub(crate) fn decision(&self, decision: &Decision, fallback: bool) {
+        match decision {
+            Decision::Allow { .. } => self.decisions_allow.fetch_add(1, Relaxed),
+            Decision::Deny { .. } => self.decisions_deny.fetch_add(1, Relaxed),
+            Decision::Challenge { .. } => self.decisions_challenge.fetch_add(1, Relaxed),
+        };
+        if fallback {
+            self.decisions_fallback.fetch_add(1, Relaxed);
+        }
+    }
+
+    pub(crate) fn monitored(&self) {
+        self.decisions_monitored.fetch_add(1, Relaxed);
+    }
+
+    pub(crate) fn rate_limited(&self) {
+        self.rate_limited.fetch_add(1, Relaxed);
+    }
+
+    pub(crate) fn api_call(&self, latency: Duration, ok: bool) {
+        self.api_requests.fetch_add(1, Relaxed);
+        if !ok {
+            self.api_errors.fetch_add(1, Relaxed);
+        }
+        self.latency_sum_us.fetch_add(latency.as_micros() as u64, Relaxed);
+        let secs = latency.as_secs_f64();
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|&le| secs <= le) {
+            self.latency_buckets[i].fetch_add(1, Relaxed);
+        }
+    }
+
+    pub(crate) fn shed(&self) {
+        self.api_shed.fetch_add(1, Relaxed);
+    }
+
+    pub(crate) fn throttled(&self) {
+        self.api_throttled.fetch_add(1, Relaxed);
+    }
+
+    pub(crate) fn hedged(&self) {
+        self.api_hedged.fetch_add(1, Relaxed);
+    }
+
+    pub(crate) fn cache(&self, hit: bool) {
+        if hit {
+            self.cache_hits.fetch_add(1, Relaxed);
+        } else {
+            self.cache_misses.fetch_add(1, Relaxed);
+        }
+    }
+
+    pub(crate) fn stale_served(&self) {
+        self.stale_served.fetch_add(1, Relaxed);
+    }
+
+    pub(crate) fn local_scored(&self) {
+        self.local_scored.fetch_add(1, Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> MetricsSnapshot {
+        let mut cumulative = 0;
+        let buckets = LATENCY_BUCKETS.iter().zip(&self.latency_buckets)
+            .map(|(&le, n)| {
+                cumulative += n.load(Relaxed);
+                (le, cumulative)
+            })
+            .collect();
+
+        MetricsSnapshot {
+            decisions_allow: self.decisions_allow.load(Relaxed),
+            decisions_deny: self.decisions_deny.load(Relaxed),
+            decisions_challenge: self.decisions_challenge.load(Relaxed),
+            decisions_fallback: self.decisions_fallback.load(Relaxed),
+            decisions_monitored: self.decisions_monitored.load(Relaxed),
+            rate_limited: self.rate_limited.load(Relaxed),
+            api_requests: self.api_requests.load(Relaxed),
+            api_errors: self.api_errors.load(Relaxed),
+            api_shed: self.api_shed.load(Relaxed),
+            api_throttled: self.api_throttled.load(Relaxed),
+            api_hedged: self.api_hedged.load(Relaxed),
+            cache_hits: self.cache_hits.load(Relaxed),
+            cache_misses: self.cache_misses.load(Relaxed),
+            stale_served: self.stale_served.load(Relaxed),
+            local_scored: self.local_scored.load(Relaxed),
+            api_latency_buckets: buckets,
+            api_latency_sum_seconds: self.latency_sum_us.load(Relaxed) as f64 / 1e6,
+        }
+    }
+}
+
+/// Point-in-time copy of the counters kept by an `EGuard` (shared by its clones).
+#[derive(Clone, Debug, Serialize)]
+pub struct MetricsSnapshot {
+    pub decisions_allow: u64,
+    pub decisions_deny: u64,
+    pub decisions_challenge: u64,
+    /// Decisions that came from the circuit breaker or failure mode rather than a score.
+    pub decisions_fallback: u64,
+    /// Denies and challenges let through because the session is outside `enforcement_percentage`.
+    pub decisions_monitored: u64,
+    /// Requests over `rate_limit`, whether denied or only flagged.
+    pub rate_limited: u64,
+    /// Individual Trust API attempts, including retries.
+    pub api_requests: u64,
+    pub api_errors: u64,
+    /// Trust API calls not made because of `concurrency_limit`.
+    pub api_shed: u64,
+    /// Trust API calls answered 429, or not made while backing off after one.
+    pub api_throttled: u64,
+    /// Second lookups sent because the first was slower than the `hedge` delay.
+    pub api_hedged: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// Expired cached scores used because the Trust API call failed.
+    pub stale_served: u64,
+    /// Scores from `local_scorer` because the Trust API call failed.
+    pub local_scored: u64,
+    /// Cumulative `(upper bound in seconds, count)` pairs; slower calls only count towards the sum.
+    pub api_latency_buckets: Vec<(f64, u64)>,
+    pub api_latency_sum_seconds: f64,
+}
+
+impl MetricsSnapshot {
+    pub fn cache_hit_rate(&self) -> f64 {
+        ratio(self.cache_hits, self.cache_hits + self.cache_misses)
+    }
+
+    pub fn api_error_rate(&self) -> f64 {
+        ratio(self.api_errors, self.api_requests)
+    }
+
+    /// Renders the snapshot in the Prometheus text exposition format.
+    #[cfg(feature = "prometheus")]
+    pub fn to_prometheus(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP eguard_decisions_total Trust decisions by outcome.");
+        let _ = writeln!(out, "# TYPE eguard_decisions_total counter");
+        let _ = writeln!(out, "eguard_decisions_total{{outcome=\"allow\"}} {}", self.decisions_allow);
+        let _ = writeln!(out, "eguard_decisions_total{{outcome=\"deny\"}} {}", self.decisions_deny);
+        let _ = writeln!(out, "eguard_decisions_total{{outcome=\"challenge\"}} {}", self.decisions_challenge);
+        let _ = writeln!(out, "# HELP eguard_fallback_decisions_total Decisions made without a trust score.");
+        let _ = writeln!(out, "# TYPE eguard_fallback_decisions_total counter");
+        let _ = writeln!(out, "eguard_fallback_decisions_total {}", self.decisions_fallback);
+        let _ = writeln!(out, "# HELP eguard_monitored_decisions_total Denies and challenges not enforced because of enforcement_percentage.");
+        let _ = writeln!(out, "# TYPE eguard_monitored_decisions_total counter");
+        let _ = writeln!(out, "eguard_monitored_decisions_total {}", self.decisions_monitored);
+        let _ = writeln!(out, "# HELP eguard_rate_limited_total Requests over the local rate limit.");
+        let _ = writeln!(out, "# TYPE eguard_rate_limited_total counter");
+        let _ = writeln!(out, "eguard_rate_limited_total {}", self.rate_limited);
+        let _ = writeln!(out, "# HELP eguard_api_requests_total Trust API attempts, including retries.");
+        let _ = writeln!(out, "# TYPE eguard_api_requests_total counter");
+        let _ = writeln!(out, "eguard_api_requests_total {}", self.api_requests);
+        let _ = writeln!(out, "# HELP eguard_api_errors_total Failed Trust API attempts.");
+        let _ = writeln!(out, "# TYPE eguard_api_errors_total counter");
+        let _ = writeln!(out, "eguard_api_errors_total {}", self.api_errors);
+        let _ = writeln!(out, "# HELP eguard_api_shed_total Trust API calls shed by the concurrency limit.");
+        let _ = writeln!(out, "# TYPE eguard_api_shed_total counter");
+        let _ = writeln!(out, "eguard_api_shed_total {}", self.api_shed);
+        let _ = writeln!(out, "# HELP eguard_api_throttled_total Trust API calls rate limited with 429 or skipped while backing off.");
+        let _ = writeln!(out, "# TYPE eguard_api_throttled_total counter");
+        let _ = writeln!(out, "eguard_api_throttled_total {}", self.api_throttled);
+        let _ = writeln!(out, "# HELP eguard_api_hedged_total Second lookups sent because the first was slow.");
+        let _ = writeln!(out, "# TYPE eguard_api_hedged_total counter");
+        let _ = writeln!(out, "eguard_api_hedged_total {}", self.api_hedged);
+        let _ = writeln!(out, "# HELP eguard_cache_requests_total Trust cache lookups by result.");
+        let _ = writeln!(out, "# TYPE eguard_cache_requests_total counter");
+        let _ = writeln!(out, "eguard_cache_requests_total{{result=\"hit\"}} {}", self.cache_hits);
+        let _ = writeln!(out, "eguard_cache_requests_total{{result=\"miss\"}} {}", self.cache_misses);
+        let _ = writeln!(out, "# HELP eguard_stale_scores_total Expired cached scores served because the Trust API failed.");
+        let _ = writeln!(out, "# TYPE eguard_stale_scores_total counter");
+        let _ = writeln!(out, "eguard_stale_scores_total {}", self.stale_served);
+        let _ = writeln!(out, "# HELP eguard_local_scores_total Scores from the local scorer because the Trust API failed.");
+        let _ = writeln!(out, "# TYPE eguard_local_scores_total counter");
+        let _ = writeln!(out, "eguard_local_scores_total {}", self.local_scored);
+        let _ = writeln!(out, "# HELP eguard_api_latency_seconds Trust API call latency.");
+        let _ = writeln!(out, "# TYPE eguard_api_latency_seconds histogram");
+        for (le, count) in &self.api_latency_buckets {
+            let _ = writeln!(out, "eguard_api_latency_seconds_bucket{{le=\"{le}\"}} {count}");
+        }
+        let _ = writeln!(out, "eguard_api_latency_seconds_bucket{{le=\"+Inf\"}} {}", self.api_requests);
+        let _ = writeln!(out, "eguard_api_latency_seconds_sum {}", self.api_latency_sum_seconds);
+        let _ = writeln!(out, "eguard_api_latency_seconds_count {}", self.api_requests);
+        out
+    }
+}
+
+fn ratio(n: u64, d: u64) -> f64 {
+    if d == 0 { 0.0 } else { n as f64 / d as f64 }
+}
@@ -0,0 +1,38 @@
+//! Privacy mode: session ids are replaced with `HMAC-SHA256(salt, session_id)` (hex)
+//! as soon as they reach the guard, so raw tokens are never sent to the Trust API,
+//! put in a cache or logged.
+//!
+//! Every other id the guard deals with is the hashed one too: the `session_id` of
+//! returned scores, and those in webhooks, the push stream and the denylist. All
+//! instances of a deployment must use the same salt. The headers and query parameter
+//! session ids are read from are left out of the request context sent with them.
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::secrets::SecretString;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionHashingConfig {
+    /// Secret key of the HMAC; keep it out of the Trust API's reach. Never serialized.
+    #[serde(skip_serializing)]
+    pub salt: SecretString,
+}
+
+pub(crate) struct SessionHasher(Hmac<Sha256>);
+
+impl SessionHasher {
+    pub fn new(cfg: &SessionHashingConfig) -> anyhow::Result<Self> {
+        if cfg.salt.is_empty() {
+            return Err(anyhow::anyhow!("session_hashing.salt must not be empty"));
+        }
+        Ok(Self(Hmac::new_from_slice(cfg.salt.expose().as_bytes())?))
+    }
+
+    pub fn hash(&self, session_id: &str) -> String {
+        let mut mac = self.0.clone();
+        mac.update(session_id.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
@@ -0,0 +1,261 @@
+//! Where trust scores come from. `EGuard` talks to the Trust API through the HTTP or
+//! gRPC transport by default; `EGuard::with_provider` swaps in any other backend.
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{ReasonCode, RequestContext, TrustResponse, denylist::DenylistDelta, events::{Event, Outcome}, redact::Redacted, rt, secrets::SecretString};
+
+/// One lookup of a session's trust score. Retries, caching and circuit breaking are
+/// layered on top by `EGuard`, so implementations should make exactly one call.
+///
+/// Unknown sessions are not an error: return a zero score with reason `unknown_session`.
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait TrustProvider: Send + Sync {
+    async fn fetch(&self, session_id: &str) -> anyhow::Result<TrustResponse>;
+
+    /// Scores a session for a specific request. The default ignores the context.
+    async fn fetch_with_context(&self, session_id: &str, ctx: &RequestContext) -> anyhow::Result<TrustResponse> {
+        let _ = ctx;
+        self.fetch(session_id).await
+    }
+
+    /// Scores several sessions in one call, returning results in input order.
+    /// The default issues one `fetch` per session.
+    async fn fetch_batch(&self, session_ids: &[&str]) -> anyhow::Result<Vec<TrustResponse>> {
+        let mut out = Vec::with_capacity(session_ids.len());
+        for sid in session_ids {
+            out.push(self.fetch(sid).await?);
+        }
+        Ok(out)
+    }
+
+    /// Sends behavioural events to the scoring backend. Providers that don't take
+    /// events return an error, which `EGuard` logs.
+    async fn report_events(&self, events: &[Event]) -> anyhow::Result<()> {
+        let _ = events;
+        Err(anyhow::anyhow!("This trust provider does not accept events"))
+    }
+
+    /// Labels a session as confirmed fraud or legitimate.
+    async fn report_outcome(&self, session_id: &str, outcome: Outcome) -> anyhow::Result<()> {
+        let _ = (session_id, outcome);
+        Err(anyhow::anyhow!("This trust provider does not accept outcomes"))
+    }
+
+    /// Bans a session, so the backend scores it as banned from now on.
+    async fn revoke_session(&self, session_id: &str, reason: &ReasonCode) -> anyhow::Result<()> {
+        let _ = (session_id, reason);
+        Err(anyhow::anyhow!("This trust provider does not support bans"))
+    }
+
+    /// Banned sessions added and removed since `cursor`, or the whole list without one.
+    async fn fetch_denylist(&self, cursor: Option<&str>) -> anyhow::Result<DenylistDelta> {
+        let _ = cursor;
+        Err(anyhow::anyhow!("This trust provider does not serve a denylist"))
+    }
+}
+
+pub(crate) fn unknown_session(session_id: &str) -> TrustResponse {
+    TrustResponse { session_id: session_id.into(), trust_score: 0.0, reason: Some(ReasonCode::UnknownSession), ..Default::default() }
+}
+
+/// Fixed scores held in memory, for tests, local development and offline setups.
+/// Sessions without a score are unknown unless a default is set.
+#[derive(Default)]
+pub struct MemoryProvider {
+    scores: RwLock<HashMap<String, TrustResponse>>,
+    default_score: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FileEntry {
+    Score(f32),
+    Trust {
+        trust_score: f32,
+        #[serde(default)]
+        reason: Option<ReasonCode>,
+        #[serde(default)]
+        bot_score: Option<f32>,
+        #[serde(default)]
+        fraud_score: Option<f32>,
+        #[serde(default)]
+        abuse_score: Option<f32>,
+    },
+}
+
+impl MemoryProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads scores from a `.json`, `.yaml`/`.yml` or `.toml` file mapping session ids
+    /// to either a score or `{ trust_score, reason }`, optionally with `bot_score`,
+    /// `fraud_score` and `abuse_score`.
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read scores {}: {}", path.display(), e))?;
+
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+        let entries: HashMap<String, FileEntry> = match ext.as_str() {
+            "yaml" | "yml" => serde_yaml::from_str(&text)?,
+            "toml" => toml::from_str(&text)?,
+            "json" => serde_json::from_str(&text)?,
+            _ => return Err(anyhow::anyhow!("Unsupported scores format: {}", path.display())),
+        };
+
+        let provider = Self::new();
+        for (session_id, entry) in entries {
+            let trust = match entry {
+                FileEntry::Score(trust_score) => TrustResponse { session_id, trust_score, ..Default::default() },
+                FileEntry::Trust { trust_score, reason, bot_score, fraud_score, abuse_score } => {
+                    TrustResponse { session_id, trust_score, reason, bot_score, fraud_score, abuse_score }
+                }
+            };
+            provider.set_trust(trust);
+        }
+        Ok(provider)
+    }
+
+    /// Score for sessions without their own, instead of treating them as unknown.
+    pub fn with_default_score(mut self, score: f32) -> Self {
+        self.default_score = Some(score);
+        self
+    }
+
+    pub fn set_score(&self, session_id: &str, score: f32) {
+        self.set_trust(TrustResponse { session_id: session_id.to_string(), trust_score: score, reason: None, ..Default::default() });
+    }
+
+    pub fn set_trust(&self, trust: TrustResponse) {
+        self.scores.write().unwrap().insert(trust.session_id.clone(), trust);
+    }
+
+    pub fn remove(&self, session_id: &str) {
+        self.scores.write().unwrap().remove(session_id);
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl TrustProvider for MemoryProvider {
+    async fn fetch(&self, session_id: &str) -> anyhow::Result<TrustResponse> {
+        if let Some(trust) = self.scores.read().unwrap().get(session_id) {
+            return Ok(trust.clone());
+        }
+        Ok(match self.default_score {
+            Some(trust_score) => TrustResponse { session_id: session_id.to_string(), trust_score, reason: None, ..Default::default() },
+            None => unknown_session(session_id),
+        })
+    }
+}
+
+/// Another Trust API deployment, from `EGuardConfig::fallbacks`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FallbackConfig {
+    pub api_base_url: String,
+    /// Defaults to the main `api_key`, following its rotations.
+    #[serde(default, skip_serializing)]
+    pub api_key: Option<SecretString>,
+    /// Defaults to `timeout_ms`.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+/// A provider that didn't answer within its `FallbackProvider` timeout.
+#[derive(Debug)]
+pub struct ProviderTimeout(pub Duration);
+
+impl std::fmt::Display for ProviderTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Trust provider timed out after {}ms", self.0.as_millis())
+    }
+}
+
+impl std::error::Error for ProviderTimeout {}
+
+/// Tries providers in order until one answers, e.g. the primary API, then a regional
+/// replica, then a local scorer. A provider that fails or exceeds its timeout is
+/// skipped; an unknown session is an answer. If all fail, the last error is returned.
+#[derive(Default)]
+pub struct FallbackProvider {
+    providers: Vec<(Arc<dyn TrustProvider>, Option<Duration>)>,
+}
+
+impl FallbackProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `provider`, giving up on it after `timeout` if set.
+    pub fn with_provider(mut self, provider: Arc<dyn TrustProvider>, timeout: Option<Duration>) -> Self {
+        self.providers.push((provider, timeout));
+        self
+    }
+
+    async fn first<T, F, Fut>(&self, call: F) -> anyhow::Result<T>
+    where
+        F: Fn(Arc<dyn TrustProvider>) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut last = None;
+        for (i, (provider, timeout)) in self.providers.iter().enumerate() {
+            let result = match timeout {
+                Some(t) => rt::timeout(*t, call(provider.clone())).await.unwrap_or_else(|| Err(ProviderTimeout(*t).into())),
+                None => call(provider.clone()).await,
+            };
+            match result {
+                Ok(out) => return Ok(out),
+                Err(e) => {
+                    if i + 1 < self.providers.len() {
+                        tracing::warn!(error = %Redacted(&e), provider = i, "trust provider failed, trying the next one");
+                    }
+                    last = Some(e);
+                }
+            }
+        }
+        Err(last.unwrap_or_else(|| anyhow::anyhow!("FallbackProvider has no providers")))
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl TrustProvider for FallbackProvider {
+    async fn fetch(&self, session_id: &str) -> anyhow::Result<TrustResponse> {
+        self.first(|p| async move { p.fetch(session_id).await }).await
+    }
+
+    async fn fetch_with_context(&self, session_id: &str, ctx: &RequestContext) -> anyhow::Result<TrustResponse> {
+        self.first(|p| async move { p.fetch_with_context(session_id, ctx).await }).await
+    }
+
+    async fn fetch_batch(&self, session_ids: &[&str]) -> anyhow::Result<Vec<TrustResponse>> {
+        self.first(|p| async move { p.fetch_batch(session_ids).await }).await
+    }
+
+    async fn report_events(&self, events: &[Event]) -> anyhow::Result<()> {
+        self.first(|p| async move { p.report_events(events).await }).await
+    }
+
+    async fn report_outcome(&self, session_id: &str, outcome: Outcome) -> anyhow::Result<()> {
+        self.first(|p| async move { p.report_outcome(session_id, outcome).await }).await
+    }
+
+    async fn revoke_session(&self, session_id: &str, reason: &ReasonCode) -> anyhow::Result<()> {
+        self.first(|p| async move { p.revoke_session(session_id, reason).await }).await
+    }
+
+    async fn fetch_denylist(&self, cursor: Option<&str>) -> anyhow::Result<DenylistDelta> {
+        self.first(|p| async move { p.fetch_denylist(cursor).await }).await
+    }
+}
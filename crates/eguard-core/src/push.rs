@@ -0,0 +1,119 @@
+//! A Server-Sent Events stream of score changes and bans from the Trust API, applied to
+//! the cache as they arrive, so bans take effect in near real time without polling or
+//! a public webhook endpoint. Not available on wasm32.
+//!
+//! `GET {api_base_url}{path}` answers with `text/event-stream`; the `data` of each event
+//! is a webhook payload (`score_updated` or `session_banned`). The connection is opened
+//! again after `reconnect_ms` when it drops, with `Last-Event-ID` set so the API can
+//! replay what was missed.
+
+// The stream itself is native-only (it needs a tokio runtime).
+#![cfg_attr(target_arch = "wasm32", allow(dead_code))]
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PushConfig {
+    /// Path of the stream under `api_base_url`.
+    pub path: String,
+    /// Delay before reconnecting after the stream closes or fails.
+    pub reconnect_ms: u64,
+}
+
+impl Default for PushConfig {
+    fn default() -> Self {
+        Self { path: "/eguard/stream".into(), reconnect_ms: 1000 }
+    }
+}
+
+/// Splits an event stream into events. Only the `data` and `id` fields are used.
+#[derive(Default)]
+pub(crate) struct SseParser {
+    line: Vec<u8>,
+    data: String,
+    last_id: Option<String>,
+}
+
+impl SseParser {
+    /// Feeds the next bytes of the stream, returning the data of each event they complete.
+    pub(crate) fn feed(&mut self, bytes: &[u8]) -> Vec<String> {
+        let mut events = Vec::new();
+        for &b in bytes {
+            if b != b'\n' {
+                self.line.push(b);
+                continue;
+            }
+            if self.line.last() == Some(&b'\r') {
+                self.line.pop();
+            }
+            let line = String::from_utf8_lossy(&self.line).into_owned();
+            self.line.clear();
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    self.data.pop();
+                    events.push(std::mem::take(&mut self.data));
+                }
+                continue;
+            }
+            let (field, value) = line.split_once(':').unwrap_or((&line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "data" => {
+                    self.data.push_str(value);
+                    self.data.push('\n');
+                }
+                "id" => self.last_id = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        events
+    }
+
+    /// Drops a partly received event, keeping the last event id for the next connection.
+    pub(crate) fn reset(&mut self) {
+        self.line.clear();
+        self.data.clear();
+    }
+
+    pub(crate) fn last_id(&self) -> Option<&str> {
+        self.last_id.as_deref()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn listen(guard: &crate::EGuard, client: &reqwest::Client, base_url: &str, cfg: &PushConfig, parser: &mut SseParser) -> anyhow::Result<()> {
+    use reqwest::header::ACCEPT;
+
+    use crate::{ApiStatusError, redact::Redacted, webhook::WebhookEvent};
+
+    parser.reset();
+    let url = reqwest::Url::parse(&format!("{}{}", base_url, cfg.path))?;
+    let mut req = client.get(url.clone()).bearer_auth(guard.keys.primary().as_str()).header(ACCEPT, "text/event-stream");
+    if let Some(id) = parser.last_id() {
+        req = req.header("last-event-id", id);
+    }
+    if let Some(signer) = &guard.signer {
+        req = req.headers(signer.headers(&reqwest::Method::GET, &url, &[]));
+    }
+    let mut resp = req.send().await?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        return Err(ApiStatusError { status, body }.into());
+    }
+    tracing::debug!("push stream connected");
+    while let Some(chunk) = resp.chunk().await? {
+        for data in parser.feed(&chunk) {
+            match serde_json::from_str::<WebhookEvent>(&data) {
+                Ok(event) => {
+                    if let Err(e) = guard.apply_event(&event).await {
+                        tracing::warn!(error = %Redacted(&e), "failed to apply push event");
+                    }
+                }
+                Err(e) => tracing::warn!(error = %Redacted(&e), "ignoring malformed push event"),
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+use web_time::Instant;
+
+/// Local token-bucket limit on how fast one session (or client IP) may make
+/// secured requests, checked before the Trust API is called.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    #[serde(default)]
+    pub key: RateLimitKey,
+    /// Sustained rate each key is allowed.
+    pub requests_per_second: f64,
+    /// Requests a key may make at once after being idle; defaults to one second's worth.
+    #[serde(default)]
+    pub burst: Option<u32>,
+    #[serde(default)]
+    pub action: RateLimitAction,
+    /// Keys tracked at once; the least recently used one is dropped to make room for a new one.
+    #[serde(default = "default_max_keys")]
+    pub max_keys: usize,
+}
+
+fn default_max_keys() -> usize { 100_000 }
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitKey {
+    #[default]
+    Session,
+    /// Requests without a known client IP are not limited.
+    Ip,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitAction {
+    /// Deny with 429 without calling the Trust API.
+    #[default]
+    Deny,
+    /// Count and log the request, then decide it as usual.
+    Flag,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// Position in `Buckets::order`.
+    seq: u64,
+}
+
+/// Buckets by key, with the keys in order of last use so the oldest can be evicted
+/// without a scan.
+#[derive(Default)]
+struct Buckets {
+    map: HashMap<String, Bucket>,
+    order: BTreeMap<u64, String>,
+    next_seq: u64,
+}
+
+pub(crate) struct RateLimiter {
+    pub(crate) key: RateLimitKey,
+    pub(crate) action: RateLimitAction,
+    rate: f64,
+    burst: f64,
+    max_keys: usize,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(cfg: &RateLimitConfig) -> anyhow::Result<Self> {
+        if cfg.requests_per_second.is_nan() || cfg.requests_per_second <= 0.0 {
+            return Err(anyhow::anyhow!("rate_limit.requests_per_second must be positive"));
+        }
+        let burst = cfg.burst.map_or(cfg.requests_per_second.ceil(), f64::from).max(1.0);
+        Ok(Self {
+            key: cfg.key,
+            action: cfg.action,
+            rate: cfg.requests_per_second,
+            burst,
+            max_keys: cfg.max_keys.max(1),
+            buckets: Mutex::default(),
+        })
+    }
+
+    /// Takes a token for `key`; false when its bucket is empty.
+    pub(crate) fn acquire(&self, key: &str) -> bool {
+        let now = Instant::now();
+        let mut guard = self.buckets.lock().unwrap();
+        let buckets = &mut *guard;
+        let seq = buckets.next_seq;
+        buckets.next_seq += 1;
+        if !buckets.map.contains_key(key)
+            && buckets.map.len() >= self.max_keys
+            && let Some((_, oldest)) = buckets.order.pop_first()
+        {
+            buckets.map.remove(&oldest);
+        }
+        let bucket = buckets.map.entry(key.to_string()).or_insert(Bucket { tokens: self.burst, updated: now, seq });
+        buckets.order.remove(&bucket.seq);
+        buckets.order.insert(seq, key.to_string());
+        bucket.seq = seq;
+        bucket.tokens = self.refill(bucket, now);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.rate).min(self.burst)
+    }
+}
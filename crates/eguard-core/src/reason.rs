@@ -0,0 +1,75 @@
+//! Why the Trust API (or the guard itself) scored a session the way it did.
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A `reason` code. On the wire it is a plain string such as `bot_detected`; codes
+/// this version doesn't know are kept as `Other`, so newer Trust API reasons pass
+/// through unchanged.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ReasonCode {
+    /// The Trust API has never seen the session.
+    UnknownSession,
+    BotDetected,
+    /// Too many requests or actions in a short time.
+    Velocity,
+    /// Banned by an operator or a pushed `session_banned` event.
+    SessionBanned,
+    /// Scored by the local heuristic scorer because the Trust API was unavailable.
+    LocalHeuristic,
+    Other(String),
+}
+
+impl ReasonCode {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ReasonCode::UnknownSession => "unknown_session",
+            ReasonCode::BotDetected => "bot_detected",
+            ReasonCode::Velocity => "velocity",
+            ReasonCode::SessionBanned => "session_banned",
+            ReasonCode::LocalHeuristic => "local_heuristic",
+            ReasonCode::Other(code) => code,
+        }
+    }
+}
+
+impl From<&str> for ReasonCode {
+    fn from(code: &str) -> Self {
+        match code {
+            "unknown_session" => ReasonCode::UnknownSession,
+            "bot_detected" => ReasonCode::BotDetected,
+            "velocity" => ReasonCode::Velocity,
+            "session_banned" => ReasonCode::SessionBanned,
+            "local_heuristic" => ReasonCode::LocalHeuristic,
+            other => ReasonCode::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for ReasonCode {
+    fn from(code: String) -> Self {
+        match ReasonCode::from(code.as_str()) {
+            ReasonCode::Other(_) => ReasonCode::Other(code),
+            known => known,
+        }
+    }
+}
+
+impl fmt::Display for ReasonCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for ReasonCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ReasonCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(ReasonCode::from)
+    }
+}
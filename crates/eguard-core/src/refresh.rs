@@ -0,0 +1,98 @@
+// The refresh task itself is native-only (it needs a tokio runtime).
+#![cfg_attr(target_arch = "wasm32", allow(dead_code))]
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use web_time::Instant;
+
+/// Refresh-ahead for cached scores: sessions that were used during the last TTL
+/// window are re-fetched shortly before their cache entry expires.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RefreshConfig {
+    /// How often the background task scans for entries to refresh.
+    pub interval_ms: u64,
+    /// Refresh an entry once it is this close to expiring.
+    pub refresh_ahead_ms: u64,
+}
+
+impl Default for RefreshConfig {
+    fn default() -> Self {
+        Self { interval_ms: 1000, refresh_ahead_ms: 5000 }
+    }
+}
+
+struct Hot {
+    last_access: Instant,
+    fetched_at: Instant,
+}
+
+/// Sessions seen recently, with when their cached score was fetched.
+pub(crate) struct HotSessions {
+    ttl: Duration,
+    refresh_ahead: Duration,
+    max_entries: usize,
+    sessions: Mutex<HashMap<String, Hot>>,
+}
+
+impl HotSessions {
+    pub(crate) fn new(cfg: &RefreshConfig, ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            refresh_ahead: Duration::from_millis(cfg.refresh_ahead_ms).min(ttl),
+            max_entries,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn touch(&self, session_id: &str) {
+        if let Some(hot) = self.sessions.lock().unwrap().get_mut(session_id) {
+            hot.last_access = Instant::now();
+        }
+    }
+
+    pub(crate) fn fetched(&self, session_id: &str) {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        if sessions.len() >= self.max_entries && !sessions.contains_key(session_id) {
+            return;
+        }
+        sessions.insert(session_id.to_string(), Hot { last_access: now, fetched_at: now });
+    }
+
+    /// Sessions due for a refresh. Sessions idle for a whole TTL are forgotten.
+    pub(crate) fn due(&self) -> Vec<String> {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, h| now.duration_since(h.last_access) < self.ttl);
+        sessions.iter()
+            .filter(|(_, h)| now.duration_since(h.fetched_at) + self.refresh_ahead >= self.ttl)
+            .map(|(k, _)| k.clone())
+            .collect()
+    }
+}
+
+/// Stops the background refresh task when dropped.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct RefreshHandle(pub(crate) tokio::task::JoinHandle<()>);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for RefreshHandle {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RefreshHandle {
+    /// Stops the task and waits until it has finished.
+    pub async fn stop(mut self) {
+        self.0.abort();
+        let _ = (&mut self.0).await;
+    }
+}
@@ -0,0 +1,175 @@
+//! Reading trust scores from Trust API response bodies.
+
+use serde::{Deserialize, Serialize, de::Error as _};
+use serde_json::{Map, Value};
+
+use crate::{ReasonCode, TrustResponse, redact::Redacted, wire::WireFormat};
+
+/// How closely a Trust API response has to match the expected schema.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseParsing {
+    /// Ignore unknown fields, treat optional fields of the wrong type as missing, accept
+    /// scores given as strings, and use the requested id when `session_id` is missing,
+    /// so additions and small changes to the API don't fail lookups.
+    #[default]
+    Lenient,
+    /// Reject unknown fields, missing or mistyped fields and scores that aren't
+    /// floats. Meant for CI and testing, to catch schema changes early.
+    Strict,
+}
+
+/// Request header offering the newest protocol version the client speaks; the Trust API
+/// answers with the version it used in the same header.
+pub const PROTOCOL_HEADER: &str = "x-eguard-protocol";
+/// Request header naming this SDK and its version.
+pub const SDK_HEADER: &str = "x-eguard-sdk";
+pub const SDK: &str = concat!("eguard-rust/", env!("CARGO_PKG_VERSION"));
+
+/// Trust API payload versions. v1 has the scores at the top level (`trust_score`,
+/// `bot_score`, ...); v2 nests them as `"scores": {"trust": ..., "bot": ..., ...}`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProtocolVersion {
+    V1,
+    #[default]
+    V2,
+}
+
+impl ProtocolVersion {
+    pub fn as_header(self) -> &'static str {
+        match self {
+            Self::V1 => "1",
+            Self::V2 => "2",
+        }
+    }
+
+    /// The version a response is in, from its `PROTOCOL_HEADER`. APIs that predate
+    /// negotiation don't send one and speak v1; unknown versions are read as v1 too.
+    pub fn from_header(value: Option<&str>) -> Self {
+        match value.map(str::trim) {
+            Some("2") => Self::V2,
+            _ => Self::V1,
+        }
+    }
+}
+
+const FIELDS: &[&str] = &["session_id", "trust_score", "reason", "bot_score", "fraud_score", "abuse_score"];
+const V2_FIELDS: &[&str] = &["session_id", "scores", "reason"];
+/// v2 score names and the v1 fields they map to.
+const V2_SCORES: &[(&str, &str)] =
+    &[("trust", "trust_score"), ("bot", "bot_score"), ("fraud", "fraud_score"), ("abuse", "abuse_score")];
+
+/// The score in a `/eguard/trust` response body in `format` for `session_id`.
+pub fn parse_trust(body: &[u8], format: WireFormat, session_id: &str, mode: ResponseParsing, version: ProtocolVersion) -> anyhow::Result<TrustResponse> {
+    trust(format.decode(body)?, Some(session_id), mode, version)
+}
+
+/// The scores in a `/eguard/trust/batch` response body, `{"results": [...]}`. Leniently,
+/// results without a usable `session_id` or score are skipped, so those sessions count
+/// as unknown.
+pub fn parse_batch(body: &[u8], format: WireFormat, mode: ResponseParsing, version: ProtocolVersion) -> anyhow::Result<Vec<TrustResponse>> {
+    let mut body = object(format.decode(body)?)?;
+    if mode == ResponseParsing::Strict
+        && let Some(field) = body.keys().find(|k| *k != "results")
+    {
+        return Err(invalid(format_args!("Unknown field `{}` in Trust API batch response", field)));
+    }
+    let Some(Value::Array(results)) = body.remove("results") else {
+        return Err(invalid(format_args!("Trust API batch response has no `results` array")));
+    };
+    results.into_iter()
+        .filter_map(|result| match (trust(result, None, mode, version), mode) {
+            (Err(e), ResponseParsing::Lenient) => {
+                tracing::warn!(error = %Redacted(&e), "skipping unreadable Trust API batch result");
+                None
+            }
+            (result, _) => Some(result),
+        })
+        .collect()
+}
+
+fn trust(value: Value, session_id: Option<&str>, mode: ResponseParsing, version: ProtocolVersion) -> anyhow::Result<TrustResponse> {
+    let strict = mode == ResponseParsing::Strict;
+    let mut fields = object(value)?;
+    let known = match version {
+        ProtocolVersion::V1 => FIELDS,
+        ProtocolVersion::V2 => V2_FIELDS,
+    };
+    if strict && let Some(field) = fields.keys().find(|k| !known.contains(&k.as_str())) {
+        return Err(invalid(format_args!("Unknown field `{}` in Trust API response", field)));
+    }
+    if version == ProtocolVersion::V2 {
+        flatten_scores(&mut fields, strict)?;
+    }
+    let session_id = match (fields.remove("session_id"), session_id) {
+        (Some(Value::String(sid)), _) => sid,
+        (_, Some(sid)) if !strict => sid.to_string(),
+        _ => return Err(invalid(format_args!("Trust API response has no `session_id` string"))),
+    };
+    let trust_score = score(fields.remove("trust_score"), strict)
+        .map_err(|e| invalid(format_args!("`trust_score` {}", e)))?
+        .ok_or_else(|| invalid(format_args!("Trust API response has no `trust_score`")))?;
+    let reason = match fields.remove("reason") {
+        Some(Value::String(code)) => Some(ReasonCode::from(code)),
+        None | Some(Value::Null) => None,
+        Some(_) if !strict => None,
+        Some(other) => return Err(invalid(format_args!("`reason` is not a string: {}", other))),
+    };
+    let mut optional = |name: &str| {
+        score(fields.remove(name), strict).or_else(|e| match strict {
+            true => Err(invalid(format_args!("`{}` {}", name, e))),
+            false => Ok(None),
+        })
+    };
+    Ok(TrustResponse {
+        session_id,
+        trust_score,
+        reason,
+        bot_score: optional("bot_score")?,
+        fraud_score: optional("fraud_score")?,
+        abuse_score: optional("abuse_score")?,
+    })
+}
+
+/// A deserialization error, like those of the JSON parser itself.
+fn invalid(msg: std::fmt::Arguments) -> anyhow::Error {
+    serde_json::Error::custom(msg).into()
+}
+
+/// Moves v2's `scores` to the top level under their v1 names.
+fn flatten_scores(fields: &mut Map<String, Value>, strict: bool) -> anyhow::Result<()> {
+    let scores = match fields.remove("scores") {
+        Some(Value::Object(scores)) => scores,
+        None if !strict => return Ok(()),
+        _ => return Err(invalid(format_args!("Trust API v2 response has no `scores` object"))),
+    };
+    for (name, score) in scores {
+        match V2_SCORES.iter().find(|(v2, _)| *v2 == name) {
+            Some((_, v1)) => {
+                fields.insert(v1.to_string(), score);
+            }
+            None if strict => return Err(invalid(format_args!("Unknown score `{}` in Trust API response", name))),
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+fn object(value: Value) -> anyhow::Result<Map<String, Value>> {
+    match value {
+        Value::Object(fields) => Ok(fields),
+        other => Err(invalid(format_args!("Trust API response is not a JSON object: {}", other))),
+    }
+}
+
+/// A score, `None` when missing or null.
+fn score(value: Option<Value>, strict: bool) -> anyhow::Result<Option<f32>> {
+    match value {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Number(n)) if n.is_f64() || !strict => Ok(n.as_f64().map(|s| s as f32)),
+        Some(Value::String(s)) if !strict => s.trim().parse().map(Some)
+            .map_err(|_| invalid(format_args!("is not a number: {:?}", s))),
+        Some(other) => Err(invalid(format_args!("is not a float: {}", other))),
+    }
+}
@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Backoff policy for transient Trust API failures (timeouts, connection errors, 5xx).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Total attempts including the first call; 1 disables retries.
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// Randomize each delay between zero and its computed value ("full jitter").
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_attempts: 1, base_delay_ms: 50, max_delay_ms: 1000, jitter: true }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry` (1-based).
+    pub fn delay(&self, retry: u32) -> Duration {
+        let exp = self.base_delay_ms.saturating_mul(1u64 << retry.saturating_sub(1).min(32));
+        let capped = exp.min(self.max_delay_ms);
+        let ms = if self.jitter && capped > 0 { rand::random_range(0..=capped) } else { capped };
+        Duration::from_millis(ms)
+    }
+}
@@ -0,0 +1,266 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use regex::RegexSet;
+
+use crate::{EGuardConfig, RouteSyntax, SecureRoute};
+
+/// Secure and excluded route patterns compiled into `RegexSet`s, so matching a
+/// path is one pass over the table however many routes are configured. Paths are
+/// normalized first so encoded or dotted variants can't slip past a pattern.
+pub(crate) struct RouteMatcher {
+    /// Routes without a method filter, used for methods no route names.
+    any_method: Bucket,
+    /// Per method: the routes that list it plus the routes without a filter.
+    by_method: HashMap<String, Bucket>,
+    /// Per route: the lowercased `hosts`, if restricted.
+    hosts: Vec<Option<Vec<String>>>,
+    excludes: RegexSet,
+    ignore_trailing_slash: bool,
+}
+
+/// A `RegexSet` over a subset of the routes, with the route index of each pattern.
+struct Bucket {
+    set: RegexSet,
+    indices: Vec<usize>,
+}
+
+impl Bucket {
+    fn new(patterns: &[String], indices: Vec<usize>) -> anyhow::Result<Self> {
+        let set = RegexSet::new(indices.iter().map(|&i| &patterns[i])).map_err(|e| anyhow::anyhow!("Invalid route regex: {}", e))?;
+        Ok(Self { set, indices })
+    }
+}
+
+impl RouteMatcher {
+    pub(crate) fn new(cfg: &EGuardConfig) -> anyhow::Result<Self> {
+        let routes = &cfg.secure_routes;
+        let patterns: Vec<String> = routes.iter().map(to_regex).collect();
+        let excludes = RegexSet::new(&cfg.exclude_routes).map_err(|e| anyhow::anyhow!("Invalid exclude regex: {}", e))?;
+
+        let mut unfiltered = Vec::new();
+        let mut filtered: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, r) in routes.iter().enumerate() {
+            match &r.methods {
+                None => unfiltered.push(i),
+                Some(ms) => {
+                    for m in ms {
+                        filtered.entry(m.to_uppercase()).or_default().push(i);
+                    }
+                }
+            }
+        }
+
+        let by_method = filtered.into_iter()
+            .map(|(m, mut indices)| {
+                indices.extend(&unfiltered);
+                Ok((m, Bucket::new(&patterns, indices)?))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        let hosts = routes.iter()
+            .map(|r| r.hosts.as_ref().map(|hs| hs.iter().map(|h| h.to_ascii_lowercase()).collect()))
+            .collect();
+
+        Ok(Self {
+            any_method: Bucket::new(&patterns, unfiltered)?,
+            by_method,
+            hosts,
+            excludes,
+            ignore_trailing_slash: cfg.ignore_trailing_slash,
+        })
+    }
+
+    /// An unknown `host` matches every route, so host restrictions never make a route less strict.
+    pub(crate) fn is_secure(&self, host: Option<&str>, path: &str, method: &str) -> bool {
+        let path = normalize_path(path, self.ignore_trailing_slash);
+        if self.excludes.is_match(&path) {
+            return false;
+        }
+        let bucket = self.by_method.get(&method.to_uppercase()).unwrap_or(&self.any_method);
+        bucket.set.matches(&path).iter().any(|j| self.host_matches(bucket.indices[j], host))
+    }
+
+    /// Index of the first secure route matching `host`, `path` and `method`, or
+    /// `None` when the path is excluded; the same routes `is_secure` considers.
+    pub(crate) fn route_index(&self, host: Option<&str>, path: &str, method: &str) -> Option<usize> {
+        let path = normalize_path(path, self.ignore_trailing_slash);
+        if self.excludes.is_match(&path) {
+            return None;
+        }
+        let bucket = self.by_method.get(&method.to_uppercase()).unwrap_or(&self.any_method);
+        bucket.set.matches(&path).iter().map(|j| bucket.indices[j]).filter(|&i| self.host_matches(i, host)).min()
+    }
+
+    fn host_matches(&self, route: usize, host: Option<&str>) -> bool {
+        let (Some(allowed), Some(host)) = (&self.hosts[route], host) else { return true };
+        let host = strip_port(host).to_ascii_lowercase();
+        allowed.iter().any(|h| host_matches(h, &host))
+    }
+}
+
+/// Percent-decodes `path`, collapses repeated slashes and resolves `.`/`..`
+/// segments (never above the root).
+pub(crate) fn normalize_path(path: &str, strip_trailing_slash: bool) -> Cow<'_, str> {
+    let dirty = path.contains('%')
+        || path.contains("//")
+        || path.split('/').any(|seg| seg == "." || seg == "..")
+        || (strip_trailing_slash && path.len() > 1 && path.ends_with('/'));
+    if !dirty {
+        return Cow::Borrowed(path);
+    }
+
+    let decoded = percent_decode(path);
+    let mut segments: Vec<&str> = Vec::new();
+    for seg in decoded.split('/') {
+        match seg {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            seg => segments.push(seg),
+        }
+    }
+
+    let mut out = format!("/{}", segments.join("/"));
+    let had_trailing = decoded.ends_with('/') || decoded.ends_with("/.") || decoded.ends_with("/..");
+    if had_trailing && !strip_trailing_slash && out.len() > 1 {
+        out.push('/');
+    }
+    Cow::Owned(out)
+}
+
+pub(crate) fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Whether lowercased `host` (without port) is `pattern`, or a subdomain of `*.domain`.
+pub(crate) fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host.strip_suffix(domain).is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+        None => pattern == host,
+    }
+}
+
+/// `example.com:8080` -> `example.com`, leaving IPv6 literals like `[::1]` intact.
+pub(crate) fn strip_port(host: &str) -> &str {
+    match host.rfind(':') {
+        Some(i) if !host[i..].contains(']') && (host.starts_with('[') || !host[..i].contains(':')) => &host[..i],
+        _ => host,
+    }
+}
+
+fn to_regex(route: &SecureRoute) -> String {
+    let pattern = &route.path_pattern;
+    match route.syntax {
+        RouteSyntax::Regex => pattern.clone(),
+        RouteSyntax::Template => {
+            let segments = pattern.split('/')
+                .map(|seg| if seg.starts_with(':') { "[^/]+".to_string() } else { regex::escape(seg) })
+                .collect::<Vec<_>>();
+            format!("^{}$", segments.join("/"))
+        }
+        RouteSyntax::Glob => {
+            let mut re = String::from("^");
+            let mut rest = pattern.as_str();
+            while let Some(c) = rest.chars().next() {
+                if let Some(tail) = rest.strip_prefix("/**") {
+                    // `/static/**` also matches `/static` itself.
+                    re.push_str("(?:/.*)?");
+                    rest = tail;
+                } else if let Some(tail) = rest.strip_prefix("**") {
+                    re.push_str(".*");
+                    rest = tail;
+                } else {
+                    match c {
+                        '*' => re.push_str("[^/]*"),
+                        '?' => re.push_str("[^/]"),
+                        c => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+                    }
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+            re.push('$');
+            re
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::test_config;
+
+    fn matcher(yaml: &str) -> RouteMatcher {
+        RouteMatcher::new(&test_config(yaml)).unwrap()
+    }
+
+    #[test]
+    fn normalize_clean_paths_untouched() {
+        assert!(matches!(normalize_path("/api/pay", false), Cow::Borrowed("/api/pay")));
+        assert!(matches!(normalize_path("/api/pay/", false), Cow::Borrowed("/api/pay/")));
+        assert!(matches!(normalize_path("/", true), Cow::Borrowed("/")));
+    }
+
+    #[test]
+    fn normalize_dirty_paths() {
+        assert_eq!(normalize_path("/api//pay", false), "/api/pay");
+        assert_eq!(normalize_path("/api/./pay", false), "/api/pay");
+        assert_eq!(normalize_path("/public/../api/pay", false), "/api/pay");
+        assert_eq!(normalize_path("/../../api/pay", false), "/api/pay");
+        assert_eq!(normalize_path("/api/%70ay", false), "/api/pay");
+        assert_eq!(normalize_path("/public/%2e%2e/api/pay", false), "/api/pay");
+        assert_eq!(normalize_path("/api/pay/", true), "/api/pay");
+        assert_eq!(normalize_path("/api//pay/", false), "/api/pay/");
+        assert_eq!(normalize_path("/api/pay/..", false), "/api/");
+    }
+
+    #[test]
+    fn percent_decode_keeps_invalid_escapes() {
+        assert_eq!(percent_decode("a%20b"), "a b");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+    }
+
+    #[test]
+    fn encoded_and_dotted_paths_still_match() {
+        let routes = matcher("secure_routes: [{path_pattern: '^/api/pay$'}]");
+        for path in ["/api/pay", "//api/pay", "/api/./pay", "/x/../api/pay", "/api/%70ay"] {
+            assert!(routes.is_secure(None, path, "POST"), "{path}");
+        }
+        assert!(!routes.is_secure(None, "/api/pay/", "POST"));
+        assert!(matcher("secure_routes: [{path_pattern: '^/api/pay$'}]\nignore_trailing_slash: true").is_secure(None, "/api/pay/", "POST"));
+    }
+
+    #[test]
+    fn excludes_match_the_normalized_path() {
+        let routes = matcher("secure_routes: [{path_pattern: '^/api/'}]\nexclude_routes: ['^/api/health$']");
+        assert!(!routes.is_secure(None, "/api//health", "GET"));
+        assert!(routes.is_secure(None, "/api/health/../pay", "GET"));
+    }
+
+    #[test]
+    fn route_index_follows_methods_and_excludes() {
+        let routes = matcher("secure_routes:\n - {path_pattern: '^/pay', methods: [POST]}\n - {path_pattern: '^/pay'}\nexclude_routes: ['^/pay/public']");
+        assert_eq!(routes.route_index(None, "/pay", "post"), Some(0));
+        assert_eq!(routes.route_index(None, "/pay", "GET"), Some(1));
+        assert_eq!(routes.route_index(None, "/pay/public", "POST"), None);
+    }
+}
@@ -0,0 +1,26 @@
+//! Small runtime shims so the core builds for both native tokio and wasm32 edge runtimes.
+
+use std::{task::Poll, time::Duration};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn sleep(d: Duration) {
+    tokio::time::sleep(d).await
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn sleep(d: Duration) {
+    gloo_timers::future::sleep(d).await
+}
+
+/// `fut`'s output, or `None` if it takes longer than `d`.
+pub(crate) async fn timeout<F: Future>(d: Duration, fut: F) -> Option<F::Output> {
+    let mut fut = std::pin::pin!(fut);
+    let mut sleep = std::pin::pin!(sleep(d));
+    std::future::poll_fn(|cx| {
+        if let Poll::Ready(out) = fut.as_mut().poll(cx) {
+            return Poll::Ready(Some(out));
+        }
+        sleep.as_mut().poll(cx).map(|_| None)
+    })
+    .await
+}
@@ -0,0 +1,481 @@
+//! Operator-defined rules, checked alongside the trust score:
+//!
+//! ```text
+//! if path ~ "^/export" and score < 0.9 then deny
+//! if ip in ["203.0.113.7", "203.0.113.8"] then allow
+//! if country == "KP" or header.x-bot == "1" then deny 429 "Automated traffic"
+//! ```
+//!
+//! Fields: `path`, `method`, `host`, `ip`, `user_agent`, `country`, `asn`, `ja3`, `ja4`,
+//! `score` and `header.<name>`. Operators: `==`, `!=`, `<`, `<=`, `>`, `>=` (numbers), `~` (regex)
+//! and `in [...]`, combined with `and`, `or`, `not` and parentheses. A comparison on a
+//! value the request doesn't have is false. `path` is normalized the way routes are
+//! matched, so `//export` or `/%65xport` can't slip past `path ~ "^/export"`.
+//!
+//! Rules that don't mention `score` run before the Trust API is called and can skip
+//! it entirely; the others run once the score is known and take precedence over the
+//! threshold. In each phase the first matching rule wins.
+
+use std::borrow::Cow;
+
+use regex::Regex;
+
+use crate::{Decision, RequestContext, routes::normalize_path};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Path,
+    Method,
+    Host,
+    Ip,
+    UserAgent,
+    Country,
+    Asn,
+    Ja3,
+    Ja4,
+    Score,
+}
+
+#[derive(Clone, PartialEq)]
+enum Value {
+    Str(String),
+    Num(f64),
+}
+
+enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match(Regex),
+    In(Vec<Value>),
+}
+
+enum Subject {
+    Field(Field),
+    Header(String),
+}
+
+enum Expr {
+    Cmp(Subject, Cmp, Option<Value>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+struct Rule {
+    cond: Expr,
+    action: Decision,
+}
+
+/// Compiled `rules` from the config, split by phase.
+#[derive(Default)]
+pub(crate) struct Rules {
+    pre: Vec<Rule>,
+    post: Vec<Rule>,
+    ignore_trailing_slash: bool,
+}
+
+/// What a rule can look at.
+struct Facts<'a> {
+    ctx: Option<&'a RequestContext>,
+    /// `ctx.path`, normalized.
+    path: Option<Cow<'a, str>>,
+    score: Option<f32>,
+}
+
+impl Rules {
+    pub(crate) fn new(sources: &[String], ignore_trailing_slash: bool) -> anyhow::Result<Self> {
+        let mut rules = Self { ignore_trailing_slash, ..Self::default() };
+        for src in sources {
+            let rule = parse(src).map_err(|e| anyhow::anyhow!("Invalid rule `{}`: {}", src, e))?;
+            if rule.cond.uses_score() {
+                rules.post.push(rule);
+            } else {
+                rules.pre.push(rule);
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Decision from the rules that don't need a score.
+    pub(crate) fn before_api(&self, ctx: Option<&RequestContext>) -> Option<Decision> {
+        first_match(&self.pre, &self.facts(ctx, None))
+    }
+
+    /// Decision from the rules that need a score; `None` leaves it to the threshold.
+    pub(crate) fn after_api(&self, ctx: Option<&RequestContext>, score: f32) -> Option<Decision> {
+        first_match(&self.post, &self.facts(ctx, Some(score)))
+    }
+
+    fn facts<'a>(&self, ctx: Option<&'a RequestContext>, score: Option<f32>) -> Facts<'a> {
+        let path = ctx.map(|c| normalize_path(&c.path, self.ignore_trailing_slash));
+        Facts { ctx, path, score }
+    }
+}
+
+fn first_match(rules: &[Rule], facts: &Facts) -> Option<Decision> {
+    rules.iter().find(|r| r.cond.eval(facts)).map(|r| r.action.clone())
+}
+
+impl Expr {
+    fn uses_score(&self) -> bool {
+        match self {
+            Expr::Cmp(Subject::Field(f), ..) => *f == Field::Score,
+            Expr::Cmp(..) => false,
+            Expr::And(a, b) | Expr::Or(a, b) => a.uses_score() || b.uses_score(),
+            Expr::Not(e) => e.uses_score(),
+        }
+    }
+
+    fn eval(&self, facts: &Facts) -> bool {
+        match self {
+            Expr::And(a, b) => a.eval(facts) && b.eval(facts),
+            Expr::Or(a, b) => a.eval(facts) || b.eval(facts),
+            Expr::Not(e) => !e.eval(facts),
+            Expr::Cmp(subject, cmp, rhs) => {
+                let Some(lhs) = subject.value(facts) else { return false };
+                match (cmp, rhs) {
+                    (Cmp::Eq, Some(rhs)) => lhs == *rhs,
+                    (Cmp::Ne, Some(rhs)) => lhs != *rhs,
+                    (Cmp::Lt | Cmp::Le | Cmp::Gt | Cmp::Ge, Some(Value::Num(rhs))) => {
+                        let Value::Num(lhs) = lhs else { return false };
+                        match cmp {
+                            Cmp::Lt => lhs < *rhs,
+                            Cmp::Le => lhs <= *rhs,
+                            Cmp::Gt => lhs > *rhs,
+                            _ => lhs >= *rhs,
+                        }
+                    }
+                    (Cmp::Match(re), _) => match lhs {
+                        Value::Str(s) => re.is_match(&s),
+                        Value::Num(n) => re.is_match(&n.to_string()),
+                    },
+                    (Cmp::In(list), _) => list.contains(&lhs),
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+impl Subject {
+    fn value(&self, facts: &Facts) -> Option<Value> {
+        let ctx = facts.ctx;
+        let geo = ctx.and_then(|c| c.geo.as_ref());
+        let s = |v: Option<&str>| v.map(|v| Value::Str(v.to_string()));
+        match self {
+            Subject::Field(Field::Score) => facts.score.map(|v| Value::Num(v.into())),
+            Subject::Field(Field::Path) => s(facts.path.as_deref()),
+            Subject::Field(Field::Method) => s(ctx.map(|c| c.method.as_str())),
+            Subject::Field(Field::Host) => s(ctx.and_then(|c| c.host())),
+            Subject::Field(Field::Ip) => ctx?.ip.map(|ip| Value::Str(ip.to_canonical().to_string())),
+            Subject::Field(Field::UserAgent) => s(ctx.and_then(|c| c.user_agent.as_deref())),
+            Subject::Field(Field::Country) => s(geo.and_then(|g| g.country.as_deref())),
+            Subject::Field(Field::Asn) => geo?.asn.map(|n| Value::Num(n.into())),
+            Subject::Field(Field::Ja3) => s(ctx.and_then(|c| c.ja3.as_deref())),
+            Subject::Field(Field::Ja4) => s(ctx.and_then(|c| c.ja4.as_deref())),
+            Subject::Header(name) => s(ctx.and_then(|c| c.headers.get(name)).map(String::as_str)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Op(&'static str),
+}
+
+fn tokenize(src: &str) -> anyhow::Result<Vec<Token>> {
+    const OPS: [&str; 12] = ["==", "!=", "<=", ">=", "<", ">", "~", "(", ")", "[", "]", ","];
+    let mut tokens = Vec::new();
+    let mut rest = src.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if let Some(op) = OPS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else if c == '"' {
+            let mut s = String::new();
+            let mut chars = rest[1..].char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((i, '"')) => break i + 2,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c)) => s.push(c),
+                        None => anyhow::bail!("unterminated string"),
+                    },
+                    Some((_, c)) => s.push(c),
+                    None => anyhow::bail!("unterminated string"),
+                }
+            };
+            tokens.push(Token::Str(s));
+            rest = &rest[end..];
+        } else if c.is_ascii_digit() || c == '-' {
+            let len = rest.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-')).unwrap_or(rest.len());
+            let n = rest[..len].parse().map_err(|_| anyhow::anyhow!("bad number {}", &rest[..len]))?;
+            tokens.push(Token::Num(n));
+            rest = &rest[len..];
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest.find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '.' | '-'))).unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            rest = &rest[len..];
+        } else {
+            anyhow::bail!("unexpected `{}`", c);
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+fn parse(src: &str) -> anyhow::Result<Rule> {
+    let mut p = Parser { tokens: tokenize(src)?, pos: 0 };
+    p.keyword("if")?;
+    let cond = p.or()?;
+    p.keyword("then")?;
+    let action = p.action()?;
+    if let Some(t) = p.peek() {
+        anyhow::bail!("unexpected {:?} after action", t);
+    }
+    Ok(Rule { cond, action })
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> anyhow::Result<Token> {
+        let t = self.tokens.get(self.pos).cloned().ok_or_else(|| anyhow::anyhow!("unexpected end of rule"))?;
+        self.pos += 1;
+        Ok(t)
+    }
+
+    fn is_keyword(&self, kw: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(s)) if s.eq_ignore_ascii_case(kw))
+    }
+
+    fn keyword(&mut self, kw: &str) -> anyhow::Result<()> {
+        if !self.is_keyword(kw) {
+            anyhow::bail!("expected `{}`", kw);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn or(&mut self) -> anyhow::Result<Expr> {
+        let mut lhs = self.and()?;
+        while self.is_keyword("or") {
+            self.pos += 1;
+            lhs = Expr::Or(Box::new(lhs), Box::new(self.and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> anyhow::Result<Expr> {
+        let mut lhs = self.not()?;
+        while self.is_keyword("and") {
+            self.pos += 1;
+            lhs = Expr::And(Box::new(lhs), Box::new(self.not()?));
+        }
+        Ok(lhs)
+    }
+
+    fn not(&mut self) -> anyhow::Result<Expr> {
+        if self.is_keyword("not") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        if self.peek() == Some(&Token::Op("(")) {
+            self.pos += 1;
+            let e = self.or()?;
+            if self.next()? != Token::Op(")") {
+                anyhow::bail!("expected `)`");
+            }
+            return Ok(e);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> anyhow::Result<Expr> {
+        let Token::Ident(name) = self.next()? else { anyhow::bail!("expected a field name") };
+        let subject = match name.as_str() {
+            "path" => Subject::Field(Field::Path),
+            "method" => Subject::Field(Field::Method),
+            "host" => Subject::Field(Field::Host),
+            "ip" => Subject::Field(Field::Ip),
+            "user_agent" => Subject::Field(Field::UserAgent),
+            "country" => Subject::Field(Field::Country),
+            "asn" => Subject::Field(Field::Asn),
+            "ja3" => Subject::Field(Field::Ja3),
+            "ja4" => Subject::Field(Field::Ja4),
+            "score" => Subject::Field(Field::Score),
+            other => match other.strip_prefix("header.") {
+                Some(h) if !h.is_empty() => Subject::Header(h.to_ascii_lowercase()),
+                _ => anyhow::bail!("unknown field `{}`", other),
+            },
+        };
+
+        let op = self.next()?;
+        let (cmp, rhs) = match op {
+            Token::Op("~") => {
+                let Token::Str(pattern) = self.next()? else { anyhow::bail!("`~` needs a string pattern") };
+                (Cmp::Match(Regex::new(&pattern)?), None)
+            }
+            Token::Ident(kw) if kw.eq_ignore_ascii_case("in") => (Cmp::In(self.list()?), None),
+            Token::Op(op) => {
+                let cmp = match op {
+                    "==" => Cmp::Eq,
+                    "!=" => Cmp::Ne,
+                    "<" => Cmp::Lt,
+                    "<=" => Cmp::Le,
+                    ">" => Cmp::Gt,
+                    ">=" => Cmp::Ge,
+                    _ => anyhow::bail!("unexpected `{}`", op),
+                };
+                let value = self.value()?;
+                if matches!(cmp, Cmp::Lt | Cmp::Le | Cmp::Gt | Cmp::Ge) && !matches!(value, Value::Num(_)) {
+                    anyhow::bail!("`{}` needs a number", op);
+                }
+                (cmp, Some(value))
+            }
+            t => anyhow::bail!("expected an operator, got {:?}", t),
+        };
+        Ok(Expr::Cmp(subject, cmp, rhs))
+    }
+
+    fn value(&mut self) -> anyhow::Result<Value> {
+        match self.next()? {
+            Token::Str(s) => Ok(Value::Str(s)),
+            Token::Num(n) => Ok(Value::Num(n)),
+            t => anyhow::bail!("expected a value, got {:?}", t),
+        }
+    }
+
+    fn list(&mut self) -> anyhow::Result<Vec<Value>> {
+        if self.next()? != Token::Op("[") {
+            anyhow::bail!("`in` needs a [list]");
+        }
+        let mut items = Vec::new();
+        loop {
+            if self.peek() == Some(&Token::Op("]")) {
+                self.pos += 1;
+                return Ok(items);
+            }
+            items.push(self.value()?);
+            match self.next()? {
+                Token::Op(",") => {}
+                Token::Op("]") => return Ok(items),
+                t => anyhow::bail!("expected `,` or `]`, got {:?}", t),
+            }
+        }
+    }
+
+    fn action(&mut self) -> anyhow::Result<Decision> {
+        match self.next()? {
+            Token::Ident(a) if a.eq_ignore_ascii_case("allow") => Ok(Decision::allow()),
+            Token::Ident(a) if a.eq_ignore_ascii_case("deny") => {
+                let mut status = 403;
+                let mut message = "Denied by rule".to_string();
+                if let Some(Token::Num(n)) = self.peek() {
+                    if n.fract() != 0.0 || !(100.0..=599.0).contains(n) {
+                        anyhow::bail!("expected an HTTP status from 100 to 599 after `deny`, got {}", n);
+                    }
+                    status = *n as u16;
+                    self.pos += 1;
+                }
+                if let Some(Token::Str(s)) = self.peek() {
+                    message = s.clone();
+                    self.pos += 1;
+                }
+                Ok(Decision::Deny { status, message })
+            }
+            t => anyhow::bail!("expected `allow` or `deny`, got {:?}", t),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(sources: &[&str], ignore_trailing_slash: bool) -> Rules {
+        let sources: Vec<String> = sources.iter().map(|s| s.to_string()).collect();
+        Rules::new(&sources, ignore_trailing_slash).unwrap()
+    }
+
+    fn denied(decision: Option<Decision>) -> Option<(u16, String)> {
+        match decision {
+            Some(Decision::Deny { status, message }) => Some((status, message)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn path_is_normalized() {
+        let rules = rules(&[r#"if path ~ "^/export$" then deny"#], false);
+        for path in ["/export", "//export", "/%65xport", "/public/../export", "/./export"] {
+            let ctx = RequestContext::new("GET", path);
+            assert!(denied(rules.before_api(Some(&ctx))).is_some(), "{path}");
+        }
+        assert!(rules.before_api(Some(&RequestContext::new("GET", "/exports"))).is_none());
+    }
+
+    #[test]
+    fn trailing_slash_follows_the_route_setting() {
+        let ctx = RequestContext::new("GET", "/export/");
+        assert!(rules(&[r#"if path == "/export" then deny"#], false).before_api(Some(&ctx)).is_none());
+        assert!(rules(&[r#"if path == "/export" then deny"#], true).before_api(Some(&ctx)).is_some());
+    }
+
+    #[test]
+    fn score_rules_run_after_the_api() {
+        let rules = rules(&[r#"if path ~ "^/export" and score < 0.9 then deny 429 "Step up""#], false);
+        let ctx = RequestContext::new("POST", "//export/all");
+        assert!(rules.before_api(Some(&ctx)).is_none());
+        assert_eq!(denied(rules.after_api(Some(&ctx), 0.5)), Some((429, "Step up".to_string())));
+        assert!(rules.after_api(Some(&ctx), 0.95).is_none());
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = rules(&[r#"if header.x-internal == "1" then allow"#, r#"if path ~ "^/admin" or method in ["DELETE"] then deny"#], false);
+        let internal = RequestContext::new("DELETE", "/admin").with_headers([("X-Internal", "1")]);
+        assert!(matches!(rules.before_api(Some(&internal)), Some(Decision::Allow { .. })));
+        assert!(denied(rules.before_api(Some(&RequestContext::new("DELETE", "/api")))).is_some());
+        assert!(rules.before_api(Some(&RequestContext::new("GET", "/api"))).is_none());
+    }
+
+    #[test]
+    fn missing_values_never_match() {
+        let rules = rules(&[r#"if country != "US" then deny"#, r#"if not (path ~ "^/") then deny"#], false);
+        assert!(rules.before_api(Some(&RequestContext::new("GET", "/"))).is_none());
+        assert!(denied(rules.before_api(None)).is_some());
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        for src in ["if path then deny", r#"if path ~ "(" then deny"#, r#"if nope == "1" then deny"#, r#"if path == "/" then block"#] {
+            assert!(Rules::new(&[src.to_string()], false).is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn deny_status_must_be_an_http_status() {
+        for status in ["0", "99", "600", "99999", "403.7", "-403"] {
+            let src = [format!(r#"if path == "/" then deny {status}"#)];
+            assert!(Rules::new(&src, false).is_err(), "{}", src[0]);
+        }
+        let rules = rules(&[r#"if path == "/" then deny 451 "Gone""#], false);
+        let ctx = RequestContext::new("GET", "/");
+        assert_eq!(denied(rules.before_api(Some(&ctx))), Some((451, "Gone".to_string())));
+    }
+}
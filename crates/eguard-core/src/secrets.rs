@@ -0,0 +1,253 @@
+//! Loading `api_key` and the request signing key from a secret store at runtime.
+//!
+//! Env and file sources are always available; AWS Secrets Manager and Vault need the
+//! `aws` and `vault` features.
+
+use std::{fmt, sync::Arc};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{EGuardConfig, ProxyConfig};
+
+/// Somewhere a secret can be read from. Called again on every refresh, so it should
+/// return the current value rather than a copy taken at startup.
+#[async_trait]
+pub trait SecretProvider: Send + Sync {
+    async fn load(&self) -> anyhow::Result<String>;
+}
+
+/// A secret held in the config, such as `api_key`. It is zeroed when dropped and
+/// shown as `[REDACTED]` by `Debug`; `expose` gives the value.
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub struct SecretString(Zeroizing<String>);
+
+impl SecretString {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self(Zeroizing::new(value))
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        value.to_string().into()
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SecretsConfig {
+    /// Replaces `api_key`. A changed key is rotated in, keeping the previous one as the
+    /// secondary key.
+    #[serde(default)]
+    pub api_key: Option<SecretSource>,
+    /// Replaces `request_signing.key`.
+    #[serde(default)]
+    pub signing_key: Option<SecretSource>,
+    /// How often secrets are loaded again; 0 loads them once.
+    #[serde(default = "default_refresh_ms")]
+    pub refresh_ms: u64,
+}
+
+pub(crate) fn default_refresh_ms() -> u64 { 300_000 }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SecretSource {
+    Env { var: String },
+    /// Surrounding whitespace, such as a trailing newline, is ignored.
+    File { path: String },
+    /// The `SecretString` of a secret, or one field of it if it holds a JSON object.
+    /// Credentials and region come from the usual AWS environment and config files.
+    AwsSecretsManager {
+        secret_id: String,
+        #[serde(default)]
+        key: Option<String>,
+    },
+    /// One field of a KV secret, e.g. path `secret/data/eguard` for KV v2.
+    Vault {
+        address: String,
+        path: String,
+        key: String,
+        /// Defaults to the `VAULT_TOKEN` environment variable. Never serialized.
+        #[serde(default, skip_serializing)]
+        token: Option<SecretString>,
+    },
+}
+
+/// The providers a guard loads its secrets from.
+#[derive(Clone, Default)]
+pub(crate) struct SecretProviders {
+    pub(crate) api_key: Option<Arc<dyn SecretProvider>>,
+    pub(crate) signing_key: Option<Arc<dyn SecretProvider>>,
+}
+
+impl SecretProviders {
+    pub(crate) fn new(cfg: &EGuardConfig) -> anyhow::Result<Self> {
+        let Some(secrets) = &cfg.secrets else {
+            return Ok(Self::default());
+        };
+        if secrets.signing_key.is_some() && cfg.request_signing.is_none() {
+            return Err(anyhow::anyhow!("secrets.signing_key is set but request_signing is not"));
+        }
+        let build = |source: &Option<SecretSource>| source.as_ref().map(|s| build(s, cfg.proxy.as_ref())).transpose();
+        Ok(Self { api_key: build(&secrets.api_key)?, signing_key: build(&secrets.signing_key)? })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.api_key.is_none() && self.signing_key.is_none()
+    }
+}
+
+fn build(source: &SecretSource, proxy: Option<&ProxyConfig>) -> anyhow::Result<Arc<dyn SecretProvider>> {
+    match source {
+        SecretSource::Env { var } => Ok(Arc::new(EnvSecret(var.clone()))),
+        SecretSource::File { path } => Ok(Arc::new(FileSecret(path.clone()))),
+        #[cfg(feature = "aws")]
+        SecretSource::AwsSecretsManager { secret_id, key } => Ok(Arc::new(aws::AwsSecret::new(secret_id, key.as_deref()))),
+        #[cfg(not(feature = "aws"))]
+        SecretSource::AwsSecretsManager { .. } => {
+            Err(anyhow::anyhow!("secret source is aws_secrets_manager but eguard-core was built without the `aws` feature"))
+        }
+        #[cfg(feature = "vault")]
+        SecretSource::Vault { address, path, key, token } => {
+            Ok(Arc::new(vault::VaultSecret::new(address, path, key, token.clone(), proxy)?))
+        }
+        #[cfg(not(feature = "vault"))]
+        SecretSource::Vault { .. } => {
+            let _ = proxy;
+            Err(anyhow::anyhow!("secret source is vault but eguard-core was built without the `vault` feature"))
+        }
+    }
+}
+
+struct EnvSecret(String);
+
+#[async_trait]
+impl SecretProvider for EnvSecret {
+    async fn load(&self) -> anyhow::Result<String> {
+        std::env::var(&self.0).map_err(|e| anyhow::anyhow!("Reading secret from `{}`: {}", self.0, e))
+    }
+}
+
+struct FileSecret(String);
+
+#[async_trait]
+impl SecretProvider for FileSecret {
+    async fn load(&self) -> anyhow::Result<String> {
+        let secret = std::fs::read_to_string(&self.0).map_err(|e| anyhow::anyhow!("Reading secret from `{}`: {}", self.0, e))?;
+        Ok(secret.trim().to_string())
+    }
+}
+
+/// The `key` string field of a JSON secret.
+#[cfg(any(feature = "aws", feature = "vault"))]
+fn field(value: &serde_json::Value, key: &str) -> anyhow::Result<String> {
+    value.get(key)
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("Secret has no string field `{}`", key))
+}
+
+#[cfg(feature = "aws")]
+mod aws {
+    use aws_sdk_secretsmanager::Client;
+    use tokio::sync::OnceCell;
+
+    use super::*;
+
+    pub(super) struct AwsSecret {
+        secret_id: String,
+        key: Option<String>,
+        client: OnceCell<Client>,
+    }
+
+    impl AwsSecret {
+        pub(super) fn new(secret_id: &str, key: Option<&str>) -> Self {
+            Self { secret_id: secret_id.to_string(), key: key.map(str::to_string), client: OnceCell::new() }
+        }
+    }
+
+    #[async_trait]
+    impl SecretProvider for AwsSecret {
+        async fn load(&self) -> anyhow::Result<String> {
+            let client = self.client
+                .get_or_init(|| async {
+                    Client::new(&aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await)
+                })
+                .await;
+            let out = client.get_secret_value().secret_id(&self.secret_id).send().await
+                .map_err(|e| anyhow::anyhow!("Reading secret `{}`: {}", self.secret_id, aws_sdk_secretsmanager::error::DisplayErrorContext(e)))?;
+            let secret = out.secret_string()
+                .ok_or_else(|| anyhow::anyhow!("Secret `{}` has no SecretString", self.secret_id))?;
+            match &self.key {
+                Some(key) => field(&serde_json::from_str(secret)?, key),
+                None => Ok(secret.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "vault")]
+mod vault {
+    use reqwest::Client;
+
+    use super::*;
+
+    pub(super) struct VaultSecret {
+        client: Client,
+        url: String,
+        key: String,
+        token: Option<SecretString>,
+    }
+
+    impl VaultSecret {
+        pub(super) fn new(address: &str, path: &str, key: &str, token: Option<SecretString>, proxy: Option<&ProxyConfig>) -> anyhow::Result<Self> {
+            Ok(Self {
+                client: crate::transport::client_builder(proxy)?.build()?,
+                url: format!("{}/v1/{}", address.trim_end_matches('/'), path.trim_start_matches('/')),
+                key: key.to_string(),
+                token,
+            })
+        }
+    }
+
+    #[async_trait]
+    impl SecretProvider for VaultSecret {
+        async fn load(&self) -> anyhow::Result<String> {
+            let token = match &self.token {
+                Some(token) => token.expose().to_string(),
+                None => std::env::var("VAULT_TOKEN").map_err(|_| anyhow::anyhow!("Vault token not set and VAULT_TOKEN is missing"))?,
+            };
+            let resp = self.client.get(&self.url).header("x-vault-token", token).send().await?;
+            if !resp.status().is_success() {
+                return Err(anyhow::anyhow!("Vault returned {} for `{}`", resp.status(), self.url));
+            }
+            let body: serde_json::Value = resp.json().await?;
+            // KV v2 nests the secret under `data.data`, KV v1 under `data`.
+            let data = &body["data"];
+            match data.get("data") {
+                Some(inner) if inner.is_object() => field(inner, &self.key),
+                _ => field(data, &self.key),
+            }
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use std::{borrow::Cow, fmt, str::FromStr};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{JwtConfig, routes::percent_decode};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionExtraction {
+    /// Where to look for the session id, in priority order, e.g.
+    /// `["cookie:sid", "header:x-session", "query:sid"]`. When empty, the cookie,
+    /// header and query settings below are used in that order.
+    #[serde(default)]
+    pub sources: Vec<SessionSource>,
+    pub cookie_name: Option<String>,
+    /// More cookies tried after `cookie_name`, in priority order, e.g. while migrating
+    /// from `legacy_session` to `sid`.
+    #[serde(default)]
+    pub cookie_names: Vec<String>,
+    /// Also look for each cookie under its `__Host-` and `__Secure-` prefixed names,
+    /// which take priority over the plain one.
+    #[serde(default)]
+    pub cookie_prefixes: bool,
+    /// Which value counts when a cookie name appears more than once.
+    #[serde(default)]
+    pub cookie_duplicates: CookieDuplicates,
+    pub header_name: Option<String>,
+    #[serde(default)]
+    pub header_bearer: bool,
+    /// Schemes stripped from `bearer:` header values, in any case, e.g. `["Bearer",
+    /// "Token", "ApiKey"]`.
+    #[serde(default = "default_bearer_schemes")]
+    pub bearer_schemes: Vec<String>,
+    /// Ignore `bearer:` header values without one of `bearer_schemes` instead of taking
+    /// them as they are.
+    #[serde(default)]
+    pub bearer_require_scheme: bool,
+    /// Query parameter (`?sid=...`) checked after the cookie and header.
+    #[serde(default)]
+    pub query_param: Option<String>,
+    /// Decode the extracted token as a JWT and use one of its claims instead.
+    #[serde(default)]
+    pub jwt: Option<JwtConfig>,
+    /// Where to look for a device or fingerprint id, in priority order, e.g.
+    /// `["header:x-device-id", "cookie:did"]`. It is sent to the Trust API as
+    /// `RequestContext::device_id`.
+    #[serde(default)]
+    pub device_sources: Vec<SessionSource>,
+    /// Longest session id accepted. Longer ones are denied without calling the Trust API.
+    #[serde(default)]
+    pub max_length: Option<usize>,
+    /// Regex a session id must match in full, e.g. `[A-Za-z0-9_-]{16,64}`. Ids that
+    /// don't are denied like those over `max_length`.
+    #[serde(default)]
+    pub pattern: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CookieDuplicates {
+    /// The first occurrence, which browsers send for the most specific path.
+    #[default]
+    First,
+    Last,
+}
+
+/// One place a session id can come from, written `kind:name`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum SessionSource {
+    /// `cookie:sid`
+    Cookie(String),
+    /// `header:x-session`, the raw header value.
+    Header(String),
+    /// `bearer:authorization`, the header value without its scheme (`bearer_schemes`).
+    Bearer(String),
+    /// `query:sid`
+    Query(String),
+}
+
+impl FromStr for SessionSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (kind, name) = s.split_once(':')
+            .filter(|(_, name)| !name.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Invalid session source `{}`, expected `kind:name`", s))?;
+        Ok(match kind {
+            "cookie" => SessionSource::Cookie(name.to_string()),
+            "header" => SessionSource::Header(name.to_string()),
+            "bearer" => SessionSource::Bearer(name.to_string()),
+            "query" => SessionSource::Query(name.to_string()),
+            _ => anyhow::bail!("Unknown session source kind `{}` in `{}`", kind, s),
+        })
+    }
+}
+
+impl TryFrom<String> for SessionSource {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for SessionSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionSource::Cookie(n) => write!(f, "cookie:{n}"),
+            SessionSource::Header(n) => write!(f, "header:{n}"),
+            SessionSource::Bearer(n) => write!(f, "bearer:{n}"),
+            SessionSource::Query(n) => write!(f, "query:{n}"),
+        }
+    }
+}
+
+impl From<SessionSource> for String {
+    fn from(s: SessionSource) -> Self {
+        s.to_string()
+    }
+}
+
+impl SessionExtraction {
+    /// `sources`, or the equivalent of the individual settings when it is empty.
+    pub fn effective_sources(&self) -> Cow<'_, [SessionSource]> {
+        if !self.sources.is_empty() {
+            return Cow::Borrowed(&self.sources);
+        }
+        let mut sources = Vec::new();
+        for name in self.cookie_name.iter().chain(&self.cookie_names) {
+            sources.push(SessionSource::Cookie(name.clone()));
+        }
+        if let Some(name) = &self.header_name {
+            sources.push(if self.header_bearer {
+                SessionSource::Bearer(name.clone())
+            } else {
+                SessionSource::Header(name.clone())
+            });
+        }
+        if let Some(name) = &self.query_param {
+            sources.push(SessionSource::Query(name.clone()));
+        }
+        Cow::Owned(sources)
+    }
+
+    /// The raw token from the first source that has one.
+    pub(crate) fn token<'h>(
+        &self,
+        cookies: Option<&str>,
+        header: impl Fn(&str) -> Option<&'h str>,
+        query: Option<&str>,
+    ) -> Option<String> {
+        self.lookup(&self.effective_sources(), cookies, header, query)
+    }
+
+    /// The device id from the first of `device_sources` that has one.
+    pub(crate) fn device_id<'h>(
+        &self,
+        cookies: Option<&str>,
+        header: impl Fn(&str) -> Option<&'h str>,
+        query: Option<&str>,
+    ) -> Option<String> {
+        self.lookup(&self.device_sources, cookies, header, query)
+    }
+
+    fn lookup<'h>(
+        &self,
+        sources: &[SessionSource],
+        cookies: Option<&str>,
+        header: impl Fn(&str) -> Option<&'h str>,
+        query: Option<&str>,
+    ) -> Option<String> {
+        sources.iter().find_map(|source| match source {
+            SessionSource::Cookie(name) => self.cookie(cookies?, name),
+            SessionSource::Header(name) => header(name).map(str::to_string),
+            SessionSource::Bearer(name) => self.strip_scheme(header(name)?.trim()),
+            SessionSource::Query(name) => query_param(query?, name),
+        })
+    }
+
+    /// The value of cookie `name`, or of its prefixed variants with `cookie_prefixes`.
+    fn cookie(&self, header: &str, name: &str) -> Option<String> {
+        if self.cookie_prefixes && !name.starts_with("__Host-") && !name.starts_with("__Secure-") {
+            let prefixed = ["__Host-", "__Secure-"].iter()
+                .find_map(|prefix| cookie(header, &format!("{prefix}{name}"), self.cookie_duplicates));
+            if prefixed.is_some() {
+                return prefixed;
+            }
+        }
+        cookie(header, name, self.cookie_duplicates)
+    }
+
+    /// `value` without its `bearer_schemes` scheme. Values without one are taken as
+    /// they are unless `bearer_require_scheme` is set. Blank tokens are ignored.
+    fn strip_scheme(&self, value: &str) -> Option<String> {
+        let token = self.bearer_schemes.iter().find_map(|scheme| {
+            let (prefix, rest) = value.split_at_checked(scheme.len())?;
+            // A bare scheme counts too: `Bearer ` arrives trimmed to `Bearer`.
+            if !prefix.eq_ignore_ascii_case(scheme) || !(rest.is_empty() || rest.starts_with(|c: char| c.is_ascii_whitespace())) {
+                return None;
+            }
+            Some(rest.trim_start())
+        });
+        let token = match token {
+            Some(token) => token,
+            None if self.bearer_require_scheme => return None,
+            None => value,
+        };
+        (!token.is_empty()).then(|| token.to_string())
+    }
+}
+
+fn default_bearer_schemes() -> Vec<String> {
+    vec!["Bearer".to_string()]
+}
+
+/// `max_length` and `pattern` of a `SessionExtraction`, compiled.
+pub(crate) struct SessionValidator {
+    max_length: Option<usize>,
+    pattern: Option<Regex>,
+}
+
+impl SessionValidator {
+    /// `None` when neither is set.
+    pub fn new(cfg: &SessionExtraction) -> anyhow::Result<Option<Self>> {
+        if cfg.max_length.is_none() && cfg.pattern.is_none() {
+            return Ok(None);
+        }
+        let pattern = cfg.pattern.as_ref()
+            .map(|p| Regex::new(&format!("^(?:{p})$")).map_err(|e| anyhow::anyhow!("Invalid session_extraction.pattern: {}", e)))
+            .transpose()?;
+        Ok(Some(Self { max_length: cfg.max_length, pattern }))
+    }
+
+    pub fn is_valid(&self, session_id: &str) -> bool {
+        self.max_length.is_none_or(|max| session_id.len() <= max)
+            && self.pattern.as_ref().is_none_or(|p| p.is_match(session_id))
+    }
+}
+
+/// The value of cookie `name` in a `Cookie` header (RFC 6265 section 4.2), with
+/// surrounding double quotes removed and percent-escapes decoded. Pairs without
+/// `=` and empty values are skipped.
+fn cookie(header: &str, name: &str, duplicates: CookieDuplicates) -> Option<String> {
+    let mut values = header.split(';')
+        .filter_map(|pair| pair.split_once('='))
+        .filter(|(k, _)| k.trim() == name)
+        .map(|(_, v)| {
+            let v = v.trim();
+            v.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(v)
+        })
+        .filter(|v| !v.is_empty());
+    let value = match duplicates {
+        CookieDuplicates::First => values.next(),
+        CookieDuplicates::Last => values.next_back(),
+    };
+    value.map(percent_decode)
+}
+
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query.split('&')
+        .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+        .find(|(k, v)| percent_decode(k) == name && !v.is_empty())
+        .map(|(_, v)| percent_decode(v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::test_config;
+
+    fn extraction(yaml: &str) -> SessionExtraction {
+        test_config(&format!("session_extraction: {yaml}")).session_extraction
+    }
+
+    fn from_cookies(cfg: &SessionExtraction, cookies: &str) -> Option<String> {
+        cfg.token(Some(cookies), |_| None, None)
+    }
+
+    fn from_bearer(cfg: &SessionExtraction, value: &'static str) -> Option<String> {
+        cfg.token(None, |name| (name == "authorization").then_some(value), None)
+    }
+
+    #[test]
+    fn prefixed_cookies_take_priority() {
+        let cfg = extraction("{cookie_name: sid, cookie_prefixes: true}");
+        assert_eq!(from_cookies(&cfg, "sid=plain; __Secure-sid=secure; __Host-sid=host").as_deref(), Some("host"));
+        assert_eq!(from_cookies(&cfg, "sid=plain; __Secure-sid=secure").as_deref(), Some("secure"));
+        assert_eq!(from_cookies(&cfg, "sid=plain").as_deref(), Some("plain"));
+    }
+
+    #[test]
+    fn prefixed_cookies_ignored_by_default() {
+        let cfg = extraction("{cookie_name: sid}");
+        assert_eq!(from_cookies(&cfg, "__Host-sid=host; sid=plain").as_deref(), Some("plain"));
+        assert_eq!(from_cookies(&cfg, "__Host-sid=host"), None);
+    }
+
+    #[test]
+    fn cookie_names_tried_in_order() {
+        let cfg = extraction("{cookie_name: sid, cookie_names: [legacy_session]}");
+        assert_eq!(from_cookies(&cfg, "legacy_session=old; sid=new").as_deref(), Some("new"));
+        assert_eq!(from_cookies(&cfg, "legacy_session=old").as_deref(), Some("old"));
+    }
+
+    #[test]
+    fn duplicate_cookies() {
+        let first = extraction("{cookie_name: sid}");
+        let last = extraction("{cookie_name: sid, cookie_duplicates: last}");
+        assert_eq!(from_cookies(&first, "sid=a; other=x; sid=b").as_deref(), Some("a"));
+        assert_eq!(from_cookies(&last, "sid=a; other=x; sid=b").as_deref(), Some("b"));
+        // Empty values don't count as an occurrence.
+        assert_eq!(from_cookies(&first, "sid=; sid=b").as_deref(), Some("b"));
+        assert_eq!(from_cookies(&last, "sid=a; sid=").as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn cookie_values_unquoted_and_decoded() {
+        let cfg = extraction("{cookie_name: sid}");
+        assert_eq!(from_cookies(&cfg, "sid=\"a%2Bb\"").as_deref(), Some("a+b"));
+        assert_eq!(from_cookies(&cfg, "xsid=a; sid =b").as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn bearer_scheme_in_any_case() {
+        let cfg = extraction("{header_name: authorization, header_bearer: true}");
+        assert_eq!(from_bearer(&cfg, "Bearer abc").as_deref(), Some("abc"));
+        assert_eq!(from_bearer(&cfg, "bearer abc").as_deref(), Some("abc"));
+        assert_eq!(from_bearer(&cfg, "BEARER   abc").as_deref(), Some("abc"));
+        // Not a scheme without the separating space.
+        assert_eq!(from_bearer(&cfg, "Bearerabc").as_deref(), Some("Bearerabc"));
+    }
+
+    #[test]
+    fn bearer_schemes_and_require_scheme() {
+        let cfg = extraction("{header_name: authorization, header_bearer: true, bearer_schemes: [Bearer, Token]}");
+        assert_eq!(from_bearer(&cfg, "token abc").as_deref(), Some("abc"));
+        assert_eq!(from_bearer(&cfg, "abc").as_deref(), Some("abc"));
+
+        let strict = extraction("{header_name: authorization, header_bearer: true, bearer_require_scheme: true}");
+        assert_eq!(from_bearer(&strict, "bearer abc").as_deref(), Some("abc"));
+        assert_eq!(from_bearer(&strict, "Basic abc"), None);
+        assert_eq!(from_bearer(&strict, "abc"), None);
+    }
+
+    #[test]
+    fn blank_bearer_tokens_are_ignored() {
+        let cfg = extraction("{header_name: authorization, header_bearer: true}");
+        for value in ["Bearer    ", "bearer \t", "Bearer", "", "   "] {
+            assert_eq!(from_bearer(&cfg, value), None, "{value:?}");
+        }
+        let strict = extraction("{header_name: authorization, header_bearer: true, bearer_require_scheme: true}");
+        assert_eq!(from_bearer(&strict, "Bearer "), None);
+    }
+}
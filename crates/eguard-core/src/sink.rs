@@ -0,0 +1,231 @@
+//! Streaming decisions to analytics pipelines. Unlike a [`DecisionLogger`], sinks
+//! are async and run off the request path: records are queued and handed to every
+//! sink in batches by a background task. Not available on wasm32.
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::DecisionRecord;
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::*;
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait DecisionSink: Send + Sync {
+    /// Delivers a batch of records. Errors are logged and the batch is not retried.
+    async fn emit(&self, records: &[DecisionRecord]) -> anyhow::Result<()>;
+}
+
+/// A sink from the `decision_sinks` config.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DecisionSinkConfig {
+    /// One JSON line per record on stdout.
+    Stdout,
+    /// POSTs each batch as a JSON array.
+    Http {
+        url: String,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+    },
+    /// Produces one message per record, keyed by session hash (requires the `kafka` feature).
+    Kafka {
+        brokers: Vec<String>,
+        topic: String,
+        #[serde(default)]
+        partition: i32,
+    },
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::sync::{
+        Arc, OnceLock,
+        atomic::{AtomicU64, Ordering::Relaxed},
+    };
+
+    use async_trait::async_trait;
+    use reqwest::Client;
+    use tokio::sync::mpsc;
+
+    use super::{BTreeMap, DecisionRecord, DecisionSink, DecisionSinkConfig};
+    use crate::{
+        audit::DecisionLogger,
+        redact::Redacted,
+        transport::{ProxyConfig, client_builder},
+    };
+
+    /// Records waiting for the background task; new records are dropped once it is full.
+    const QUEUE_CAPACITY: usize = 10_000;
+    /// Most records passed to `emit` at once.
+    const MAX_BATCH: usize = 500;
+
+    pub(crate) fn build(cfg: &DecisionSinkConfig, proxy: Option<&ProxyConfig>) -> anyhow::Result<Arc<dyn DecisionSink>> {
+        Ok(match cfg {
+            DecisionSinkConfig::Stdout => Arc::new(StdoutSink),
+            DecisionSinkConfig::Http { url, headers } => Arc::new(HttpSink::with_proxy(url, headers, proxy)?),
+            #[cfg(feature = "kafka")]
+            DecisionSinkConfig::Kafka { brokers, topic, partition } => {
+                Arc::new(kafka::KafkaSink::new(brokers.clone(), topic, *partition))
+            }
+            #[cfg(not(feature = "kafka"))]
+            DecisionSinkConfig::Kafka { .. } => {
+                return Err(anyhow::anyhow!("A kafka decision sink is set but eguard-core was built without the `kafka` feature"));
+            }
+        })
+    }
+
+    pub struct StdoutSink;
+
+    #[async_trait]
+    impl DecisionSink for StdoutSink {
+        async fn emit(&self, records: &[DecisionRecord]) -> anyhow::Result<()> {
+            let mut out = Vec::new();
+            for record in records {
+                serde_json::to_writer(&mut out, record)?;
+                out.push(b'\n');
+            }
+            tokio::task::spawn_blocking(move || std::io::Write::write_all(&mut std::io::stdout().lock(), &out)).await??;
+            Ok(())
+        }
+    }
+
+    pub struct HttpSink {
+        client: Client,
+        url: String,
+    }
+
+    impl HttpSink {
+        pub fn new(url: &str, headers: &BTreeMap<String, String>) -> anyhow::Result<Self> {
+            Self::with_proxy(url, headers, None)
+        }
+
+        pub fn with_proxy(url: &str, headers: &BTreeMap<String, String>, proxy: Option<&ProxyConfig>) -> anyhow::Result<Self> {
+            let headers = headers.iter()
+                .map(|(k, v)| Ok((k.parse()?, v.parse()?)))
+                .collect::<anyhow::Result<_>>()
+                .map_err(|e| anyhow::anyhow!("Invalid decision sink header: {}", e))?;
+            let client = client_builder(proxy)?.default_headers(headers).build()?;
+            Ok(Self { client, url: url.to_string() })
+        }
+    }
+
+    #[async_trait]
+    impl DecisionSink for HttpSink {
+        async fn emit(&self, records: &[DecisionRecord]) -> anyhow::Result<()> {
+            self.client.post(&self.url).json(records).send().await?.error_for_status()?;
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "kafka")]
+    mod kafka {
+        use async_trait::async_trait;
+        use rskafka::{
+            chrono::{TimeZone, Utc},
+            client::{
+                ClientBuilder,
+                partition::{Compression, PartitionClient, UnknownTopicHandling},
+            },
+            record::Record,
+        };
+        use tokio::sync::OnceCell;
+
+        use super::{DecisionRecord, DecisionSink};
+
+        /// Connects on the first batch, so building the guard never waits on the brokers.
+        pub(crate) struct KafkaSink {
+            brokers: Vec<String>,
+            topic: String,
+            partition: i32,
+            client: OnceCell<PartitionClient>,
+        }
+
+        impl KafkaSink {
+            pub(crate) fn new(brokers: Vec<String>, topic: &str, partition: i32) -> Self {
+                Self { brokers, topic: topic.to_string(), partition, client: OnceCell::new() }
+            }
+        }
+
+        #[async_trait]
+        impl DecisionSink for KafkaSink {
+            async fn emit(&self, records: &[DecisionRecord]) -> anyhow::Result<()> {
+                let client = self.client
+                    .get_or_try_init(|| async {
+                        let client = ClientBuilder::new(self.brokers.clone()).build().await?;
+                        client.partition_client(&self.topic, self.partition, UnknownTopicHandling::Retry).await
+                    })
+                    .await?;
+                let records = records.iter()
+                    .map(|r| {
+                        Ok(Record {
+                            key: Some(r.session_hash.clone().into_bytes()),
+                            value: Some(serde_json::to_vec(r)?),
+                            headers: Default::default(),
+                            timestamp: Utc.timestamp_millis_opt(r.timestamp_ms as i64).single().unwrap_or_default(),
+                        })
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                client.produce(records, Compression::NoCompression).await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Queues decisions for a set of sinks. It is a [`DecisionLogger`], so it can also be
+    /// passed to `EGuard::with_decision_logger` with custom sinks.
+    ///
+    /// The background task is started on the first decision, from inside the caller's
+    /// tokio runtime; decisions made outside a runtime are dropped.
+    pub struct SinkDispatcher {
+        sinks: Vec<Arc<dyn DecisionSink>>,
+        tx: OnceLock<mpsc::Sender<DecisionRecord>>,
+        dropped: AtomicU64,
+    }
+
+    impl SinkDispatcher {
+        pub fn new(sinks: Vec<Arc<dyn DecisionSink>>) -> Self {
+            Self { sinks, tx: OnceLock::new(), dropped: AtomicU64::new(0) }
+        }
+
+        /// Records discarded because the queue was full or no runtime was available.
+        pub fn dropped(&self) -> u64 {
+            self.dropped.load(Relaxed)
+        }
+
+        fn sender(&self) -> Option<&mpsc::Sender<DecisionRecord>> {
+            if let Some(tx) = self.tx.get() {
+                return Some(tx);
+            }
+            let handle = tokio::runtime::Handle::try_current().ok()?;
+            Some(self.tx.get_or_init(|| {
+                let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+                handle.spawn(run(rx, self.sinks.clone()));
+                tx
+            }))
+        }
+    }
+
+    impl DecisionLogger for SinkDispatcher {
+        fn log(&self, record: &DecisionRecord) {
+            let sent = self.sender().is_some_and(|tx| tx.try_send(record.clone()).is_ok());
+            if !sent {
+                self.dropped.fetch_add(1, Relaxed);
+            }
+        }
+    }
+
+    async fn run(mut rx: mpsc::Receiver<DecisionRecord>, sinks: Vec<Arc<dyn DecisionSink>>) {
+        let mut batch = Vec::with_capacity(MAX_BATCH);
+        while rx.recv_many(&mut batch, MAX_BATCH).await > 0 {
+            for sink in &sinks {
+                if let Err(e) = sink.emit(&batch).await {
+                    tracing::warn!(error = %Redacted(&e), records = batch.len(), "decision sink failed");
+                }
+            }
+            batch.clear();
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use sha2::{Digest, Sha256};
+
+/// Short, stable identifier for a session that is safe to put in span attributes.
+pub fn session_hash(session_id: &str) -> String {
+    let digest = Sha256::digest(session_id.as_bytes());
+    hex::encode(&digest[..8])
+}
+
+/// W3C trace context (`traceparent`, `tracestate`) for the current span, as
+/// produced by the globally installed OpenTelemetry propagator. Empty without
+/// the `otel` feature or when no propagator is installed.
+pub fn trace_context() -> Vec<(String, String)> {
+    #[cfg(feature = "otel")]
+    {
+        use std::collections::HashMap;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let cx = tracing::Span::current().context();
+        let mut carrier = HashMap::new();
+        opentelemetry::global::get_text_map_propagator(|p| p.inject_context(&cx, &mut carrier));
+        carrier.into_iter().collect()
+    }
+    #[cfg(not(feature = "otel"))]
+    {
+        Vec::new()
+    }
+}
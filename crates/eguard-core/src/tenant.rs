@@ -0,0 +1,74 @@
+//! Several customer apps behind one guard. Each tenant is the top-level config with
+//! its own overrides, and gets its own transport, cache and policy.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    EGuardConfig, RiskBand, SecureRoute,
+    routes::{host_matches, strip_port},
+    secrets::SecretString,
+};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TenantConfig {
+    /// Hosts served by this tenant (port ignored, case-insensitive). `*.example.com`
+    /// matches subdomains.
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    #[serde(default, skip_serializing)]
+    pub api_key: Option<SecretString>,
+    /// Only used together with `api_key`; the top-level `secondary_api_key` and
+    /// `secrets.api_key` aren't inherited when `api_key` is set.
+    #[serde(default, skip_serializing)]
+    pub secondary_api_key: Option<SecretString>,
+    /// Also drops the top-level `fallbacks`.
+    #[serde(default)]
+    pub api_base_url: Option<String>,
+    /// Replaces the top-level `secure_routes`.
+    #[serde(default)]
+    pub secure_routes: Option<Vec<SecureRoute>>,
+    /// Also drops the top-level `risk_bands`.
+    #[serde(default)]
+    pub min_trust_score: Option<f32>,
+    /// Replaces the top-level `risk_bands`.
+    #[serde(default)]
+    pub risk_bands: Option<Vec<RiskBand>>,
+}
+
+impl TenantConfig {
+    /// `base` with this tenant's overrides applied.
+    pub(crate) fn apply(&self, base: &EGuardConfig) -> EGuardConfig {
+        let mut cfg = base.clone();
+        cfg.tenants = BTreeMap::new();
+        cfg.tenant_header = None;
+        if let Some(key) = &self.api_key {
+            cfg.api_key = key.clone();
+            cfg.secondary_api_key = self.secondary_api_key.clone();
+            if let Some(secrets) = &mut cfg.secrets {
+                secrets.api_key = None;
+            }
+        }
+        if let Some(url) = &self.api_base_url {
+            cfg.api_base_url = url.clone();
+            cfg.fallbacks = Vec::new();
+        }
+        if let Some(routes) = &self.secure_routes {
+            cfg.secure_routes = routes.clone();
+        }
+        if let Some(score) = self.min_trust_score {
+            cfg.min_trust_score = Some(score);
+            cfg.risk_bands = Vec::new();
+        }
+        if let Some(bands) = &self.risk_bands {
+            cfg.risk_bands = bands.clone();
+        }
+        cfg
+    }
+
+    pub(crate) fn serves(&self, host: &str) -> bool {
+        let host = strip_port(host).to_ascii_lowercase();
+        self.hosts.iter().any(|h| host_matches(&h.to_ascii_lowercase(), &host))
+    }
+}
@@ -0,0 +1,15 @@
+//! Fixtures shared by the unit tests.
+
+use serde_yaml::Value;
+
+use crate::EGuardConfig;
+
+/// A config with just the required settings, overridden by the top-level keys in `yaml`.
+pub(crate) fn test_config(yaml: &str) -> EGuardConfig {
+    let base = "api_base_url: http://127.0.0.1:1\napi_key: k\nsecure_routes: []\nsession_extraction: {cookie_name: sid, header_bearer: false}\nmin_trust_score: 0.5";
+    let mut cfg: Value = serde_yaml::from_str(base).unwrap();
+    if let Value::Mapping(overrides) = serde_yaml::from_str(yaml).unwrap() {
+        cfg.as_mapping_mut().unwrap().extend(overrides);
+    }
+    serde_yaml::from_value(cfg).unwrap()
+}
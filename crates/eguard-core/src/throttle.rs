@@ -0,0 +1,97 @@
+use std::{sync::Mutex, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use web_time::{Instant, SystemTime};
+
+use crate::FailureMode;
+
+/// How to back off when the Trust API answers 429 Too Many Requests.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ThrottleConfig {
+    /// How long to stop calling the API after a 429 without a `Retry-After` header.
+    #[serde(default = "default_backoff_ms")]
+    pub default_backoff_ms: u64,
+    /// Upper bound on the back-off, whatever `Retry-After` asks for.
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// Decision to return while rate limited; defaults to `failure_mode`.
+    #[serde(default)]
+    pub fallback: Option<FailureMode>,
+}
+
+fn default_backoff_ms() -> u64 { 1000 }
+fn default_max_backoff_ms() -> u64 { 60_000 }
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self { default_backoff_ms: default_backoff_ms(), max_backoff_ms: default_max_backoff_ms(), fallback: None }
+    }
+}
+
+/// The Trust API answered 429, or a call was skipped because an earlier 429 asked us
+/// to wait. Holds how long the API asked to wait, if it said.
+#[derive(Debug)]
+pub struct Throttled(pub Option<Duration>);
+
+impl std::fmt::Display for Throttled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(wait) => write!(f, "Trust API is rate limiting; retry after {}ms", wait.as_millis()),
+            None => f.write_str("Trust API is rate limiting"),
+        }
+    }
+}
+
+impl std::error::Error for Throttled {}
+
+/// A `Retry-After` header value as a delay, from either delay-seconds or an HTTP date.
+pub fn retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = httpdate::parse_http_date(value).ok()?.duration_since(std::time::UNIX_EPOCH).ok()?;
+    let now = SystemTime::now().duration_since(web_time::UNIX_EPOCH).ok()?;
+    Some(at.saturating_sub(now))
+}
+
+/// Shared by every call of a guard, so one 429 pauses all of them.
+pub(crate) struct Throttle {
+    default_backoff: Duration,
+    max_backoff: Duration,
+    until: Mutex<Option<Instant>>,
+}
+
+impl Throttle {
+    pub(crate) fn new(cfg: &ThrottleConfig) -> Self {
+        Self {
+            default_backoff: Duration::from_millis(cfg.default_backoff_ms),
+            max_backoff: Duration::from_millis(cfg.max_backoff_ms),
+            until: Mutex::new(None),
+        }
+    }
+
+    /// `Throttled` with the time left if we're still backing off.
+    pub(crate) fn check(&self) -> Result<(), Throttled> {
+        let mut until = self.until.lock().unwrap();
+        match *until {
+            Some(at) if at > Instant::now() => Err(Throttled(Some(at - Instant::now()))),
+            Some(_) => {
+                *until = None;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Starts backing off after `err`, keeping whichever back-off ends later.
+    pub(crate) fn record(&self, err: &Throttled) {
+        let backoff = err.0.unwrap_or(self.default_backoff).min(self.max_backoff);
+        let at = Instant::now() + backoff;
+        let mut until = self.until.lock().unwrap();
+        if until.is_none_or(|current| current < at) {
+            tracing::warn!(backoff_ms = backoff.as_millis() as u64, "Trust API rate limited, backing off");
+            *until = Some(at);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use http::{HeaderMap, HeaderValue, Request, Response, StatusCode, header::{CONTENT_TYPE, HOST, LOCATION}};
+use serde_json::json;
+use tracing::Instrument;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{Decision, EGuard, RequestContext};
+
+/// `tower::Layer` that runs the secure-route, session and trust checks in front of
+/// any `http`-based service (hyper, axum, tonic, ...).
+///
+/// Rejections are JSON bodies, so the wrapped service's response body must be
+/// constructible from a `String`. Challenges are `302 Found` redirects to the
+/// challenge page, with the same details in the body. With `response_headers`
+/// configured, decisions are described in `X-EGuard-*` response headers, and with
+/// `deny_body` denials get its body instead.
+#[derive(Clone)]
+pub struct EGuardLayer {
+    guard: EGuard,
+}
+
+impl EGuardLayer {
+    pub fn new(guard: EGuard) -> Self {
+        Self { guard }
+    }
+}
+
+impl<S> Layer<S> for EGuardLayer {
+    type Service = EGuardService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        EGuardService { guard: self.guard.clone(), inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct EGuardService<S> {
+    guard: EGuard,
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for EGuardService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+    ResBody: From<String>,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        // The clone may not be ready; keep the one that was polled and leave the clone behind.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let guard = self.guard.clone();
+        let span = tracing::info_span!(
+            "eguard.check",
+            http.route = %req.uri().path(),
+            http.method = %req.method(),
+        );
+
+        Box::pin(async move {
+            let ctx = context(&req);
+            let guard = guard.for_request(&ctx);
+            if !guard.is_secure_host(ctx.host(), &ctx.path, &ctx.method) {
+                return inner.call(req).await;
+            }
+
+            let Some(sid) = session_id(guard, req.headers(), req.uri().query()) else {
+                return Ok(reject(StatusCode::UNAUTHORIZED, json!({ "error": "missing_session" })));
+            };
+            let ctx = ctx.with_device_id(device_id(guard, req.headers(), req.uri().query()));
+
+            let (decision, trust) = match guard.decide_request_with_trust(&ctx, &sid).await {
+                Ok(decided) => decided,
+                Err(_) => return Ok(reject(StatusCode::BAD_GATEWAY, json!({ "error": "trust_service_unavailable" }))),
+            };
+            let request_id = guard.request_id(Some(&ctx));
+            let headers = guard.response_headers(&decision, trust.as_ref(), &request_id);
+            let mut resp = match decision {
+                Decision::Allow { .. } => {
+                    if guard.config().response_headers.as_ref().is_some_and(|h| h.forward) {
+                        insert_headers(req.headers_mut(), &headers);
+                    }
+                    inner.call(req).await?
+                }
+                Decision::Deny { status, ref message } => {
+                    let code = StatusCode::from_u16(status).unwrap_or(StatusCode::FORBIDDEN);
+                    match guard.deny_body(&decision, trust.as_ref(), &request_id) {
+                        Some(body) => respond(code, body.content_type, body.body),
+                        None => reject(code, json!({ "error": "forbidden", "detail": message })),
+                    }
+                }
+                Decision::Challenge { kind, redirect_url } => {
+                    let body = json!({ "error": "challenge", "kind": kind, "redirect_url": redirect_url });
+                    let mut resp = reject(StatusCode::FOUND, body);
+                    if let Ok(location) = HeaderValue::from_str(&redirect_url) {
+                        resp.headers_mut().insert(LOCATION, location);
+                    }
+                    resp
+                }
+            };
+            insert_headers(resp.headers_mut(), &headers);
+            Ok(resp)
+        }.instrument(span))
+    }
+}
+
+/// Plain `tower` has no standard way to learn the peer address, so `ip` is left unset.
+fn context<B>(req: &Request<B>) -> RequestContext {
+    let headers = req.headers().iter().filter_map(|(k, v)| Some((k.as_str(), v.to_str().ok()?)));
+    let mut ctx = RequestContext::new(req.method().as_str(), req.uri().path()).with_headers(headers);
+    // HTTP/2 carries the host in the URI authority instead of a `Host` header.
+    if ctx.host().is_none()
+        && let Some(host) = req.uri().host()
+    {
+        ctx.headers.insert(HOST.as_str().to_string(), host.to_string());
+    }
+    ctx
+}
+
+fn session_id(guard: &EGuard, headers: &HeaderMap, query: Option<&str>) -> Option<String> {
+    let headers = headers.iter().filter_map(|(k, v)| Some((k.as_str(), v.to_str().ok()?)));
+    guard.extract_session_id_from_headers(headers, query)
+}
+
+fn device_id(guard: &EGuard, headers: &HeaderMap, query: Option<&str>) -> Option<String> {
+    let headers = headers.iter().filter_map(|(k, v)| Some((k.as_str(), v.to_str().ok()?)));
+    guard.extract_device_id_from_headers(headers, query)
+}
+
+fn insert_headers(map: &mut HeaderMap, headers: &[(&'static str, String)]) {
+    for (name, value) in headers {
+        if let Ok(value) = HeaderValue::from_str(value) {
+            map.insert(*name, value);
+        }
+    }
+}
+
+fn reject<B: From<String>>(status: StatusCode, body: serde_json::Value) -> Response<B> {
+    respond(status, "application/json", body.to_string())
+}
+
+fn respond<B: From<String>>(status: StatusCode, content_type: &'static str, body: String) -> Response<B> {
+    let mut resp = Response::new(B::from(body));
+    *resp.status_mut() = status;
+    resp.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    resp
+}
@@ -0,0 +1,544 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use reqwest::{
+    Client, Method, RequestBuilder, Response, StatusCode, Url,
+    header::{ACCEPT, CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{
+    ApiStatusError, ReasonCode, RequestContext, TrustResponse,
+    denylist::DenylistDelta,
+    dns::DnsConfig,
+    events::{Event, Outcome},
+    provider::{TrustProvider, unknown_session},
+    secrets::SecretString,
+    redact,
+    signing::{Signer, SigningConfig},
+    response::{self, ProtocolVersion, ResponseParsing},
+    telemetry,
+    throttle::{self, Throttled},
+    wire::WireFormat,
+};
+
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "grpc")]
+pub use grpc::GrpcTransport;
+
+/// Wire protocol used to reach the Trust API.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum TransportKind {
+    #[default]
+    Http,
+    /// `eguard.v1.TrustService` over gRPC (requires the `grpc` feature).
+    Grpc,
+}
+
+/// Outbound proxy for every HTTP request the guard makes (Trust API, JWKS, decision
+/// sinks). When set, `HTTP_PROXY`/`HTTPS_PROXY` from the environment are ignored.
+/// Not available on wasm32.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    /// `http://`, `https://`, `socks5://` or `socks5h://` (DNS resolved by the proxy).
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Never serialized.
+    #[serde(default, skip_serializing)]
+    pub password: Option<SecretString>,
+    /// Comma-separated hosts, domains and CIDRs reached directly, as in `NO_PROXY`.
+    #[serde(default)]
+    pub no_proxy: Option<String>,
+}
+
+/// Connection reuse for Trust API calls. Unset fields keep reqwest's defaults. Not
+/// available on wasm32.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PoolConfig {
+    /// Idle connections kept open per host; unlimited by default.
+    #[serde(default)]
+    pub max_idle_per_host: Option<usize>,
+    /// How long an idle connection is kept open (reqwest's default is 90s).
+    #[serde(default)]
+    pub idle_timeout_ms: Option<u64>,
+    /// Interval of TCP keepalive probes, which stop idle connections from being dropped
+    /// by NATs and load balancers.
+    #[serde(default)]
+    pub tcp_keepalive_ms: Option<u64>,
+    /// Speak HTTP/2 without negotiating it, for a plain `http://` Trust API known to
+    /// support it. Over TLS, HTTP/2 is negotiated anyway.
+    #[serde(default)]
+    pub http2_prior_knowledge: bool,
+    /// Interval of HTTP/2 PING frames on idle connections.
+    #[serde(default)]
+    pub http2_keep_alive_interval_ms: Option<u64>,
+}
+
+impl PoolConfig {
+    fn apply(&self, builder: reqwest::ClientBuilder) -> anyhow::Result<reqwest::ClientBuilder> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut builder = builder;
+            if let Some(max) = self.max_idle_per_host {
+                builder = builder.pool_max_idle_per_host(max);
+            }
+            if let Some(ms) = self.idle_timeout_ms {
+                builder = builder.pool_idle_timeout(Duration::from_millis(ms));
+            }
+            if let Some(ms) = self.tcp_keepalive_ms {
+                builder = builder.tcp_keepalive(Duration::from_millis(ms));
+            }
+            if self.http2_prior_knowledge {
+                builder = builder.http2_prior_knowledge();
+            }
+            if let Some(ms) = self.http2_keep_alive_interval_ms {
+                builder = builder.http2_keep_alive_interval(Duration::from_millis(ms)).http2_keep_alive_while_idle(true);
+            }
+            Ok(builder)
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = builder;
+            Err(anyhow::anyhow!("pool is not supported on wasm32"))
+        }
+    }
+}
+
+/// A client builder that goes through `proxy` when one is configured.
+pub(crate) fn client_builder(proxy: Option<&ProxyConfig>) -> anyhow::Result<reqwest::ClientBuilder> {
+    let builder = Client::builder();
+    let Some(cfg) = proxy else { return Ok(builder) };
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut proxy = reqwest::Proxy::all(&cfg.url)
+            .map_err(|e| anyhow::anyhow!("Invalid proxy url `{}`: {}", cfg.url, e))?;
+        if let Some(username) = &cfg.username {
+            proxy = proxy.basic_auth(username, cfg.password.as_ref().map_or("", SecretString::expose));
+        }
+        proxy = proxy.no_proxy(cfg.no_proxy.as_deref().and_then(reqwest::NoProxy::from_string));
+        Ok(builder.proxy(proxy))
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = cfg;
+        Err(anyhow::anyhow!("proxy is not supported on wasm32"))
+    }
+}
+
+/// Scheme of an `api_base_url` that names a Unix domain socket, for a Trust API running
+/// as a local sidecar: `unix:///var/run/eguard.sock`.
+pub const UNIX_SCHEME: &str = "unix://";
+
+/// A client for the Trust API at `base_url`, and the URL to put request paths after. A
+/// `unix://` URL connects every request to the socket, skipping TCP and TLS.
+pub(crate) fn api_client(
+    base_url: &str,
+    proxy: Option<&ProxyConfig>,
+    pool: Option<&PoolConfig>,
+    dns: Option<&DnsConfig>,
+) -> anyhow::Result<(Client, String)> {
+    let pooled = |builder| match pool {
+        Some(pool) => pool.apply(builder),
+        None => Ok(builder),
+    };
+    let Some(socket) = base_url.strip_prefix(UNIX_SCHEME) else {
+        let builder = pooled(client_builder(proxy)?)?;
+        let builder = match dns {
+            Some(dns) => dns.apply(builder, base_url)?,
+            None => builder,
+        };
+        return Ok((builder.build()?, base_url.to_string()));
+    };
+    if socket.is_empty() {
+        return Err(anyhow::anyhow!("api_base_url `{}` names no socket", base_url));
+    }
+    if proxy.is_some() {
+        return Err(anyhow::anyhow!("proxy can't be used with a unix:// api_base_url"));
+    }
+    #[cfg(unix)]
+    {
+        // The host only goes in the `Host` header.
+        Ok((pooled(Client::builder().unix_socket(socket))?.build()?, "http://localhost".to_string()))
+    }
+    #[cfg(not(unix))]
+    {
+        Err(anyhow::anyhow!("unix:// api_base_url is only supported on Unix"))
+    }
+}
+
+/// Changes each request the HTTP transport sends to the Trust API, e.g. to add a
+/// corporate auth header. Runs after eGuard's own headers and request signing.
+pub trait RequestMiddleware: Send + Sync {
+    fn on_request(&self, req: RequestBuilder) -> RequestBuilder;
+}
+
+/// A client and middleware supplied by the application for the HTTP transport.
+#[derive(Clone, Default)]
+pub(crate) struct HttpHooks {
+    pub client: Option<Client>,
+    pub middleware: Vec<Arc<dyn RequestMiddleware>>,
+}
+
+impl HttpHooks {
+    pub fn is_empty(&self) -> bool {
+        self.client.is_none() && self.middleware.is_empty()
+    }
+}
+
+/// The API keys a transport authenticates with. Requests use the primary key; when
+/// the Trust API rejects it, the request is repeated once with the secondary key, which
+/// then becomes the primary. If that is rejected too, requests stop switching keys
+/// until new ones are set or rotated in.
+pub struct ApiKeys {
+    keys: RwLock<Keys>,
+}
+
+struct Keys {
+    primary: Zeroizing<String>,
+    secondary: Option<Zeroizing<String>>,
+    /// The primary key is the former secondary one, swapped in by `fail_over`.
+    failed_over: bool,
+}
+
+impl ApiKeys {
+    pub fn new(primary: &str, secondary: Option<&str>) -> Self {
+        Self { keys: RwLock::new(Self::pair(primary, secondary)) }
+    }
+
+    pub fn primary(&self) -> Zeroizing<String> {
+        self.keys.read().unwrap().primary.clone()
+    }
+
+    /// Replaces both keys.
+    pub fn set(&self, primary: &str, secondary: Option<&str>) {
+        *self.keys.write().unwrap() = Self::pair(primary, secondary);
+    }
+
+    /// Makes `key` the primary key and keeps the current one as the secondary, so
+    /// requests keep working while the new key propagates.
+    pub fn rotate(&self, key: &str) {
+        let mut keys = self.keys.write().unwrap();
+        if *keys.primary != key {
+            redact::register_secret(key);
+            let old = std::mem::replace(&mut keys.primary, Zeroizing::new(key.to_string()));
+            // An empty key is a placeholder for one loaded from `secrets`.
+            if !old.is_empty() {
+                keys.secondary = Some(old);
+            }
+            keys.failed_over = false;
+        }
+    }
+
+    /// The key to retry with after `rejected` failed, if there is another one to try.
+    pub(crate) fn fail_over(&self, rejected: &str) -> Option<Zeroizing<String>> {
+        let mut keys = self.keys.write().unwrap();
+        if *keys.primary != rejected {
+            // Another request already switched keys.
+            return Some(keys.primary.clone());
+        }
+        if keys.failed_over {
+            // Both keys were rejected; swapping back would only repeat every request.
+            return None;
+        }
+        let secondary = keys.secondary.take()?;
+        tracing::warn!("primary API key rejected, failing over to the secondary key");
+        keys.secondary = Some(std::mem::replace(&mut keys.primary, secondary));
+        keys.failed_over = true;
+        Some(keys.primary.clone())
+    }
+
+    /// Registers both keys with `redact`, so they never show up in logs or errors.
+    fn pair(primary: &str, secondary: Option<&str>) -> Keys {
+        redact::register_secret(primary);
+        secondary.into_iter().for_each(redact::register_secret);
+        Keys {
+            primary: Zeroizing::new(primary.to_string()),
+            secondary: secondary.map(|key| Zeroizing::new(key.to_string())),
+            failed_over: false,
+        }
+    }
+}
+
+fn trace_headers() -> HeaderMap {
+    telemetry::trace_context().into_iter()
+        .filter_map(|(k, v)| Some((HeaderName::try_from(k).ok()?, HeaderValue::try_from(v).ok()?)))
+        .collect()
+}
+
+#[derive(Serialize)]
+struct ContextRequest<'a> {
+    sid: &'a str,
+    context: &'a RequestContext,
+}
+
+#[derive(Serialize)]
+struct BatchRequest<'a> {
+    sids: &'a [&'a str],
+}
+
+#[derive(Serialize)]
+struct EventsRequest<'a> {
+    events: &'a [Event],
+}
+
+#[derive(Serialize)]
+struct OutcomeRequest<'a> {
+    sid: &'a str,
+    outcome: Outcome,
+}
+
+#[derive(Serialize)]
+struct BanRequest<'a> {
+    sid: &'a str,
+    reason: &'a ReasonCode,
+}
+
+/// REST transport: `GET {api_base_url}/eguard/trust?sid=...`, or `POST` to the same
+/// path with `{"sid": ..., "context": {...}}` when there is a request context, and
+/// `POST {api_base_url}/eguard/trust/batch` with `{"sids": [...]}`. Events go to
+/// `POST {api_base_url}/eguard/events` as `{"events": [...]}`, and outcomes to
+/// `POST {api_base_url}/eguard/outcomes` as `{"sid": ..., "outcome": "fraud"}`. Bans
+/// are `POST {api_base_url}/eguard/ban` with `{"sid": ..., "reason": ...}`, and the
+/// denylist feed is `GET {api_base_url}/eguard/denylist?since=...`. Every request
+/// offers a protocol version in `x-eguard-protocol`, and trust responses are read in
+/// the version the API names in the same header (v1 if it names none).
+pub struct HttpTransport {
+    client: Client,
+    base_url: String,
+    keys: Arc<ApiKeys>,
+    timeout: Duration,
+    signer: Option<Arc<Signer>>,
+    parsing: ResponseParsing,
+    protocol: ProtocolVersion,
+    format: WireFormat,
+    middleware: Vec<Arc<dyn RequestMiddleware>>,
+}
+
+impl HttpTransport {
+    pub fn new(base_url: &str, api_key: &str, timeout: Duration) -> anyhow::Result<Self> {
+        Self::with_proxy(base_url, api_key, timeout, None)
+    }
+
+    pub fn with_proxy(base_url: &str, api_key: &str, timeout: Duration, proxy: Option<&ProxyConfig>) -> anyhow::Result<Self> {
+        let (client, base_url) = api_client(base_url, proxy, None, None)?;
+        Ok(Self::with_client(&base_url, api_key, timeout, client))
+    }
+
+    /// Sends requests with `client` as it is; it is not set up for a proxy or a
+    /// `unix://` URL.
+    pub fn with_client(base_url: &str, api_key: &str, timeout: Duration, client: Client) -> Self {
+        Self {
+            client,
+            base_url: base_url.into(),
+            keys: Arc::new(ApiKeys::new(api_key, None)),
+            timeout,
+            signer: None,
+            parsing: ResponseParsing::default(),
+            protocol: ProtocolVersion::default(),
+            format: WireFormat::default(),
+            middleware: Vec::new(),
+        }
+    }
+
+    /// Passes every request through `middleware`, after any added before.
+    pub fn with_middleware(mut self, middleware: Arc<dyn RequestMiddleware>) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    /// Reads responses as `parsing` says instead of leniently.
+    pub fn with_parsing(mut self, parsing: ResponseParsing) -> Self {
+        self.parsing = parsing;
+        self
+    }
+
+    /// Sends bodies in `format` and asks for responses in it instead of JSON.
+    pub fn with_format(mut self, format: WireFormat) -> anyhow::Result<Self> {
+        format.check()?;
+        self.format = format;
+        Ok(self)
+    }
+
+    /// Offers at most `protocol` instead of the newest version.
+    pub fn with_protocol(mut self, protocol: ProtocolVersion) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Authenticates with `keys` instead of a single fixed key.
+    pub fn with_keys(mut self, keys: Arc<ApiKeys>) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    /// Signs every request; see the `signing` module.
+    pub fn with_signing(self, cfg: &SigningConfig) -> anyhow::Result<Self> {
+        if cfg.key.is_empty() {
+            return Err(anyhow::anyhow!("request_signing.key must not be empty"));
+        }
+        Ok(self.with_signer(Arc::new(Signer::new(cfg)?)))
+    }
+
+    pub(crate) fn with_signer(mut self, signer: Arc<Signer>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Sends a request, repeating it with the secondary API key on 401.
+    async fn send(&self, method: Method, path: &str, query: &[(&str, &str)], body: Option<Vec<u8>>) -> anyhow::Result<Response> {
+        let key = self.keys.primary();
+        let resp = self.request(method.clone(), path, &key, query, body.clone()).send().await?;
+        if resp.status() == StatusCode::UNAUTHORIZED
+            && let Some(key) = self.keys.fail_over(&key)
+        {
+            return Ok(self.request(method, path, &key, query, body).send().await?);
+        }
+        Ok(resp)
+    }
+
+    /// `body` is already encoded in `format`, and the URL is built here, so the
+    /// signature covers the exact bytes and request target sent.
+    fn request(&self, method: Method, path: &str, key: &str, query: &[(&str, &str)], body: Option<Vec<u8>>) -> RequestBuilder {
+        let url = format!("{}{}", self.base_url, path);
+        let url = match query {
+            [] => Url::parse(&url),
+            query => Url::parse_with_params(&url, query),
+        };
+        let url = match url {
+            Ok(url) => url,
+            // Left to reqwest to report when the request is sent.
+            Err(_) => return self.client.request(method, format!("{}{}", self.base_url, path)),
+        };
+        let mut req = self.client
+            .request(method.clone(), url.clone())
+            .bearer_auth(key)
+            .timeout(self.timeout)
+            .header(response::PROTOCOL_HEADER, self.protocol.as_header())
+            .header(response::SDK_HEADER, response::SDK)
+            .header(ACCEPT, self.format.content_type())
+            .headers(trace_headers());
+        if let Some(signer) = &self.signer {
+            req = req.headers(signer.headers(&method, &url, body.as_deref().unwrap_or_default()));
+        }
+        if let Some(body) = body {
+            req = req.header(CONTENT_TYPE, self.format.content_type()).body(body);
+        }
+        self.middleware.iter().fold(req, |req, m| m.on_request(req))
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl TrustProvider for HttpTransport {
+    async fn fetch(&self, session_id: &str) -> anyhow::Result<TrustResponse> {
+        let resp = self.send(Method::GET, "/eguard/trust", &[("sid", session_id)], None).await?;
+        read_trust(resp, session_id, self.format, self.parsing).await
+    }
+
+    async fn fetch_with_context(&self, session_id: &str, ctx: &RequestContext) -> anyhow::Result<TrustResponse> {
+        let body = self.format.encode(&ContextRequest { sid: session_id, context: ctx })?;
+        let resp = self.send(Method::POST, "/eguard/trust", &[], Some(body)).await?;
+        read_trust(resp, session_id, self.format, self.parsing).await
+    }
+
+    async fn fetch_batch(&self, session_ids: &[&str]) -> anyhow::Result<Vec<TrustResponse>> {
+        let body = self.format.encode(&BatchRequest { sids: session_ids })?;
+        let resp = self.send(Method::POST, "/eguard/trust/batch", &[], Some(body)).await?;
+
+        if !resp.status().is_success() {
+            return Err(status_error(resp).await);
+        }
+
+        // Sessions the API doesn't know are omitted from `results`.
+        let (format, version) = (format(&resp, self.format), protocol(&resp));
+        let mut by_sid: HashMap<String, TrustResponse> = response::parse_batch(&resp.bytes().await?, format, self.parsing, version)?
+            .into_iter()
+            .map(|t| (t.session_id.clone(), t))
+            .collect();
+        Ok(session_ids.iter()
+            .map(|sid| by_sid.remove(*sid).unwrap_or_else(|| unknown_session(sid)))
+            .collect())
+    }
+
+    async fn report_events(&self, events: &[Event]) -> anyhow::Result<()> {
+        let body = self.format.encode(&EventsRequest { events })?;
+        let resp = self.send(Method::POST, "/eguard/events", &[], Some(body)).await?;
+        read_ack(resp).await
+    }
+
+    async fn report_outcome(&self, session_id: &str, outcome: Outcome) -> anyhow::Result<()> {
+        let body = self.format.encode(&OutcomeRequest { sid: session_id, outcome })?;
+        let resp = self.send(Method::POST, "/eguard/outcomes", &[], Some(body)).await?;
+        read_ack(resp).await
+    }
+
+    async fn revoke_session(&self, session_id: &str, reason: &ReasonCode) -> anyhow::Result<()> {
+        let body = self.format.encode(&BanRequest { sid: session_id, reason })?;
+        let resp = self.send(Method::POST, "/eguard/ban", &[], Some(body)).await?;
+        read_ack(resp).await
+    }
+
+    async fn fetch_denylist(&self, cursor: Option<&str>) -> anyhow::Result<DenylistDelta> {
+        let query: &[(&str, &str)] = match cursor {
+            Some(cursor) => &[("since", cursor)],
+            None => &[],
+        };
+        let resp = self.send(Method::GET, "/eguard/denylist", query, None).await?;
+        if !resp.status().is_success() {
+            return Err(status_error(resp).await);
+        }
+        let format = format(&resp, self.format);
+        format.decode(&resp.bytes().await?)
+    }
+}
+
+/// `Ok` for any success status; the body is ignored.
+async fn read_ack(resp: Response) -> anyhow::Result<()> {
+    if resp.status().is_success() {
+        return Ok(());
+    }
+    Err(status_error(resp).await)
+}
+
+async fn read_trust(resp: Response, session_id: &str, format: WireFormat, parsing: ResponseParsing) -> anyhow::Result<TrustResponse> {
+    if resp.status().is_success() {
+        let (format, version) = (self::format(&resp, format), protocol(&resp));
+        response::parse_trust(&resp.bytes().await?, format, session_id, parsing, version)
+    } else if resp.status() == StatusCode::NOT_FOUND {
+        Ok(unknown_session(session_id))
+    } else {
+        Err(status_error(resp).await)
+    }
+}
+
+/// The format the response's `Content-Type` names, or `requested` if it names none we
+/// know, so an API that answers in JSON anyway is still understood.
+fn format(resp: &Response, requested: WireFormat) -> WireFormat {
+    resp.headers().get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(WireFormat::from_content_type)
+        .unwrap_or(requested)
+}
+
+/// The protocol version the Trust API answered in.
+fn protocol(resp: &Response) -> ProtocolVersion {
+    ProtocolVersion::from_header(resp.headers().get(response::PROTOCOL_HEADER).and_then(|v| v.to_str().ok()))
+}
+
+/// `Throttled` for a 429, otherwise `ApiStatusError`.
+async fn status_error(resp: Response) -> anyhow::Error {
+    let status = resp.status();
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let wait = resp.headers().get(RETRY_AFTER).and_then(|v| v.to_str().ok()).and_then(throttle::retry_after);
+        return Throttled(wait).into();
+    }
+    let body = resp.text().await.unwrap_or_default();
+    ApiStatusError { status, body }.into()
+}
@@ -0,0 +1,185 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use http::uri::PathAndQuery;
+use tonic::{
+    Code, Request,
+    client::Grpc,
+    metadata::{Ascii, MetadataKey, MetadataValue},
+    transport::{Channel, ClientTlsConfig, Endpoint},
+};
+use tonic_prost::ProstCodec;
+
+use super::ApiKeys;
+use crate::{
+    ReasonCode, RequestContext, TrustResponse,
+    provider::{TrustProvider, unknown_session},
+    telemetry,
+    throttle::{self, Throttled},
+};
+
+// Hand-written mirror of proto/eguard/v1/trust.proto.
+#[derive(Clone, PartialEq, prost::Message)]
+struct TrustRequest {
+    #[prost(string, tag = "1")]
+    session_id: String,
+    #[prost(message, optional, tag = "2")]
+    context: Option<Context>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Context {
+    #[prost(string, optional, tag = "1")]
+    ip: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    user_agent: Option<String>,
+    #[prost(btree_map = "string, string", tag = "3")]
+    headers: BTreeMap<String, String>,
+    #[prost(string, tag = "4")]
+    path: String,
+    #[prost(string, tag = "5")]
+    method: String,
+    #[prost(string, optional, tag = "6")]
+    country: Option<String>,
+    #[prost(uint32, optional, tag = "7")]
+    asn: Option<u32>,
+    #[prost(string, optional, tag = "8")]
+    as_org: Option<String>,
+    #[prost(string, optional, tag = "9")]
+    device_id: Option<String>,
+    #[prost(string, optional, tag = "10")]
+    ja3: Option<String>,
+    #[prost(string, optional, tag = "11")]
+    ja4: Option<String>,
+}
+
+impl From<&RequestContext> for Context {
+    fn from(ctx: &RequestContext) -> Self {
+        Self {
+            ip: ctx.ip.map(|ip| ip.to_string()),
+            user_agent: ctx.user_agent.clone(),
+            headers: ctx.headers.clone(),
+            path: ctx.path.clone(),
+            method: ctx.method.clone(),
+            country: ctx.geo.as_ref().and_then(|g| g.country.clone()),
+            asn: ctx.geo.as_ref().and_then(|g| g.asn),
+            as_org: ctx.geo.as_ref().and_then(|g| g.as_org.clone()),
+            device_id: ctx.device_id.clone(),
+            ja3: ctx.ja3.clone(),
+            ja4: ctx.ja4.clone(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct TrustReply {
+    #[prost(string, tag = "1")]
+    session_id: String,
+    #[prost(float, tag = "2")]
+    trust_score: f32,
+    #[prost(string, optional, tag = "3")]
+    reason: Option<String>,
+    #[prost(float, optional, tag = "4")]
+    bot_score: Option<f32>,
+    #[prost(float, optional, tag = "5")]
+    fraud_score: Option<f32>,
+    #[prost(float, optional, tag = "6")]
+    abuse_score: Option<f32>,
+}
+
+const GET_TRUST: &str = "/eguard.v1.TrustService/GetTrust";
+
+/// gRPC transport. `api_base_url` is the channel endpoint, e.g. `https://trust.example.com:443`.
+/// The connection is established lazily on the first call.
+pub struct GrpcTransport {
+    channel: Channel,
+    keys: Arc<ApiKeys>,
+    timeout: Duration,
+}
+
+impl GrpcTransport {
+    pub fn new(endpoint: &str, api_key: &str, timeout: Duration) -> anyhow::Result<Self> {
+        let mut ep = Endpoint::from_shared(endpoint.to_string())
+            .map_err(|e| anyhow::anyhow!("Invalid gRPC endpoint {}: {}", endpoint, e))?
+            .connect_timeout(timeout);
+        if endpoint.starts_with("https://") {
+            ep = ep.tls_config(ClientTlsConfig::new().with_webpki_roots())?;
+        }
+        authorization(api_key)?;
+        Ok(Self { channel: ep.connect_lazy(), keys: Arc::new(ApiKeys::new(api_key, None)), timeout })
+    }
+
+    /// Authenticates with `keys` instead of a single fixed key.
+    pub fn with_keys(mut self, keys: Arc<ApiKeys>) -> Self {
+        self.keys = keys;
+        self
+    }
+}
+
+fn authorization(api_key: &str) -> anyhow::Result<MetadataValue<Ascii>> {
+    format!("Bearer {api_key}").parse()
+        .map_err(|_| anyhow::anyhow!("api_key is not valid gRPC metadata"))
+}
+
+impl GrpcTransport {
+    /// Calls `GetTrust`, repeating the call with the secondary API key on `UNAUTHENTICATED`.
+    async fn get_trust(&self, request: TrustRequest) -> anyhow::Result<TrustResponse> {
+        let key = self.keys.primary();
+        match self.call(request.clone(), &key).await {
+            Err(e) if e.downcast_ref::<tonic::Status>().is_some_and(|s| s.code() == Code::Unauthenticated) => {
+                match self.keys.fail_over(&key) {
+                    Some(key) => self.call(request, &key).await,
+                    None => Err(e),
+                }
+            }
+            result => result,
+        }
+    }
+
+    async fn call(&self, request: TrustRequest, key: &str) -> anyhow::Result<TrustResponse> {
+        let session_id = request.session_id.clone();
+        let mut grpc = Grpc::new(self.channel.clone());
+        grpc.ready().await?;
+
+        let mut req = Request::new(request);
+        req.set_timeout(self.timeout);
+        req.metadata_mut().insert("authorization", authorization(key)?);
+        for (k, v) in telemetry::trace_context() {
+            if let (Ok(k), Ok(v)) = (k.parse::<MetadataKey<Ascii>>(), v.parse()) {
+                req.metadata_mut().insert(k, v);
+            }
+        }
+
+        let codec = ProstCodec::<TrustRequest, TrustReply>::default();
+        match grpc.unary(req, PathAndQuery::from_static(GET_TRUST), codec).await {
+            Ok(resp) => {
+                let reply = resp.into_inner();
+                Ok(TrustResponse {
+                    session_id: reply.session_id,
+                    trust_score: reply.trust_score,
+                    reason: reply.reason.map(ReasonCode::from),
+                    bot_score: reply.bot_score,
+                    fraud_score: reply.fraud_score,
+                    abuse_score: reply.abuse_score,
+                })
+            }
+            Err(status) if status.code() == Code::NotFound => Ok(unknown_session(&session_id)),
+            Err(status) if status.code() == Code::ResourceExhausted => {
+                let wait = status.metadata().get("retry-after").and_then(|v| v.to_str().ok()).and_then(throttle::retry_after);
+                Err(Throttled(wait).into())
+            }
+            Err(status) => Err(status.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl TrustProvider for GrpcTransport {
+    async fn fetch(&self, session_id: &str) -> anyhow::Result<TrustResponse> {
+        self.get_trust(TrustRequest { session_id: session_id.into(), context: None }).await
+    }
+
+    async fn fetch_with_context(&self, session_id: &str, ctx: &RequestContext) -> anyhow::Result<TrustResponse> {
+        self.get_trust(TrustRequest { session_id: session_id.into(), context: Some(ctx.into()) }).await
+    }
+}
@@ -0,0 +1,145 @@
+//! Score updates pushed by the Trust API.
+//!
+//! Requests carry `X-EGuard-Timestamp` (unix seconds) and
+//! `X-EGuard-Signature: sha256=<hex>`, where the digest is
+//! `HMAC-SHA256(webhook_secret, "{timestamp}.{body}")`.
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use crate::{ReasonCode, TrustResponse};
+
+pub const TIMESTAMP_HEADER: &str = "x-eguard-timestamp";
+pub const SIGNATURE_HEADER: &str = "x-eguard-signature";
+
+/// Deliveries older (or newer) than this are rejected as replays.
+const TOLERANCE_SECS: u64 = 300;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    ScoreUpdated {
+        session_id: String,
+        trust_score: f32,
+        reason: Option<ReasonCode>,
+    },
+    SessionBanned {
+        session_id: String,
+        reason: Option<ReasonCode>,
+    },
+}
+
+impl WebhookEvent {
+    /// The cache entry this event implies. Bans are stored as a zero score.
+    pub fn trust(&self) -> TrustResponse {
+        match self {
+            WebhookEvent::ScoreUpdated { session_id, trust_score, reason } => TrustResponse {
+                session_id: session_id.clone(),
+                trust_score: *trust_score,
+                reason: reason.clone(),
+                ..Default::default()
+            },
+            WebhookEvent::SessionBanned { session_id, reason } => TrustResponse {
+                session_id: session_id.clone(),
+                trust_score: 0.0,
+                reason: Some(reason.clone().unwrap_or(ReasonCode::SessionBanned)),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn session_id(&self) -> &str {
+        match self {
+            WebhookEvent::ScoreUpdated { session_id, .. } | WebhookEvent::SessionBanned { session_id, .. } => session_id,
+        }
+    }
+}
+
+/// Checks the signature and freshness of a delivery and parses its body.
+pub fn verify(secret: &str, body: &[u8], timestamp: &str, signature: &str) -> anyhow::Result<WebhookEvent> {
+    let ts: u64 = timestamp.trim().parse()
+        .map_err(|_| anyhow::anyhow!("Invalid webhook timestamp"))?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    if now.abs_diff(ts) > TOLERANCE_SECS {
+        return Err(anyhow::anyhow!("Webhook timestamp outside tolerance"));
+    }
+
+    let sig = signature.trim().strip_prefix("sha256=")
+        .and_then(|h| hex::decode(h).ok())
+        .ok_or_else(|| anyhow::anyhow!("Malformed webhook signature"))?;
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+    mac.update(timestamp.trim().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac.verify_slice(&sig).map_err(|_| anyhow::anyhow!("Webhook signature mismatch"))?;
+
+    Ok(serde_json::from_slice(body)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "whsec";
+    const BODY: &[u8] = br#"{"type":"session_banned","session_id":"s1","reason":null}"#;
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    fn sign(secret: &str, timestamp: &str, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{timestamp}.").as_bytes());
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn accepts_a_valid_delivery() {
+        let ts = now().to_string();
+        let event = verify(SECRET, BODY, &ts, &sign(SECRET, &ts, BODY)).unwrap();
+        assert_eq!(event.session_id(), "s1");
+        assert_eq!(event.trust().trust_score, 0.0);
+    }
+
+    #[test]
+    fn accepts_timestamps_within_tolerance() {
+        for ts in [now() - TOLERANCE_SECS + 5, now() + TOLERANCE_SECS - 5] {
+            let ts = ts.to_string();
+            assert!(verify(SECRET, BODY, &ts, &sign(SECRET, &ts, BODY)).is_ok());
+        }
+    }
+
+    #[test]
+    fn rejects_timestamps_outside_tolerance() {
+        for ts in [now() - TOLERANCE_SECS - 5, now() + TOLERANCE_SECS + 5] {
+            let ts = ts.to_string();
+            let err = verify(SECRET, BODY, &ts, &sign(SECRET, &ts, BODY)).unwrap_err();
+            assert_eq!(err.to_string(), "Webhook timestamp outside tolerance");
+        }
+        assert_eq!(verify(SECRET, BODY, "soon", "sha256=00").unwrap_err().to_string(), "Invalid webhook timestamp");
+    }
+
+    #[test]
+    fn rejects_signature_mismatch() {
+        let ts = now().to_string();
+        let mismatch = |secret: &str, signed_ts: &str, body: &[u8]| {
+            verify(SECRET, BODY, &ts, &sign(secret, signed_ts, body)).unwrap_err().to_string()
+        };
+        assert_eq!(mismatch("other", &ts, BODY), "Webhook signature mismatch");
+        assert_eq!(mismatch(SECRET, &(now() - 1).to_string(), BODY), "Webhook signature mismatch");
+        assert_eq!(mismatch(SECRET, &ts, b"{}"), "Webhook signature mismatch");
+    }
+
+    #[test]
+    fn rejects_malformed_signature() {
+        let ts = now().to_string();
+        let hex = sign(SECRET, &ts, BODY).trim_start_matches("sha256=").to_string();
+        for signature in [hex.as_str(), "sha256=zz", "sha1=00"] {
+            assert_eq!(verify(SECRET, BODY, &ts, signature).unwrap_err().to_string(), "Malformed webhook signature");
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Encodings of Trust API request and response bodies.
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+/// Body encoding for the Trust API. The binary formats are smaller and faster to parse
+/// than JSON, which matters on high-throughput gateways.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WireFormat {
+    #[default]
+    Json,
+    /// Requires the `cbor` feature.
+    Cbor,
+    /// Requires the `msgpack` feature.
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+impl WireFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Cbor => "application/cbor",
+            Self::MessagePack => "application/msgpack",
+        }
+    }
+
+    /// The format named by a `Content-Type` header, ignoring parameters like `charset`.
+    pub fn from_content_type(value: &str) -> Option<Self> {
+        match value.split(';').next()?.trim() {
+            "application/json" => Some(Self::Json),
+            "application/cbor" => Some(Self::Cbor),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(Self::MessagePack),
+            _ => None,
+        }
+    }
+
+    /// `Err` if the feature the format needs is off.
+    pub fn check(self) -> anyhow::Result<()> {
+        match self {
+            #[cfg(not(feature = "cbor"))]
+            Self::Cbor => Err(missing("cbor")),
+            #[cfg(not(feature = "msgpack"))]
+            Self::MessagePack => Err(missing("msgpack")),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Json => Ok(serde_json::to_vec(value)?),
+            #[cfg(feature = "cbor")]
+            Self::Cbor => {
+                let mut body = Vec::new();
+                ciborium::into_writer(value, &mut body)?;
+                Ok(body)
+            }
+            // Structs as maps, so the API sees field names as in JSON.
+            #[cfg(feature = "msgpack")]
+            Self::MessagePack => Ok(rmp_serde::to_vec_named(value)?),
+            #[cfg(not(feature = "cbor"))]
+            Self::Cbor => Err(missing("cbor")),
+            #[cfg(not(feature = "msgpack"))]
+            Self::MessagePack => Err(missing("msgpack")),
+        }
+    }
+
+    /// Errors are `serde_json::Error`s whatever the format, so they're all reported as
+    /// deserialization errors.
+    pub fn decode<T: DeserializeOwned>(self, body: &[u8]) -> anyhow::Result<T> {
+        match self {
+            Self::Json => Ok(serde_json::from_slice(body)?),
+            #[cfg(feature = "cbor")]
+            Self::Cbor => ciborium::from_reader(body).map_err(|e| invalid(self, e)),
+            #[cfg(feature = "msgpack")]
+            Self::MessagePack => rmp_serde::from_slice(body).map_err(|e| invalid(self, e)),
+            #[cfg(not(feature = "cbor"))]
+            Self::Cbor => Err(missing("cbor")),
+            #[cfg(not(feature = "msgpack"))]
+            Self::MessagePack => Err(missing("msgpack")),
+        }
+    }
+}
+
+#[cfg(not(all(feature = "cbor", feature = "msgpack")))]
+fn missing(feature: &str) -> anyhow::Error {
+    anyhow::anyhow!("wire_format is {0} but eguard-core was built without the `{0}` feature", feature)
+}
+
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+fn invalid(format: WireFormat, err: impl std::fmt::Display) -> anyhow::Error {
+    use serde::de::Error as _;
+    serde_json::Error::custom(format_args!("Invalid {:?} body: {}", format, err)).into()
+}
@@ -0,0 +1,21 @@
+//! Fixtures shared by the integration tests.
+
+use eguard_core::{EGuard, EGuardConfig};
+use eguard_testing::MockTrustApi;
+use serde_yaml::Value;
+
+/// A guard calling `api`, with just the required settings overridden by the
+/// top-level keys in `yaml`.
+pub fn guard(api: &MockTrustApi, yaml: &str) -> EGuard {
+    EGuard::new(config(&api.url(), yaml)).unwrap()
+}
+
+pub fn config(api_base_url: &str, yaml: &str) -> EGuardConfig {
+    let base = "api_key: k\nsecure_routes: []\nsession_extraction: {cookie_name: sid, header_bearer: false}\nmin_trust_score: 0.5";
+    let mut cfg: Value = serde_yaml::from_str(base).unwrap();
+    cfg["api_base_url"] = api_base_url.into();
+    if let Value::Mapping(overrides) = serde_yaml::from_str(yaml).unwrap() {
+        cfg.as_mapping_mut().unwrap().extend(overrides);
+    }
+    serde_yaml::from_value(cfg).unwrap()
+}
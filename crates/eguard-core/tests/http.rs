@@ -0,0 +1,57 @@
+//! `EGuard::decide_http`, the whole-request pipeline the bindings share, and the flat
+//! shape its decisions serialize to.
+
+mod common;
+
+use common::guard;
+use eguard_core::Decision;
+use eguard_testing::MockTrustApi;
+use serde_json::json;
+
+const ROUTES: &str = "secure_routes: [{path_pattern: ^/checkout}]";
+
+#[tokio::test]
+async fn requests_are_routed_before_the_trust_check() {
+    let api = MockTrustApi::start().await.unwrap();
+    let guard = guard(&api, ROUTES);
+
+    let decided = guard.decide_http("GET", "/home", &[("cookie", "sid=s1")], None).await;
+    assert_eq!(serde_json::to_value(&decided).unwrap(), json!({ "matched": false, "allow": true }));
+    let decided = guard.decide_http("POST", "/checkout", &[], None).await;
+    assert_eq!(
+        serde_json::to_value(&decided).unwrap(),
+        json!({ "matched": true, "allow": false, "status": 401, "message": "Missing session" }),
+    );
+    assert_eq!(api.request_count(), 0);
+}
+
+#[tokio::test]
+async fn matched_requests_are_decided_with_their_session() {
+    let api = MockTrustApi::start().await.unwrap();
+    api.set_score("s1", 0.9);
+    api.set_score("s2", 0.1);
+    let guard = guard(&api, ROUTES);
+
+    let decided = guard.decide_http("POST", "/checkout?step=2", &[("cookie", "sid=s1")], None).await;
+    assert_eq!(
+        serde_json::to_value(&decided).unwrap(),
+        json!({ "matched": true, "allow": true, "score": 0.9f32, "session_id": "s1" }),
+    );
+    let decided = guard.decide_http("POST", "/checkout", &[("cookie", "sid=s2")], None).await;
+    assert!(matches!(decided.decision, Decision::Deny { status: 403, .. }));
+    assert_eq!(decided.session_id.as_deref(), Some("s2"));
+}
+
+#[tokio::test]
+async fn trust_api_failures_are_answered_with_502() {
+    let api = MockTrustApi::start().await.unwrap();
+    api.fail_with(Some(503));
+    let guard = guard(&api, ROUTES);
+
+    let decided = guard.decide_http("POST", "/checkout", &[("cookie", "sid=s1")], None).await;
+    assert_eq!(decided.error.as_ref().map(|e| e.kind()), Some("ApiStatus"));
+    assert_eq!(
+        serde_json::to_value(&decided).unwrap(),
+        json!({ "matched": true, "allow": false, "status": 502, "message": "Trust service unavailable" }),
+    );
+}
@@ -0,0 +1,30 @@
+//! Privacy mode end to end: the Trust API only ever sees hashed session ids.
+
+mod common;
+
+use common::guard;
+use eguard_core::RequestContext;
+use eguard_testing::MockTrustApi;
+
+#[tokio::test]
+async fn raw_session_ids_are_not_forwarded() {
+    let api = MockTrustApi::start().await.unwrap();
+    api.set_default_score(Some(0.9));
+    let guard = guard(&api, r#"
+session_hashing: {salt: pepper}
+session_extraction: {sources: ["header:x-session", "bearer:authorization", "query:sid"], device_sources: ["header:x-device"], header_bearer: false}
+"#);
+    let ctx = RequestContext::new("GET", "/orders?page=2&sid=raw-sid-1")
+        .with_headers([("X-Session", "raw-sid-1"), ("X-Device", "raw-device-1"), ("User-Agent", "test")])
+        .with_device_id(Some("raw-device-1".into()));
+
+    guard.decide_request(&ctx, "raw-sid-1").await.unwrap();
+    let received = api.received();
+    assert_eq!(received.len(), 1);
+    assert_ne!(received[0].session_ids, ["raw-sid-1"]);
+    let sent = received[0].context.clone().unwrap();
+    assert_eq!(sent.path, "/orders?page=2");
+    assert_eq!(sent.user_agent.as_deref(), Some("test"));
+    assert!(!serde_json::to_string(&sent).unwrap().contains("raw-sid-1"));
+    assert!(!sent.headers.contains_key("x-device"));
+}
@@ -0,0 +1,50 @@
+//! Concurrent lookups of one session share a single Trust API call.
+
+mod common;
+
+use std::time::Duration;
+
+use common::guard;
+use eguard_core::{Decision, EGuard, EGuardError};
+use eguard_testing::MockTrustApi;
+
+/// Decides `session_id` from `n` tasks at once.
+async fn decide_concurrently(guard: &EGuard, session_id: &'static str, n: usize) -> Vec<Result<Decision, EGuardError>> {
+    let tasks: Vec<_> = (0..n)
+        .map(|_| {
+            let guard = guard.clone();
+            tokio::spawn(async move { guard.decide(session_id).await })
+        })
+        .collect();
+    let mut results = Vec::new();
+    for task in tasks {
+        results.push(task.await.unwrap());
+    }
+    results
+}
+
+#[tokio::test]
+async fn concurrent_decides_share_one_lookup() {
+    let api = MockTrustApi::start().await.unwrap();
+    api.set_score("s1", 0.9);
+    api.set_latency(Duration::from_millis(100));
+    let guard = guard(&api, "{}");
+
+    for result in decide_concurrently(&guard, "s1", 8).await {
+        assert!(matches!(result.unwrap(), Decision::Allow { trust_score: Some(_), .. }));
+    }
+    assert_eq!(api.request_count(), 1);
+}
+
+#[tokio::test]
+async fn every_waiter_gets_the_same_error() {
+    let api = MockTrustApi::start().await.unwrap();
+    api.fail_with(Some(503));
+    api.set_latency(Duration::from_millis(100));
+    let guard = guard(&api, "{}");
+
+    for result in decide_concurrently(&guard, "s1", 8).await {
+        assert_eq!(result.unwrap_err().kind(), "ApiStatus");
+    }
+    assert_eq!(api.request_count(), 1);
+}
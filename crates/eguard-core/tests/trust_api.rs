@@ -0,0 +1,129 @@
+//! The Trust API path end to end, against the mock: failure modes, retries, the
+//! circuit breaker, 429 back-off, endpoint and API key failover, and revocation.
+
+mod common;
+
+use common::guard;
+use eguard_core::{Decision, ReasonCode};
+use eguard_testing::MockTrustApi;
+
+#[tokio::test]
+async fn failure_mode_decides_when_the_api_fails() {
+    let api = MockTrustApi::start().await.unwrap();
+    api.fail_with(Some(503));
+
+    let err = guard(&api, "{}").decide("s1").await.unwrap_err();
+    assert_eq!(err.kind(), "ApiStatus");
+    let decision = guard(&api, "failure_mode: FailOpen").decide("s1").await.unwrap();
+    assert!(matches!(decision, Decision::Allow { trust_score: None, .. }));
+    let decision = guard(&api, "failure_mode: FailClosed").decide("s1").await.unwrap();
+    assert!(matches!(decision, Decision::Deny { status: 503, .. }));
+    let decision = guard(&api, "failure_mode: !Custom {status: 429, message: busy}").decide("s1").await.unwrap();
+    assert!(matches!(decision, Decision::Deny { status: 429, ref message } if message == "busy"));
+}
+
+#[tokio::test]
+async fn transient_failures_are_retried() {
+    let api = MockTrustApi::start().await.unwrap();
+    api.set_score("s1", 0.9);
+    let guard = guard(&api, "retry: {max_attempts: 3, base_delay_ms: 1, max_delay_ms: 1, jitter: false}");
+
+    api.fail_next(2, 503);
+    assert!(matches!(guard.decide("s1").await.unwrap(), Decision::Allow { .. }));
+    assert_eq!(api.request_count(), 3);
+
+    api.fail_next(1, 400);
+    assert_eq!(guard.decide("s1").await.unwrap_err().kind(), "ApiStatus");
+    assert_eq!(api.request_count(), 4);
+}
+
+#[tokio::test]
+async fn open_circuit_skips_the_api() {
+    let api = MockTrustApi::start().await.unwrap();
+    api.set_score("s1", 0.9);
+    api.fail_with(Some(503));
+    let guard = guard(&api, "circuit_breaker: {failure_threshold: 2, open_ms: 60000}");
+
+    for _ in 0..2 {
+        assert_eq!(guard.decide("s1").await.unwrap_err().kind(), "ApiStatus");
+    }
+    api.fail_with(None);
+    let decision = guard.decide("s1").await.unwrap();
+    assert!(matches!(decision, Decision::Deny { status: 503, .. }));
+    assert_eq!(api.request_count(), 2);
+}
+
+#[tokio::test]
+async fn throttled_calls_back_off() {
+    let api = MockTrustApi::start().await.unwrap();
+    api.set_score("s1", 0.9);
+    let guard = guard(&api, "throttle: {default_backoff_ms: 60000, fallback: FailOpen}");
+
+    api.fail_next(1, 429);
+    for _ in 0..2 {
+        let decision = guard.decide("s1").await.unwrap();
+        assert!(matches!(decision, Decision::Allow { trust_score: None, .. }));
+    }
+    assert_eq!(api.request_count(), 1);
+    assert_eq!(guard.metrics().api_throttled, 2);
+}
+
+#[tokio::test]
+async fn failing_endpoint_is_skipped() {
+    let down = MockTrustApi::start().await.unwrap();
+    down.fail_with(Some(503));
+    let up = MockTrustApi::start().await.unwrap();
+    up.set_score("s1", 0.9);
+    let yaml = format!("endpoints: {{api_base_urls: ['{}'], unhealthy_after: 1, retry_after_ms: 60000}}", up.url());
+    let guard = guard(&down, &yaml);
+
+    for _ in 0..2 {
+        assert!(matches!(guard.decide("s1").await.unwrap(), Decision::Allow { trust_score: Some(_), .. }));
+    }
+    assert_eq!(guard.unhealthy_endpoints(), [down.url()]);
+    assert_eq!(down.request_count(), 1);
+    assert_eq!(up.request_count(), 2);
+}
+
+#[tokio::test]
+async fn rejected_keys_fail_over_once() {
+    let api = MockTrustApi::start().await.unwrap();
+    api.set_score("s1", 0.9);
+    api.require_api_key(Some("new"));
+    let guard = guard(&api, "secondary_api_key: k2");
+
+    assert_eq!(guard.decide("s1").await.unwrap_err().kind(), "ApiStatus");
+    assert_eq!(guard.decide("s1").await.unwrap_err().kind(), "ApiStatus");
+    let keys: Vec<_> = api.received().into_iter().map(|r| r.api_key.unwrap()).collect();
+    assert_eq!(keys, ["k", "k2", "k2"]);
+
+    guard.rotate_key("new");
+    assert!(matches!(guard.decide("s1").await.unwrap(), Decision::Allow { .. }));
+}
+
+#[tokio::test]
+async fn revoked_session_is_denied_locally() {
+    let api = MockTrustApi::start().await.unwrap();
+    api.set_score("s1", 0.9);
+    let guard = guard(&api, "{}");
+    assert!(matches!(guard.decide("s1").await.unwrap(), Decision::Allow { .. }));
+
+    api.fail_with(Some(503));
+    assert!(guard.revoke_session("s1", ReasonCode::SessionBanned).await.is_err());
+    let requests = api.request_count();
+    assert!(matches!(guard.decide("s1").await.unwrap(), Decision::Deny { status: 403, .. }));
+    assert_eq!(api.request_count(), requests);
+}
+
+#[tokio::test]
+async fn bans_are_enforced_outside_the_rollout() {
+    let api = MockTrustApi::start().await.unwrap();
+    api.set_score("s1", 0.1);
+    let guard = guard(&api, "enforcement_percentage: 0");
+    assert!(matches!(guard.decide("s1").await.unwrap(), Decision::Allow { .. }));
+
+    guard.revoke_session("s1", ReasonCode::SessionBanned).await.unwrap();
+    assert!(matches!(guard.decide("s1").await.unwrap(), Decision::Deny { status: 403, .. }));
+    let decisions = guard.decide_batch(&["s1"]).await.unwrap();
+    assert!(matches!(decisions[..], [Decision::Deny { status: 403, .. }]));
+}
@@ -0,0 +1,47 @@
+#ifndef EGUARD_H
+#define EGUARD_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct EGuardHandle EGuardHandle;
+
+typedef struct EGuardDecision {
+  int allow;
+  uint16_t status;
+  /* NULL unless denied; release with eguard_decision_free. */
+  char *message;
+  /* "captcha", "turnstile", "hcaptcha", "recaptcha" or "step_up"; NULL unless
+   * challenged. Static, never freed. */
+  const char *challenge_kind;
+  /* NULL unless challenged; released by eguard_decision_free. */
+  char *redirect_url;
+} EGuardDecision;
+
+/* Creates a guard from a JSON-encoded EGuardConfig (snake_case keys).
+ * Returns NULL on failure; the error message is stored in *err_out if non-NULL. */
+EGuardHandle *eguard_new(const char *config_json, char **err_out);
+void eguard_free(EGuardHandle *handle);
+
+/* 1 if protected, 0 if not, -1 on invalid arguments. */
+int eguard_is_secure(const EGuardHandle *handle, const char *path, const char *method);
+
+/* Returns the session id (release with eguard_string_free) or NULL. */
+char *eguard_extract_session_id(const EGuardHandle *handle, const char *cookie_header,
+                                const char *header_name, const char *header_value);
+
+/* Blocks the calling thread until the Trust API answers. 0 on success, -1 on error. */
+int eguard_decide_blocking(const EGuardHandle *handle, const char *session_id,
+                           EGuardDecision *out, char **err_out);
+
+void eguard_decision_free(EGuardDecision *decision);
+void eguard_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* EGUARD_H */
@@ -0,0 +1,202 @@
+//! C ABI for embedding eGuard in non-Rust services. See `include/eguard.h`.
+//!
+//! Strings returned to the caller are owned by Rust and must be released with
+//! `eguard_string_free`; decisions with `eguard_decision_free`.
+
+use std::{
+    ffi::{CStr, CString, c_char, c_int},
+    ptr,
+};
+
+use eguard_core::{ChallengeKind, Decision, EGuard, EGuardConfig};
+use tokio::runtime::Runtime;
+
+pub struct EGuardHandle {
+    guard: EGuard,
+    rt: Runtime,
+}
+
+#[repr(C)]
+pub struct EGuardDecision {
+    pub allow: c_int,
+    pub status: u16,
+    /// NULL unless denied.
+    pub message: *mut c_char,
+    /// Challenge kind (`"turnstile"`, `"step_up"`, ...); NULL unless challenged.
+    pub challenge_kind: *const c_char,
+    /// NULL unless challenged.
+    pub redirect_url: *mut c_char,
+}
+
+impl EGuardDecision {
+    const EMPTY: Self = Self {
+        allow: 0,
+        status: 0,
+        message: ptr::null_mut(),
+        challenge_kind: ptr::null(),
+        redirect_url: ptr::null_mut(),
+    };
+}
+
+/// Static, NUL-terminated names so callers never have to free them.
+fn challenge_kind_name(kind: ChallengeKind) -> &'static CStr {
+    match kind {
+        ChallengeKind::Captcha => c"captcha",
+        ChallengeKind::Turnstile => c"turnstile",
+        ChallengeKind::Hcaptcha => c"hcaptcha",
+        ChallengeKind::Recaptcha => c"recaptcha",
+        ChallengeKind::StepUp => c"step_up",
+    }
+}
+
+unsafe fn str_arg<'a>(p: *const c_char) -> Option<&'a str> {
+    if p.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(p) }.to_str().ok()
+}
+
+fn into_c_string(s: String) -> *mut c_char {
+    // Interior NULs can't cross the boundary; drop them rather than the whole string.
+    CString::new(s.replace('\0', "")).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+unsafe fn set_err(err_out: *mut *mut c_char, msg: String) {
+    if !err_out.is_null() {
+        unsafe { *err_out = into_c_string(msg) };
+    }
+}
+
+/// Creates a guard from a JSON-encoded `EGuardConfig`. Returns NULL on failure and,
+/// if `err_out` is non-NULL, stores an error message there.
+///
+/// # Safety
+/// `config_json` must be a valid NUL-terminated string; `err_out` must be NULL or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn eguard_new(config_json: *const c_char, err_out: *mut *mut c_char) -> *mut EGuardHandle {
+    let result = (|| {
+        let raw = unsafe { str_arg(config_json) }.ok_or("config_json must be valid UTF-8")?;
+        let cfg: EGuardConfig = serde_json::from_str(raw).map_err(|e| format!("Invalid config: {e}"))?;
+        let guard = EGuard::new(cfg).map_err(|e| e.to_string())?;
+        let rt = Runtime::new().map_err(|e| format!("failed to create tokio runtime: {e}"))?;
+        Ok::<_, String>(EGuardHandle { guard, rt })
+    })();
+
+    match result {
+        Ok(handle) => Box::into_raw(Box::new(handle)),
+        Err(e) => {
+            unsafe { set_err(err_out, e) };
+            ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// `handle` must be NULL or a pointer returned by `eguard_new` that was not freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn eguard_free(handle: *mut EGuardHandle) {
+    if !handle.is_null() {
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
+
+/// Returns 1 if the route is protected, 0 if not, -1 on invalid arguments.
+///
+/// # Safety
+/// `handle` must come from `eguard_new`; `path` and `method` must be NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn eguard_is_secure(handle: *const EGuardHandle, path: *const c_char, method: *const c_char) -> c_int {
+    let Some(handle) = (unsafe { handle.as_ref() }) else { return -1 };
+    let (Some(path), Some(method)) = (unsafe { str_arg(path) }, unsafe { str_arg(method) }) else {
+        return -1;
+    };
+    c_int::from(handle.guard.is_secure(path, method))
+}
+
+/// Returns the session id or NULL if none was found. Any argument except `handle` may be NULL.
+///
+/// # Safety
+/// `handle` must come from `eguard_new`; non-NULL strings must be NUL-terminated.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn eguard_extract_session_id(
+    handle: *const EGuardHandle,
+    cookie_header: *const c_char,
+    header_name: *const c_char,
+    header_value: *const c_char,
+) -> *mut c_char {
+    let Some(handle) = (unsafe { handle.as_ref() }) else { return ptr::null_mut() };
+    let cookies = unsafe { str_arg(cookie_header) };
+    let header = unsafe { str_arg(header_name) }.zip(unsafe { str_arg(header_value) });
+    let _enter = handle.rt.enter();
+    handle.guard.extract_session_id(cookies, header).map_or(ptr::null_mut(), into_c_string)
+}
+
+/// Runs the trust decision on the handle's runtime, blocking the calling thread.
+/// Returns 0 and fills `out` on success; -1 on failure with the message in `err_out`.
+///
+/// Must not be called from a thread that is already driving the handle's runtime.
+///
+/// # Safety
+/// `handle` must come from `eguard_new`; `session_id` must be NUL-terminated;
+/// `out` must be writable; `err_out` must be NULL or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn eguard_decide_blocking(
+    handle: *const EGuardHandle,
+    session_id: *const c_char,
+    out: *mut EGuardDecision,
+    err_out: *mut *mut c_char,
+) -> c_int {
+    let (Some(handle), Some(sid)) = (unsafe { handle.as_ref() }, unsafe { str_arg(session_id) }) else {
+        unsafe { set_err(err_out, "invalid arguments".into()) };
+        return -1;
+    };
+    if out.is_null() {
+        unsafe { set_err(err_out, "out must not be NULL".into()) };
+        return -1;
+    }
+
+    match handle.rt.block_on(handle.guard.decide(sid)) {
+        Ok(decision) => {
+            let decision = match decision {
+                Decision::Allow { .. } => EGuardDecision { allow: 1, ..EGuardDecision::EMPTY },
+                Decision::Deny { status, message } => {
+                    EGuardDecision { allow: 0, status, message: into_c_string(message), ..EGuardDecision::EMPTY }
+                }
+                Decision::Challenge { kind, redirect_url } => EGuardDecision {
+                    allow: 0,
+                    challenge_kind: challenge_kind_name(kind).as_ptr(),
+                    redirect_url: into_c_string(redirect_url),
+                    ..EGuardDecision::EMPTY
+                },
+            };
+            unsafe { out.write(decision) };
+            0
+        }
+        Err(e) => {
+            unsafe { set_err(err_out, e.to_string()) };
+            -1
+        }
+    }
+}
+
+/// # Safety
+/// `decision` must be NULL or point to a decision filled by `eguard_decide_blocking`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn eguard_decision_free(decision: *mut EGuardDecision) {
+    if let Some(d) = unsafe { decision.as_mut() } {
+        unsafe { eguard_string_free(d.message) };
+        unsafe { eguard_string_free(d.redirect_url) };
+        d.message = ptr::null_mut();
+        d.redirect_url = ptr::null_mut();
+        d.challenge_kind = ptr::null();
+    }
+}
+
+/// # Safety
+/// `s` must be NULL or a string returned by this library that was not freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn eguard_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
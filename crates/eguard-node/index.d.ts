@@ -1,38 +1,718 @@
 /* auto-generated by NAPI-RS */
 /* eslint-disable */
+/**
+ * Errors are rejected with their kind leading the message, e.g. `RateLimited: ...`;
+ * see `EGuardError` for the kinds.
+ */
 export declare class JsEGuard {
   constructor(cfg: JsEGuardConfig)
-  /** Pure check; no I/O. */
-  isSecure(path: string, method: string): boolean
+  /** Pure check; no I/O. Pass `host` to apply routes' `hosts` restrictions. */
+  isSecure(path: string, method: string, host?: string | undefined | null): boolean
   /** Extract session id from cookie/header values provided by the caller. */
-  extractSessionId(cookieHeader?: string | undefined | null, headerName?: string | undefined | null, headerValue?: string | undefined | null): string | null
+  extractSessionId(cookieHeader?: string | undefined | null, headerName?: string | undefined | null, headerValue?: string | undefined | null, query?: string | undefined | null): string | null
+  /**
+   * Takes the raw request headers, e.g. Node's `req.headers`; names are matched
+   * case-insensitively and repeated `cookie` headers are combined.
+   */
+  extractSessionIdFromHeaders(headers: Record<string, string | Array<string> | undefined | null>, query?: string | undefined | null): string | null
+  /** Counters and latency histogram in the Prometheus text exposition format. */
+  metrics(): string
+  /** URLs of the `endpoints` currently marked unhealthy. */
+  unhealthyEndpoints(): Array<string>
+  /** Load the configured `secrets` now; they are also loaded in the background. */
+  loadSecrets(): Promise<void>
+  /** Switch to a new API key; the previous one stays in use as the secondary key. */
+  rotateKey(key: string): void
+  /**
+   * Verify a pushed score update (raw body plus the `x-eguard-timestamp` and
+   * `x-eguard-signature` headers) and apply it to the cache. Rejects on a bad signature.
+   */
+  handleWebhook(body: Buffer, timestamp: string, signature: string): Promise<void>
+  /** Queue a behavioural event for the Trust API; it is sent in the background. */
+  reportEvent(sessionId: string, kind: JsEventKind, metadata?: Record<string, any> | undefined | null): void
+  /** Report a confirmed fraud or legitimate outcome for a session. */
+  reportOutcome(sessionId: string, outcome: JsOutcome): Promise<void>
+  /**
+   * Ban a session: it is denied locally from the next request on, and the ban is
+   * sent to the Trust API. `reason` defaults to `session_banned`.
+   */
+  revokeSession(sessionId: string, reason?: string | undefined | null): Promise<void>
   /** Asynchronous trust decision (calls your Sentry Cloud API). */
-  decide(sessionId: string): Promise<unknown>
+  decide(sessionId: string): Promise<JsDecision>
+  /**
+   * Route matching, session extraction and the trust check for one request in a single
+   * call. `path` may include the query string; `cookies` is a parsed cookie object such as
+   * Express's `req.cookies`, only used when `headers` has no `cookie` header, as parsed
+   * values are decoded and lose duplicates. Requests without a
+   * session are rejected with 401, and with 502 if the Trust API fails and no
+   * `failureMode` is set.
+   */
+  decideRequest(path: string, method: string, headers: Record<string, string | Array<string> | undefined | null>, cookies?: Record<string, string> | undefined | null, ip?: string | undefined | null): Promise<JsRequestDecision>
+  /**
+   * Decisions for many sessions in one call, in input order. Sessions that aren't
+   * cached are fetched from the Trust API in batches of `batchMaxSize`.
+   */
+  decideMany(sessionIds: Array<string>): Promise<Array<JsDecision>>
+  /** `decide` with the settings of a configured tenant. */
+  decideFor(tenant: string, sessionId: string): Promise<JsDecision>
+  /**
+   * Stop the background tasks, waiting up to the runtime's `shutdownTimeoutMs`.
+   * Calls that reach the Trust API reject afterwards; calling it again is a no-op.
+   * The tokio runtime is left running: other guards and napi's own async calls share
+   * it, and a shut-down custom runtime can't be restarted (napi would silently start a
+   * default one). It stops when the addon is unloaded.
+   */
+  dispose(): Promise<void>
+  /**
+   * Swap routes, thresholds and session extraction without losing the cache; see
+   * `EGuard::reload` for what can change. An invalid config is rejected and the
+   * current one kept.
+   */
+  reloadConfig(cfg: JsEGuardConfig): void
+  /**
+   * Call `callback` with every decision this guard and its tenants make, e.g. to feed
+   * your own logging or analytics. Session ids are only passed hashed.
+   */
+  onDecision(callback: (arg: JsDecisionRecord) => void): void
+}
+
+/**
+ * Configure the tokio runtime shared by every `JsEGuard` in the process, worker
+ * threads included. Call it once, before the first `JsEGuard` is created. The runtime
+ * lives until the addon is unloaded, when napi shuts it down with the last environment.
+ */
+export declare function configureRuntime(options: JsRuntimeOptions): void
+
+export interface JsAdaptiveTimeout {
+  /** Latency percentile the timeout follows (default 99). */
+  percentile?: number
+  /** Timeout as a multiple of that percentile (default 3). */
+  multiplier?: number
+  /** Lower bound of the timeout (default 50). */
+  minMs?: number
+  /** Recent lookups the percentile is taken over (default 500). */
+  window?: number
+  /** Lookups observed before adapting; until then the timeout is `timeoutMs` (default 50). */
+  minSamples?: number
+}
+
+export declare const enum JsBandAction {
+  Allow = 'Allow',
+  Challenge = 'Challenge',
+  Deny = 'Deny'
+}
+
+export interface JsChallengeConfig {
+  /** Scores from `minTrustScore` up to this value are challenged instead of allowed. */
+  below: number
+  kind: JsChallengeKind
+  redirectUrl: string
+}
+
+export declare const enum JsChallengeKind {
+  Captcha = 'Captcha',
+  Turnstile = 'Turnstile',
+  Hcaptcha = 'Hcaptcha',
+  Recaptcha = 'Recaptcha',
+  StepUp = 'StepUp'
+}
+
+export interface JsCircuitBreaker {
+  /** Consecutive transient failures before the circuit opens (default 5). */
+  failureThreshold?: number
+  /** How long the circuit stays open before a probe is let through (default 10000). */
+  openMs?: number
+  /** Decision returned while open; defaults to a 503 deny. */
+  fallback?: JsDecision
+}
+
+export interface JsConcurrencyLimit {
+  /** At least 1. */
+  maxInFlight: number
+  /** `Fallback` (default) fails calls over the limit at once, so the failure mode applies; `Queue` waits for a slot. */
+  shed?: JsShedPolicy
+  /** How long `Queue` waits for a slot (default 100). */
+  queueTimeoutMs?: number
+}
+
+export declare const enum JsCookieDuplicates {
+  First = 'First',
+  Last = 'Last'
 }
 
 export interface JsDecision {
   allow: boolean
   status?: number
   message?: string
+  /** Set, with `redirectUrl`, when the user should be challenged rather than denied. */
+  challengeKind?: JsChallengeKind
+  redirectUrl?: string
+  /** Set on allows based on a trust score, with the Trust API's `reason`. */
+  trustScore?: number
+  reason?: string
+  /** Set on allows for requests matching a secure route: its `id`, or `pathPattern`. */
+  routeId?: string
+  /** `X-EGuard-*` headers for the response, when `responseHeaders` is configured. */
+  headers?: Record<string, string>
+  /** The rendered `denyBody` for denies, to send instead of `message`. */
+  body?: string
+  contentType?: string
+}
+
+/** A decision as passed to `onDecision` callbacks; the same fields as the audit log. */
+export interface JsDecisionRecord {
+  timestampMs: number
+  route?: string
+  sessionHash: string
+  trustScore?: number
+  /** `allow`, `deny` or `challenge`. */
+  outcome: string
+  status?: number
+  fallback: boolean
+  enforced: boolean
+  latencyMs: number
+}
+
+export interface JsDecisionSink {
+  type: JsDecisionSinkKind
+  /** `Http` only. */
+  url?: string
+  headers?: Record<string, string>
+  /** `Kafka` only. */
+  brokers?: Array<string>
+  topic?: string
+  partition?: number
+}
+
+export declare const enum JsDecisionSinkKind {
+  Stdout = 'Stdout',
+  Http = 'Http',
+  Kafka = 'Kafka'
+}
+
+export interface JsDenyBody {
+  type: JsDenyBodyKind
+  /** `Json` only: a JSON value whose strings may use the variables. */
+  template?: any
+  /** `Html` only: a page read when the config is loaded; variables are HTML-escaped. */
+  path?: string
+}
+
+export declare const enum JsDenyBodyKind {
+  Json = 'Json',
+  Html = 'Html'
+}
+
+export interface JsDenylistConfig {
+  /** How often to pull changes to the denylist (default 30000). */
+  intervalMs?: number
+}
+
+export interface JsDnsConfig {
+  /** How long looked-up addresses are used before they are refreshed in the background (default 60000). */
+  ttlMs?: number
+  /** Fixed addresses for hosts, used instead of DNS: `{ 'trust.example.com': ['10.0.0.5'] }`. */
+  addresses?: Record<string, Array<string>>
 }
 
 export interface JsEGuardConfig {
+  /** `unix:///var/run/eguard.sock` reaches a Trust API listening on a local socket (HTTP transport only). */
   apiBaseUrl: string
+  /** May be empty when `secrets.apiKey` is set. */
   apiKey: string
+  /** Used when the Trust API rejects `apiKey` with 401, after which it stays in use. */
+  secondaryApiKey?: string
   secureRoutes: Array<JsSecureRoute>
+  /** Path regexes that are never secure, even if a secure route matches. */
+  excludeRoutes?: Array<string>
+  /** Treat `/admin/` like `/admin` when matching routes. */
+  ignoreTrailingSlash?: boolean
+  /** Client IP ranges allowed or denied without calling the Trust API. */
+  ipRules?: JsIpRules
+  /** Local MaxMind databases for country/ASN lookups of client IPs. */
+  geoip?: JsGeoIpConfig
+  /** Headers carrying the client's JA3/JA4 TLS fingerprints, forwarded to the Trust API and usable in `rules`. */
+  tlsFingerprint?: JsTlsFingerprint
+  /** Local rules, e.g. `if path ~ "^/export" and score < 0.9 then deny`. */
+  rules?: Array<string>
+  /** Per-session or per-IP request rate enforced locally. */
+  rateLimit?: JsRateLimit
   sessionExtraction: JsSessionExtraction
-  /** JS numbers are 64-bit floats; use f64 at the boundary. */
-  minTrustScore: number
+  /** Scores below this are denied. Required unless `riskBands` is set. JS numbers are 64-bit floats; use f64 at the boundary. */
+  minTrustScore?: number
+  /** Score ranges mapped to actions, listed from the highest `minScore` down; replaces `minTrustScore` and `challenge`. */
+  riskBands?: Array<JsRiskBand>
+  /** Share of sessions (0-100) whose score-based denies and challenges are enforced; the rest are only logged. IP, country and session id checks always apply. Defaults to 100. */
+  enforcementPercentage?: number
+  /** Challenge instead of allowing scores just above `minTrustScore`. */
+  challenge?: JsChallengeConfig
   timeoutMs?: number
+  /** Time out lookups after a multiple of recent latency, capped at `timeoutMs`. */
+  adaptiveTimeout?: JsAdaptiveTimeout
+  /** Send a second lookup when the first is slow and use the first answer. */
+  hedge?: JsHedge
+  /** Reuse a session's trust score for this long; 0 (default) disables caching. */
+  cacheTtlMs?: number
+  cacheMaxEntries?: number
+  /** Remember unknown sessions in-process for this long; 0 (default) disables it. */
+  negativeCacheTtlMs?: number
+  /** On Trust API failure, use a cached score up to this long past its expiry; 0 (default) disables it. */
+  serveStaleMs?: number
+  /** Share cached scores between workers through Redis, e.g. `redis://127.0.0.1/`. */
+  cacheRedisUrl?: string
+  retry?: JsRetryPolicy
+  /** Back-off after the Trust API answers 429, following its `Retry-After`. */
+  throttle?: JsThrottle
+  circuitBreaker?: JsCircuitBreaker
+  /** Cap on Trust API calls in flight; calls over it are shed or queued. */
+  concurrencyLimit?: JsConcurrencyLimit
+  /** Decision returned when the Trust API can't be reached; unset rejects the promise. */
+  failureMode?: JsFailureMode
+  /** `Grpc` treats `apiBaseUrl` as the gRPC endpoint. Defaults to `Http`. */
+  transport?: JsTransportKind
+  /** How strictly Trust API responses are read (HTTP transport only). Defaults to `Lenient`. */
+  responseParsing?: JsResponseParsing
+  /** Newest Trust API payload version to offer; the API answers in the version it picks. Defaults to `V2`; `V1` pins the old shape (HTTP transport only). */
+  protocolVersion?: JsProtocolVersion
+  /** Encoding of Trust API bodies (HTTP transport only). Defaults to `Json`. */
+  wireFormat?: JsWireFormat
+  /** More Trust API deployments beside `apiBaseUrl`, chosen between by health and priority or latency. */
+  endpoints?: JsEndpoints
+  /** Other Trust API deployments, such as regional replicas, tried in order when `apiBaseUrl` (or every one of `endpoints`) fails. */
+  fallbacks?: Array<JsFallbackConfig>
+  /** Score sessions locally when the Trust API fails and no stale score is cached. */
+  localScorer?: JsLocalScorerConfig
+  /** Send outgoing HTTP requests through this proxy instead of the one from the environment. */
+  proxy?: JsProxyConfig
+  /** Connection pool and keep-alive settings for Trust API calls (HTTP transport only). */
+  pool?: JsPoolConfig
+  /** Cache Trust API DNS lookups, or skip them for fixed addresses (HTTP transport only). */
+  dns?: JsDnsConfig
+  /** Maximum session ids per batch Trust API call (default 100). */
+  batchMaxSize?: number
+  /** Shared secret for pushed score updates; `handleWebhook` rejects when unset. */
+  webhookSecret?: string
+  /** HMAC-sign every Trust API request (HTTP transport only). */
+  requestSigning?: JsSigningConfig
+  /** Privacy mode: session ids are replaced with `HMAC-SHA256(salt, sid)` before they are sent, cached or logged, and returned scores carry the hashed id. */
+  sessionHashing?: JsSessionHashing
+  /** Load `apiKey` and the signing key from a secret store instead. */
+  secrets?: JsSecretsConfig
+  /** Re-fetch scores of recently used sessions before their cache entry expires. */
+  refresh?: JsRefreshConfig
+  /** Keep a local copy of banned sessions, denied without a Trust API call. */
+  denylist?: JsDenylistConfig
+  /** Apply score changes and bans streamed by the Trust API over Server-Sent Events. */
+  push?: JsPushConfig
+  /** Append every decision as a JSON line to this file (`-` for stdout). */
+  auditLogPath?: string
+  /** Stream every decision to these sinks in the background. */
+  decisionSinks?: Array<JsDecisionSink>
+  /** Describe decisions in `X-EGuard-Score`, `X-EGuard-Decision` and `X-EGuard-Request-Id` headers. */
+  responseHeaders?: JsResponseHeadersConfig
+  /** Body template for denies, with `{{status}}`, `{{message}}`, `{{score}}`, `{{reason}}` and `{{request_id}}`. */
+  denyBody?: JsDenyBody
+  /** Named tenants, each this config with its own overrides; unmatched requests use this config. */
+  tenants?: Record<string, JsTenantConfig>
+  /** Request header naming the tenant, checked before the tenants' `hosts`. */
+  tenantHeader?: string
+}
+
+export interface JsEndpoints {
+  /** Deployments in addition to `apiBaseUrl`, which comes first. */
+  apiBaseUrls: Array<string>
+  /** Defaults to `Priority`. */
+  selection?: JsEndpointSelection
+  /** Consecutive failures after which an endpoint is marked unhealthy (default 3). */
+  unhealthyAfter?: number
+  /** How long an unhealthy endpoint is skipped before a lookup tries it again (default 10000). */
+  retryAfterMs?: number
+  /** Probe every endpoint in the background. */
+  healthCheck?: JsHealthCheck
+}
+
+export declare const enum JsEndpointSelection {
+  /** The first healthy endpoint in the order listed. */
+  Priority = 'Priority',
+  /** The healthy endpoint with the lowest recent latency. */
+  Latency = 'Latency'
+}
+
+export declare const enum JsEventKind {
+  Login = 'Login',
+  LoginFailed = 'LoginFailed',
+  Signup = 'Signup',
+  Checkout = 'Checkout',
+  PasswordReset = 'PasswordReset'
+}
+
+export interface JsFailureMode {
+  mode: JsFailureModeKind
+  /** Only used with `Custom`; defaults to 403. */
+  status?: number
+  message?: string
+}
+
+export declare const enum JsFailureModeKind {
+  FailOpen = 'FailOpen',
+  FailClosed = 'FailClosed',
+  Custom = 'Custom'
+}
+
+export interface JsFallbackConfig {
+  apiBaseUrl: string
+  /** Defaults to `apiKey`. */
+  apiKey?: string
+  /** Defaults to `timeoutMs`. */
+  timeoutMs?: number
+}
+
+export interface JsGeoIpConfig {
+  /** GeoLite2/GeoIP2 Country or City database. */
+  countryDbPath: string
+  /** GeoLite2/GeoIP2 ASN database. */
+  asnDbPath?: string
+}
+
+export interface JsHealthCheck {
+  /** Default 5000. */
+  intervalMs?: number
+  /** Looked up as the probe; any answer, including an unknown session, is healthy. */
+  sessionId?: string
+}
+
+export interface JsHedge {
+  /** How long to wait for the first lookup before sending the second (default 100). */
+  delayMs?: number
+  /** Wait for this latency percentile of recent lookups (e.g. 95) instead, once enough have been seen. */
+  percentile?: number
+}
+
+export interface JsIpRules {
+  /** CIDR ranges or addresses that are always allowed; wins over `deny`. */
+  allow?: Array<string>
+  /** CIDR ranges or addresses that are always denied with 403. */
+  deny?: Array<string>
+}
+
+export interface JsJwtConfig {
+  /** Dot-separated claim path; defaults to `sid`. */
+  claim?: string
+  /** Verify signatures against this JWKS; without it tokens are decoded unverified. */
+  jwksUrl?: string
+  jwksRefreshMs?: number
+  issuer?: string
+  audience?: string
+}
+
+export interface JsLocalScorerConfig {
+  /** Score of a request with no signals (default 0.6). */
+  baseScore?: number
+  /** Case-insensitive regexes for bot user agents; replaces the built-in list. */
+  botUserAgents?: Array<string>
+  userAgentPenalty?: number
+  /** Hosting provider ASNs; needs `geoip` with an ASN database. Replaces the built-in list. */
+  datacenterAsns?: Array<number>
+  datacenterPenalty?: number
+  maxRequestsPerMinute?: number
+  ratePenalty?: number
+  maxSessions?: number
+}
+
+export declare const enum JsOutcome {
+  Fraud = 'Fraud',
+  Legit = 'Legit'
+}
+
+export interface JsPoolConfig {
+  /** Idle connections kept open per host; unlimited by default. */
+  maxIdlePerHost?: number
+  /** How long an idle connection is kept open (default 90s). */
+  idleTimeoutMs?: number
+  /** Interval of TCP keepalive probes. */
+  tcpKeepaliveMs?: number
+  /** Speak HTTP/2 without negotiating it, for a plain `http://` Trust API known to support it. */
+  http2PriorKnowledge?: boolean
+  /** Interval of HTTP/2 PING frames on idle connections. */
+  http2KeepAliveIntervalMs?: number
+}
+
+export interface JsProxyConfig {
+  /** `http://`, `https://`, `socks5://` or `socks5h://`. */
+  url: string
+  username?: string
+  password?: string
+  /** Comma-separated hosts, domains and CIDRs reached directly, as in `NO_PROXY`. */
+  noProxy?: string
+}
+
+export interface JsPushConfig {
+  /** Path of the event stream under `apiBaseUrl` (default `/eguard/stream`). */
+  path?: string
+  /** Delay before reconnecting after the stream drops (default 1000). */
+  reconnectMs?: number
+}
+
+export interface JsRateLimit {
+  /** What requests are counted by; defaults to `Session`. */
+  key?: JsRateLimitKey
+  requestsPerSecond: number
+  /** Requests allowed at once after being idle; defaults to one second's worth. */
+  burst?: number
+  /** `Deny` answers 429; `Flag` only counts and logs. Defaults to `Deny`. */
+  action?: JsRateLimitAction
+  maxKeys?: number
+}
+
+export interface JsRefreshConfig {
+  /** How often to scan for entries to refresh (default 1000). */
+  intervalMs?: number
+  /** Refresh an entry once it is this close to expiring (default 5000). */
+  refreshAheadMs?: number
+}
+
+/** What `decideRequest` decided for a request. */
+export interface JsRequestDecision {
+  /** Whether the request is on a secure route; other requests are allowed unchecked. */
+  matched: boolean
+  allow: boolean
+  status?: number
+  message?: string
+  score?: number
+  sessionId?: string
+  challengeKind?: JsChallengeKind
+  redirectUrl?: string
+  /** `X-EGuard-*` headers for the response, when `responseHeaders` is configured. */
+  headers?: Record<string, string>
+  /** The rendered `denyBody` for denies, to send instead of `message`. */
+  body?: string
+  contentType?: string
+}
+
+export interface JsResponseHeadersConfig {
+  /** Defaults to true; omitted when the decision was made without a score. */
+  score?: boolean
+  /** Defaults to true. */
+  decision?: boolean
+  /** Defaults to true. */
+  requestId?: boolean
+  /** Request header whose id is reused instead of generating one; defaults to `x-request-id`. */
+  requestIdHeader?: string
+  /** Also add the headers to allowed requests before they reach the application. */
+  forward?: boolean
+}
+
+export interface JsRetryPolicy {
+  /** Total attempts including the first call; 1 (default) disables retries. */
+  maxAttempts?: number
+  baseDelayMs?: number
+  maxDelayMs?: number
+  jitter?: boolean
+}
+
+/** Scores from `minScore` up to the previous band get `action`; scores below every band are denied. */
+export interface JsRiskBand {
+  minScore: number
+  action: JsBandAction
+  /** `Challenge` only. */
+  kind?: JsChallengeKind
+  /** `Challenge` only. */
+  redirectUrl?: string
+}
+
+export interface JsRuntimeOptions {
+  /** Worker threads of the tokio runtime (default: one per CPU core). */
+  workerThreads?: number
+  /** How long `dispose()` waits for background tasks to stop (default 5000). */
+  shutdownTimeoutMs?: number
+}
+
+export interface JsScoreThreshold {
+  score: JsScoreKind
+  /** Deny below this value, for trust-like scores. */
+  min?: number
+  /** Deny above this value, for risk-like scores such as `Bot`. */
+  max?: number
+}
+
+export interface JsSecretsConfig {
+  /** Replaces `apiKey`; a changed key is rotated in. */
+  apiKey?: JsSecretSource
+  /** Replaces `requestSigning.key`. */
+  signingKey?: JsSecretSource
+  /** How often secrets are loaded again (default 300000); 0 loads them once. */
+  refreshMs?: number
+}
+
+export interface JsSecretSource {
+  type: JsSecretSourceKind
+  /** `Env` only. */
+  var?: string
+  /** `File` and `Vault`. */
+  path?: string
+  /** `AwsSecretsManager` only. */
+  secretId?: string
+  /** JSON field holding the secret; required for `Vault`. */
+  key?: string
+  /** `Vault` only. */
+  address?: string
+  token?: string
+}
+
+export declare const enum JsSecretSourceKind {
+  Env = 'Env',
+  File = 'File',
+  AwsSecretsManager = 'AwsSecretsManager',
+  Vault = 'Vault'
 }
 
 export interface JsSecureRoute {
+  /** Names the route in `JsDecision.routeId`; defaults to `pathPattern`. */
+  id?: string
   pathPattern: string
   methods?: Array<string>
+  /** How `pathPattern` is read: a regex (default), `/orders/:id` template or `/static/**` glob. */
+  syntax?: JsRouteSyntax
+  /** Only apply this route to these hosts (`admin.example.com` or `*.example.com`). */
+  hosts?: Array<string>
+  /** Deny requests from outside these countries (ISO codes); requires `geoip`. */
+  allowedCountries?: Array<string>
+  /** Overrides the global `minTrustScore` or `riskBands` for this route. */
+  minTrustScore?: number
+  /** Overrides the global `riskBands` or `minTrustScore` for this route. */
+  riskBands?: Array<JsRiskBand>
+  /** Thresholds on multi-score responses, all of which must pass; replaces `minTrustScore` when set. */
+  scores?: Array<JsScoreThreshold>
+  /** Status code for denials on this route (default 403). */
+  denyStatus?: number
+  /** Message for denials on this route (default `Low trust score: <score>`). */
+  denyMessage?: string
 }
 
 export interface JsSessionExtraction {
+  /** Sources in priority order, e.g. `['cookie:sid', 'header:x-session', 'query:sid']`. */
+  sources?: Array<string>
   cookieName?: string
+  /** More cookies tried after `cookieName`, in priority order. */
+  cookieNames?: Array<string>
+  /** Also look for `__Host-` and `__Secure-` prefixed cookie names, ahead of the plain ones. */
+  cookiePrefixes?: boolean
+  /** Which value counts when the cookie appears more than once (default `First`). */
+  cookieDuplicates?: JsCookieDuplicates
   headerName?: string
   headerBearer?: boolean
+  /** Schemes stripped from bearer header values, in any case (default `['Bearer']`). */
+  bearerSchemes?: Array<string>
+  /** Ignore bearer header values without one of `bearerSchemes` instead of taking them as they are. */
+  bearerRequireScheme?: boolean
+  /** Query parameter (`?sid=...`) checked after the cookie and header. */
+  queryParam?: string
+  /** Decode the extracted token as a JWT and use one of its claims as the session id. */
+  jwt?: JsJwtConfig
+  /** Where to find a device or fingerprint id sent along to the Trust API, e.g. `['header:x-device-id', 'cookie:did']`. */
+  deviceSources?: Array<string>
+  /** Longest session id accepted; longer ones are denied (400) without calling the Trust API. */
+  maxLength?: number
+  /** Regex session ids must match in full, e.g. `[A-Za-z0-9_-]{16,64}`; others are denied like `maxLength`. */
+  pattern?: string
+}
+
+export declare const enum JsShedPolicy {
+  Fallback = 'Fallback',
+  Queue = 'Queue'
+}
+
+export interface JsSessionHashing {
+  /** HMAC key; the same on every instance of a deployment. */
+  salt: string
+}
+
+export interface JsSigningConfig {
+  /** Shared secret for `HMAC-SHA256("{timestamp}.{nonce}.{METHOD}.{path?query}.{body}")`; may be omitted with `secrets.signingKey`. */
+  key?: string
+  /** Defaults to `x-eguard-timestamp`. */
+  timestampHeader?: string
+  /** Defaults to `x-eguard-nonce`. */
+  nonceHeader?: string
+  /** Defaults to `x-eguard-signature`. */
+  signatureHeader?: string
+}
+
+export interface JsTenantConfig {
+  /** Hosts served by this tenant; `*.example.com` matches subdomains. */
+  hosts?: Array<string>
+  apiKey?: string
+  /** Only used together with `apiKey`. */
+  secondaryApiKey?: string
+  apiBaseUrl?: string
+  /** Replaces the top-level `secureRoutes`. */
+  secureRoutes?: Array<JsSecureRoute>
+  /** Also drops the top-level `riskBands`. */
+  minTrustScore?: number
+  /** Replaces the top-level `riskBands`. */
+  riskBands?: Array<JsRiskBand>
+}
+
+export interface JsThrottle {
+  /** How long to stop calling the API after a 429 without `Retry-After` (default 1000). */
+  defaultBackoffMs?: number
+  /** Upper bound on the back-off, whatever `Retry-After` asks for (default 60000). */
+  maxBackoffMs?: number
+  /** Decision returned while rate limited; defaults to `failureMode`. */
+  fallback?: JsFailureMode
+}
+
+export interface JsTlsFingerprint {
+  /** e.g. `x-ja3-fingerprint` */
+  ja3Header?: string
+  /** e.g. `x-ja4-fingerprint` */
+  ja4Header?: string
+}
+
+export declare const enum JsProtocolVersion {
+  /** Scores at the top level: `trust_score`, `bot_score`, ... */
+  V1 = 'V1',
+  /** Scores nested under `scores`: `{"trust": ..., "bot": ...}`. */
+  V2 = 'V2'
+}
+
+export declare const enum JsRateLimitAction {
+  Deny = 'Deny',
+  Flag = 'Flag'
+}
+
+export declare const enum JsRateLimitKey {
+  Session = 'Session',
+  Ip = 'Ip'
+}
+
+export declare const enum JsResponseParsing {
+  /** Ignore unknown fields and mistyped optional fields, and accept scores given as strings. */
+  Lenient = 'Lenient',
+  /** Reject responses that don't match the schema exactly; for CI and testing. */
+  Strict = 'Strict'
+}
+
+export declare const enum JsRouteSyntax {
+  Regex = 'Regex',
+  Template = 'Template',
+  Glob = 'Glob'
+}
+
+export declare const enum JsScoreKind {
+  Trust = 'Trust',
+  Bot = 'Bot',
+  Fraud = 'Fraud',
+  Abuse = 'Abuse'
+}
+
+export declare const enum JsTransportKind {
+  Http = 'Http',
+  Grpc = 'Grpc'
+}
+
+export declare const enum JsWireFormat {
+  Json = 'Json',
+  Cbor = 'Cbor',
+  MessagePack = 'MessagePack'
 }
@@ -509,3 +509,23 @@ if (!nativeBinding) {
 
 module.exports = nativeBinding
 module.exports.JsEGuard = nativeBinding.JsEGuard
+module.exports.JsBandAction = nativeBinding.JsBandAction
+module.exports.JsChallengeKind = nativeBinding.JsChallengeKind
+module.exports.JsCookieDuplicates = nativeBinding.JsCookieDuplicates
+module.exports.JsDecisionSinkKind = nativeBinding.JsDecisionSinkKind
+module.exports.JsDenyBodyKind = nativeBinding.JsDenyBodyKind
+module.exports.JsEndpointSelection = nativeBinding.JsEndpointSelection
+module.exports.JsEventKind = nativeBinding.JsEventKind
+module.exports.JsFailureModeKind = nativeBinding.JsFailureModeKind
+module.exports.JsOutcome = nativeBinding.JsOutcome
+module.exports.JsProtocolVersion = nativeBinding.JsProtocolVersion
+module.exports.JsRateLimitAction = nativeBinding.JsRateLimitAction
+module.exports.JsRateLimitKey = nativeBinding.JsRateLimitKey
+module.exports.JsResponseParsing = nativeBinding.JsResponseParsing
+module.exports.JsRouteSyntax = nativeBinding.JsRouteSyntax
+module.exports.JsScoreKind = nativeBinding.JsScoreKind
+module.exports.JsSecretSourceKind = nativeBinding.JsSecretSourceKind
+module.exports.JsShedPolicy = nativeBinding.JsShedPolicy
+module.exports.JsTransportKind = nativeBinding.JsTransportKind
+module.exports.JsWireFormat = nativeBinding.JsWireFormat
+module.exports.configureRuntime = nativeBinding.configureRuntime
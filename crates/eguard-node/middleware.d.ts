@@ -0,0 +1,32 @@
+import type { JsEGuard, JsEGuardConfig, JsRequestDecision } from './index'
+
+/**
+ * Express middleware that checks each request with `decideRequest`. Allowed requests
+ * continue with the decision as `req.eguard`; others get the deny response (or a 302
+ * to the challenge). Reads `req.cookies` when a cookie parser is installed.
+ */
+export declare function createExpressMiddleware(
+  guard: JsEGuard | JsEGuardConfig,
+): (req: any, res: any, next: (err?: unknown) => void) => void
+
+/**
+ * Fastify plugin adding an `onRequest` hook that does the same for the whole app; the
+ * decision is available as `request.eguard`.
+ */
+export declare function createFastifyPlugin(guard: JsEGuard | JsEGuardConfig): (fastify: any) => Promise<void>
+
+/** Koa middleware doing the same; the decision is available as `ctx.state.eguard`. */
+export declare function createKoaMiddleware(
+  guard: JsEGuard | JsEGuardConfig,
+): (ctx: any, next: () => Promise<unknown>) => Promise<unknown>
+
+/**
+ * A NestJS guard for `app.useGlobalGuards()` or `@UseGuards()`, on the Express or Fastify
+ * platform. Rejections are thrown as `HttpException`s with the same bodies as the
+ * middlewares; the decision is available as `request.eguard`.
+ */
+export declare function createNestGuard(guard: JsEGuard | JsEGuardConfig): {
+  canActivate(context: any): Promise<boolean>
+}
+
+export type { JsRequestDecision }
@@ -0,0 +1,134 @@
+// Express, Fastify, Koa and NestJS integrations, thin wrappers around `JsEGuard.decideRequest`.
+//
+//   const { createExpressMiddleware } = require('eguard/middleware')
+//   app.use(createExpressMiddleware({ apiBaseUrl, apiKey, secureRoutes, sessionExtraction, minTrustScore }))
+
+const { JsEGuard } = require('./index.js')
+
+function toGuard(guardOrConfig) {
+  return guardOrConfig instanceof JsEGuard ? guardOrConfig : new JsEGuard(guardOrConfig)
+}
+
+/** The JSON body for a rejection without a `denyBody`, as the Rust middlewares send it. */
+function rejection(decision) {
+  if (decision.redirectUrl) {
+    return { error: 'challenge', kind: decision.challengeKind, redirect_url: decision.redirectUrl }
+  }
+  if (!decision.sessionId) {
+    return { error: decision.status === 401 ? 'missing_session' : 'trust_service_unavailable' }
+  }
+  return { error: 'forbidden', detail: decision.message }
+}
+
+function createExpressMiddleware(guardOrConfig) {
+  const guard = toGuard(guardOrConfig)
+  return function eguard(req, res, next) {
+    guard
+      .decideRequest(req.originalUrl, req.method, req.headers, req.cookies, req.ip)
+      .then((decision) => {
+        for (const [name, value] of Object.entries(decision.headers ?? {})) {
+          res.setHeader(name, value)
+        }
+        if (decision.allow) {
+          req.eguard = decision
+          return next()
+        }
+        if (decision.redirectUrl) {
+          res.setHeader('location', decision.redirectUrl)
+          return res.status(302).json(rejection(decision))
+        }
+        if (decision.body != null) {
+          return res.status(decision.status).type(decision.contentType).send(decision.body)
+        }
+        res.status(decision.status).json(rejection(decision))
+      })
+      .catch(next)
+  }
+}
+
+function createFastifyPlugin(guardOrConfig) {
+  const guard = toGuard(guardOrConfig)
+  async function eguard(fastify) {
+    fastify.decorateRequest('eguard', null)
+    fastify.addHook('onRequest', async (request, reply) => {
+      const decision = await guard.decideRequest(request.url, request.method, request.headers, request.cookies, request.ip)
+      reply.headers(decision.headers ?? {})
+      if (decision.allow) {
+        request.eguard = decision
+        return
+      }
+      if (decision.redirectUrl) {
+        return reply.code(302).header('location', decision.redirectUrl).send(rejection(decision))
+      }
+      if (decision.body != null) {
+        return reply.code(decision.status).type(decision.contentType).send(decision.body)
+      }
+      return reply.code(decision.status).send(rejection(decision))
+    })
+  }
+  // Apply the hook to the whole app rather than an encapsulated context, like `fastify-plugin`.
+  eguard[Symbol.for('skip-override')] = true
+  return eguard
+}
+
+function createKoaMiddleware(guardOrConfig) {
+  const guard = toGuard(guardOrConfig)
+  return async function eguard(ctx, next) {
+    // Koa's `ctx.cookies` only reads single cookies; the `cookie` header is used instead.
+    const decision = await guard.decideRequest(ctx.originalUrl, ctx.method, ctx.headers, null, ctx.ip)
+    ctx.set(decision.headers ?? {})
+    if (decision.allow) {
+      ctx.state.eguard = decision
+      return next()
+    }
+    if (decision.redirectUrl) {
+      ctx.status = 302
+      ctx.set('location', decision.redirectUrl)
+      ctx.body = rejection(decision)
+      return
+    }
+    ctx.status = decision.status
+    if (decision.body != null) {
+      ctx.type = decision.contentType
+      ctx.body = decision.body
+      return
+    }
+    ctx.body = rejection(decision)
+  }
+}
+
+function createNestGuard(guardOrConfig) {
+  // Only needed, and only present, in NestJS apps.
+  const { HttpException } = require('@nestjs/common')
+  const guard = toGuard(guardOrConfig)
+  return {
+    async canActivate(context) {
+      const http = context.switchToHttp()
+      const req = http.getRequest()
+      // An Express response or a Fastify reply; both have `header(name, value)`.
+      const res = http.getResponse()
+      const decision = await guard.decideRequest(req.originalUrl ?? req.url, req.method, req.headers, req.cookies, req.ip)
+      for (const [name, value] of Object.entries(decision.headers ?? {})) {
+        res.header(name, value)
+      }
+      if (decision.allow) {
+        req.eguard = decision
+        return true
+      }
+      if (decision.redirectUrl) {
+        res.header('location', decision.redirectUrl)
+        throw new HttpException(rejection(decision), 302)
+      }
+      if (decision.body != null) {
+        res.header('content-type', decision.contentType)
+        throw new HttpException(decision.body, decision.status)
+      }
+      throw new HttpException(rejection(decision), decision.status)
+    },
+  }
+}
+
+module.exports.createExpressMiddleware = createExpressMiddleware
+module.exports.createFastifyPlugin = createFastifyPlugin
+module.exports.createKoaMiddleware = createKoaMiddleware
+module.exports.createNestGuard = createNestGuard
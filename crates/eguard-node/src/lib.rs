@@ -1,32 +1,1059 @@
-use eguard_core::{Decision, EGuard, EGuardConfig, SecureRoute, SessionExtraction};
-use napi::bindgen_prelude::*;
-use napi_derive::napi;
-use once_cell::sync::OnceCell;
-use tokio::runtime::Runtime;
+use std::{
+  collections::HashMap,
+  sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Mutex, RwLock,
+  },
+  time::Duration,
+};
 
-static RT: OnceCell<Runtime> = OnceCell::new();
+use eguard_core::{
+  adaptive::AdaptiveTimeoutConfig, audit::{DecisionLogger, DecisionRecord}, secrets::SecretSource, rate_limit::{RateLimitAction, RateLimitKey}, refresh::RefreshHandle, BandAction, ChallengeConfig, ChallengeKind, CircuitBreakerConfig, ConcurrencyLimitConfig, CookieDuplicates, Decision, DecisionSinkConfig, DenyBodyConfig, DenylistConfig, EGuard, EGuardConfig, EGuardError, EndpointSelection, EndpointsConfig, EventKind, Outcome, FailureMode, FallbackConfig,
+  DnsConfig, GeoIpConfig, HealthCheckConfig, HedgeConfig, IpRulesConfig, JwtConfig, LocalScorerConfig, PoolConfig, ProtocolVersion, ProxyConfig, PushConfig, RateLimitConfig, ReasonCode, RefreshConfig, RequestDecision, ResponseHeadersConfig, ResponseParsing, RetryPolicy, RiskBand, RouteSyntax, ScoreKind, ScoreThreshold, SecretsConfig, SecureRoute, SessionExtraction, SessionHashingConfig, SessionSource, ShedPolicy, SigningConfig, TenantConfig, ThrottleConfig, TlsFingerprintConfig, TransportKind, TrustResponse, WireFormat,
+};
+use napi::{
+  bindgen_prelude::*,
+  threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode},
+};
+use napi_derive::napi;
 
 #[napi(object)]
 pub struct JsSecureRoute {
+  pub id: Option<String>,
   pub path_pattern: String,
   pub methods: Option<Vec<String>>,
+  pub syntax: Option<JsRouteSyntax>,
+  pub hosts: Option<Vec<String>>,
+  pub allowed_countries: Option<Vec<String>>,
+  pub min_trust_score: Option<f64>,
+  pub risk_bands: Option<Vec<JsRiskBand>>,
+  pub scores: Option<Vec<JsScoreThreshold>>,
+  pub deny_status: Option<u16>,
+  pub deny_message: Option<String>,
+}
+
+impl TryFrom<JsSecureRoute> for SecureRoute {
+  type Error = Error;
+
+  fn try_from(r: JsSecureRoute) -> Result<Self> {
+    Ok(Self {
+      id: r.id,
+      path_pattern: r.path_pattern,
+      methods: r.methods,
+      syntax: r.syntax.map(Into::into).unwrap_or_default(),
+      hosts: r.hosts,
+      allowed_countries: r.allowed_countries,
+      min_trust_score: r.min_trust_score.map(|v| v as f32),
+      risk_bands: r.risk_bands.map(risk_bands).transpose()?,
+      scores: r.scores.unwrap_or_default().into_iter().map(Into::into).collect(),
+      deny_status: r.deny_status,
+      deny_message: r.deny_message,
+    })
+  }
+}
+
+#[napi(object)]
+pub struct JsRiskBand {
+  pub min_score: f64,
+  pub action: JsBandAction,
+  /// `Challenge` only.
+  pub kind: Option<JsChallengeKind>,
+  /// `Challenge` only.
+  pub redirect_url: Option<String>,
+}
+
+#[napi(string_enum)]
+pub enum JsBandAction {
+  Allow,
+  Challenge,
+  Deny,
+}
+
+impl TryFrom<JsRiskBand> for RiskBand {
+  type Error = Error;
+
+  fn try_from(b: JsRiskBand) -> Result<Self> {
+    let missing = |field: &str| Error::from_reason(format!("risk band is missing `{field}`"));
+    let action = match b.action {
+      JsBandAction::Allow => BandAction::Allow,
+      JsBandAction::Challenge => BandAction::Challenge {
+        kind: b.kind.ok_or_else(|| missing("kind"))?.into(),
+        redirect_url: b.redirect_url.ok_or_else(|| missing("redirectUrl"))?,
+      },
+      JsBandAction::Deny => BandAction::Deny,
+    };
+    Ok(Self { min_score: b.min_score as f32, action })
+  }
+}
+
+fn risk_bands(bands: Vec<JsRiskBand>) -> Result<Vec<RiskBand>> {
+  bands.into_iter().map(TryInto::try_into).collect()
+}
+
+#[napi(string_enum)]
+pub enum JsRouteSyntax {
+  Regex,
+  Template,
+  Glob,
+}
+
+impl From<JsRouteSyntax> for RouteSyntax {
+  fn from(s: JsRouteSyntax) -> Self {
+    match s {
+      JsRouteSyntax::Regex => RouteSyntax::Regex,
+      JsRouteSyntax::Template => RouteSyntax::Template,
+      JsRouteSyntax::Glob => RouteSyntax::Glob,
+    }
+  }
+}
+
+#[napi(object)]
+pub struct JsScoreThreshold {
+  pub score: JsScoreKind,
+  pub min: Option<f64>,
+  pub max: Option<f64>,
+}
+
+impl From<JsScoreThreshold> for ScoreThreshold {
+  fn from(t: JsScoreThreshold) -> Self {
+    Self { score: t.score.into(), min: t.min.map(|v| v as f32), max: t.max.map(|v| v as f32) }
+  }
+}
+
+#[napi(string_enum)]
+pub enum JsScoreKind {
+  Trust,
+  Bot,
+  Fraud,
+  Abuse,
+}
+
+impl From<JsScoreKind> for ScoreKind {
+  fn from(k: JsScoreKind) -> Self {
+    match k {
+      JsScoreKind::Trust => ScoreKind::Trust,
+      JsScoreKind::Bot => ScoreKind::Bot,
+      JsScoreKind::Fraud => ScoreKind::Fraud,
+      JsScoreKind::Abuse => ScoreKind::Abuse,
+    }
+  }
 }
 
 #[napi(object)]
 pub struct JsSessionExtraction {
+  pub sources: Option<Vec<String>>,
   pub cookie_name: Option<String>,
+  pub cookie_names: Option<Vec<String>>,
+  pub cookie_prefixes: Option<bool>,
+  pub cookie_duplicates: Option<JsCookieDuplicates>,
   pub header_name: Option<String>,
   pub header_bearer: Option<bool>,
+  pub bearer_schemes: Option<Vec<String>>,
+  pub bearer_require_scheme: Option<bool>,
+  pub query_param: Option<String>,
+  pub jwt: Option<JsJwtConfig>,
+  pub device_sources: Option<Vec<String>>,
+  pub max_length: Option<u32>,
+  pub pattern: Option<String>,
+}
+
+#[napi(string_enum)]
+pub enum JsEventKind {
+  Login,
+  LoginFailed,
+  Signup,
+  Checkout,
+  PasswordReset,
+}
+
+impl From<JsEventKind> for EventKind {
+  fn from(k: JsEventKind) -> Self {
+    match k {
+      JsEventKind::Login => EventKind::Login,
+      JsEventKind::LoginFailed => EventKind::LoginFailed,
+      JsEventKind::Signup => EventKind::Signup,
+      JsEventKind::Checkout => EventKind::Checkout,
+      JsEventKind::PasswordReset => EventKind::PasswordReset,
+    }
+  }
+}
+
+#[napi(string_enum)]
+pub enum JsOutcome {
+  Fraud,
+  Legit,
+}
+
+impl From<JsOutcome> for Outcome {
+  fn from(o: JsOutcome) -> Self {
+    match o {
+      JsOutcome::Fraud => Outcome::Fraud,
+      JsOutcome::Legit => Outcome::Legit,
+    }
+  }
+}
+
+#[napi(string_enum)]
+pub enum JsCookieDuplicates {
+  First,
+  Last,
+}
+
+#[napi(object)]
+pub struct JsJwtConfig {
+  pub claim: Option<String>,
+  pub jwks_url: Option<String>,
+  pub jwks_refresh_ms: Option<u32>,
+  pub issuer: Option<String>,
+  pub audience: Option<String>,
+}
+
+impl From<JsJwtConfig> for JwtConfig {
+  fn from(j: JsJwtConfig) -> Self {
+    Self {
+      claim: j.claim.unwrap_or_else(|| "sid".into()),
+      jwks_url: j.jwks_url,
+      jwks_refresh_ms: j.jwks_refresh_ms.map_or(600_000, u64::from),
+      issuer: j.issuer,
+      audience: j.audience,
+    }
+  }
+}
+
+#[napi(object)]
+pub struct JsSigningConfig {
+  pub key: Option<String>,
+  pub timestamp_header: Option<String>,
+  pub nonce_header: Option<String>,
+  pub signature_header: Option<String>,
+}
+
+impl From<JsSigningConfig> for SigningConfig {
+  fn from(s: JsSigningConfig) -> Self {
+    Self {
+      key: s.key.unwrap_or_default().into(),
+      timestamp_header: s.timestamp_header.unwrap_or_else(|| "x-eguard-timestamp".into()),
+      nonce_header: s.nonce_header.unwrap_or_else(|| "x-eguard-nonce".into()),
+      signature_header: s.signature_header.unwrap_or_else(|| "x-eguard-signature".into()),
+    }
+  }
+}
+
+#[napi(object)]
+pub struct JsSecretSource {
+  #[napi(js_name = "type")]
+  pub kind: JsSecretSourceKind,
+  /// `Env` only.
+  pub var: Option<String>,
+  /// `File` and `Vault`.
+  pub path: Option<String>,
+  /// `AwsSecretsManager` only.
+  pub secret_id: Option<String>,
+  /// JSON field holding the secret; required for `Vault`.
+  pub key: Option<String>,
+  /// `Vault` only.
+  pub address: Option<String>,
+  pub token: Option<String>,
+}
+
+#[napi(string_enum)]
+pub enum JsSecretSourceKind {
+  Env,
+  File,
+  AwsSecretsManager,
+  Vault,
+}
+
+impl TryFrom<JsSecretSource> for SecretSource {
+  type Error = Error;
+
+  fn try_from(s: JsSecretSource) -> Result<Self> {
+    let missing = |field: &str| Error::from_reason(format!("secret source is missing `{field}`"));
+    Ok(match s.kind {
+      JsSecretSourceKind::Env => SecretSource::Env { var: s.var.ok_or_else(|| missing("var"))? },
+      JsSecretSourceKind::File => SecretSource::File { path: s.path.ok_or_else(|| missing("path"))? },
+      JsSecretSourceKind::AwsSecretsManager => SecretSource::AwsSecretsManager {
+        secret_id: s.secret_id.ok_or_else(|| missing("secretId"))?,
+        key: s.key,
+      },
+      JsSecretSourceKind::Vault => SecretSource::Vault {
+        address: s.address.ok_or_else(|| missing("address"))?,
+        path: s.path.ok_or_else(|| missing("path"))?,
+        key: s.key.ok_or_else(|| missing("key"))?,
+        token: s.token.map(Into::into),
+      },
+    })
+  }
+}
+
+#[napi(object)]
+pub struct JsSecretsConfig {
+  pub api_key: Option<JsSecretSource>,
+  pub signing_key: Option<JsSecretSource>,
+  pub refresh_ms: Option<u32>,
+}
+
+impl TryFrom<JsSecretsConfig> for SecretsConfig {
+  type Error = Error;
+
+  fn try_from(s: JsSecretsConfig) -> Result<Self> {
+    Ok(SecretsConfig {
+      api_key: s.api_key.map(TryInto::try_into).transpose()?,
+      signing_key: s.signing_key.map(TryInto::try_into).transpose()?,
+      refresh_ms: s.refresh_ms.map_or(300_000, u64::from),
+    })
+  }
+}
+
+#[napi(object)]
+pub struct JsRetryPolicy {
+  pub max_attempts: Option<u32>,
+  pub base_delay_ms: Option<u32>,
+  pub max_delay_ms: Option<u32>,
+  pub jitter: Option<bool>,
+}
+
+impl From<JsRetryPolicy> for RetryPolicy {
+  fn from(r: JsRetryPolicy) -> Self {
+    let d = RetryPolicy::default();
+    RetryPolicy {
+      max_attempts: r.max_attempts.unwrap_or(d.max_attempts),
+      base_delay_ms: r.base_delay_ms.map_or(d.base_delay_ms, u64::from),
+      max_delay_ms: r.max_delay_ms.map_or(d.max_delay_ms, u64::from),
+      jitter: r.jitter.unwrap_or(d.jitter),
+    }
+  }
+}
+
+#[napi(object)]
+pub struct JsThrottle {
+  pub default_backoff_ms: Option<u32>,
+  pub max_backoff_ms: Option<u32>,
+  pub fallback: Option<JsFailureMode>,
+}
+
+impl From<JsThrottle> for ThrottleConfig {
+  fn from(t: JsThrottle) -> Self {
+    let d = ThrottleConfig::default();
+    ThrottleConfig {
+      default_backoff_ms: t.default_backoff_ms.map_or(d.default_backoff_ms, u64::from),
+      max_backoff_ms: t.max_backoff_ms.map_or(d.max_backoff_ms, u64::from),
+      fallback: t.fallback.map(Into::into),
+    }
+  }
+}
+
+#[napi(object)]
+pub struct JsCircuitBreaker {
+  pub failure_threshold: Option<u32>,
+  pub open_ms: Option<u32>,
+  pub fallback: Option<JsDecision>,
+}
+
+impl From<JsCircuitBreaker> for CircuitBreakerConfig {
+  fn from(b: JsCircuitBreaker) -> Self {
+    let d = CircuitBreakerConfig::default();
+    CircuitBreakerConfig {
+      failure_threshold: b.failure_threshold.unwrap_or(d.failure_threshold),
+      open_ms: b.open_ms.map_or(d.open_ms, u64::from),
+      fallback: b.fallback.map_or(d.fallback, Into::into),
+    }
+  }
+}
+
+#[napi(string_enum)]
+pub enum JsShedPolicy {
+  Fallback,
+  Queue,
+}
+
+#[napi(object)]
+pub struct JsConcurrencyLimit {
+  pub max_in_flight: u32,
+  pub shed: Option<JsShedPolicy>,
+  pub queue_timeout_ms: Option<u32>,
+}
+
+impl From<JsConcurrencyLimit> for ConcurrencyLimitConfig {
+  fn from(c: JsConcurrencyLimit) -> Self {
+    ConcurrencyLimitConfig {
+      max_in_flight: c.max_in_flight as usize,
+      shed: match c.shed {
+        Some(JsShedPolicy::Queue) => ShedPolicy::Queue { timeout_ms: c.queue_timeout_ms.map_or(100, u64::from) },
+        Some(JsShedPolicy::Fallback) | None => ShedPolicy::Fallback,
+      },
+    }
+  }
+}
+
+#[napi(object)]
+pub struct JsHedge {
+  pub delay_ms: Option<u32>,
+  pub percentile: Option<f64>,
+}
+
+impl From<JsHedge> for HedgeConfig {
+  fn from(h: JsHedge) -> Self {
+    let d = HedgeConfig::default();
+    HedgeConfig { delay_ms: h.delay_ms.map_or(d.delay_ms, u64::from), percentile: h.percentile }
+  }
+}
+
+#[napi(object)]
+pub struct JsAdaptiveTimeout {
+  pub percentile: Option<f64>,
+  pub multiplier: Option<f64>,
+  pub min_ms: Option<u32>,
+  pub window: Option<u32>,
+  pub min_samples: Option<u32>,
+}
+
+impl From<JsAdaptiveTimeout> for AdaptiveTimeoutConfig {
+  fn from(a: JsAdaptiveTimeout) -> Self {
+    let d = AdaptiveTimeoutConfig::default();
+    AdaptiveTimeoutConfig {
+      percentile: a.percentile.unwrap_or(d.percentile),
+      multiplier: a.multiplier.unwrap_or(d.multiplier),
+      min_ms: a.min_ms.map_or(d.min_ms, u64::from),
+      window: a.window.map_or(d.window, |w| w as usize),
+      min_samples: a.min_samples.map_or(d.min_samples, |n| n as usize),
+    }
+  }
+}
+
+#[napi(string_enum)]
+pub enum JsFailureModeKind {
+  FailOpen,
+  FailClosed,
+  Custom,
+}
+
+#[napi(object)]
+pub struct JsFailureMode {
+  pub mode: JsFailureModeKind,
+  pub status: Option<u16>,
+  pub message: Option<String>,
+}
+
+impl From<JsFailureMode> for FailureMode {
+  fn from(f: JsFailureMode) -> Self {
+    match f.mode {
+      JsFailureModeKind::FailOpen => FailureMode::FailOpen,
+      JsFailureModeKind::FailClosed => FailureMode::FailClosed,
+      JsFailureModeKind::Custom => FailureMode::Custom {
+        status: f.status.unwrap_or(403),
+        message: f.message.unwrap_or_default(),
+      },
+    }
+  }
+}
+
+#[napi(string_enum)]
+pub enum JsTransportKind {
+  Http,
+  Grpc,
+}
+
+impl From<JsTransportKind> for TransportKind {
+  fn from(t: JsTransportKind) -> Self {
+    match t {
+      JsTransportKind::Http => TransportKind::Http,
+      JsTransportKind::Grpc => TransportKind::Grpc,
+    }
+  }
+}
+
+#[napi(string_enum)]
+pub enum JsResponseParsing {
+  Lenient,
+  Strict,
+}
+
+impl From<JsResponseParsing> for ResponseParsing {
+  fn from(p: JsResponseParsing) -> Self {
+    match p {
+      JsResponseParsing::Lenient => ResponseParsing::Lenient,
+      JsResponseParsing::Strict => ResponseParsing::Strict,
+    }
+  }
+}
+
+#[napi(string_enum)]
+pub enum JsProtocolVersion {
+  V1,
+  V2,
+}
+
+impl From<JsProtocolVersion> for ProtocolVersion {
+  fn from(v: JsProtocolVersion) -> Self {
+    match v {
+      JsProtocolVersion::V1 => ProtocolVersion::V1,
+      JsProtocolVersion::V2 => ProtocolVersion::V2,
+    }
+  }
+}
+
+#[napi(string_enum)]
+pub enum JsWireFormat {
+  Json,
+  Cbor,
+  MessagePack,
+}
+
+impl From<JsWireFormat> for WireFormat {
+  fn from(f: JsWireFormat) -> Self {
+    match f {
+      JsWireFormat::Json => WireFormat::Json,
+      JsWireFormat::Cbor => WireFormat::Cbor,
+      JsWireFormat::MessagePack => WireFormat::MessagePack,
+    }
+  }
+}
+
+#[napi(object)]
+pub struct JsIpRules {
+  pub allow: Option<Vec<String>>,
+  pub deny: Option<Vec<String>>,
+}
+
+impl From<JsIpRules> for IpRulesConfig {
+  fn from(r: JsIpRules) -> Self {
+    Self { allow: r.allow.unwrap_or_default(), deny: r.deny.unwrap_or_default() }
+  }
+}
+
+#[napi(string_enum)]
+pub enum JsEndpointSelection {
+  Priority,
+  Latency,
+}
+
+#[napi(object)]
+pub struct JsHealthCheck {
+  pub interval_ms: Option<u32>,
+  pub session_id: Option<String>,
+}
+
+impl From<JsHealthCheck> for HealthCheckConfig {
+  fn from(h: JsHealthCheck) -> Self {
+    let d = HealthCheckConfig::default();
+    HealthCheckConfig {
+      interval_ms: h.interval_ms.map_or(d.interval_ms, u64::from),
+      session_id: h.session_id.unwrap_or(d.session_id),
+    }
+  }
+}
+
+#[napi(object)]
+pub struct JsEndpoints {
+  pub api_base_urls: Vec<String>,
+  pub selection: Option<JsEndpointSelection>,
+  pub unhealthy_after: Option<u32>,
+  pub retry_after_ms: Option<u32>,
+  pub health_check: Option<JsHealthCheck>,
+}
+
+impl From<JsEndpoints> for EndpointsConfig {
+  fn from(e: JsEndpoints) -> Self {
+    EndpointsConfig {
+      api_base_urls: e.api_base_urls,
+      selection: match e.selection {
+        Some(JsEndpointSelection::Latency) => EndpointSelection::Latency,
+        Some(JsEndpointSelection::Priority) | None => EndpointSelection::Priority,
+      },
+      unhealthy_after: e.unhealthy_after.unwrap_or(3),
+      retry_after_ms: e.retry_after_ms.map_or(10_000, u64::from),
+      health_check: e.health_check.map(Into::into),
+    }
+  }
+}
+
+#[napi(object)]
+pub struct JsFallbackConfig {
+  pub api_base_url: String,
+  pub api_key: Option<String>,
+  pub timeout_ms: Option<u32>,
+}
+
+impl From<JsFallbackConfig> for FallbackConfig {
+  fn from(f: JsFallbackConfig) -> Self {
+    Self { api_base_url: f.api_base_url, api_key: f.api_key.map(Into::into), timeout_ms: f.timeout_ms.map(u64::from) }
+  }
+}
+
+#[napi(object)]
+pub struct JsLocalScorerConfig {
+  pub base_score: Option<f64>,
+  pub bot_user_agents: Option<Vec<String>>,
+  pub user_agent_penalty: Option<f64>,
+  pub datacenter_asns: Option<Vec<u32>>,
+  pub datacenter_penalty: Option<f64>,
+  pub max_requests_per_minute: Option<u32>,
+  pub rate_penalty: Option<f64>,
+  pub max_sessions: Option<u32>,
+}
+
+impl From<JsLocalScorerConfig> for LocalScorerConfig {
+  fn from(l: JsLocalScorerConfig) -> Self {
+    let d = LocalScorerConfig::default();
+    Self {
+      base_score: l.base_score.map_or(d.base_score, |v| v as f32),
+      bot_user_agents: l.bot_user_agents.unwrap_or(d.bot_user_agents),
+      user_agent_penalty: l.user_agent_penalty.map_or(d.user_agent_penalty, |v| v as f32),
+      datacenter_asns: l.datacenter_asns.unwrap_or(d.datacenter_asns),
+      datacenter_penalty: l.datacenter_penalty.map_or(d.datacenter_penalty, |v| v as f32),
+      max_requests_per_minute: l.max_requests_per_minute.unwrap_or(d.max_requests_per_minute),
+      rate_penalty: l.rate_penalty.map_or(d.rate_penalty, |v| v as f32),
+      max_sessions: l.max_sessions.map_or(d.max_sessions, |v| v as usize),
+    }
+  }
+}
+
+#[napi(object)]
+pub struct JsSessionHashing {
+  pub salt: String,
+}
+
+#[napi(object)]
+pub struct JsDnsConfig {
+  pub ttl_ms: Option<u32>,
+  pub addresses: Option<HashMap<String, Vec<String>>>,
+}
+
+impl TryFrom<JsDnsConfig> for DnsConfig {
+  type Error = Error;
+
+  fn try_from(d: JsDnsConfig) -> Result<Self> {
+    let addresses = d.addresses.unwrap_or_default().into_iter()
+      .map(|(host, ips)| {
+        let ips = ips.iter()
+          .map(|ip| ip.parse().map_err(|_| Error::from_reason(format!("Invalid address `{}` for `{}`", ip, host))))
+          .collect::<Result<_>>()?;
+        Ok((host, ips))
+      })
+      .collect::<Result<_>>()?;
+    Ok(Self { ttl_ms: d.ttl_ms.map_or(DnsConfig::default().ttl_ms, Into::into), addresses })
+  }
+}
+
+#[napi(object)]
+pub struct JsPoolConfig {
+  pub max_idle_per_host: Option<u32>,
+  pub idle_timeout_ms: Option<u32>,
+  pub tcp_keepalive_ms: Option<u32>,
+  pub http2_prior_knowledge: Option<bool>,
+  pub http2_keep_alive_interval_ms: Option<u32>,
+}
+
+impl From<JsPoolConfig> for PoolConfig {
+  fn from(p: JsPoolConfig) -> Self {
+    Self {
+      max_idle_per_host: p.max_idle_per_host.map(|n| n as usize),
+      idle_timeout_ms: p.idle_timeout_ms.map(Into::into),
+      tcp_keepalive_ms: p.tcp_keepalive_ms.map(Into::into),
+      http2_prior_knowledge: p.http2_prior_knowledge.unwrap_or(false),
+      http2_keep_alive_interval_ms: p.http2_keep_alive_interval_ms.map(Into::into),
+    }
+  }
+}
+
+#[napi(object)]
+pub struct JsProxyConfig {
+  pub url: String,
+  pub username: Option<String>,
+  pub password: Option<String>,
+  pub no_proxy: Option<String>,
+}
+
+impl From<JsProxyConfig> for ProxyConfig {
+  fn from(p: JsProxyConfig) -> Self {
+    Self { url: p.url, username: p.username, password: p.password.map(Into::into), no_proxy: p.no_proxy }
+  }
+}
+
+#[napi(object)]
+pub struct JsRateLimit {
+  pub key: Option<JsRateLimitKey>,
+  pub requests_per_second: f64,
+  pub burst: Option<u32>,
+  pub action: Option<JsRateLimitAction>,
+  pub max_keys: Option<u32>,
+}
+
+#[napi(string_enum)]
+pub enum JsRateLimitKey {
+  Session,
+  Ip,
+}
+
+#[napi(string_enum)]
+pub enum JsRateLimitAction {
+  Deny,
+  Flag,
+}
+
+impl From<JsRateLimit> for RateLimitConfig {
+  fn from(r: JsRateLimit) -> Self {
+    Self {
+      key: match r.key {
+        Some(JsRateLimitKey::Ip) => RateLimitKey::Ip,
+        Some(JsRateLimitKey::Session) | None => RateLimitKey::Session,
+      },
+      requests_per_second: r.requests_per_second,
+      burst: r.burst,
+      action: match r.action {
+        Some(JsRateLimitAction::Flag) => RateLimitAction::Flag,
+        Some(JsRateLimitAction::Deny) | None => RateLimitAction::Deny,
+      },
+      max_keys: r.max_keys.map_or(100_000, |n| n as usize),
+    }
+  }
+}
+
+#[napi(object)]
+pub struct JsDecisionSink {
+  #[napi(js_name = "type")]
+  pub kind: JsDecisionSinkKind,
+  /// `Http` only.
+  pub url: Option<String>,
+  pub headers: Option<HashMap<String, String>>,
+  /// `Kafka` only.
+  pub brokers: Option<Vec<String>>,
+  pub topic: Option<String>,
+  pub partition: Option<i32>,
+}
+
+#[napi(string_enum)]
+pub enum JsDecisionSinkKind {
+  Stdout,
+  Http,
+  Kafka,
+}
+
+impl TryFrom<JsDecisionSink> for DecisionSinkConfig {
+  type Error = Error;
+
+  fn try_from(s: JsDecisionSink) -> Result<Self> {
+    let missing = |field: &str| Error::from_reason(format!("decision sink is missing `{field}`"));
+    Ok(match s.kind {
+      JsDecisionSinkKind::Stdout => DecisionSinkConfig::Stdout,
+      JsDecisionSinkKind::Http => DecisionSinkConfig::Http {
+        url: s.url.ok_or_else(|| missing("url"))?,
+        headers: s.headers.unwrap_or_default().into_iter().collect(),
+      },
+      JsDecisionSinkKind::Kafka => DecisionSinkConfig::Kafka {
+        brokers: s.brokers.ok_or_else(|| missing("brokers"))?,
+        topic: s.topic.ok_or_else(|| missing("topic"))?,
+        partition: s.partition.unwrap_or(0),
+      },
+    })
+  }
+}
+
+#[napi(object)]
+pub struct JsGeoIpConfig {
+  pub country_db_path: String,
+  pub asn_db_path: Option<String>,
+}
+
+impl From<JsGeoIpConfig> for GeoIpConfig {
+  fn from(g: JsGeoIpConfig) -> Self {
+    Self { country_db_path: g.country_db_path, asn_db_path: g.asn_db_path }
+  }
+}
+
+#[napi(object)]
+pub struct JsTlsFingerprint {
+  pub ja3_header: Option<String>,
+  pub ja4_header: Option<String>,
+}
+
+impl From<JsTlsFingerprint> for TlsFingerprintConfig {
+  fn from(t: JsTlsFingerprint) -> Self {
+    Self { ja3_header: t.ja3_header, ja4_header: t.ja4_header }
+  }
+}
+
+#[napi(object)]
+pub struct JsRefreshConfig {
+  pub interval_ms: Option<u32>,
+  pub refresh_ahead_ms: Option<u32>,
+}
+
+impl From<JsRefreshConfig> for RefreshConfig {
+  fn from(r: JsRefreshConfig) -> Self {
+    let d = RefreshConfig::default();
+    RefreshConfig {
+      interval_ms: r.interval_ms.map_or(d.interval_ms, u64::from),
+      refresh_ahead_ms: r.refresh_ahead_ms.map_or(d.refresh_ahead_ms, u64::from),
+    }
+  }
+}
+
+#[napi(object)]
+pub struct JsDenylistConfig {
+  pub interval_ms: Option<u32>,
+}
+
+impl From<JsDenylistConfig> for DenylistConfig {
+  fn from(d: JsDenylistConfig) -> Self {
+    DenylistConfig {
+      interval_ms: d.interval_ms.map_or(DenylistConfig::default().interval_ms, u64::from),
+    }
+  }
+}
+
+#[napi(object)]
+pub struct JsPushConfig {
+  pub path: Option<String>,
+  pub reconnect_ms: Option<u32>,
+}
+
+impl From<JsPushConfig> for PushConfig {
+  fn from(p: JsPushConfig) -> Self {
+    let d = PushConfig::default();
+    PushConfig {
+      path: p.path.unwrap_or(d.path),
+      reconnect_ms: p.reconnect_ms.map_or(d.reconnect_ms, u64::from),
+    }
+  }
 }
 
 #[napi(object)]
 pub struct JsEGuardConfig {
   pub api_base_url: String,
   pub api_key: String,
+  pub secondary_api_key: Option<String>,
   pub secure_routes: Vec<JsSecureRoute>,
+  pub exclude_routes: Option<Vec<String>>,
+  pub ignore_trailing_slash: Option<bool>,
+  pub ip_rules: Option<JsIpRules>,
+  pub geoip: Option<JsGeoIpConfig>,
+  pub tls_fingerprint: Option<JsTlsFingerprint>,
+  pub rules: Option<Vec<String>>,
+  pub rate_limit: Option<JsRateLimit>,
   pub session_extraction: JsSessionExtraction,
-  pub min_trust_score: f64,
+  pub min_trust_score: Option<f64>,
+  pub risk_bands: Option<Vec<JsRiskBand>>,
+  pub enforcement_percentage: Option<f64>,
+  pub challenge: Option<JsChallengeConfig>,
   pub timeout_ms: Option<u32>,
+  pub adaptive_timeout: Option<JsAdaptiveTimeout>,
+  pub hedge: Option<JsHedge>,
+  pub cache_ttl_ms: Option<u32>,
+  pub cache_max_entries: Option<u32>,
+  pub negative_cache_ttl_ms: Option<u32>,
+  pub serve_stale_ms: Option<u32>,
+  pub cache_redis_url: Option<String>,
+  pub retry: Option<JsRetryPolicy>,
+  pub throttle: Option<JsThrottle>,
+  pub circuit_breaker: Option<JsCircuitBreaker>,
+  pub concurrency_limit: Option<JsConcurrencyLimit>,
+  pub failure_mode: Option<JsFailureMode>,
+  pub transport: Option<JsTransportKind>,
+  pub response_parsing: Option<JsResponseParsing>,
+  pub protocol_version: Option<JsProtocolVersion>,
+  pub wire_format: Option<JsWireFormat>,
+  pub endpoints: Option<JsEndpoints>,
+  pub fallbacks: Option<Vec<JsFallbackConfig>>,
+  pub local_scorer: Option<JsLocalScorerConfig>,
+  pub proxy: Option<JsProxyConfig>,
+  pub pool: Option<JsPoolConfig>,
+  pub dns: Option<JsDnsConfig>,
+  pub batch_max_size: Option<u32>,
+  pub webhook_secret: Option<String>,
+  pub request_signing: Option<JsSigningConfig>,
+  pub session_hashing: Option<JsSessionHashing>,
+  pub secrets: Option<JsSecretsConfig>,
+  pub refresh: Option<JsRefreshConfig>,
+  pub denylist: Option<JsDenylistConfig>,
+  pub push: Option<JsPushConfig>,
+  pub audit_log_path: Option<String>,
+  pub decision_sinks: Option<Vec<JsDecisionSink>>,
+  pub response_headers: Option<JsResponseHeadersConfig>,
+  pub deny_body: Option<JsDenyBody>,
+  pub tenants: Option<HashMap<String, JsTenantConfig>>,
+  pub tenant_header: Option<String>,
+}
+
+impl TryFrom<JsEGuardConfig> for EGuardConfig {
+  type Error = Error;
+
+  fn try_from(cfg: JsEGuardConfig) -> Result<Self> {
+    Ok(Self {
+      api_base_url: cfg.api_base_url,
+      api_key: cfg.api_key.into(),
+      secondary_api_key: cfg.secondary_api_key.map(Into::into),
+      secure_routes: cfg.secure_routes.into_iter().map(TryInto::try_into).collect::<Result<_>>()?,
+      exclude_routes: cfg.exclude_routes.unwrap_or_default(),
+      ignore_trailing_slash: cfg.ignore_trailing_slash.unwrap_or(false),
+      ip_rules: cfg.ip_rules.map(Into::into),
+      geoip: cfg.geoip.map(Into::into),
+      tls_fingerprint: cfg.tls_fingerprint.map(Into::into),
+      rules: cfg.rules.unwrap_or_default(),
+      rate_limit: cfg.rate_limit.map(Into::into),
+      session_extraction: SessionExtraction {
+        sources: cfg
+          .session_extraction
+          .sources
+          .unwrap_or_default()
+          .iter()
+          .map(|s| s.parse::<SessionSource>().map_err(|e| Error::from_reason(e.to_string())))
+          .collect::<Result<_>>()?,
+        cookie_name: cfg.session_extraction.cookie_name,
+        cookie_names: cfg.session_extraction.cookie_names.unwrap_or_default(),
+        cookie_prefixes: cfg.session_extraction.cookie_prefixes.unwrap_or(false),
+        cookie_duplicates: match cfg.session_extraction.cookie_duplicates {
+          Some(JsCookieDuplicates::Last) => CookieDuplicates::Last,
+          Some(JsCookieDuplicates::First) | None => CookieDuplicates::First,
+        },
+        header_name: cfg.session_extraction.header_name,
+        header_bearer: cfg.session_extraction.header_bearer.unwrap_or(false),
+        bearer_schemes: cfg.session_extraction.bearer_schemes.unwrap_or_else(|| vec!["Bearer".to_string()]),
+        bearer_require_scheme: cfg.session_extraction.bearer_require_scheme.unwrap_or(false),
+        query_param: cfg.session_extraction.query_param,
+        jwt: cfg.session_extraction.jwt.map(Into::into),
+        device_sources: cfg
+          .session_extraction
+          .device_sources
+          .unwrap_or_default()
+          .iter()
+          .map(|s| s.parse::<SessionSource>().map_err(|e| Error::from_reason(e.to_string())))
+          .collect::<Result<_>>()?,
+        max_length: cfg.session_extraction.max_length.map(|n| n as usize),
+        pattern: cfg.session_extraction.pattern,
+      },
+      
+      min_trust_score: cfg.min_trust_score.map(|v| v as f32),
+      risk_bands: cfg.risk_bands.map(risk_bands).transpose()?.unwrap_or_default(),
+      enforcement_percentage: cfg.enforcement_percentage.map_or(100.0, |p| p as f32),
+      challenge: cfg.challenge.map(Into::into),
+      timeout_ms: cfg.timeout_ms.unwrap_or(1500) as u64,
+      adaptive_timeout: cfg.adaptive_timeout.map(Into::into),
+      hedge: cfg.hedge.map(Into::into),
+      cache_ttl_ms: cfg.cache_ttl_ms.unwrap_or(0) as u64,
+      cache_max_entries: cfg.cache_max_entries.unwrap_or(10_000) as usize,
+      negative_cache_ttl_ms: cfg.negative_cache_ttl_ms.unwrap_or(0) as u64,
+      serve_stale_ms: cfg.serve_stale_ms.unwrap_or(0) as u64,
+      cache_redis_url: cfg.cache_redis_url,
+      retry: cfg.retry.map(Into::into).unwrap_or_default(),
+      throttle: cfg.throttle.map(Into::into).unwrap_or_default(),
+      circuit_breaker: cfg.circuit_breaker.map(Into::into),
+      concurrency_limit: cfg.concurrency_limit.map(Into::into),
+      failure_mode: cfg.failure_mode.map(Into::into),
+      transport: cfg.transport.map(Into::into).unwrap_or_default(),
+      response_parsing: cfg.response_parsing.map(Into::into).unwrap_or_default(),
+      protocol_version: cfg.protocol_version.map(Into::into).unwrap_or_default(),
+      wire_format: cfg.wire_format.map(Into::into).unwrap_or_default(),
+      endpoints: cfg.endpoints.map(Into::into),
+      fallbacks: cfg.fallbacks.unwrap_or_default().into_iter().map(Into::into).collect(),
+      local_scorer: cfg.local_scorer.map(Into::into),
+      proxy: cfg.proxy.map(Into::into),
+      pool: cfg.pool.map(Into::into),
+      dns: cfg.dns.map(TryInto::try_into).transpose()?,
+      batch_max_size: cfg.batch_max_size.unwrap_or(100) as usize,
+      webhook_secret: cfg.webhook_secret.map(Into::into),
+      request_signing: cfg.request_signing.map(Into::into),
+      session_hashing: cfg.session_hashing.map(|h| SessionHashingConfig { salt: h.salt.into() }),
+      secrets: cfg.secrets.map(TryInto::try_into).transpose()?,
+      refresh: cfg.refresh.map(Into::into),
+      denylist: cfg.denylist.map(Into::into),
+      push: cfg.push.map(Into::into),
+      audit_log_path: cfg.audit_log_path,
+      decision_sinks: cfg
+        .decision_sinks
+        .unwrap_or_default()
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<_>>()?,
+      response_headers: cfg.response_headers.map(Into::into),
+      deny_body: cfg.deny_body.map(TryInto::try_into).transpose()?,
+      tenants: cfg
+        .tenants
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, t)| Ok((name, t.try_into()?)))
+        .collect::<Result<_>>()?,
+      tenant_header: cfg.tenant_header,
+    })
+  }
+}
+
+#[napi(object)]
+pub struct JsResponseHeadersConfig {
+  pub score: Option<bool>,
+  pub decision: Option<bool>,
+  pub request_id: Option<bool>,
+  pub request_id_header: Option<String>,
+  pub forward: Option<bool>,
+}
+
+impl From<JsResponseHeadersConfig> for ResponseHeadersConfig {
+  fn from(h: JsResponseHeadersConfig) -> Self {
+    let d = ResponseHeadersConfig::default();
+    Self {
+      score: h.score.unwrap_or(d.score),
+      decision: h.decision.unwrap_or(d.decision),
+      request_id: h.request_id.unwrap_or(d.request_id),
+      request_id_header: h.request_id_header.or(d.request_id_header),
+      forward: h.forward.unwrap_or(d.forward),
+    }
+  }
+}
+
+#[napi(object)]
+pub struct JsDenyBody {
+  #[napi(js_name = "type")]
+  pub kind: JsDenyBodyKind,
+  /// `Json` only.
+  pub template: Option<serde_json::Value>,
+  /// `Html` only.
+  pub path: Option<String>,
+}
+
+#[napi(string_enum)]
+pub enum JsDenyBodyKind {
+  Json,
+  Html,
+}
+
+impl TryFrom<JsDenyBody> for DenyBodyConfig {
+  type Error = Error;
+
+  fn try_from(b: JsDenyBody) -> Result<Self> {
+    let missing = |field: &str| Error::from_reason(format!("deny body is missing `{field}`"));
+    Ok(match b.kind {
+      JsDenyBodyKind::Json => DenyBodyConfig::Json { template: b.template.ok_or_else(|| missing("template"))? },
+      JsDenyBodyKind::Html => DenyBodyConfig::Html { path: b.path.ok_or_else(|| missing("path"))? },
+    })
+  }
+}
+
+#[napi(object)]
+pub struct JsTenantConfig {
+  pub hosts: Option<Vec<String>>,
+  pub api_key: Option<String>,
+  pub secondary_api_key: Option<String>,
+  pub api_base_url: Option<String>,
+  pub secure_routes: Option<Vec<JsSecureRoute>>,
+  pub min_trust_score: Option<f64>,
+  pub risk_bands: Option<Vec<JsRiskBand>>,
+}
+
+impl TryFrom<JsTenantConfig> for TenantConfig {
+  type Error = Error;
+
+  fn try_from(t: JsTenantConfig) -> Result<Self> {
+    Ok(Self {
+      hosts: t.hosts.unwrap_or_default(),
+      api_key: t.api_key.map(Into::into),
+      secondary_api_key: t.secondary_api_key.map(Into::into),
+      api_base_url: t.api_base_url,
+      secure_routes: t.secure_routes.map(|routes| routes.into_iter().map(TryInto::try_into).collect()).transpose()?,
+      min_trust_score: t.min_trust_score.map(|v| v as f32),
+      risk_bands: t.risk_bands.map(risk_bands).transpose()?,
+    })
+  }
 }
 
 #[napi(object)]
@@ -34,111 +1061,520 @@ pub struct JsDecision {
   pub allow: bool,
   pub status: Option<u16>,
   pub message: Option<String>,
+  pub challenge_kind: Option<JsChallengeKind>,
+  pub redirect_url: Option<String>,
+  pub trust_score: Option<f64>,
+  pub reason: Option<String>,
+  pub route_id: Option<String>,
+  /// `X-EGuard-*` headers for the response, when `responseHeaders` is configured.
+  pub headers: Option<HashMap<String, String>>,
+  /// Rendered `denyBody` for denies, with its content type.
+  pub body: Option<String>,
+  pub content_type: Option<String>,
+}
+
+/// A decision as passed to `onDecision` callbacks; the same fields as the audit log.
+#[napi(object)]
+pub struct JsDecisionRecord {
+  pub timestamp_ms: f64,
+  pub route: Option<String>,
+  pub session_hash: String,
+  pub trust_score: Option<f64>,
+  /// `allow`, `deny` or `challenge`.
+  pub outcome: String,
+  pub status: Option<u16>,
+  pub fallback: bool,
+  pub enforced: bool,
+  pub latency_ms: f64,
+}
+
+impl From<&DecisionRecord> for JsDecisionRecord {
+  fn from(r: &DecisionRecord) -> Self {
+    Self {
+      timestamp_ms: r.timestamp_ms as f64,
+      route: r.route.clone(),
+      session_hash: r.session_hash.clone(),
+      trust_score: r.trust_score.map(f64::from),
+      outcome: r.outcome.to_string(),
+      status: r.status,
+      fallback: r.fallback,
+      enforced: r.enforced,
+      latency_ms: r.latency_ms,
+    }
+  }
+}
+
+/// What `decideRequest` decided for a request.
+#[napi(object)]
+pub struct JsRequestDecision {
+  /// Whether the request is on a secure route; other requests are allowed unchecked.
+  pub matched: bool,
+  pub allow: bool,
+  pub status: Option<u16>,
+  pub message: Option<String>,
+  pub score: Option<f64>,
+  pub session_id: Option<String>,
+  pub challenge_kind: Option<JsChallengeKind>,
+  pub redirect_url: Option<String>,
+  pub headers: Option<HashMap<String, String>>,
+  pub body: Option<String>,
+  pub content_type: Option<String>,
+}
+
+impl From<RequestDecision> for JsRequestDecision {
+  fn from(d: RequestDecision) -> Self {
+    let js = JsDecision::from(d.decision);
+    JsRequestDecision {
+      matched: d.matched,
+      allow: js.allow,
+      status: js.status,
+      message: js.message,
+      score: d.trust.map(|t| f64::from(t.trust_score)),
+      session_id: d.session_id,
+      challenge_kind: js.challenge_kind,
+      redirect_url: js.redirect_url,
+      headers: Some(d.headers)
+        .filter(|h| !h.is_empty())
+        .map(|h| h.into_iter().map(|(k, v)| (k.to_string(), v)).collect()),
+      content_type: d.body.as_ref().map(|b| b.content_type.to_string()),
+      body: d.body.map(|b| b.body),
+    }
+  }
+}
+
+#[napi(string_enum)]
+pub enum JsChallengeKind {
+  Captcha,
+  Turnstile,
+  Hcaptcha,
+  Recaptcha,
+  StepUp,
+}
+
+impl From<JsChallengeKind> for ChallengeKind {
+  fn from(k: JsChallengeKind) -> Self {
+    match k {
+      JsChallengeKind::Captcha => ChallengeKind::Captcha,
+      JsChallengeKind::Turnstile => ChallengeKind::Turnstile,
+      JsChallengeKind::Hcaptcha => ChallengeKind::Hcaptcha,
+      JsChallengeKind::Recaptcha => ChallengeKind::Recaptcha,
+      JsChallengeKind::StepUp => ChallengeKind::StepUp,
+    }
+  }
+}
+
+impl From<ChallengeKind> for JsChallengeKind {
+  fn from(k: ChallengeKind) -> Self {
+    match k {
+      ChallengeKind::Captcha => JsChallengeKind::Captcha,
+      ChallengeKind::Turnstile => JsChallengeKind::Turnstile,
+      ChallengeKind::Hcaptcha => JsChallengeKind::Hcaptcha,
+      ChallengeKind::Recaptcha => JsChallengeKind::Recaptcha,
+      ChallengeKind::StepUp => JsChallengeKind::StepUp,
+    }
+  }
+}
+
+#[napi(object)]
+pub struct JsChallengeConfig {
+  pub below: f64,
+  pub kind: JsChallengeKind,
+  pub redirect_url: String,
+}
+
+impl From<JsChallengeConfig> for ChallengeConfig {
+  fn from(c: JsChallengeConfig) -> Self {
+    Self { below: c.below as f32, kind: c.kind.into(), redirect_url: c.redirect_url }
+  }
+}
+
+impl From<JsDecision> for Decision {
+  fn from(d: JsDecision) -> Self {
+    if d.allow {
+      Decision::Allow { trust_score: d.trust_score.map(|v| v as f32), reason: d.reason.map(ReasonCode::from), route_id: d.route_id }
+    } else if let (Some(kind), Some(redirect_url)) = (d.challenge_kind, d.redirect_url) {
+      Decision::Challenge { kind: kind.into(), redirect_url }
+    } else {
+      Decision::Deny {
+        status: d.status.unwrap_or(403),
+        message: d.message.unwrap_or_default(),
+      }
+    }
+  }
+}
+
+type Headers = HashMap<String, Option<Either<String, Vec<String>>>>;
+
+#[napi(object)]
+pub struct JsRuntimeOptions {
+  /// Worker threads of the tokio runtime (default: one per CPU core).
+  pub worker_threads: Option<u32>,
+  /// How long `dispose()` waits for background tasks to stop (default 5000).
+  pub shutdown_timeout_ms: Option<u32>,
+}
+
+/// Set by the first `JsEGuard`; the runtime can't be replaced after that.
+static RUNTIME_IN_USE: AtomicBool = AtomicBool::new(false);
+static RUNTIME_CONFIGURED: AtomicBool = AtomicBool::new(false);
+static SHUTDOWN_TIMEOUT_MS: AtomicU64 = AtomicU64::new(5000);
+
+/// Configure the tokio runtime shared by every `JsEGuard` in the process, worker
+/// threads included. Call it once, before the first `JsEGuard` is created. The runtime
+/// lives until the addon is unloaded, when napi shuts it down with the last environment.
+#[napi]
+pub fn configure_runtime(options: JsRuntimeOptions) -> Result<()> {
+  if RUNTIME_IN_USE.load(Ordering::SeqCst) {
+    return Err(Error::from_reason("configureRuntime must be called before the first JsEGuard is created"));
+  }
+  if RUNTIME_CONFIGURED.swap(true, Ordering::SeqCst) {
+    return Err(Error::from_reason("configureRuntime was already called"));
+  }
+  if let Some(ms) = options.shutdown_timeout_ms {
+    SHUTDOWN_TIMEOUT_MS.store(u64::from(ms), Ordering::SeqCst);
+  }
+  if let Some(threads) = options.worker_threads {
+    let rt = napi::tokio::runtime::Builder::new_multi_thread()
+      .worker_threads(threads.max(1) as usize)
+      .enable_all()
+      .build()
+      .map_err(|e| Error::from_reason(e.to_string()))?;
+    // napi starts its default runtime when the module loads; nothing has run on it yet,
+    // so swap it for ours.
+    create_custom_tokio_runtime(rt);
+    shutdown_async_runtime();
+    start_async_runtime();
+  }
+  Ok(())
+}
+
+/// Weak, so a registered callback doesn't keep the process alive.
+type DecisionCallback = ThreadsafeFunction<JsDecisionRecord, (), JsDecisionRecord, Status, false, true>;
+
+/// Forwards each decision to the callbacks registered with `onDecision`.
+#[derive(Default)]
+struct DecisionListeners(RwLock<Vec<DecisionCallback>>);
+
+impl DecisionLogger for DecisionListeners {
+  fn log(&self, record: &DecisionRecord) {
+    for callback in self.0.read().unwrap().iter() {
+      callback.call(record.into(), ThreadsafeFunctionCallMode::NonBlocking);
+    }
+  }
 }
 
+/// Errors are rejected with their kind leading the message, e.g. `RateLimited: ...`;
+/// see `EGuardError` for the kinds.
 #[napi]
 pub struct JsEGuard {
   inner: EGuard,
+  listeners: Arc<DecisionListeners>,
+  /// Refreshers, denylist sync, health checks and push listener, stopped by `dispose()`.
+  tasks: Mutex<Vec<RefreshHandle>>,
+  disposed: AtomicBool,
 }
 
 #[napi]
 impl JsEGuard {
-  #[napi(constructor)]
+  /// Runs on napi's tokio runtime, where the background tasks are spawned.
+  #[napi(constructor, async_runtime)]
   pub fn new(cfg: JsEGuardConfig) -> Result<Self> {
-    
-    RT.get_or_init(|| Runtime::new().expect("failed to create tokio runtime"));
+    RUNTIME_IN_USE.store(true, Ordering::SeqCst);
+    let listeners = Arc::new(DecisionListeners::default());
+    let inner = EGuard::new(cfg.try_into()?)
+      .map_err(js_error)?
+      .with_extra_decision_logger(listeners.clone());
+    let push = inner.spawn_push_listener().map_err(js_error)?;
+    let tasks = [
+      inner.spawn_refresher(),
+      inner.spawn_secret_refresher(),
+      inner.spawn_denylist_sync(),
+      inner.spawn_health_checker(),
+      push,
+    ];
+    Ok(Self {
+      inner,
+      listeners,
+      tasks: Mutex::new(tasks.into_iter().flatten().collect()),
+      disposed: AtomicBool::new(false),
+    })
+  }
 
-    let core_cfg = EGuardConfig {
-      api_base_url: cfg.api_base_url,
-      api_key: cfg.api_key,
-      secure_routes: cfg
-        .secure_routes
-        .into_iter()
-        .map(|r| SecureRoute {
-          path_pattern: r.path_pattern,
-          methods: r.methods,
-        })
-        .collect(),
-      session_extraction: SessionExtraction {
-        cookie_name: cfg.session_extraction.cookie_name,
-        header_name: cfg.session_extraction.header_name,
-        header_bearer: cfg.session_extraction.header_bearer.unwrap_or(false),
-      },
-      
-      min_trust_score: cfg.min_trust_score as f32,
-      timeout_ms: cfg.timeout_ms.unwrap_or(1500) as u64,
+  /// Stop the background tasks, waiting up to the runtime's `shutdownTimeoutMs`.
+  /// Calls that reach the Trust API reject afterwards; calling it again is a no-op.
+  /// The tokio runtime is left running: other guards and napi's own async calls share
+  /// it, and a shut-down custom runtime can't be restarted (napi would silently start a
+  /// default one). It stops when the addon is unloaded.
+  #[napi]
+  pub async fn dispose(&self) {
+    self.disposed.store(true, Ordering::SeqCst);
+    self.listeners.0.write().unwrap().clear();
+    let tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+    let timeout = Duration::from_millis(SHUTDOWN_TIMEOUT_MS.load(Ordering::SeqCst));
+    let stopped = async {
+      for task in tasks {
+        task.stop().await;
+      }
     };
+    let _ = napi::tokio::time::timeout(timeout, stopped).await;
+  }
 
-    let inner = EGuard::new(core_cfg).map_err(|e| Error::from_reason(e.to_string()))?;
-    Ok(Self { inner })
+  /// Swap routes, thresholds and session extraction without losing the cache; see
+  /// `EGuard::reload` for what can change. An invalid config is rejected and the
+  /// current one kept.
+  #[napi]
+  pub fn reload_config(&self, cfg: JsEGuardConfig) -> Result<()> {
+    self.inner.reload(cfg.try_into()?).map_err(js_error)
   }
 
+  /// Call `callback` with every decision this guard and its tenants make, e.g. to feed
+  /// your own logging or analytics. Session ids are only passed hashed.
   #[napi]
-  pub fn is_secure(&self, path: String, method: String) -> bool {
-    self.inner.is_secure(&path, &method)
+  pub fn on_decision(&self, callback: Function<JsDecisionRecord, ()>) -> Result<()> {
+    let callback = callback.build_threadsafe_function().callee_handled::<false>().weak::<true>().build()?;
+    self.listeners.0.write().unwrap().push(callback);
+    Ok(())
+  }
+
+  fn guard(&self) -> Result<&EGuard> {
+    if self.disposed.load(Ordering::SeqCst) {
+      return Err(Error::from_reason("JsEGuard has been disposed"));
+    }
+    Ok(&self.inner)
   }
 
   #[napi]
+  pub fn is_secure(&self, path: String, method: String, host: Option<String>) -> bool {
+    self.inner.is_secure_host(host.as_deref(), &path, &method)
+  }
+
+  // JWKS refreshes are spawned from here.
+  #[napi(async_runtime)]
   pub fn extract_session_id(
     &self,
     cookie_header: Option<String>,
     header_name: Option<String>,
     header_value: Option<String>,
+    query: Option<String>,
   ) -> Option<String> {
-    match (header_name, header_value) {
-      (Some(n), Some(v)) => self
-        .inner
-        .extract_session_id(cookie_header.as_deref(), Some((n.as_str(), v.as_str()))),
-      _ => self.inner.extract_session_id(cookie_header.as_deref(), None),
-    }
+    let header = header_name.as_deref().zip(header_value.as_deref());
+    self
+      .inner
+      .extract_session_id_with_query(cookie_header.as_deref(), header, query.as_deref())
+  }
+
+  /// Takes the raw request headers, e.g. Node's `req.headers`; names are matched
+  /// case-insensitively and repeated `cookie` headers are combined.
+  #[napi(async_runtime)]
+  pub fn extract_session_id_from_headers(
+    &self,
+    headers: Headers,
+    query: Option<String>,
+  ) -> Option<String> {
+    self.inner.extract_session_id_from_headers(header_pairs(&headers), query.as_deref())
   }
 
+  /// Counters and latency histogram in the Prometheus text exposition format.
   #[napi]
-  pub fn decide(&self, session_id: String) -> AsyncTask<DecideTask> {
-    AsyncTask::new(DecideTask {
-      guard: self.inner.clone(),
-      session_id,
-    })
+  pub fn metrics(&self) -> String {
+    self.inner.metrics().to_prometheus()
   }
-}
 
-pub struct DecideTask {
-  guard: EGuard,
-  session_id: String,
-}
+  /// URLs of the `endpoints` currently marked unhealthy.
+  #[napi]
+  pub fn unhealthy_endpoints(&self) -> Vec<String> {
+    self.inner.unhealthy_endpoints()
+  }
 
-impl DecideTask {
-  async fn run(&self) -> napi::Result<Decision> {
+  /// Switch to a new API key; the previous one stays in use as the secondary key.
+  #[napi]
+  pub fn rotate_key(&self, key: String) {
+    self.inner.rotate_key(&key);
+  }
+
+  /// Load the configured `secrets` now; they are also loaded in the background.
+  #[napi]
+  pub async fn load_secrets(&self) -> Result<()> {
+    self.guard()?.load_secrets().await.map_err(js_error)
+  }
+
+  #[napi]
+  pub async fn handle_webhook(&self, body: Buffer, timestamp: String, signature: String) -> Result<()> {
     self
-      .guard
-      .decide(&self.session_id)
+      .guard()?
+      .handle_webhook(&body, &timestamp, &signature)
+      .await
+      .map(|_| ())
+      .map_err(js_error)
+  }
+
+  /// Queue a behavioural event for the Trust API; it is sent in the background.
+  #[napi(async_runtime)]
+  pub fn report_event(
+    &self,
+    session_id: String,
+    kind: JsEventKind,
+    metadata: Option<serde_json::Map<String, serde_json::Value>>,
+  ) -> Result<()> {
+    self
+      .guard()?
+      .report_event(&session_id, kind.into(), metadata.unwrap_or_default());
+    Ok(())
+  }
+
+  /// Report a confirmed fraud or legitimate outcome for a session.
+  #[napi]
+  pub async fn report_outcome(&self, session_id: String, outcome: JsOutcome) -> Result<()> {
+    self
+      .guard()?
+      .report_outcome(&session_id, outcome.into())
+      .await
+      .map_err(js_error)
+  }
+
+  /// Ban a session: it is denied locally from the next request on, and the ban is
+  /// sent to the Trust API. `reason` defaults to `session_banned`.
+  #[napi]
+  pub async fn revoke_session(&self, session_id: String, reason: Option<String>) -> Result<()> {
+    let reason = reason.map_or(ReasonCode::SessionBanned, ReasonCode::from);
+    self
+      .guard()?
+      .revoke_session(&session_id, reason)
+      .await
+      .map_err(js_error)
+  }
+
+  /// Resolves on napi's tokio runtime, without holding a libuv worker thread.
+  #[napi]
+  pub async fn decide(&self, session_id: String) -> Result<JsDecision> {
+    decide(self.guard()?, &session_id).await
+  }
+
+  /// Route matching, session extraction and the trust check for one request in a single
+  /// call. `path` may include the query string; `cookies` is a parsed cookie object such as
+  /// Express's `req.cookies`, only used when `headers` has no `cookie` header, as parsed
+  /// values are decoded and lose duplicates. Requests without a
+  /// session are rejected with 401, and with 502 if the Trust API fails and no
+  /// `failureMode` is set.
+  #[napi]
+  pub async fn decide_request(
+    &self,
+    path: String,
+    method: String,
+    headers: Headers,
+    cookies: Option<HashMap<String, String>>,
+    ip: Option<String>,
+  ) -> Result<JsRequestDecision> {
+    let has_cookie_header = header_pairs(&headers).any(|(name, _)| name.eq_ignore_ascii_case("cookie"));
+    let cookie = cookies
+      .filter(|_| !has_cookie_header)
+      .map(|c| c.iter().map(|(k, v)| format!("{k}={v}")).collect::<Vec<_>>().join("; "));
+    let pairs: Vec<(&str, &str)> = header_pairs(&headers).chain(cookie.as_deref().map(|c| ("cookie", c))).collect();
+    let ip = ip.and_then(|ip| ip.parse().ok());
+    Ok(self.guard()?.decide_http(&method, &path, &pairs, ip).await.into())
+  }
+
+  /// Decisions for many sessions in one call, in input order. Sessions that aren't
+  /// cached are fetched from the Trust API in batches of `batchMaxSize`.
+  #[napi]
+  pub async fn decide_many(&self, session_ids: Vec<String>) -> Result<Vec<JsDecision>> {
+    let guard = self.guard()?;
+    let ids: Vec<&str> = session_ids.iter().map(String::as_str).collect();
+    let decided = guard
+      .decide_batch_with_trust(&ids)
       .await
-      .map_err(|e| Error::from_reason(e.to_string()))
+      .map_err(js_error)?;
+    Ok(
+      decided
+        .into_iter()
+        .map(|(decision, trust)| respond(guard, decision, trust.as_ref(), &guard.request_id(None)))
+        .collect(),
+    )
+  }
+
+  /// `decide` with the settings of a configured tenant.
+  #[napi]
+  pub async fn decide_for(&self, tenant: String, session_id: String) -> Result<JsDecision> {
+    let guard = self
+      .guard()?
+      .tenant(&tenant)
+      .ok_or_else(|| Error::from_reason(format!("Unknown tenant `{tenant}`")))?;
+    decide(guard, &session_id).await
   }
 }
 
-#[napi]
-impl Task for DecideTask {
-  type Output = Decision;
-  type JsValue = JsDecision;
+fn js_error(e: EGuardError) -> Error {
+  Error::from_reason(format!("{}: {}", e.kind(), e))
+}
+
+/// Node's `req.headers` as name/value pairs, one per value of a repeated header.
+fn header_pairs(headers: &Headers) -> impl Iterator<Item = (&str, &str)> {
+  headers.iter().flat_map(|(name, values)| {
+    let values = match values {
+      Some(Either::A(v)) => std::slice::from_ref(v),
+      Some(Either::B(vs)) => vs.as_slice(),
+      None => &[],
+    };
+    values.iter().map(move |v| (name.as_str(), v.as_str()))
+  })
+}
+
+/// A decision with the headers and deny body the caller needs to respond to it.
+async fn decide(guard: &EGuard, session_id: &str) -> Result<JsDecision> {
+  let (decision, trust) = guard
+    .decide_with_trust(session_id)
+    .await
+    .map_err(js_error)?;
+  let request_id = guard.request_id(None);
+  Ok(respond(guard, decision, trust.as_ref(), &request_id))
+}
+
+/// `decision` with its `X-EGuard-*` headers and rendered deny body.
+fn respond(guard: &EGuard, decision: Decision, trust: Option<&TrustResponse>, request_id: &str) -> JsDecision {
+  let headers = guard.response_headers(&decision, trust, request_id);
+  let body = guard.deny_body(&decision, trust, request_id);
 
-  fn compute(&mut self) -> Result<Self::Output> {
-    let rt = RT.get().expect("tokio runtime not initialized");
-    rt.block_on(self.run())
+  let mut js = JsDecision::from(decision);
+  if !headers.is_empty() {
+    js.headers = Some(headers.into_iter().map(|(k, v)| (k.to_string(), v)).collect());
   }
+  if let Some(body) = body {
+    js.content_type = Some(body.content_type.to_string());
+    js.body = Some(body.body);
+  }
+  js
+}
 
-  fn resolve(&mut self, _env: Env, out: Decision) -> Result<Self::JsValue> {
-    Ok(match out {
-      Decision::Allow => JsDecision {
-        allow: true,
-        status: None,
-        message: None,
-      },
-      Decision::Deny { status, message } => JsDecision {
-        allow: false,
-        status: Some(status),
-        message: Some(message),
-      },
-    })
+impl From<Decision> for JsDecision {
+  fn from(d: Decision) -> Self {
+    let mut js = JsDecision {
+      allow: false,
+      status: None,
+      message: None,
+      challenge_kind: None,
+      redirect_url: None,
+      trust_score: None,
+      reason: None,
+      route_id: None,
+      headers: None,
+      body: None,
+      content_type: None,
+    };
+    match d {
+      Decision::Allow { trust_score, reason, route_id } => {
+        js.allow = true;
+        js.trust_score = trust_score.map(f64::from);
+        js.reason = reason.map(|r| r.to_string());
+        js.route_id = route_id;
+      }
+      Decision::Deny { status, message } => {
+        js.status = Some(status);
+        js.message = Some(message);
+      }
+      Decision::Challenge { kind, redirect_url } => {
+        js.challenge_kind = Some(kind.into());
+        js.redirect_url = Some(redirect_url);
+      }
+    }
+    js
   }
 }
@@ -0,0 +1,229 @@
+//! eGuard as a proxy-wasm HTTP filter, so Envoy and Istio enforce trust checks in the
+//! proxy itself. The filter configuration is the core's config (snake_case, as JSON) plus
+//! the Envoy `cluster` that routes to `api_base_url`:
+//!
+//! ```json
+//! {
+//!   "cluster": "eguard_trust_api",
+//!   "api_base_url": "https://trust.example.com",
+//!   "api_key": "...",
+//!   "secure_routes": [{ "path_pattern": "^/checkout" }],
+//!   "session_extraction": { "cookie_name": "sid" },
+//!   "min_trust_score": 0.5
+//! }
+//! ```
+//!
+//! Rejections carry the same status codes, JSON bodies and `X-EGuard-*` headers as the
+//! tower middleware. Trust API calls go through the cluster with the API key given
+//! inline: `secrets`, `request_signing`, retries and the background tasks (refresh,
+//! denylist sync, push) need a runtime the filter doesn't have and are not used.
+
+mod task;
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    rc::Rc,
+    sync::Arc,
+    time::Duration,
+};
+
+use eguard_core::{Decision, EGuard, EGuardConfig, HttpRoute, RequestDecision, redact::Redacted};
+use proxy_wasm::{
+    traits::{Context, HttpContext, RootContext},
+    types::{Action, ContextType, LogLevel},
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::task::{CallProvider, Reply, Upstream};
+
+proxy_wasm::main! {{
+    proxy_wasm::set_log_level(LogLevel::Info);
+    proxy_wasm::set_root_context(|_| Box::new(EGuardRoot::default()));
+}}
+
+#[derive(Deserialize)]
+struct FilterConfig {
+    cluster: String,
+    #[serde(flatten)]
+    guard: EGuardConfig,
+}
+
+struct Shared {
+    guard: EGuard,
+    upstream: Rc<Upstream>,
+}
+
+/// Holds the guard built from the filter configuration; one per VM.
+#[derive(Default)]
+struct EGuardRoot {
+    shared: Option<Rc<Shared>>,
+}
+
+impl Context for EGuardRoot {
+    fn on_http_call_response(&mut self, token_id: u32, _num_headers: usize, _body_size: usize, _num_trailers: usize) {
+        task::call_response(token_id, None);
+    }
+}
+
+impl RootContext for EGuardRoot {
+    fn on_configure(&mut self, _plugin_configuration_size: usize) -> bool {
+        let config = self.get_plugin_configuration().unwrap_or_default();
+        match configure(&config) {
+            Ok(shared) => {
+                self.shared = Some(Rc::new(shared));
+                true
+            }
+            Err(e) => {
+                log::error!("Invalid eGuard filter configuration: {:#}", Redacted(&e));
+                false
+            }
+        }
+    }
+
+    fn on_tick(&mut self) {
+        task::run_deferred();
+    }
+
+    fn create_http_context(&self, context_id: u32) -> Option<Box<dyn HttpContext>> {
+        Some(Box::new(EGuardFilter { id: context_id, shared: self.shared.clone()? }))
+    }
+
+    fn get_type(&self) -> Option<ContextType> {
+        Some(ContextType::HttpContext)
+    }
+}
+
+fn configure(config: &[u8]) -> anyhow::Result<Shared> {
+    let FilterConfig { cluster, guard: mut cfg } = serde_json::from_slice(config)?;
+    if cfg.api_key.is_empty() {
+        return Err(anyhow::anyhow!("api_key must be set; secrets are not loaded by the filter"));
+    }
+    // Retries wait on a timer, which the filter has no way to await.
+    cfg.retry.max_attempts = 1;
+    let url = cfg.api_base_url.trim_end_matches('/');
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let (authority, base_path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, ""),
+    };
+    let upstream = Upstream {
+        cluster,
+        authority: authority.to_string(),
+        base_path: base_path.to_string(),
+        api_key: cfg.api_key.clone(),
+        timeout: Duration::from_millis(cfg.timeout_ms),
+        parsing: cfg.response_parsing,
+        protocol: cfg.protocol_version,
+        format: cfg.wire_format,
+    };
+    Ok(Shared { guard: EGuard::new(cfg)?, upstream: Rc::new(upstream) })
+}
+
+/// One per request; pauses secure requests until their decision is made.
+struct EGuardFilter {
+    id: u32,
+    shared: Rc<Shared>,
+}
+
+impl Drop for EGuardFilter {
+    fn drop(&mut self) {
+        task::remove(self.id);
+    }
+}
+
+impl Context for EGuardFilter {
+    fn on_http_call_response(&mut self, token_id: u32, _num_headers: usize, _body_size: usize, _num_trailers: usize) {
+        task::call_response(token_id, Some(self.id));
+    }
+}
+
+impl HttpContext for EGuardFilter {
+    fn on_http_request_headers(&mut self, _num_headers: usize, _end_of_stream: bool) -> Action {
+        let mut method = String::new();
+        let mut target = String::new();
+        let mut authority = None;
+        let mut headers = Vec::new();
+        for (name, value) in self.get_http_request_headers() {
+            match name.as_str() {
+                ":method" => method = value,
+                ":path" => target = value,
+                ":authority" => authority = Some(value),
+                _ if name.starts_with(':') => {}
+                _ => headers.push((name, value)),
+            }
+        }
+        if let Some(host) = authority
+            && !headers.iter().any(|(name, _)| name == "host")
+        {
+            headers.push(("host".to_string(), host));
+        }
+        // `source.address` is `ip:port`.
+        let ip = self.get_property(vec!["source", "address"])
+            .and_then(|a| String::from_utf8(a).ok())
+            .and_then(|a| a.parse::<SocketAddr>().map(|s| s.ip()).or_else(|_| a.parse::<IpAddr>()).ok());
+        let pairs: Vec<(&str, &str)> = headers.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+
+        let (guard, ctx, sid) = match self.shared.guard.route_http(&method, &target, &pairs, ip) {
+            HttpRoute::Unmatched => return Action::Continue,
+            HttpRoute::MissingSession => {
+                let body = json!({ "error": "missing_session" }).to_string();
+                self.send_http_response(401, vec![("content-type", "application/json")], Some(body.as_bytes()));
+                return Action::Pause;
+            }
+            HttpRoute::Matched { guard, ctx, session_id } => (guard, ctx, session_id),
+        };
+
+        let provider = CallProvider::new();
+        let upstream = self.shared.upstream.clone();
+        let guard = guard.clone().with_provider(Arc::new(provider.clone()));
+        task::start(self.id, async move { reply(&guard, guard.decide_matched(&ctx, sid).await) }, &provider, upstream)
+    }
+
+    fn on_http_response_headers(&mut self, _num_headers: usize, _end_of_stream: bool) -> Action {
+        for (name, value) in task::response_headers(self.id) {
+            self.add_http_response_header(&name, &value);
+        }
+        Action::Continue
+    }
+}
+
+fn reply(guard: &EGuard, decided: RequestDecision) -> Reply {
+    if let Some(e) = &decided.error {
+        log::warn!("trust check failed: {:#}", e);
+        return rejected(502, json!({ "error": "trust_service_unavailable" }), Vec::new());
+    }
+
+    let headers: Vec<_> = decided.headers
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+    match decided.decision {
+        Decision::Allow { .. } => {
+            let upstream = match guard.config().response_headers.as_ref().is_some_and(|h| h.forward) {
+                true => headers.clone(),
+                false => Vec::new(),
+            };
+            Reply::Continue { upstream, response: headers }
+        }
+        Decision::Deny { status, message } => match decided.body {
+            Some(body) => {
+                let mut headers = headers;
+                headers.push(("content-type".to_string(), body.content_type.to_string()));
+                Reply::Respond { status: u32::from(status), headers, body: body.body }
+            }
+            None => rejected(status, json!({ "error": "forbidden", "detail": message }), headers),
+        },
+        Decision::Challenge { kind, redirect_url } => {
+            let body = json!({ "error": "challenge", "kind": kind, "redirect_url": redirect_url });
+            let mut headers = headers;
+            headers.push(("location".to_string(), redirect_url));
+            rejected(302, body, headers)
+        }
+    }
+}
+
+fn rejected(status: u16, body: serde_json::Value, mut headers: Vec<(String, String)>) -> Reply {
+    headers.push(("content-type".to_string(), "application/json".to_string()));
+    Reply::Respond { status: u32::from(status), headers, body: body.to_string() }
+}
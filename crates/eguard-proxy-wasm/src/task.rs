@@ -0,0 +1,319 @@
+//! Runs decisions across proxy-wasm callbacks. The host has no async runtime: a decision
+//! is polled until it needs the Trust API, the call goes out through Envoy, and the
+//! decision is polled again from `on_http_call_response`.
+//!
+//! The SDK hands a call's response to the context that was active when it was made, so a
+//! decision only makes calls from its own context's callbacks. Others (one taking over a
+//! lookup from a request that went away) make theirs from the root context's next tick.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    pin::Pin,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Wake, Waker},
+    time::Duration,
+};
+
+use eguard_core::{ApiStatusError, ProtocolVersion, ReasonCode, RequestContext, ResponseParsing, SecretString, Throttled, TrustProvider, TrustResponse, WireFormat, response, throttle};
+use proxy_wasm::{
+    hostcalls,
+    types::{Action, BufferType, MapType},
+};
+use serde_json::json;
+
+/// What the filter does with the request once its decision is made.
+pub(crate) enum Reply {
+    /// Send the request upstream with `upstream` headers added, and add `response` to its response.
+    Continue { upstream: Vec<(String, String)>, response: Vec<(String, String)> },
+    /// Answer the request locally.
+    Respond { status: u32, headers: Vec<(String, String)>, body: String },
+}
+
+/// Where and how the Trust API is called.
+pub(crate) struct Upstream {
+    /// The Envoy cluster that routes to `api_base_url`.
+    pub cluster: String,
+    pub authority: String,
+    /// The path of `api_base_url`, without a trailing slash.
+    pub base_path: String,
+    pub api_key: SecretString,
+    pub timeout: Duration,
+    pub parsing: ResponseParsing,
+    pub protocol: ProtocolVersion,
+    pub format: WireFormat,
+}
+
+/// The Trust API call of one decision.
+#[derive(Default)]
+enum Call {
+    #[default]
+    Idle,
+    /// Asked for by the provider, not dispatched yet.
+    Wanted { sid: String, ctx: Option<RequestContext> },
+    Sent { sid: String },
+    Done(anyhow::Result<TrustResponse>),
+}
+
+type Slot = Arc<Mutex<Call>>;
+
+struct Task {
+    future: Option<Pin<Box<dyn Future<Output = Reply>>>>,
+    call: Slot,
+    upstream: Rc<Upstream>,
+    /// Headers for the response, once the request was let through.
+    response: Vec<(String, String)>,
+}
+
+thread_local! {
+    /// Decisions by HTTP context id, kept until the context is deleted.
+    static TASKS: RefCell<HashMap<u32, Task>> = RefCell::new(HashMap::new());
+    /// Contexts whose decision can make progress.
+    static WOKEN: RefCell<Vec<u32>> = const { RefCell::new(Vec::new()) };
+    /// The context each Trust API call in flight belongs to, by token.
+    static CALLS: RefCell<HashMap<u32, u32>> = RefCell::new(HashMap::new());
+    /// Contexts with a Trust API call to make from the root context.
+    static DEFERRED: RefCell<Vec<u32>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A `TrustProvider` answering from the Trust API call its decision's filter makes.
+#[derive(Clone)]
+pub(crate) struct CallProvider(Slot);
+
+impl CallProvider {
+    pub(crate) fn new() -> Self {
+        Self(Slot::default())
+    }
+
+    async fn call(&self, sid: &str, ctx: Option<&RequestContext>) -> anyhow::Result<TrustResponse> {
+        std::future::poll_fn(|_| {
+            let mut call = self.0.lock().unwrap();
+            match std::mem::take(&mut *call) {
+                Call::Done(result) => Poll::Ready(result),
+                Call::Idle => {
+                    *call = Call::Wanted { sid: sid.to_string(), ctx: ctx.cloned() };
+                    Poll::Pending
+                }
+                pending => {
+                    *call = pending;
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+impl TrustProvider for CallProvider {
+    async fn fetch(&self, session_id: &str) -> anyhow::Result<TrustResponse> {
+        self.call(session_id, None).await
+    }
+
+    async fn fetch_with_context(&self, session_id: &str, ctx: &RequestContext) -> anyhow::Result<TrustResponse> {
+        self.call(session_id, Some(ctx)).await
+    }
+}
+
+struct Wakeup(u32);
+
+impl Wake for Wakeup {
+    fn wake(self: Arc<Self>) {
+        WOKEN.with(|w| w.borrow_mut().push(self.0));
+    }
+}
+
+/// Starts the decision for context `id`, whose Trust API calls go through `provider`.
+pub(crate) fn start(
+    id: u32,
+    future: impl Future<Output = Reply> + 'static,
+    provider: &CallProvider,
+    upstream: Rc<Upstream>,
+) -> Action {
+    let task = Task { future: Some(Box::pin(future)), call: provider.0.clone(), upstream, response: Vec::new() };
+    TASKS.with(|t| t.borrow_mut().insert(id, task));
+    match step(id, true) {
+        Some(reply) => finish(id, reply, false),
+        None => Action::Pause,
+    }
+}
+
+/// The headers to add to context `id`'s response.
+pub(crate) fn response_headers(id: u32) -> Vec<(String, String)> {
+    TASKS.with(|t| t.borrow_mut().get_mut(&id).map(|task| std::mem::take(&mut task.response)).unwrap_or_default())
+}
+
+/// Drops context `id`'s decision, if it is still running.
+pub(crate) fn remove(id: u32) {
+    let task = TASKS.with(|t| t.borrow_mut().remove(&id));
+    // Dropped outside the borrow: a cancelled lookup wakes decisions waiting on it.
+    drop(task);
+    run_woken(Some(id));
+}
+
+/// Hands the Trust API response for `token` to the decision that asked for it. `current`
+/// is the HTTP context the callback came to, or `None` for the root context.
+pub(crate) fn call_response(token: u32, current: Option<u32>) {
+    let Some(id) = CALLS.with(|c| c.borrow_mut().remove(&token)) else { return };
+    let Some((slot, upstream)) = TASKS.with(|t| t.borrow().get(&id).map(|task| (task.call.clone(), task.upstream.clone()))) else {
+        return;
+    };
+    let mut call = slot.lock().unwrap();
+    let Call::Sent { sid } = std::mem::take(&mut *call) else { return };
+    *call = Call::Done(read_trust(&sid, &upstream));
+    drop(call);
+    WOKEN.with(|w| w.borrow_mut().push(id));
+    run_woken(current);
+}
+
+/// Makes the deferred Trust API calls; run from the root context's tick.
+pub(crate) fn run_deferred() {
+    let _ = hostcalls::set_tick_period(Duration::ZERO);
+    let deferred = DEFERRED.with(|d| std::mem::take(&mut *d.borrow_mut()));
+    WOKEN.with(|w| w.borrow_mut().extend(deferred));
+    run_woken(None);
+}
+
+fn run_woken(current: Option<u32>) {
+    while let Some(id) = WOKEN.with(|w| w.borrow_mut().pop()) {
+        if hostcalls::set_effective_context(id).is_ok()
+            && let Some(reply) = step(id, current.is_none_or(|c| c == id))
+        {
+            finish(id, reply, true);
+        }
+    }
+}
+
+/// Polls context `id`'s decision, making the Trust API calls it asks for, or deferring
+/// them unless `can_call`.
+fn step(id: u32, can_call: bool) -> Option<Reply> {
+    let (mut future, slot, upstream) =
+        TASKS.with(|t| t.borrow_mut().get_mut(&id).and_then(|task| Some((task.future.take()?, task.call.clone(), task.upstream.clone()))))?;
+    let waker = Waker::from(Arc::new(Wakeup(id)));
+    loop {
+        if let Poll::Ready(reply) = future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            return Some(reply);
+        }
+        let mut call = slot.lock().unwrap();
+        if !can_call && matches!(*call, Call::Wanted { .. }) {
+            DEFERRED.with(|d| d.borrow_mut().push(id));
+            let _ = hostcalls::set_tick_period(Duration::from_millis(1));
+            break;
+        }
+        let (sid, ctx) = match std::mem::take(&mut *call) {
+            Call::Wanted { sid, ctx } => (sid, ctx),
+            other => {
+                *call = other;
+                break;
+            }
+        };
+        match dispatch(&upstream, &sid, ctx.as_ref()) {
+            Ok(token) => {
+                CALLS.with(|c| c.borrow_mut().insert(token, id));
+                *call = Call::Sent { sid };
+                break;
+            }
+            // Poll again so the decision sees the failure.
+            Err(e) => *call = Call::Done(Err(e)),
+        }
+    }
+    TASKS.with(|t| {
+        if let Some(task) = t.borrow_mut().get_mut(&id) {
+            task.future = Some(future);
+        }
+    });
+    None
+}
+
+fn finish(id: u32, reply: Reply, resume: bool) -> Action {
+    match reply {
+        Reply::Continue { upstream, response } => {
+            for (name, value) in &upstream {
+                let _ = hostcalls::add_map_value(MapType::HttpRequestHeaders, name, value);
+            }
+            TASKS.with(|t| {
+                if let Some(task) = t.borrow_mut().get_mut(&id) {
+                    task.response = response;
+                }
+            });
+            if resume {
+                let _ = hostcalls::resume_http_request();
+            }
+            Action::Continue
+        }
+        Reply::Respond { status, headers, body } => {
+            let headers = headers.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+            let _ = hostcalls::send_http_response(status, headers, Some(body.as_bytes()));
+            Action::Pause
+        }
+    }
+}
+
+/// `POST {api_base_url}/eguard/trust` with the request context, or `GET` without one, as
+/// the core's HTTP transport sends it.
+fn dispatch(upstream: &Upstream, sid: &str, ctx: Option<&RequestContext>) -> anyhow::Result<u32> {
+    let auth = format!("Bearer {}", upstream.api_key.expose());
+    let (method, path, body) = match ctx {
+        Some(ctx) => {
+            let body = upstream.format.encode(&json!({ "sid": sid, "context": ctx }))?;
+            ("POST", format!("{}/eguard/trust", upstream.base_path), Some(body))
+        }
+        None => ("GET", format!("{}/eguard/trust?sid={}", upstream.base_path, encode(sid)), None),
+    };
+    let mut headers = vec![
+        (":method", method),
+        (":path", path.as_str()),
+        (":authority", upstream.authority.as_str()),
+        ("authorization", auth.as_str()),
+        (response::PROTOCOL_HEADER, upstream.protocol.as_header()),
+        (response::SDK_HEADER, response::SDK),
+        ("accept", upstream.format.content_type()),
+    ];
+    if body.is_some() {
+        headers.push(("content-type", upstream.format.content_type()));
+    }
+    hostcalls::dispatch_http_call(&upstream.cluster, headers, body.as_deref(), Vec::new(), upstream.timeout)
+        .map_err(|status| anyhow::anyhow!("Could not call the Trust API through cluster `{}`: {:?}", upstream.cluster, status))
+}
+
+/// The response of the call being handled, read like the core's HTTP transport reads it.
+fn read_trust(sid: &str, upstream: &Upstream) -> anyhow::Result<TrustResponse> {
+    // Envoy answers calls that failed or timed out without a `:status`.
+    let status = hostcalls::get_map_value(MapType::HttpCallResponseHeaders, ":status")
+        .ok()
+        .flatten()
+        .and_then(|s| http::StatusCode::from_bytes(s.as_bytes()).ok())
+        .ok_or_else(|| anyhow::anyhow!("Trust API call failed or timed out"))?;
+    let body = hostcalls::get_buffer(BufferType::HttpCallResponseBody, 0, usize::MAX)
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    if status.is_success() {
+        let header = |name| hostcalls::get_map_value(MapType::HttpCallResponseHeaders, name).ok().flatten();
+        let format = header("content-type").and_then(|v| WireFormat::from_content_type(&v)).unwrap_or(upstream.format);
+        let version = ProtocolVersion::from_header(header(response::PROTOCOL_HEADER).as_deref());
+        response::parse_trust(&body, format, sid, upstream.parsing, version)
+    } else if status == http::StatusCode::NOT_FOUND {
+        Ok(TrustResponse { session_id: sid.into(), trust_score: 0.0, reason: Some(ReasonCode::UnknownSession), ..Default::default() })
+    } else if status == http::StatusCode::TOO_MANY_REQUESTS {
+        let wait = hostcalls::get_map_value(MapType::HttpCallResponseHeaders, "retry-after")
+            .ok()
+            .flatten()
+            .and_then(|v| throttle::retry_after(&v));
+        Err(Throttled(wait).into())
+    } else {
+        Err(ApiStatusError { status, body: String::from_utf8_lossy(&body).into_owned() }.into())
+    }
+}
+
+/// Percent-encodes a query parameter value.
+fn encode(value: &str) -> String {
+    value.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
@@ -0,0 +1,135 @@
+use eguard_core::{Decision, EGuard as CoreGuard, EGuardConfig, EGuardError, refresh::RefreshHandle};
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyDict};
+
+/// Python mirror of `JsDecision`.
+#[pyclass(name = "Decision", frozen, get_all)]
+pub struct PyDecision {
+    allow: bool,
+    status: Option<u16>,
+    message: Option<String>,
+    /// Set, with `redirect_url`, when the user should be challenged rather than denied.
+    challenge_kind: Option<&'static str>,
+    redirect_url: Option<String>,
+}
+
+#[pymethods]
+impl PyDecision {
+    fn __repr__(&self) -> String {
+        let allow = if self.allow { "True" } else { "False" };
+        let status = self.status.map_or("None".into(), |s| s.to_string());
+        let message = self.message.as_ref().map_or("None".into(), |m| format!("{m:?}"));
+        match (&self.challenge_kind, &self.redirect_url) {
+            (Some(kind), Some(url)) => format!("Decision(allow={allow}, challenge_kind={kind:?}, redirect_url={url:?})"),
+            _ => format!("Decision(allow={allow}, status={status}, message={message})"),
+        }
+    }
+}
+
+impl From<Decision> for PyDecision {
+    fn from(d: Decision) -> Self {
+        match d {
+            Decision::Allow { .. } => PyDecision { allow: true, status: None, message: None, challenge_kind: None, redirect_url: None },
+            Decision::Deny { status, message } => PyDecision {
+                allow: false,
+                status: Some(status),
+                message: Some(message),
+                challenge_kind: None,
+                redirect_url: None,
+            },
+            Decision::Challenge { kind, redirect_url } => PyDecision {
+                allow: false,
+                status: None,
+                message: None,
+                challenge_kind: Some(kind.as_str()),
+                redirect_url: Some(redirect_url),
+            },
+        }
+    }
+}
+
+/// ```python
+/// guard = eguard.EGuard({
+///     "api_base_url": "https://sentry.example.com",
+///     "api_key": "...",
+///     "secure_routes": [{"path_pattern": "^/checkout", "methods": ["POST"]}],
+///     "session_extraction": {"cookie_name": "sid", "header_bearer": False},
+///     "min_trust_score": 0.5,
+/// })
+/// decision = await guard.decide(sid)
+/// ```
+#[pyclass(name = "EGuard", frozen)]
+pub struct PyEGuard {
+    inner: CoreGuard,
+    _refresher: Option<RefreshHandle>,
+    _secrets: Option<RefreshHandle>,
+    _denylist: Option<RefreshHandle>,
+    _push: Option<RefreshHandle>,
+}
+
+#[pymethods]
+impl PyEGuard {
+    /// Takes the same keys as `EGuardConfig` (snake_case); optional keys use the core defaults.
+    #[new]
+    fn new(cfg: &Bound<'_, PyDict>) -> PyResult<Self> {
+        let cfg: EGuardConfig = pythonize::depythonize(cfg.as_any())
+            .map_err(|e| PyValueError::new_err(format!("Invalid config: {e}")))?;
+        let inner = CoreGuard::new(cfg).map_err(|e| PyValueError::new_err(message(&e)))?;
+        let (_refresher, _secrets, _denylist, _push) = {
+            let _enter = pyo3_async_runtimes::tokio::get_runtime().enter();
+            let push = inner.spawn_push_listener().map_err(|e| PyValueError::new_err(message(&e)))?;
+            (inner.spawn_refresher(), inner.spawn_secret_refresher(), inner.spawn_denylist_sync(), push)
+        };
+        Ok(Self { inner, _refresher, _secrets, _denylist, _push })
+    }
+
+    fn is_secure(&self, path: &str, method: &str) -> bool {
+        self.inner.is_secure(path, method)
+    }
+
+    #[pyo3(signature = (cookie_header=None, header_name=None, header_value=None, query=None))]
+    fn extract_session_id(
+        &self,
+        cookie_header: Option<&str>,
+        header_name: Option<&str>,
+        header_value: Option<&str>,
+        query: Option<&str>,
+    ) -> Option<String> {
+        let header = header_name.zip(header_value);
+        let _enter = pyo3_async_runtimes::tokio::get_runtime().enter();
+        self.inner.extract_session_id_with_query(cookie_header, header, query)
+    }
+
+    /// `headers` is a list of `(name, value)` pairs, e.g. `list(request.headers.items())`.
+    #[pyo3(signature = (headers, query=None))]
+    fn extract_session_id_from_headers(&self, headers: Vec<(String, String)>, query: Option<&str>) -> Option<String> {
+        let headers = headers.iter().map(|(name, value)| (name.as_str(), value.as_str()));
+        let _enter = pyo3_async_runtimes::tokio::get_runtime().enter();
+        self.inner.extract_session_id_from_headers(headers, query)
+    }
+
+    /// Awaitable trust decision; raises `RuntimeError` if the Trust API call fails
+    /// and no failure mode is configured. Its message starts with the error kind,
+    /// e.g. `RateLimited: ...`.
+    fn decide<'py>(&self, py: Python<'py>, session_id: String) -> PyResult<Bound<'py, PyAny>> {
+        let guard = self.inner.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            guard
+                .decide(&session_id)
+                .await
+                .map(PyDecision::from)
+                .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(message(&e)))
+        })
+    }
+}
+
+/// The error with its `EGuardError` kind in front, so callers can tell kinds apart.
+fn message(e: &EGuardError) -> String {
+    format!("{}: {}", e.kind(), e)
+}
+
+#[pymodule(name = "eguard")]
+fn eguard_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyEGuard>()?;
+    m.add_class::<PyDecision>()?;
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use std::io::Cursor;
+
+use eguard_core::{ChallengeKind, Decision, EGuard, RequestContext, TrustResponse, deny_body::DenyBody};
+use rocket::{
+    Build, Request, Response, Rocket,
+    fairing::{self, Fairing, Info, Kind},
+    http::Status,
+    request::{FromRequest, Outcome},
+};
+
+/// Makes an `EGuard` available to the [`Trusted`] request guard:
+///
+/// ```ignore
+/// #[post("/checkout")]
+/// fn checkout(trust: Trusted) -> String { format!("{:?}", trust.trust) }
+///
+/// rocket::build().attach(EGuardFairing::new(guard)).mount("/", routes![checkout])
+/// ```
+///
+/// On the way out it adds the guard's `response_headers` to requests the [`Trusted`]
+/// guard checked, and replaces the catcher's body with the configured `deny_body`.
+pub struct EGuardFairing {
+    guard: EGuard,
+}
+
+impl EGuardFairing {
+    pub fn new(guard: EGuard) -> Self {
+        Self { guard }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for EGuardFairing {
+    fn info(&self) -> Info {
+        Info { name: "eGuard", kind: Kind::Ignite | Kind::Response }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        Ok(rocket.manage(self.guard.clone()))
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let headers = match req.local_cache(|| CHECK_NOT_RUN) {
+            Ok(trusted) => &trusted.headers,
+            Err(EGuardRejection::Denied { body, headers, .. }) => {
+                if let Some(body) = body {
+                    res.set_raw_header("Content-Type", body.content_type);
+                    res.set_sized_body(body.body.len(), Cursor::new(body.body.clone()));
+                }
+                headers
+            }
+            Err(EGuardRejection::Challenge { headers, .. }) => headers,
+            Err(_) => return,
+        };
+        for (name, value) in headers {
+            res.set_raw_header(*name, value.clone());
+        }
+    }
+}
+
+/// Request guard for protected routes. Taking it as a handler argument runs the
+/// trust check (regardless of `secure_routes`) and forwards the deny status on failure.
+///
+/// The check runs once per request; the result is cached request-locally.
+#[derive(Clone, Debug)]
+pub struct Trusted {
+    /// The score the request was allowed on; `None` when a fallback decision allowed it.
+    pub trust: Option<TrustResponse>,
+    /// The guard's `response_headers`; [`EGuardFairing`] adds them to the response.
+    pub headers: Vec<(&'static str, String)>,
+}
+
+#[derive(Clone, Debug)]
+pub enum EGuardRejection {
+    NotAttached,
+    MissingSession,
+    Denied { status: u16, message: String, body: Option<DenyBody>, headers: Vec<(&'static str, String)> },
+    /// Rocket catchers can't set headers from the guard, so register a `302` catcher
+    /// that reads the target from [`challenge_url`].
+    Challenge { kind: ChallengeKind, redirect_url: String, headers: Vec<(&'static str, String)> },
+    Unavailable,
+}
+
+impl EGuardRejection {
+    pub fn status(&self) -> Status {
+        match self {
+            EGuardRejection::NotAttached => Status::InternalServerError,
+            EGuardRejection::MissingSession => Status::Unauthorized,
+            EGuardRejection::Denied { status, .. } => Status::from_code(*status).unwrap_or(Status::Forbidden),
+            EGuardRejection::Challenge { .. } => Status::Found,
+            EGuardRejection::Unavailable => Status::BadGateway,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Trusted {
+    type Error = EGuardRejection;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let result = req.local_cache_async(check(req)).await;
+        match result {
+            Ok(trusted) => Outcome::Success(trusted.clone()),
+            Err(rejection) => Outcome::Error((rejection.status(), rejection.clone())),
+        }
+    }
+}
+
+async fn check(req: &Request<'_>) -> Result<Trusted, EGuardRejection> {
+    let guard = req.rocket().state::<EGuard>().ok_or(EGuardRejection::NotAttached)?;
+    let headers: Vec<_> = req.headers().iter().collect();
+    let ctx = RequestContext::new(req.method().as_str(), req.uri().path().as_str())
+        .with_ip(req.client_ip())
+        .with_headers(headers.iter().map(|h| (h.name().as_str(), h.value())));
+    let guard = guard.for_request(&ctx);
+    let sid = session_id(guard, req).ok_or(EGuardRejection::MissingSession)?;
+    let ctx = ctx.with_device_id(device_id(guard, req));
+
+    let (decision, trust) = guard.decide_request_with_trust(&ctx, &sid).await.map_err(|_| EGuardRejection::Unavailable)?;
+    let request_id = guard.request_id(Some(&ctx));
+    let headers = guard.response_headers(&decision, trust.as_ref(), &request_id);
+    let body = guard.deny_body(&decision, trust.as_ref(), &request_id);
+    match decision {
+        Decision::Allow { .. } => Ok(Trusted { trust, headers }),
+        Decision::Deny { status, message } => Err(EGuardRejection::Denied { status, message, body, headers }),
+        Decision::Challenge { kind, redirect_url } => Err(EGuardRejection::Challenge { kind, redirect_url, headers }),
+    }
+}
+
+/// The challenge page for a request the [`Trusted`] guard rejected with a challenge,
+/// for use in a `302` catcher.
+pub fn challenge_url<'r>(req: &'r Request<'_>) -> Option<&'r str> {
+    match req.local_cache(|| CHECK_NOT_RUN) {
+        Err(EGuardRejection::Challenge { redirect_url, .. }) => Some(redirect_url),
+        _ => None,
+    }
+}
+
+/// What `challenge_url` leaves in the request-local cache if the guard never ran.
+const CHECK_NOT_RUN: Result<Trusted, EGuardRejection> = Err(EGuardRejection::NotAttached);
+
+fn session_id(guard: &EGuard, req: &Request<'_>) -> Option<String> {
+    let headers: Vec<_> = req.headers().iter().collect();
+    let headers = headers.iter().map(|h| (h.name.as_str(), h.value()));
+    guard.extract_session_id_from_headers(headers, req.uri().query().map(|q| q.as_str()))
+}
+
+fn device_id(guard: &EGuard, req: &Request<'_>) -> Option<String> {
+    let headers: Vec<_> = req.headers().iter().collect();
+    let headers = headers.iter().map(|h| (h.name.as_str(), h.value()));
+    guard.extract_device_id_from_headers(headers, req.uri().query().map(|q| q.as_str()))
+}
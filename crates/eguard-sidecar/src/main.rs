@@ -0,0 +1,81 @@
+//! `eguard-sidecar`: a local HTTP server making eGuard decisions, for services that can't
+//! link one of the bindings. Run it next to the service and ask it about each request:
+//!
+//! ```text
+//! eguard-sidecar -c eguard.yaml --listen 127.0.0.1:9292
+//! curl -s localhost:9292/v1/decide -d '{"path":"/checkout","method":"POST","headers":{"cookie":"sid=abc"}}'
+//! ```
+//!
+//! `POST /v1/decide` answers `200` with the decision as a `RequestDecision`: the fields of
+//! the Node binding's `decideRequest`, but with snake_case keys (`session_id`,
+//! `challenge_kind`, `redirect_url`, `content_type`). The caller sends the response it
+//! describes. `GET /healthz` answers `200` once the config has loaded.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+};
+
+use axum::{
+    Json, Router,
+    extract::State,
+    routing::{get, post},
+};
+use clap::Parser;
+use eguard_core::{EGuard, EGuardConfig, HeaderValues, RequestDecision, http::header_pairs};
+use serde::Deserialize;
+
+#[derive(Parser)]
+#[command(name = "eguard-sidecar", version, about = "HTTP decision sidecar for eGuard")]
+struct Cli {
+    /// `.yaml`, `.toml` or `.json` config, as loaded by `EGuardConfig::from_file`.
+    #[arg(short, long, env = "EGUARD_CONFIG")]
+    config: PathBuf,
+    #[arg(long, env = "EGUARD_SIDECAR_LISTEN", default_value = "127.0.0.1:9292")]
+    listen: SocketAddr,
+}
+
+#[derive(Deserialize)]
+struct DecideRequest {
+    /// May include the query string.
+    path: String,
+    method: String,
+    #[serde(default)]
+    headers: HashMap<String, HeaderValues>,
+    /// Client IP, for `ip_rules`, `geoip` and per-IP rate limits.
+    #[serde(default)]
+    ip: Option<IpAddr>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let guard = EGuard::new(EGuardConfig::from_file(&cli.config)?)?;
+    guard.load_secrets().await?;
+    // Kept alive for as long as the server runs.
+    let _tasks = (
+        guard.spawn_refresher(),
+        guard.spawn_secret_refresher(),
+        guard.spawn_denylist_sync(),
+        guard.spawn_health_checker(),
+        guard.spawn_push_listener()?,
+    );
+
+    let app = Router::new()
+        .route("/v1/decide", post(decide))
+        .route("/healthz", get(|| async { "ok" }))
+        .with_state(guard);
+    let listener = tokio::net::TcpListener::bind(cli.listen).await?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+async fn decide(State(guard): State<EGuard>, Json(req): Json<DecideRequest>) -> Json<RequestDecision> {
+    let headers: Vec<(&str, &str)> = header_pairs(&req.headers).collect();
+    Json(guard.decide_http(&req.method, &req.path, &headers, req.ip).await)
+}
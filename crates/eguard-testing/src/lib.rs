@@ -0,0 +1,317 @@
+//! An in-process mock of the Trust API, for integration tests that shouldn't depend
+//! on a live service. Point `api_base_url` at it and script the scores:
+//!
+//! ```ignore
+//! let api = MockTrustApi::start().await?;
+//! api.set_score("good-session", 0.9);
+//! api.set_latency(Duration::from_millis(50));
+//!
+//! let guard = EGuard::new(EGuardConfig { api_base_url: api.url(), ..cfg })?;
+//! assert!(matches!(guard.decide("good-session").await?, Decision::Allow { .. }));
+//!
+//! api.fail_next(1, 503);
+//! ```
+//!
+//! Serves the HTTP transport's endpoints: `GET /eguard/trust?sid=`, `POST /eguard/trust`
+//! `POST /eguard/trust/batch`, `POST /eguard/events`,
+//! `POST /eguard/outcomes`, `POST /eguard/ban` and `GET /eguard/denylist`. Sessions without a score get 404, which the guard
+//! reads as an unknown session.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode, header::AUTHORIZATION},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use eguard_core::{Event, Outcome, ReasonCode, RequestContext, TrustResponse, denylist::DenylistDelta};
+use serde::{Deserialize, Serialize};
+use tokio::{net::TcpListener, task::JoinHandle};
+
+/// A request the mock has answered (or failed).
+#[derive(Clone, Debug)]
+pub struct ReceivedRequest {
+    pub session_ids: Vec<String>,
+    /// Set for `POST /eguard/trust`.
+    pub context: Option<RequestContext>,
+    /// The bearer token, if any.
+    pub api_key: Option<String>,
+}
+
+#[derive(Default)]
+struct Script {
+    scores: HashMap<String, TrustResponse>,
+    default_score: Option<f32>,
+    latency: Duration,
+    /// Answer every request with this status.
+    failure: Option<StatusCode>,
+    /// Answer the next `n` requests with this status, before `failure`.
+    fail_next: Option<(usize, StatusCode)>,
+    api_key: Option<String>,
+    received: Vec<ReceivedRequest>,
+    events: Vec<Event>,
+    outcomes: Vec<(String, Outcome)>,
+    denylist: Vec<String>,
+    denylist_version: u64,
+}
+
+/// The mock server. It runs on the tokio runtime it was started on and stops when dropped.
+pub struct MockTrustApi {
+    addr: SocketAddr,
+    script: Arc<Mutex<Script>>,
+    task: JoinHandle<()>,
+}
+
+impl MockTrustApi {
+    /// Listens on a free port on 127.0.0.1.
+    pub async fn start() -> anyhow::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let script = Arc::new(Mutex::new(Script::default()));
+        let app = Router::new()
+            .route("/eguard/trust", get(trust).post(trust_with_context))
+            .route("/eguard/trust/batch", post(batch))
+            .route("/eguard/events", post(events))
+            .route("/eguard/outcomes", post(outcomes))
+            .route("/eguard/ban", post(ban))
+            .route("/eguard/denylist", get(denylist))
+            .with_state(script.clone());
+        let task = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        Ok(Self { addr, script, task })
+    }
+
+    /// The value for `api_base_url`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn set_score(&self, session_id: &str, score: f32) {
+        self.set_trust(TrustResponse { session_id: session_id.to_string(), trust_score: score, reason: None, ..Default::default() });
+    }
+
+    /// Scores `trust.session_id` with the given response, including its `reason`.
+    pub fn set_trust(&self, trust: TrustResponse) {
+        self.script().scores.insert(trust.session_id.clone(), trust);
+    }
+
+    /// Forgets a session's score, so it is unknown again.
+    pub fn remove(&self, session_id: &str) {
+        self.script().scores.remove(session_id);
+    }
+
+    /// Score for sessions without their own; `None` (default) answers them with 404.
+    pub fn set_default_score(&self, score: Option<f32>) {
+        self.script().default_score = score;
+    }
+
+    /// Delay before every response, e.g. to exceed `timeout_ms`.
+    pub fn set_latency(&self, latency: Duration) {
+        self.script().latency = latency;
+    }
+
+    /// Answer every request with `status` until cleared with `None`.
+    pub fn fail_with(&self, status: Option<u16>) {
+        self.script().failure = status.map(status_code);
+    }
+
+    /// Answer the next `count` requests with `status`, then recover.
+    pub fn fail_next(&self, count: usize, status: u16) {
+        self.script().fail_next = (count > 0).then(|| (count, status_code(status)));
+    }
+
+    /// Reject requests without `Authorization: Bearer <key>` with 401.
+    pub fn require_api_key(&self, key: Option<&str>) {
+        self.script().api_key = key.map(str::to_string);
+    }
+
+    /// Requests received so far, oldest first.
+    pub fn received(&self) -> Vec<ReceivedRequest> {
+        self.script().received.clone()
+    }
+
+    /// Sessions served by `GET /eguard/denylist`, always as a full list.
+    pub fn set_denylist(&self, session_ids: &[&str]) {
+        let mut s = self.script();
+        s.denylist = session_ids.iter().map(|sid| sid.to_string()).collect();
+        s.denylist_version += 1;
+    }
+
+    /// Events accepted so far, oldest first.
+    pub fn events(&self) -> Vec<Event> {
+        self.script().events.clone()
+    }
+
+    /// Outcomes accepted so far as `(session_id, outcome)`, oldest first.
+    pub fn outcomes(&self) -> Vec<(String, Outcome)> {
+        self.script().outcomes.clone()
+    }
+
+    pub fn request_count(&self) -> usize {
+        self.script().received.len()
+    }
+
+    fn script(&self) -> std::sync::MutexGuard<'_, Script> {
+        self.script.lock().unwrap()
+    }
+}
+
+impl Drop for MockTrustApi {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn status_code(status: u16) -> StatusCode {
+    StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+type Shared = State<Arc<Mutex<Script>>>;
+
+#[derive(Deserialize)]
+struct TrustQuery {
+    sid: String,
+}
+
+#[derive(Deserialize)]
+struct ContextRequest {
+    sid: String,
+    context: Option<RequestContext>,
+}
+
+#[derive(Deserialize)]
+struct BatchRequest {
+    sids: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct EventsRequest {
+    events: Vec<Event>,
+}
+
+#[derive(Deserialize)]
+struct OutcomeRequest {
+    sid: String,
+    outcome: Outcome,
+}
+
+#[derive(Deserialize)]
+struct BanRequest {
+    sid: String,
+    reason: ReasonCode,
+}
+
+#[derive(Serialize)]
+struct BatchResponse {
+    results: Vec<TrustResponse>,
+}
+
+async fn trust(State(script): Shared, headers: HeaderMap, Query(q): Query<TrustQuery>) -> Response {
+    answer(&script, &headers, vec![q.sid], None, single).await
+}
+
+async fn trust_with_context(State(script): Shared, headers: HeaderMap, Json(req): Json<ContextRequest>) -> Response {
+    answer(&script, &headers, vec![req.sid], req.context, single).await
+}
+
+async fn batch(State(script): Shared, headers: HeaderMap, Json(req): Json<BatchRequest>) -> Response {
+    answer(&script, &headers, req.sids, None, |trust| {
+        Json(BatchResponse { results: trust.into_iter().flatten().collect() }).into_response()
+    })
+    .await
+}
+
+async fn events(State(script): Shared, headers: HeaderMap, Json(req): Json<EventsRequest>) -> Response {
+    let session_ids = req.events.iter().map(|e| e.session_id.clone()).collect();
+    let resp = answer(&script, &headers, session_ids, None, |_| StatusCode::ACCEPTED.into_response()).await;
+    if resp.status().is_success() {
+        script.lock().unwrap().events.extend(req.events);
+    }
+    resp
+}
+
+async fn outcomes(State(script): Shared, headers: HeaderMap, Json(req): Json<OutcomeRequest>) -> Response {
+    let resp = answer(&script, &headers, vec![req.sid.clone()], None, |_| StatusCode::ACCEPTED.into_response()).await;
+    if resp.status().is_success() {
+        script.lock().unwrap().outcomes.push((req.sid, req.outcome));
+    }
+    resp
+}
+
+/// A banned session is scored zero from then on, like the real API does.
+async fn ban(State(script): Shared, headers: HeaderMap, Json(req): Json<BanRequest>) -> Response {
+    let resp = answer(&script, &headers, vec![req.sid.clone()], None, |_| StatusCode::ACCEPTED.into_response()).await;
+    if resp.status().is_success() {
+        let trust = TrustResponse { session_id: req.sid.clone(), trust_score: 0.0, reason: Some(req.reason), ..Default::default() };
+        script.lock().unwrap().scores.insert(req.sid, trust);
+    }
+    resp
+}
+
+async fn denylist(State(script): Shared, headers: HeaderMap) -> Response {
+    let delta = {
+        let s = script.lock().unwrap();
+        DenylistDelta { cursor: s.denylist_version.to_string(), full: true, added: s.denylist.clone(), removed: Vec::new() }
+    };
+    answer(&script, &headers, Vec::new(), None, |_| Json(delta).into_response()).await
+}
+
+fn single(mut trust: Vec<Option<TrustResponse>>) -> Response {
+    match trust.pop().flatten() {
+        Some(trust) => Json(trust).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Records the request, applies latency and faults, then renders the scripted scores.
+async fn answer(
+    script: &Mutex<Script>,
+    headers: &HeaderMap,
+    session_ids: Vec<String>,
+    context: Option<RequestContext>,
+    render: impl FnOnce(Vec<Option<TrustResponse>>) -> Response,
+) -> Response {
+    let api_key = headers.get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::to_string);
+
+    let latency = {
+        let mut s = script.lock().unwrap();
+        s.received.push(ReceivedRequest { session_ids: session_ids.clone(), context, api_key: api_key.clone() });
+        s.latency
+    };
+    if !latency.is_zero() {
+        tokio::time::sleep(latency).await;
+    }
+
+    let mut s = script.lock().unwrap();
+    if let Some((count, status)) = s.fail_next {
+        s.fail_next = (count > 1).then_some((count - 1, status));
+        return status.into_response();
+    }
+    if let Some(status) = s.failure {
+        return status.into_response();
+    }
+    if s.api_key.is_some() && s.api_key != api_key {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let trust = session_ids.iter()
+        .map(|sid| {
+            s.scores.get(sid).cloned().or_else(|| {
+                let score = s.default_score?;
+                Some(TrustResponse { session_id: sid.clone(), trust_score: score, reason: None, ..Default::default() })
+            })
+        })
+        .collect();
+    render(trust)
+}
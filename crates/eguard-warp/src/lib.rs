@@ -0,0 +1,117 @@
+use std::net::SocketAddr;
+
+use eguard_core::{ChallengeKind, Decision, EGuard, RequestContext, deny_body::DenyBody};
+use serde_json::json;
+use warp::{
+    Filter, Rejection, Reply,
+    filters::path::FullPath,
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header::{CONTENT_TYPE, LOCATION}},
+    reject::Reject,
+};
+
+/// Why `protect` rejected a request. Turn it into a response with [`handle_rejection`].
+///
+/// `headers` are the guard's `response_headers`; `body` its rendered `deny_body`.
+#[derive(Debug)]
+pub enum EGuardRejection {
+    MissingSession,
+    Denied { status: u16, message: String, body: Option<DenyBody>, headers: Vec<(&'static str, String)> },
+    Challenge { kind: ChallengeKind, redirect_url: String, headers: Vec<(&'static str, String)> },
+    Unavailable,
+}
+
+impl Reject for EGuardRejection {}
+
+/// Filter that passes when the route is not protected or the session is trusted:
+///
+/// ```ignore
+/// let checkout = eguard_warp::protect(guard).and(warp::path("checkout")).map(|| "ok");
+/// warp::serve(checkout.recover(eguard_warp::handle_rejection)).run(addr).await;
+/// ```
+pub fn protect(guard: EGuard) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::path::full()
+        .and(warp::method())
+        .and(warp::header::headers_cloned())
+        .and(warp::addr::remote())
+        .and(warp::query::raw().map(Some).or(warp::any().map(|| None)).unify())
+        .and_then(move |path: FullPath, method: Method, headers: HeaderMap, remote: Option<SocketAddr>, query: Option<String>| {
+            let guard = guard.clone();
+            async move {
+                let headers_iter = headers.iter().filter_map(|(k, v)| Some((k.as_str(), v.to_str().ok()?)));
+                let ctx = RequestContext::new(method.as_str(), path.as_str())
+                    .with_ip(remote.map(|a| a.ip()))
+                    .with_headers(headers_iter);
+                check(&guard, &ctx, &headers, query.as_deref()).await.map_err(warp::reject::custom)
+            }
+        })
+        .untuple_one()
+}
+
+async fn check(guard: &EGuard, ctx: &RequestContext, headers: &HeaderMap, query: Option<&str>) -> Result<(), EGuardRejection> {
+    let guard = guard.for_request(ctx);
+    if !guard.is_secure_host(ctx.host(), &ctx.path, &ctx.method) {
+        return Ok(());
+    }
+
+    let sid = session_id(guard, headers, query).ok_or(EGuardRejection::MissingSession)?;
+    let ctx = ctx.clone().with_device_id(device_id(guard, headers, query));
+
+    let (decision, trust) = guard.decide_request_with_trust(&ctx, &sid).await.map_err(|_| EGuardRejection::Unavailable)?;
+    let request_id = guard.request_id(Some(&ctx));
+    let headers = guard.response_headers(&decision, trust.as_ref(), &request_id);
+    let body = guard.deny_body(&decision, trust.as_ref(), &request_id);
+    match decision {
+        Decision::Allow { .. } => Ok(()),
+        Decision::Deny { status, message } => Err(EGuardRejection::Denied { status, message, body, headers }),
+        Decision::Challenge { kind, redirect_url } => Err(EGuardRejection::Challenge { kind, redirect_url, headers }),
+    }
+}
+
+/// Renders an [`EGuardRejection`] as the configured `deny_body` or the standard JSON
+/// deny response (a `302` redirect for challenges), with the guard's response headers;
+/// other rejections pass through. Allowed requests get no headers, as a filter can't
+/// change the handler's response.
+pub async fn handle_rejection(err: Rejection) -> Result<warp::reply::Response, Rejection> {
+    let Some(rejection) = err.find::<EGuardRejection>() else {
+        return Err(err);
+    };
+
+    let json = |status, body| warp::reply::with_status(warp::reply::json(&body), status).into_response();
+    let mut resp = match rejection {
+        EGuardRejection::MissingSession => json(StatusCode::UNAUTHORIZED, json!({ "error": "missing_session" })),
+        EGuardRejection::Denied { status, message, body, .. } => {
+            let status = StatusCode::from_u16(*status).unwrap_or(StatusCode::FORBIDDEN);
+            match body {
+                Some(body) => {
+                    let reply = warp::reply::with_status(body.body.clone(), status);
+                    warp::reply::with_header(reply, CONTENT_TYPE, body.content_type).into_response()
+                }
+                None => json(status, json!({ "error": "forbidden", "detail": message })),
+            }
+        }
+        EGuardRejection::Challenge { kind, redirect_url, .. } => {
+            let body = json!({ "error": "challenge", "kind": kind, "redirect_url": redirect_url });
+            let reply = warp::reply::with_status(warp::reply::json(&body), StatusCode::FOUND);
+            warp::reply::with_header(reply, LOCATION, redirect_url.as_str()).into_response()
+        }
+        EGuardRejection::Unavailable => json(StatusCode::BAD_GATEWAY, json!({ "error": "trust_service_unavailable" })),
+    };
+    if let EGuardRejection::Denied { headers, .. } | EGuardRejection::Challenge { headers, .. } = rejection {
+        for (name, value) in headers {
+            if let Ok(value) = HeaderValue::from_str(value) {
+                resp.headers_mut().insert(HeaderName::from_static(name), value);
+            }
+        }
+    }
+    Ok(resp)
+}
+
+fn session_id(guard: &EGuard, headers: &HeaderMap, query: Option<&str>) -> Option<String> {
+    let headers = headers.iter().filter_map(|(k, v)| Some((k.as_str(), v.to_str().ok()?)));
+    guard.extract_session_id_from_headers(headers, query)
+}
+
+fn device_id(guard: &EGuard, headers: &HeaderMap, query: Option<&str>) -> Option<String> {
+    let headers = headers.iter().filter_map(|(k, v)| Some((k.as_str(), v.to_str().ok()?)));
+    guard.extract_device_id_from_headers(headers, query)
+}
@@ -0,0 +1,55 @@
+// Next.js middleware for the edge runtime, a thin wrapper around the wasm build's
+// `decideRequest`; Trust API calls go through `fetch`. Build the wasm package first with
+// `wasm-pack build --target bundler crates/eguard-wasm`, which writes it to `pkg/`.
+//
+//   // middleware.js
+//   import { createNextMiddleware } from 'eguard-wasm/next.js'
+//   export const middleware = createNextMiddleware({ api_base_url, api_key, secure_routes, session_extraction, min_trust_score })
+//   export const config = { matcher: '/checkout/:path*' }
+
+import { NextResponse } from 'next/server'
+
+import { EGuard } from './pkg/eguard_wasm.js'
+
+/** The JSON body for a rejection without a `deny_body`, as the other middlewares send it. */
+function rejection(decision) {
+  if (decision.redirect_url) {
+    return { error: 'challenge', kind: decision.challenge_kind, redirect_url: decision.redirect_url }
+  }
+  if (!decision.session_id) {
+    return { error: decision.status === 401 ? 'missing_session' : 'trust_service_unavailable' }
+  }
+  return { error: 'forbidden', detail: decision.message }
+}
+
+/** Takes an `EGuard` or its config, with the core's snake_case keys. */
+export function createNextMiddleware(guardOrConfig) {
+  const guard = guardOrConfig instanceof EGuard ? guardOrConfig : new EGuard(guardOrConfig)
+  return async function middleware(request) {
+    const url = request.nextUrl
+    const ip = request.ip ?? request.headers.get('x-forwarded-for')?.split(',')[0].trim()
+    const decision = await guard.decideRequest(
+      url.pathname + url.search,
+      request.method,
+      Object.fromEntries(request.headers),
+      ip,
+    )
+    const headers = decision.headers ?? {}
+    if (decision.allow) {
+      return NextResponse.next({ headers })
+    }
+    if (decision.redirect_url) {
+      return NextResponse.json(rejection(decision), {
+        status: 302,
+        headers: { ...headers, location: decision.redirect_url },
+      })
+    }
+    if (decision.body != null) {
+      return new NextResponse(decision.body, {
+        status: decision.status,
+        headers: { ...headers, 'content-type': decision.content_type },
+      })
+    }
+    return NextResponse.json(rejection(decision), { status: decision.status, headers })
+  }
+}
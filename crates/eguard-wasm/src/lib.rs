@@ -0,0 +1,119 @@
+use eguard_core::{ChallengeKind, Decision, EGuard, EGuardConfig, EGuardError, HeaderValues, ReasonCode, http::header_pairs};
+use std::collections::HashMap;
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{future_to_promise, js_sys::Promise};
+
+/// The fields of the Node binding's `JsDecision`, but with snake_case keys.
+#[derive(Default, Serialize)]
+struct WasmDecision {
+    allow: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    challenge_kind: Option<ChallengeKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    redirect_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trust_score: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<ReasonCode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    route_id: Option<String>,
+}
+
+impl From<Decision> for WasmDecision {
+    fn from(d: Decision) -> Self {
+        match d {
+            Decision::Allow { trust_score, reason, route_id } => {
+                WasmDecision { allow: true, trust_score, reason, route_id, ..Self::default() }
+            }
+            Decision::Deny { status, message } => {
+                WasmDecision { allow: false, status: Some(status), message: Some(message), ..Self::default() }
+            }
+            Decision::Challenge { kind, redirect_url } => {
+                WasmDecision { allow: false, challenge_kind: Some(kind), redirect_url: Some(redirect_url), ..Self::default() }
+            }
+        }
+    }
+}
+
+/// eGuard for fetch-based edge runtimes. The config object uses the core's snake_case keys.
+#[wasm_bindgen(js_name = EGuard)]
+pub struct WasmEGuard {
+    inner: EGuard,
+}
+
+#[wasm_bindgen(js_class = EGuard)]
+impl WasmEGuard {
+    #[wasm_bindgen(constructor)]
+    pub fn new(cfg: JsValue) -> Result<WasmEGuard, JsError> {
+        let cfg: EGuardConfig = serde_wasm_bindgen::from_value(cfg)
+            .map_err(|e| JsError::new(&format!("Invalid config: {e}")))?;
+        let inner = EGuard::new(cfg).map_err(js_error)?;
+        Ok(Self { inner })
+    }
+
+    #[wasm_bindgen(js_name = isSecure)]
+    pub fn is_secure(&self, path: &str, method: &str) -> bool {
+        self.inner.is_secure(path, method)
+    }
+
+    #[wasm_bindgen(js_name = extractSessionId)]
+    pub fn extract_session_id(
+        &self,
+        cookie_header: Option<String>,
+        header_name: Option<String>,
+        header_value: Option<String>,
+        query: Option<String>,
+    ) -> Option<String> {
+        let header = header_name.as_deref().zip(header_value.as_deref());
+        self.inner.extract_session_id_with_query(cookie_header.as_deref(), header, query.as_deref())
+    }
+
+    /// `headers` is a plain object of header names to a value or an array of values.
+    #[wasm_bindgen(js_name = extractSessionIdFromHeaders)]
+    pub fn extract_session_id_from_headers(&self, headers: JsValue, query: Option<String>) -> Result<Option<String>, JsError> {
+        let headers = parse_headers(headers)?;
+        Ok(self.inner.extract_session_id_from_headers(header_pairs(&headers), query.as_deref()))
+    }
+
+    /// Resolves to `{ allow, status?, message? }`.
+    pub fn decide(&self, session_id: String) -> Promise {
+        let guard = self.inner.clone();
+        future_to_promise(async move {
+            let decision = guard.decide(&session_id).await.map_err(js_error)?;
+            Ok(serde_wasm_bindgen::to_value(&WasmDecision::from(decision))?)
+        })
+    }
+
+    /// Route matching, session extraction and the trust check for one request, as in the
+    /// Node binding's `decideRequest`. `path` may include the query string and `headers` is
+    /// a plain object, e.g. `Object.fromEntries(request.headers)`. Resolves to a
+    /// `RequestDecision` with snake_case keys, unlike Node's camelCase `JsRequestDecision`:
+    /// `{ matched, allow, status?, message?, score?, session_id?, challenge_kind?,
+    /// redirect_url?, headers?, body?, content_type? }`.
+    #[wasm_bindgen(js_name = decideRequest)]
+    pub fn decide_request(&self, path: String, method: String, headers: JsValue, ip: Option<String>) -> Result<Promise, JsError> {
+        let headers = parse_headers(headers)?;
+        let guard = self.inner.clone();
+        Ok(future_to_promise(async move {
+            let headers: Vec<(&str, &str)> = header_pairs(&headers).collect();
+            let ip = ip.and_then(|ip| ip.parse().ok());
+            let decision = guard.decide_http(&method, &path, &headers, ip).await;
+            Ok(serde_wasm_bindgen::to_value(&decision)?)
+        }))
+    }
+}
+
+/// Errors carry their `EGuardError` kind in front of the message, e.g. `RateLimited: ...`.
+fn js_error(e: EGuardError) -> JsError {
+    JsError::new(&format!("{}: {}", e.kind(), e))
+}
+
+fn parse_headers(headers: JsValue) -> Result<HashMap<String, HeaderValues>, JsError> {
+    serde_wasm_bindgen::from_value(headers).map_err(|e| JsError::new(&format!("Invalid headers: {e}")))
+}